@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+
+use crate::ast::{
+    walk_block, walk_expression, walk_statement, BlockStatement, CallExpression, ExportStatement,
+    ExpressionNode, FunctionLiteral, InfixDeclaration, Node, Parameter, Pattern, Program,
+    StatementNode, Variant, Visitor,
+};
+
+/// Checks calls to function literals, to names bound with
+/// `let name = fn(...)` and to enum variants against the callee's parameters.
+///
+/// Errors start with the span of the call's `(` and report the expected and
+/// the actual argument counts.
+pub fn check_program(program: &Program) -> Vec<String> {
+    let mut checker = ArityChecker {
+        functions: HashMap::new(),
+        errors: vec![],
+    };
+
+    checker.visit_program(program);
+
+    checker.errors
+}
+
+struct ArityChecker<'a> {
+    functions: HashMap<String, Callee<'a>>,
+    errors: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+enum Callee<'a> {
+    Function(&'a FunctionLiteral),
+    Variant(&'a Variant),
+}
+
+impl<'a> Visitor<'a> for ArityChecker<'a> {
+    fn visit_statement(&mut self, stmt: &'a StatementNode) {
+        match stmt {
+            StatementNode::Let(let_stmt)
+            | StatementNode::Infix(InfixDeclaration {
+                binding: let_stmt, ..
+            })
+            | StatementNode::Export(ExportStatement {
+                binding: let_stmt, ..
+            }) => {
+                if let Some(value) = &let_stmt.value {
+                    self.visit_expression(value);
+                }
+                self.bind(&let_stmt.name, let_stmt.value.as_ref());
+            }
+            StatementNode::Import(import) => {
+                self.functions.remove(&import.alias.value);
+            }
+            StatementNode::Enum(declaration) => {
+                for variant in &declaration.variants {
+                    self.functions
+                        .insert(variant.name.value.clone(), Callee::Variant(variant));
+                }
+            }
+            StatementNode::For(for_stmt) => {
+                self.visit_expression(&for_stmt.iterable);
+                self.check_block(&for_stmt.body, &[&for_stmt.variable.value]);
+            }
+            StatementNode::Try(try_stmt) => {
+                self.visit_block(&try_stmt.body);
+                if let Some(catch) = &try_stmt.catch {
+                    self.check_block(&catch.body, &[&catch.parameter.value]);
+                }
+                if let Some(finally) = &try_stmt.finally {
+                    self.visit_block(finally);
+                }
+            }
+            _ => walk_statement(self, stmt),
+        }
+    }
+
+    fn visit_block(&mut self, block: &'a BlockStatement) {
+        self.check_block(block, &[]);
+    }
+
+    fn visit_expression(&mut self, expression: &'a ExpressionNode) {
+        match expression {
+            ExpressionNode::Assign(assign) => {
+                walk_expression(self, expression);
+                // the new value may take other arguments
+                self.functions.remove(&assign.name.value);
+            }
+            ExpressionNode::Function(func) => {
+                for param in &func.parameters {
+                    if let Some(default) = &param.default {
+                        self.visit_expression(default);
+                    }
+                }
+
+                // parameters shadow outer functions inside the body
+                let parameters: Vec<&str> = func
+                    .parameters
+                    .iter()
+                    .map(|param| param.name.value.as_str())
+                    .collect();
+                self.check_block(&func.body, &parameters);
+            }
+            ExpressionNode::Match(match_exp) => {
+                self.visit_expression(&match_exp.subject);
+                for arm in &match_exp.arms {
+                    // names bound by the pattern shadow outer functions in the arm
+                    let outer = self.functions.clone();
+                    self.forget(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.visit_expression(guard);
+                    }
+                    self.visit_expression(&arm.body);
+                    self.functions = outer;
+                }
+            }
+            ExpressionNode::Call(call) => {
+                walk_expression(self, expression);
+                self.check_call(call);
+            }
+            _ => walk_expression(self, expression),
+        }
+    }
+}
+
+impl<'a> ArityChecker<'a> {
+    /// Checks a block whose bindings, and the shadowing `parameters`, end with it.
+    fn check_block(&mut self, block: &'a BlockStatement, parameters: &[&str]) {
+        let outer = self.functions.clone();
+        for parameter in parameters {
+            self.functions.remove(*parameter);
+        }
+        walk_block(self, block);
+        self.functions = outer;
+    }
+
+    fn bind(&mut self, pattern: &Pattern, value: Option<&'a ExpressionNode>) {
+        match (pattern, value) {
+            (Pattern::Binding(name), Some(ExpressionNode::Function(func))) => {
+                self.functions
+                    .insert(name.value.clone(), Callee::Function(func));
+            }
+            _ => self.forget(pattern),
+        }
+    }
+
+    /// Drops every name the pattern rebinds to something that is not a function literal.
+    fn forget(&mut self, pattern: &Pattern) {
+        for name in pattern.names() {
+            self.functions.remove(&name);
+        }
+    }
+
+    fn check_call(&mut self, call: &CallExpression) {
+        let callee = match call.function.as_ref() {
+            ExpressionNode::Function(func) => Callee::Function(func),
+            ExpressionNode::IdentifierNode(ident) => match self.functions.get(&ident.value) {
+                Some(callee) => *callee,
+                None => return,
+            },
+            _ => return,
+        };
+
+        let checked = match callee {
+            Callee::Function(func) => match_arguments(&func.parameters, call),
+            Callee::Variant(variant) => match_payload(variant, call),
+        };
+        if let Err(msg) = checked {
+            self.errors.push(format!(
+                "{}: {} in call `{}`",
+                call.token.span,
+                msg,
+                call.print_string()
+            ));
+        }
+    }
+}
+
+fn match_arguments(parameters: &[Parameter], call: &CallExpression) -> Result<(), String> {
+    let positional: Vec<&Parameter> = parameters.iter().filter(|p| !p.rest).collect();
+    let has_rest = positional.len() < parameters.len();
+
+    let given = call.arguments.iter().filter(|a| a.name.is_none()).count();
+    let mut filled: Vec<bool> = positional.iter().map(|_| false).collect();
+    for slot in filled.iter_mut().take(given) {
+        *slot = true;
+    }
+
+    for name in call.arguments.iter().filter_map(|a| a.name.as_ref()) {
+        match positional.iter().position(|p| p.name.value == name.value) {
+            Some(idx) if filled[idx] => {
+                return Err(format!("argument `{}` given more than once", name.value));
+            }
+            Some(idx) => filled[idx] = true,
+            None => return Err(format!("unknown named argument `{}`", name.value)),
+        }
+    }
+
+    let required = positional.iter().filter(|p| p.default.is_none()).count();
+    let actual = call.arguments.len();
+    let missing = positional
+        .iter()
+        .zip(&filled)
+        .any(|(param, filled)| param.default.is_none() && !filled);
+
+    if missing || (!has_rest && given > positional.len()) {
+        let expected = if has_rest {
+            format!("at least {}", required)
+        } else if required == positional.len() {
+            format!("{}", required)
+        } else {
+            format!("{} to {}", required, positional.len())
+        };
+
+        return Err(format!(
+            "wrong number of arguments: expected {}, got {}",
+            expected, actual
+        ));
+    }
+
+    Ok(())
+}
+
+fn match_payload(variant: &Variant, call: &CallExpression) -> Result<(), String> {
+    if let Some(name) = call.arguments.iter().find_map(|a| a.name.as_ref()) {
+        return Err(format!(
+            "variant `{}` does not take named argument `{}`",
+            variant.name.value, name.value
+        ));
+    }
+
+    if call.arguments.len() != variant.fields.len() {
+        return Err(format!(
+            "wrong number of arguments: expected {}, got {}",
+            variant.fields.len(),
+            call.arguments.len()
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{lexer::Lexer, parser::Parser};
+
+    use super::check_program;
+
+    fn errors_for(input: &str) -> Vec<String> {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert!(
+            parser.errors().is_empty(),
+            "parser errors: {:?}",
+            parser.errors()
+        );
+
+        check_program(&program)
+    }
+
+    #[test]
+    fn test_valid_calls() {
+        let tests = vec![
+            "let f = fn(x, y) { x + y }; f(1, 2);",
+            "let f = fn(x, y = 10) { x + y }; f(1); f(1, 2); f(1, y: 2); f(x: 1);",
+            "let f = fn(x, ...rest) { x }; f(1); f(1, 2, 3);",
+            "fn(x) { x }(1);",
+            "let f = fn(x) { x }; let f = 5; f(1, 2);",
+            "let f = fn(x) { x }; let g = fn(f) { f(1, 2) };",
+            "unknown(1, 2, 3);",
+            "enum Shape { Circle(r), Rect(w, h) } Circle(1); Rect(1, 2);",
+            "enum Shape { Circle(r) } let f = fn(Circle) { Circle() };",
+            "let f = fn(x) { x }; try { 1 } catch (f) { f() };",
+            "let f = fn(x) { x }; try { let f = 1; } finally { 1 } f(1);",
+            "let f = fn(x) { x }; match (g) { f => f(1, 2) };",
+        ];
+
+        for input in tests {
+            let errors = errors_for(input);
+            assert!(
+                errors.is_empty(),
+                "unexpected errors for {}: {:?}",
+                input,
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn test_arity_errors() {
+        let tests = vec![
+            (
+                "let f = fn(x, y) { x + y }; f(1);",
+                "1:30-31: wrong number of arguments: expected 2, got 1 in call `f(1)`",
+            ),
+            (
+                "let f = fn(x, y = 10) { x + y }; f(1, 2, 3);",
+                "1:35-36: wrong number of arguments: expected 1 to 2, got 3 in call `f(1, 2, 3)`",
+            ),
+            (
+                "let f = fn(x, y, ...rest) { x }; f(1);",
+                "1:35-36: wrong number of arguments: expected at least 2, got 1 in call `f(1)`",
+            ),
+            (
+                "fn(x) { x }();",
+                "1:12-13: wrong number of arguments: expected 1, got 0 in call `fn(x) x()`",
+            ),
+            (
+                "let f = fn(x, y = 10) { x }; f(1, z: 2);",
+                "1:31-32: unknown named argument `z` in call `f(1, z: 2)`",
+            ),
+            (
+                "let f = fn(x, y = 10) { x }; f(1, x: 2);",
+                "1:31-32: argument `x` given more than once in call `f(1, x: 2)`",
+            ),
+            (
+                "let f = fn(x, y) { x }; let g = fn() { f(1) };",
+                "1:41-42: wrong number of arguments: expected 2, got 1 in call `f(1)`",
+            ),
+            (
+                "enum Shape { Circle(r), Rect(w, h) } Rect(1);",
+                "1:42-43: wrong number of arguments: expected 2, got 1 in call `Rect(1)`",
+            ),
+            (
+                "let f = fn(x) { x }; try { 1 } catch (e) { f() };",
+                "1:45-46: wrong number of arguments: expected 1, got 0 in call `f()`",
+            ),
+            (
+                "let f = fn(x) { x }; match (1) { n => f(n, n) };",
+                "1:40-41: wrong number of arguments: expected 1, got 2 in call `f(n, n)`",
+            ),
+            (
+                "enum Shape { Circle(r) } Circle(r: 1);",
+                "1:32-33: variant `Circle` does not take named argument `r` in call `Circle(r: 1)`",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let errors = errors_for(input);
+            assert_eq!(errors, vec![expected], "wrong errors for {}", input);
+        }
+    }
+}
@@ -1,74 +1,287 @@
-use crate::token::Token;
+use crate::token::{Span, Token, TokenKind};
 
 pub trait Node {
     fn token_literal(&self) -> String;
     fn print_string(&self) -> String;
 }
 
-#[derive(Debug)]
+/// Compares every field but the span, so that the same code parsed from
+/// differently laid out sources is equal. Listing a field that does not
+/// exist, or leaving one out, fails to compile.
+macro_rules! eq_ignoring_span {
+    ($name:ident { $($field:ident),* }) => {
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                let $name { span: _, $($field),* } = self;
+                $(*$field == other.$field)&&*
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
 pub enum StatementNode {
     Let(LetStatement),
     Return(ReturnStatement),
     Expression(ExpressionStatement),
+    Infix(InfixDeclaration),
+    Import(ImportStatement),
+    Export(ExportStatement),
+    Struct(StructDeclaration),
+    Enum(EnumDeclaration),
+    Throw(ThrowStatement),
+    Try(TryStatement),
+    Yield(YieldStatement),
+    For(ForStatement),
+}
+
+impl StatementNode {
+    /// The source range of the whole statement, including its `;`.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Let(let_stmt) => let_stmt.span,
+            Self::Return(ret_stmt) => ret_stmt.span,
+            Self::Expression(expression) => expression.span,
+            Self::Infix(infix) => infix.span,
+            Self::Import(import) => import.span,
+            Self::Export(export) => export.span,
+            Self::Struct(declaration) => declaration.span,
+            Self::Enum(declaration) => declaration.span,
+            Self::Throw(throw) => throw.span,
+            Self::Try(try_stmt) => try_stmt.span,
+            Self::Yield(yield_stmt) => yield_stmt.span,
+            Self::For(for_stmt) => for_stmt.span,
+        }
+    }
+
+    pub fn set_span(&mut self, span: Span) {
+        *self.span_mut() = span;
+    }
+
+    fn span_mut(&mut self) -> &mut Span {
+        match self {
+            Self::Let(let_stmt) => &mut let_stmt.span,
+            Self::Return(ret_stmt) => &mut ret_stmt.span,
+            Self::Expression(expression) => &mut expression.span,
+            Self::Infix(infix) => &mut infix.span,
+            Self::Import(import) => &mut import.span,
+            Self::Export(export) => &mut export.span,
+            Self::Struct(declaration) => &mut declaration.span,
+            Self::Enum(declaration) => &mut declaration.span,
+            Self::Throw(throw) => &mut throw.span,
+            Self::Try(try_stmt) => &mut try_stmt.span,
+            Self::Yield(yield_stmt) => &mut yield_stmt.span,
+            Self::For(for_stmt) => &mut for_stmt.span,
+        }
+    }
 }
 
 impl Node for StatementNode {
     fn token_literal(&self) -> String {
-        return match self {
+        match self {
             Self::Let(let_stmt) => let_stmt.token_literal(),
             Self::Return(ret_stmt) => ret_stmt.token_literal(),
             Self::Expression(expression) => expression.token_literal(),
-        };
+            Self::Infix(infix) => infix.token_literal(),
+            Self::Import(import) => import.token_literal(),
+            Self::Export(export) => export.token_literal(),
+            Self::Struct(declaration) => declaration.token_literal(),
+            Self::Enum(declaration) => declaration.token_literal(),
+            Self::Throw(throw) => throw.token_literal(),
+            Self::Try(try_stmt) => try_stmt.token_literal(),
+            Self::Yield(yield_stmt) => yield_stmt.token_literal(),
+            Self::For(for_stmt) => for_stmt.token_literal(),
+        }
     }
 
     fn print_string(&self) -> String {
-        return match self {
+        match self {
             Self::Let(let_stmt) => let_stmt.print_string(),
             Self::Return(ret_stmt) => ret_stmt.print_string(),
             Self::Expression(expression) => expression.print_string(),
-        };
+            Self::Infix(infix) => infix.print_string(),
+            Self::Import(import) => import.print_string(),
+            Self::Export(export) => export.print_string(),
+            Self::Struct(declaration) => declaration.print_string(),
+            Self::Enum(declaration) => declaration.print_string(),
+            Self::Throw(throw) => throw.print_string(),
+            Self::Try(try_stmt) => try_stmt.print_string(),
+            Self::Yield(yield_stmt) => yield_stmt.print_string(),
+            Self::For(for_stmt) => for_stmt.print_string(),
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ExpressionNode {
     IdentifierNode(Identifier),
     Integer(IntegerLiteral),
-    Prefic(PrefixExpression)
+    Boolean(BooleanLiteral),
+    StringNode(StringLiteral),
+    Array(ArrayLiteral),
+    Hash(HashLiteral),
+    Prefix(PrefixExpression),
+    Infix(InfixExpression),
+    Match(MatchExpression),
+    If(IfExpression),
+    Function(FunctionLiteral),
+    Call(CallExpression),
+    Macro(MacroLiteral),
+    Member(MemberExpression),
+    Struct(StructLiteral),
+    Index(IndexExpression),
+    Range(RangeExpression),
+    Null(NullLiteral),
+    Assign(AssignExpression),
+}
+
+impl ExpressionNode {
+    /// The token diagnostics about this expression point at, the operator for
+    /// prefix and infix expressions, the callee for calls and the name after
+    /// the `.` for member access.
+    pub fn token(&self) -> &Token {
+        match self {
+            Self::IdentifierNode(ident) => &ident.token,
+            Self::Integer(int) => &int.token,
+            Self::Boolean(boolean) => &boolean.token,
+            Self::StringNode(string) => &string.token,
+            Self::Array(array) => &array.token,
+            Self::Hash(hash) => &hash.token,
+            Self::Prefix(prefix) => &prefix.token,
+            Self::Infix(infix) => &infix.token,
+            Self::Match(match_exp) => &match_exp.token,
+            Self::If(if_exp) => &if_exp.token,
+            Self::Function(func) => &func.token,
+            Self::Call(call) => call.function.token(),
+            Self::Macro(macro_lit) => &macro_lit.token,
+            Self::Member(member) => &member.property.token,
+            Self::Struct(literal) => &literal.token,
+            Self::Index(index) => &index.token,
+            Self::Range(range) => &range.token,
+            Self::Null(null) => &null.token,
+            Self::Assign(assign) => &assign.token,
+        }
+    }
+
+    /// The source range of the whole expression. Parentheses around it are
+    /// left out, but an operator applied to a grouped operand covers them.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::IdentifierNode(ident) => ident.token.span,
+            Self::Integer(int) => int.token.span,
+            Self::Boolean(boolean) => boolean.token.span,
+            Self::StringNode(string) => string.token.span,
+            Self::Array(array) => array.span,
+            Self::Hash(hash) => hash.span,
+            Self::Prefix(prefix) => prefix.span,
+            Self::Infix(infix) => infix.span,
+            Self::Match(match_exp) => match_exp.span,
+            Self::If(if_exp) => if_exp.span,
+            Self::Function(func) => func.span,
+            Self::Call(call) => call.span,
+            Self::Macro(macro_lit) => macro_lit.span,
+            Self::Member(member) => member.span,
+            Self::Struct(literal) => literal.span,
+            Self::Index(index) => index.span,
+            Self::Range(range) => range.span,
+            Self::Null(null) => null.token.span,
+            Self::Assign(assign) => assign.span,
+        }
+    }
+
+    /// Literals and identifiers have no span of their own, this moves their token.
+    pub fn set_span(&mut self, span: Span) {
+        *self.span_mut() = span;
+    }
 
+    fn span_mut(&mut self) -> &mut Span {
+        match self {
+            Self::IdentifierNode(ident) => &mut ident.token.span,
+            Self::Integer(int) => &mut int.token.span,
+            Self::Boolean(boolean) => &mut boolean.token.span,
+            Self::StringNode(string) => &mut string.token.span,
+            Self::Array(array) => &mut array.span,
+            Self::Hash(hash) => &mut hash.span,
+            Self::Prefix(prefix) => &mut prefix.span,
+            Self::Infix(infix) => &mut infix.span,
+            Self::Match(match_exp) => &mut match_exp.span,
+            Self::If(if_exp) => &mut if_exp.span,
+            Self::Function(func) => &mut func.span,
+            Self::Call(call) => &mut call.span,
+            Self::Macro(macro_lit) => &mut macro_lit.span,
+            Self::Member(member) => &mut member.span,
+            Self::Struct(literal) => &mut literal.span,
+            Self::Index(index) => &mut index.span,
+            Self::Range(range) => &mut range.span,
+            Self::Null(null) => &mut null.token.span,
+            Self::Assign(assign) => &mut assign.span,
+        }
+    }
 }
 
 impl Node for ExpressionNode {
     fn token_literal(&self) -> String {
-        return match self {
+        match self {
             Self::IdentifierNode(ident) => ident.token_literal(),
             Self::Integer(int) => int.token_literal(),
-        };
+            Self::Boolean(boolean) => boolean.token_literal(),
+            Self::StringNode(string) => string.token_literal(),
+            Self::Array(array) => array.token_literal(),
+            Self::Hash(hash) => hash.token_literal(),
+            Self::Prefix(prefix) => prefix.token_literal(),
+            Self::Infix(infix) => infix.token_literal(),
+            Self::Match(match_exp) => match_exp.token_literal(),
+            Self::If(if_exp) => if_exp.token_literal(),
+            Self::Function(func) => func.token_literal(),
+            Self::Call(call) => call.token_literal(),
+            Self::Macro(macro_lit) => macro_lit.token_literal(),
+            Self::Member(member) => member.token_literal(),
+            Self::Struct(literal) => literal.token_literal(),
+            Self::Index(index) => index.token_literal(),
+            Self::Range(range) => range.token_literal(),
+            Self::Null(null) => null.token_literal(),
+            Self::Assign(assign) => assign.token_literal(),
+        }
     }
 
     fn print_string(&self) -> String {
-        return match self {
+        match self {
             Self::IdentifierNode(ident) => ident.print_string(),
             Self::Integer(int) => int.print_string(),
-        };
+            Self::Boolean(boolean) => boolean.print_string(),
+            Self::StringNode(string) => string.print_string(),
+            Self::Array(array) => array.print_string(),
+            Self::Hash(hash) => hash.print_string(),
+            Self::Prefix(prefix) => prefix.print_string(),
+            Self::Infix(infix) => infix.print_string(),
+            Self::Match(match_exp) => match_exp.print_string(),
+            Self::If(if_exp) => if_exp.print_string(),
+            Self::Function(func) => func.print_string(),
+            Self::Call(call) => call.print_string(),
+            Self::Macro(macro_lit) => macro_lit.print_string(),
+            Self::Member(member) => member.print_string(),
+            Self::Struct(literal) => literal.print_string(),
+            Self::Index(index) => index.print_string(),
+            Self::Range(range) => range.print_string(),
+            Self::Null(null) => null.print_string(),
+            Self::Assign(assign) => assign.print_string(),
+        }
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Program {
     pub statements: Vec<StatementNode>,
 }
 
 impl Node for Program {
     fn token_literal(&self) -> String {
-        return if self.statements.len() > 0 {
-            match &self.statements[0] {
-                StatementNode::Let(let_stmt) => let_stmt.token_literal(),
-                StatementNode::Return(ret_stmt) => ret_stmt.token_literal(),
-                StatementNode::Expression(expression) => expression.token_literal(),
-            }
+        if !self.statements.is_empty() {
+            self.statements[0].token_literal()
         } else {
             String::from("value")
-        };
+        }
     }
 
     fn print_string(&self) -> String {
@@ -82,7 +295,7 @@ impl Node for Program {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Identifier {
     pub token: Token,
     pub value: String,
@@ -98,13 +311,24 @@ impl Node for Identifier {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LetStatement {
     pub token: Token,
-    pub name: Identifier,
+    pub span: Span,
+    /// Either a plain binding or an array/hash destructuring pattern.
+    pub name: Pattern,
+    /// `let x: int = 5;`
+    pub annotation: Option<TypeExpr>,
     pub value: Option<ExpressionNode>,
 }
 
+eq_ignoring_span!(LetStatement {
+    token,
+    name,
+    annotation,
+    value
+});
+
 impl Node for LetStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
@@ -114,25 +338,292 @@ impl Node for LetStatement {
         let mut out = String::new();
 
         out.push_str(self.token_literal().as_str());
-        out.push_str(" ");
+        out.push(' ');
         out.push_str(self.name.print_string().as_str());
+        if let Some(annotation) = &self.annotation {
+            out.push_str(": ");
+            out.push_str(annotation.print_string().as_str());
+        }
         out.push_str(" = ");
 
         if let Some(value) = &self.value {
             out.push_str(value.print_string().as_str());
         }
-        out.push_str(";");
+        out.push(';');
+
+        out
+    }
+}
+
+impl LetStatement {
+    /// `const x = 5;`, whose bindings can not be assigned to later.
+    pub fn is_const(&self) -> bool {
+        self.token.kind == TokenKind::Const
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+/// `infix <+> 3 left = fn(a, b) { ... };` declares a custom operator.
+///
+/// Uses of the operator parse into calls of the bound function.
+#[derive(Debug, Clone)]
+pub struct InfixDeclaration {
+    pub token: Token,
+    pub span: Span,
+    /// Between 1 (binds like `==`) and 4 (binds like `*`).
+    pub precedence: u8,
+    pub associativity: Associativity,
+    /// The operator bound to its function, as if by `let <+> = fn(a, b) { ... };`.
+    pub binding: LetStatement,
+}
+
+eq_ignoring_span!(InfixDeclaration {
+    token,
+    precedence,
+    associativity,
+    binding
+});
+
+impl Node for InfixDeclaration {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let associativity = match self.associativity {
+            Associativity::Left => "left",
+            Associativity::Right => "right",
+        };
+
+        let mut out = String::new();
+
+        out.push_str(self.token_literal().as_str());
+        out.push(' ');
+        out.push_str(self.binding.name.print_string().as_str());
+        out.push_str(format!(" {} {} = ", self.precedence, associativity).as_str());
+        if let Some(value) = &self.binding.value {
+            out.push_str(value.print_string().as_str());
+        }
+        out.push(';');
 
         out
     }
 }
 
-#[derive(Debug, Default)]
+/// `import "path/to/file" as m;`
+#[derive(Debug, Clone)]
+pub struct ImportStatement {
+    pub token: Token,
+    pub span: Span,
+    /// Relative to the directory of the importing file.
+    pub path: StringLiteral,
+    pub alias: Identifier,
+}
+
+eq_ignoring_span!(ImportStatement { token, path, alias });
+
+impl Node for ImportStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        format!(
+            "{} {} as {};",
+            self.token_literal(),
+            self.path.print_string(),
+            self.alias.print_string()
+        )
+    }
+}
+
+/// `export let x = 5;` makes `x` visible to modules importing this one.
+#[derive(Debug, Clone)]
+pub struct ExportStatement {
+    pub token: Token,
+    pub span: Span,
+    pub binding: LetStatement,
+}
+
+eq_ignoring_span!(ExportStatement { token, binding });
+
+impl Node for ExportStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        format!("{} {}", self.token_literal(), self.binding.print_string())
+    }
+}
+
+/// `struct Point { x, y }`
+#[derive(Debug, Clone)]
+pub struct StructDeclaration {
+    pub token: Token,
+    pub span: Span,
+    pub name: Identifier,
+    pub fields: Vec<Identifier>,
+}
+
+eq_ignoring_span!(StructDeclaration {
+    token,
+    name,
+    fields
+});
+
+impl Node for StructDeclaration {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let fields: Vec<String> = self.fields.iter().map(|f| f.print_string()).collect();
+
+        format!(
+            "{} {} {{ {} }}",
+            self.token_literal(),
+            self.name.print_string(),
+            fields.join(", ")
+        )
+    }
+}
+
+impl StructDeclaration {
+    pub fn has_field(&self, name: &str) -> bool {
+        self.fields.iter().any(|field| field.value == name)
+    }
+
+    /// Errors for fields of `literal` that are unknown, repeated or left out.
+    pub fn check_literal(&self, literal: &StructLiteral) -> Vec<(Span, String)> {
+        let mut errors = vec![];
+        let mut seen: Vec<&str> = vec![];
+
+        for (field, _) in &literal.fields {
+            if !self.has_field(&field.value) {
+                let msg = format!(
+                    "struct `{}` has no field `{}`",
+                    self.name.value, field.value
+                );
+                errors.push((field.token.span, msg));
+            } else if seen.contains(&field.value.as_str()) {
+                let msg = format!("field `{}` is given more than once", field.value);
+                errors.push((field.token.span, msg));
+            }
+            seen.push(&field.value);
+        }
+
+        for field in &self.fields {
+            if !seen.contains(&field.value.as_str()) {
+                let msg = format!(
+                    "missing field `{}` in `{}`",
+                    field.value,
+                    literal.print_string()
+                );
+                errors.push((literal.name.token.span, msg));
+            }
+        }
+
+        errors
+    }
+}
+
+/// `enum Shape { Circle(r), Rect(w, h) }`
+#[derive(Debug, Clone)]
+pub struct EnumDeclaration {
+    pub token: Token,
+    pub span: Span,
+    pub name: Identifier,
+    pub variants: Vec<Variant>,
+}
+
+eq_ignoring_span!(EnumDeclaration {
+    token,
+    name,
+    variants
+});
+
+impl Node for EnumDeclaration {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let variants: Vec<String> = self.variants.iter().map(|v| v.print_string()).collect();
+
+        format!(
+            "{} {} {{ {} }}",
+            self.token_literal(),
+            self.name.print_string(),
+            variants.join(", ")
+        )
+    }
+}
+
+impl EnumDeclaration {
+    pub fn variant(&self, name: &str) -> Option<&Variant> {
+        self.variants
+            .iter()
+            .find(|variant| variant.name.value == name)
+    }
+}
+
+/// `Rect(w, h)`, or just `Empty` for a variant without a payload.
+///
+/// The payload names only document the values, a variant is constructed
+/// by calling it: `Rect(1, 2)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: Identifier,
+    pub fields: Vec<Identifier>,
+}
+
+impl Node for Variant {
+    fn token_literal(&self) -> String {
+        self.name.token_literal()
+    }
+
+    fn print_string(&self) -> String {
+        if self.fields.is_empty() {
+            return self.name.print_string();
+        }
+
+        let fields: Vec<String> = self.fields.iter().map(|f| f.print_string()).collect();
+        format!("{}({})", self.name.print_string(), fields.join(", "))
+    }
+}
+
+impl Variant {
+    /// An error if `pattern` does not give one sub-pattern per payload value.
+    pub fn check_pattern(&self, pattern: &VariantPattern) -> Option<String> {
+        if pattern.payload.len() == self.fields.len() {
+            return None;
+        }
+
+        Some(format!(
+            "variant `{}` has {} values, but the pattern `{}` has {}",
+            self.name.value,
+            self.fields.len(),
+            pattern.print_string(),
+            pattern.payload.len()
+        ))
+    }
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct ReturnStatement {
     pub token: Token,
+    pub span: Span,
     pub ret_value: Option<ExpressionNode>,
 }
 
+eq_ignoring_span!(ReturnStatement { token, ret_value });
+
 impl Node for ReturnStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
@@ -142,22 +633,150 @@ impl Node for ReturnStatement {
         let mut out = String::new();
 
         out.push_str(self.token_literal().as_str());
-        out.push_str(" ");
+        out.push(' ');
 
         if let Some(ret_value) = &self.ret_value {
             out.push_str(ret_value.print_string().as_str());
         }
-        out.push_str(";");
+        out.push(';');
+        out
+    }
+}
+
+/// `throw expr;`, any value can be thrown.
+#[derive(Debug, Clone)]
+pub struct ThrowStatement {
+    pub token: Token,
+    pub span: Span,
+    pub value: ExpressionNode,
+}
+
+eq_ignoring_span!(ThrowStatement { token, value });
+
+impl Node for ThrowStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        format!("{} {};", self.token_literal(), self.value.print_string())
+    }
+}
+
+/// `try { ... } catch (e) { ... } finally { ... }`, with at least one of
+/// `catch` and `finally`.
+#[derive(Debug, Clone)]
+pub struct TryStatement {
+    pub token: Token,
+    pub span: Span,
+    pub body: BlockStatement,
+    pub catch: Option<CatchClause>,
+    pub finally: Option<BlockStatement>,
+}
+
+eq_ignoring_span!(TryStatement {
+    token,
+    body,
+    catch,
+    finally
+});
+
+impl Node for TryStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let mut out = format!(
+            "{} {{ {} }}",
+            self.token_literal(),
+            self.body.print_string()
+        );
+
+        if let Some(catch) = &self.catch {
+            out.push_str(&format!(
+                " catch ({}) {{ {} }}",
+                catch.parameter.print_string(),
+                catch.body.print_string()
+            ));
+        }
+        if let Some(finally) = &self.finally {
+            out.push_str(&format!(" finally {{ {} }}", finally.print_string()));
+        }
+
         out
     }
 }
 
-#[derive(Debug, Default)]
+/// `yield expr;`, makes the enclosing function literal a generator.
+#[derive(Debug, Clone)]
+pub struct YieldStatement {
+    pub token: Token,
+    pub span: Span,
+    pub value: ExpressionNode,
+}
+
+eq_ignoring_span!(YieldStatement { token, value });
+
+impl Node for YieldStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        format!("{} {};", self.token_literal(), self.value.print_string())
+    }
+}
+
+/// `for x in xs { ... }` over an array or a generator.
+#[derive(Debug, Clone)]
+pub struct ForStatement {
+    pub token: Token,
+    pub span: Span,
+    pub variable: Identifier,
+    pub iterable: ExpressionNode,
+    pub body: BlockStatement,
+}
+
+eq_ignoring_span!(ForStatement {
+    token,
+    variable,
+    iterable,
+    body
+});
+
+impl Node for ForStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        format!(
+            "{} {} in {} {{ {} }}",
+            self.token_literal(),
+            self.variable.print_string(),
+            self.iterable.print_string(),
+            self.body.print_string()
+        )
+    }
+}
+
+/// `catch (e) { ... }`, binds the thrown value to `parameter`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatchClause {
+    pub parameter: Identifier,
+    pub body: BlockStatement,
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct ExpressionStatement {
     pub token: Token,
+    pub span: Span,
     pub expression: Option<ExpressionNode>,
 }
 
+eq_ignoring_span!(ExpressionStatement { token, expression });
+
 impl Node for ExpressionStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
@@ -171,7 +790,7 @@ impl Node for ExpressionStatement {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IntegerLiteral {
     pub token: Token,
     pub value: i64,
@@ -187,69 +806,1488 @@ impl Node for IntegerLiteral {
     }
 }
 
-#[derive(Debug)]
-pub struct PrefixExpression {
+#[derive(Debug, Clone, PartialEq)]
+pub struct BooleanLiteral {
     pub token: Token,
-    pub operator: String,
-    pub right: Box<ExpressionNode>
+    pub value: bool,
 }
 
-impl Node for PrefixExpression {
+impl Node for BooleanLiteral {
     fn token_literal(&self) -> String {
-        self.token_literal().clone()
+        self.token.literal.clone()
     }
 
     fn print_string(&self) -> String {
-        let mut out = String::from("");
-        out.push_str("(");
-        out.push_str(self.operator.as_str());
-        out.push_str(self.right.print_string().as_str());
-        out.push_str(")");
+        self.token_literal()
+    }
+}
 
-        out
+#[derive(Debug, Clone, PartialEq)]
+pub struct NullLiteral {
+    pub token: Token,
+}
+
+impl Node for NullLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        self.token_literal()
     }
 }
 
-#[cfg(test)]
-mod test {
-    use crate::{
-        ast::Node,
-        token::{Token, TokenKind},
-    };
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringLiteral {
+    pub token: Token,
+    pub value: String,
+}
+
+impl Node for StringLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
 
-    use super::{ExpressionNode, Identifier, LetStatement, Program, StatementNode};
+    fn print_string(&self) -> String {
+        format!("\"{}\"", self.value)
+    }
+}
 
-    #[test]
-    fn test_print_string() {
-        let program = Program {
-            statements: vec![StatementNode::Let(LetStatement {
-                token: Token {
-                    kind: TokenKind::Let,
-                    literal: String::from("let"),
-                },
-
-                name: Identifier {
-                    token: Token {
-                        kind: TokenKind::Ident,
-                        literal: String::from("myVar"),
-                    },
-                    value: String::from("myVar"),
-                },
-                value: Some(ExpressionNode::IdentifierNode(Identifier {
-                    token: Token {
-                        kind: TokenKind::Ident,
-                        literal: String::from("anotherVar"),
-                    },
-                    value: String::from("anotherVar"),
-                })),
-            })],
-        };
+#[derive(Debug, Clone)]
+pub struct ArrayLiteral {
+    pub token: Token,
+    pub span: Span,
+    pub elements: Vec<ExpressionNode>,
+}
 
-        assert_eq!(
-            program.print_string(),
-            String::from("let myVar = anotherVar;"),
-            "print string wrong. got = {}",
-            program.print_string()
-        )
+eq_ignoring_span!(ArrayLiteral { token, elements });
+
+impl Node for ArrayLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let elements: Vec<String> = self.elements.iter().map(|el| el.print_string()).collect();
+
+        format!("[{}]", elements.join(", "))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HashLiteral {
+    pub token: Token,
+    pub span: Span,
+    pub pairs: Vec<(ExpressionNode, ExpressionNode)>,
+}
+
+eq_ignoring_span!(HashLiteral { token, pairs });
+
+impl Node for HashLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let pairs: Vec<String> = self
+            .pairs
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key.print_string(), value.print_string()))
+            .collect();
+
+        format!("{{{}}}", pairs.join(", "))
+    }
+}
+
+/// `Point { x: 1, y: 2 }`
+#[derive(Debug, Clone)]
+pub struct StructLiteral {
+    pub token: Token,
+    pub span: Span,
+    pub name: Identifier,
+    pub fields: Vec<(Identifier, ExpressionNode)>,
+}
+
+eq_ignoring_span!(StructLiteral {
+    token,
+    name,
+    fields
+});
+
+impl Node for StructLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name.print_string(), value.print_string()))
+            .collect();
+
+        format!("{} {{ {} }}", self.name.print_string(), fields.join(", "))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PrefixExpression {
+    pub token: Token,
+    pub span: Span,
+    pub operator: String,
+    pub right: Box<ExpressionNode>,
+}
+
+eq_ignoring_span!(PrefixExpression {
+    token,
+    operator,
+    right
+});
+
+impl Node for PrefixExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let mut out = String::from("");
+        out.push('(');
+        out.push_str(self.operator.as_str());
+        out.push_str(self.right.print_string().as_str());
+        out.push(')');
+
+        out
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InfixExpression {
+    pub token: Token,
+    pub span: Span,
+    pub left: Box<ExpressionNode>,
+    pub operator: String,
+    pub right: Box<ExpressionNode>,
+}
+
+eq_ignoring_span!(InfixExpression {
+    token,
+    left,
+    operator,
+    right
+});
+
+impl Node for InfixExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let mut out = String::from("");
+        out.push('(');
+        out.push_str(self.left.print_string().as_str());
+        out.push_str(format!(" {} ", self.operator).as_str());
+        out.push_str(self.right.print_string().as_str());
+        out.push(')');
+
+        out
+    }
+}
+
+/// `x = 5`, gives a new value to an existing binding.
+#[derive(Debug, Clone)]
+pub struct AssignExpression {
+    pub token: Token,
+    pub span: Span,
+    pub name: Identifier,
+    pub value: Box<ExpressionNode>,
+}
+
+eq_ignoring_span!(AssignExpression { token, name, value });
+
+impl Node for AssignExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        format!(
+            "({} = {})",
+            self.name.print_string(),
+            self.value.print_string()
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockStatement {
+    pub token: Token,
+    pub span: Span,
+    pub statements: Vec<StatementNode>,
+}
+
+eq_ignoring_span!(BlockStatement { token, statements });
+
+impl Node for BlockStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let mut out = String::new();
+
+        for stmt in &self.statements {
+            out.push_str(stmt.print_string().as_str());
+        }
+
+        out
+    }
+}
+
+/// `fn(x, y = 10, ...rest) { ... }`
+#[derive(Debug, Clone)]
+pub struct FunctionLiteral {
+    pub token: Token,
+    pub span: Span,
+    pub parameters: Vec<Parameter>,
+    /// `-> bool`
+    pub return_type: Option<TypeExpr>,
+    pub body: BlockStatement,
+}
+
+eq_ignoring_span!(FunctionLiteral {
+    token,
+    parameters,
+    return_type,
+    body
+});
+
+impl Node for FunctionLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let params: Vec<String> = self.parameters.iter().map(|p| p.print_string()).collect();
+
+        let mut out = String::new();
+        out.push_str(self.token_literal().as_str());
+        out.push('(');
+        out.push_str(params.join(", ").as_str());
+        out.push_str(") ");
+        if let Some(return_type) = &self.return_type {
+            out.push_str("-> ");
+            out.push_str(return_type.print_string().as_str());
+            out.push(' ');
+        }
+        out.push_str(self.body.print_string().as_str());
+
+        out
+    }
+}
+
+impl FunctionLiteral {
+    /// Whether the body yields, outside of nested function literals and of
+    /// `if` expressions used as values.
+    pub fn is_generator(&self) -> bool {
+        fn yields(statements: &[StatementNode]) -> bool {
+            statements.iter().any(|stmt| match stmt {
+                StatementNode::Yield(_) => true,
+                StatementNode::For(for_stmt) => yields(&for_stmt.body.statements),
+                StatementNode::Expression(ExpressionStatement {
+                    expression: Some(ExpressionNode::If(if_exp)),
+                    ..
+                }) => if_exp.blocks().any(|block| yields(&block.statements)),
+                StatementNode::Try(try_stmt) => {
+                    yields(&try_stmt.body.statements)
+                        || try_stmt
+                            .catch
+                            .as_ref()
+                            .is_some_and(|catch| yields(&catch.body.statements))
+                        || try_stmt
+                            .finally
+                            .as_ref()
+                            .is_some_and(|finally| yields(&finally.statements))
+                }
+                _ => false,
+            })
+        }
+
+        yields(&self.body.statements)
+    }
+}
+
+/// `macro(cond, body) { quote(...) }`, expanded away before any other pass.
+#[derive(Debug, Clone)]
+pub struct MacroLiteral {
+    pub token: Token,
+    pub span: Span,
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+}
+
+eq_ignoring_span!(MacroLiteral {
+    token,
+    parameters,
+    body
+});
+
+impl Node for MacroLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let params: Vec<String> = self.parameters.iter().map(|p| p.print_string()).collect();
+
+        let mut out = String::new();
+        out.push_str(self.token_literal().as_str());
+        out.push('(');
+        out.push_str(params.join(", ").as_str());
+        out.push_str(") ");
+        out.push_str(self.body.print_string().as_str());
+
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: Identifier,
+    pub annotation: Option<TypeExpr>,
+    /// Used when the call leaves the parameter out.
+    pub default: Option<ExpressionNode>,
+    /// `...name`, collects the remaining positional arguments.
+    pub rest: bool,
+}
+
+impl Node for Parameter {
+    fn token_literal(&self) -> String {
+        self.name.token_literal()
+    }
+
+    fn print_string(&self) -> String {
+        let mut out = String::new();
+
+        if self.rest {
+            out.push_str("...");
+        }
+        out.push_str(self.name.print_string().as_str());
+        if let Some(annotation) = &self.annotation {
+            out.push_str(": ");
+            out.push_str(annotation.print_string().as_str());
+        }
+        if let Some(default) = &self.default {
+            out.push_str(" = ");
+            out.push_str(default.print_string().as_str());
+        }
+
+        out
+    }
+}
+
+/// `m.name`, or `m?.name` which gives null instead of failing on a null `m`.
+#[derive(Debug, Clone)]
+pub struct MemberExpression {
+    pub token: Token,
+    pub span: Span,
+    pub object: Box<ExpressionNode>,
+    pub property: Identifier,
+    pub optional: bool,
+}
+
+eq_ignoring_span!(MemberExpression {
+    token,
+    object,
+    property,
+    optional
+});
+
+impl Node for MemberExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        format!(
+            "{}{}{}",
+            self.object.print_string(),
+            if self.optional { "?." } else { "." },
+            self.property.print_string()
+        )
+    }
+}
+
+/// `xs[1]`, or a slice like `xs[1..3]` when the index is a range. `xs?.[1]`
+/// gives null when `xs` is null.
+#[derive(Debug, Clone)]
+pub struct IndexExpression {
+    pub token: Token,
+    pub span: Span,
+    pub left: Box<ExpressionNode>,
+    pub index: Box<ExpressionNode>,
+    pub optional: bool,
+}
+
+eq_ignoring_span!(IndexExpression {
+    token,
+    left,
+    index,
+    optional
+});
+
+impl Node for IndexExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        format!(
+            "({}{}[{}])",
+            self.left.print_string(),
+            if self.optional { "?." } else { "" },
+            self.index.print_string()
+        )
+    }
+}
+
+impl IndexExpression {
+    pub fn is_slice(&self) -> bool {
+        matches!(self.index.as_ref(), ExpressionNode::Range(_))
+    }
+}
+
+/// `a..b`, `a..=b`, or open at either end like `..5` and `1..`.
+#[derive(Debug, Clone)]
+pub struct RangeExpression {
+    pub token: Token,
+    pub span: Span,
+    pub start: Option<Box<ExpressionNode>>,
+    pub end: Option<Box<ExpressionNode>>,
+    pub inclusive: bool,
+}
+
+eq_ignoring_span!(RangeExpression {
+    token,
+    start,
+    end,
+    inclusive
+});
+
+impl Node for RangeExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let bound = |bound: &Option<Box<ExpressionNode>>| {
+            bound.as_ref().map_or(String::new(), |b| b.print_string())
+        };
+
+        format!(
+            "({}{}{})",
+            bound(&self.start),
+            self.token_literal(),
+            bound(&self.end)
+        )
+    }
+}
+
+/// `add(1, y: 2)`
+#[derive(Debug, Clone)]
+pub struct CallExpression {
+    pub token: Token,
+    pub span: Span,
+    pub function: Box<ExpressionNode>,
+    pub arguments: Vec<Argument>,
+}
+
+eq_ignoring_span!(CallExpression {
+    token,
+    function,
+    arguments
+});
+
+impl Node for CallExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let args: Vec<String> = self.arguments.iter().map(|a| a.print_string()).collect();
+
+        // custom operators print the way they were written
+        if let ([left, right], TokenKind::Operator) = (args.as_slice(), &self.token.kind) {
+            return format!("({} {} {})", left, self.token.literal, right);
+        }
+
+        let mut out = String::new();
+        out.push_str(self.function.print_string().as_str());
+        out.push('(');
+        out.push_str(args.join(", ").as_str());
+        out.push(')');
+
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Argument {
+    /// Set for named arguments like `y: 2`.
+    pub name: Option<Identifier>,
+    pub value: ExpressionNode,
+}
+
+impl Node for Argument {
+    fn token_literal(&self) -> String {
+        match &self.name {
+            Some(name) => name.token_literal(),
+            None => self.value.token_literal(),
+        }
+    }
+
+    fn print_string(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{}: {}", name.print_string(), self.value.print_string()),
+            None => self.value.print_string(),
+        }
+    }
+}
+
+/// `match (subject) { pattern if guard => body, ... }`
+#[derive(Debug, Clone)]
+pub struct MatchExpression {
+    pub token: Token,
+    pub span: Span,
+    pub subject: Box<ExpressionNode>,
+    pub arms: Vec<MatchArm>,
+}
+
+eq_ignoring_span!(MatchExpression {
+    token,
+    subject,
+    arms
+});
+
+impl Node for MatchExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("match (");
+        out.push_str(self.subject.print_string().as_str());
+        out.push_str(") { ");
+
+        let arms: Vec<String> = self.arms.iter().map(|arm| arm.print_string()).collect();
+        out.push_str(arms.join(", ").as_str());
+        out.push_str(" }");
+
+        out
+    }
+}
+
+/// `if (condition) { ... } else { ... }`, whose value is the value of the
+/// block that runs. `else if` is an `else` block holding just the next `if`.
+#[derive(Debug, Clone)]
+pub struct IfExpression {
+    pub token: Token,
+    pub span: Span,
+    pub condition: Box<ExpressionNode>,
+    pub consequence: BlockStatement,
+    pub alternative: Option<BlockStatement>,
+}
+
+eq_ignoring_span!(IfExpression {
+    token,
+    condition,
+    consequence,
+    alternative
+});
+
+impl IfExpression {
+    /// The consequence, then the alternative if there is one.
+    pub fn blocks(&self) -> impl Iterator<Item = &BlockStatement> {
+        std::iter::once(&self.consequence).chain(&self.alternative)
+    }
+}
+
+impl Node for IfExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("if (");
+        out.push_str(self.condition.print_string().as_str());
+        out.push_str(") { ");
+        out.push_str(self.consequence.print_string().as_str());
+        out.push_str(" }");
+        if let Some(alternative) = &self.alternative {
+            out.push_str(" else { ");
+            out.push_str(alternative.print_string().as_str());
+            out.push_str(" }");
+        }
+
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<ExpressionNode>,
+    pub body: ExpressionNode,
+}
+
+impl MatchArm {
+    fn print_string(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str(self.pattern.print_string().as_str());
+        if let Some(guard) = &self.guard {
+            out.push_str(" if ");
+            out.push_str(guard.print_string().as_str());
+        }
+        out.push_str(" => ");
+        out.push_str(self.body.print_string().as_str());
+
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Integer(IntegerLiteral),
+    Boolean(BooleanLiteral),
+    StringNode(StringLiteral),
+    /// Binds the matched value to a name.
+    Binding(Identifier),
+    /// `_`, matches anything without binding.
+    Wildcard(Token),
+    Array(ArrayPattern),
+    Hash(HashPattern),
+    Variant(VariantPattern),
+}
+
+impl Pattern {
+    /// Every name the pattern binds, in source order.
+    pub fn names(&self) -> Vec<String> {
+        self.bindings()
+            .into_iter()
+            .map(|name| name.value.clone())
+            .collect()
+    }
+
+    /// The identifiers behind [`Pattern::names`].
+    pub fn bindings(&self) -> Vec<&Identifier> {
+        match self {
+            Self::Binding(name) => vec![name],
+            Self::Array(array) => {
+                let mut names: Vec<&Identifier> =
+                    array.elements.iter().flat_map(Pattern::bindings).collect();
+                if let Some(rest) = &array.rest {
+                    names.push(rest);
+                }
+                names
+            }
+            Self::Hash(hash) => hash
+                .pairs
+                .iter()
+                .flat_map(|(_, value)| value.bindings())
+                .collect(),
+            Self::Variant(variant) => variant.payload.iter().flat_map(Pattern::bindings).collect(),
+            _ => vec![],
+        }
+    }
+
+    pub fn token(&self) -> &Token {
+        match self {
+            Self::Integer(int) => &int.token,
+            Self::Boolean(boolean) => &boolean.token,
+            Self::StringNode(string) => &string.token,
+            Self::Binding(ident) => &ident.token,
+            Self::Wildcard(token) => token,
+            Self::Array(array) => &array.token,
+            Self::Hash(hash) => &hash.token,
+            Self::Variant(variant) => &variant.name.token,
+        }
+    }
+}
+
+impl Node for Pattern {
+    fn token_literal(&self) -> String {
+        match self {
+            Self::Integer(int) => int.token_literal(),
+            Self::Boolean(boolean) => boolean.token_literal(),
+            Self::StringNode(string) => string.token_literal(),
+            Self::Binding(ident) => ident.token_literal(),
+            Self::Wildcard(token) => token.literal.clone(),
+            Self::Array(array) => array.token_literal(),
+            Self::Hash(hash) => hash.token_literal(),
+            Self::Variant(variant) => variant.token_literal(),
+        }
+    }
+
+    fn print_string(&self) -> String {
+        match self {
+            Self::Integer(int) => int.print_string(),
+            Self::Boolean(boolean) => boolean.print_string(),
+            Self::StringNode(string) => string.print_string(),
+            Self::Binding(ident) => ident.print_string(),
+            Self::Wildcard(token) => token.literal.clone(),
+            Self::Array(array) => array.print_string(),
+            Self::Hash(hash) => hash.print_string(),
+            Self::Variant(variant) => variant.print_string(),
+        }
+    }
+}
+
+/// `Rect(w, h)` or `Empty`, matches a value built by that enum variant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantPattern {
+    pub name: Identifier,
+    pub payload: Vec<Pattern>,
+}
+
+impl Node for VariantPattern {
+    fn token_literal(&self) -> String {
+        self.name.token_literal()
+    }
+
+    fn print_string(&self) -> String {
+        if self.payload.is_empty() {
+            return self.name.print_string();
+        }
+
+        let payload: Vec<String> = self.payload.iter().map(|p| p.print_string()).collect();
+        format!("{}({})", self.name.print_string(), payload.join(", "))
+    }
+}
+
+/// `[first, _, 3]` or `[head, ...tail]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayPattern {
+    pub token: Token,
+    pub elements: Vec<Pattern>,
+    /// Collects the remaining elements after `elements`.
+    pub rest: Option<Identifier>,
+}
+
+impl Node for ArrayPattern {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let mut elements: Vec<String> = self.elements.iter().map(|el| el.print_string()).collect();
+        if let Some(rest) = &self.rest {
+            elements.push(format!("...{}", rest.print_string()));
+        }
+
+        format!("[{}]", elements.join(", "))
+    }
+}
+
+/// `{"name": n, age}`, a bare key binds the value to a name of the same spelling.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashPattern {
+    pub token: Token,
+    pub pairs: Vec<(StringLiteral, Pattern)>,
+}
+
+impl Node for HashPattern {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let pairs: Vec<String> = self
+            .pairs
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key.print_string(), value.print_string()))
+            .collect();
+
+        format!("{{{}}}", pairs.join(", "))
+    }
+}
+
+/// Optional type annotation: `int`, `[string]`, `{string: int}` or `fn(int) -> bool`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeExpr {
+    Named(Identifier),
+    Array(ArrayType),
+    Hash(HashType),
+    Function(FunctionType),
+}
+
+impl Node for TypeExpr {
+    fn token_literal(&self) -> String {
+        match self {
+            Self::Named(name) => name.token_literal(),
+            Self::Array(array) => array.token_literal(),
+            Self::Hash(hash) => hash.token_literal(),
+            Self::Function(func) => func.token_literal(),
+        }
+    }
+
+    fn print_string(&self) -> String {
+        match self {
+            Self::Named(name) => name.print_string(),
+            Self::Array(array) => array.print_string(),
+            Self::Hash(hash) => hash.print_string(),
+            Self::Function(func) => func.print_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayType {
+    pub token: Token,
+    pub element: Box<TypeExpr>,
+}
+
+impl Node for ArrayType {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        format!("[{}]", self.element.print_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashType {
+    pub token: Token,
+    pub key: Box<TypeExpr>,
+    pub value: Box<TypeExpr>,
+}
+
+impl Node for HashType {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        format!(
+            "{{{}: {}}}",
+            self.key.print_string(),
+            self.value.print_string()
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionType {
+    pub token: Token,
+    pub parameters: Vec<TypeExpr>,
+    pub return_type: Box<TypeExpr>,
+}
+
+impl Node for FunctionType {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print_string(&self) -> String {
+        let params: Vec<String> = self.parameters.iter().map(|p| p.print_string()).collect();
+
+        format!(
+            "fn({}) -> {}",
+            params.join(", "),
+            self.return_type.print_string()
+        )
+    }
+}
+
+/// Read-only traversal of the AST.
+///
+/// Every method walks the children of its node by default, so an analysis
+/// overrides only the nodes it cares about and calls the matching `walk_*`
+/// function to keep descending. Macro bodies are templates and are not walked.
+///
+/// [`Visitor::visit_identifier`] sees every name that refers to a binding,
+/// both uses and assignment targets, the names a pattern declares go through
+/// [`Visitor::visit_pattern`].
+pub trait Visitor<'a> {
+    fn visit_program(&mut self, program: &'a Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_statement(&mut self, stmt: &'a StatementNode) {
+        walk_statement(self, stmt);
+    }
+
+    fn visit_block(&mut self, block: &'a BlockStatement) {
+        walk_block(self, block);
+    }
+
+    fn visit_expression(&mut self, expression: &'a ExpressionNode) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'a Pattern) {
+        walk_pattern(self, pattern);
+    }
+
+    fn visit_identifier(&mut self, _ident: &'a Identifier) {}
+}
+
+pub fn walk_program<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, program: &'a Program) {
+    for stmt in &program.statements {
+        visitor.visit_statement(stmt);
+    }
+}
+
+pub fn walk_block<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, block: &'a BlockStatement) {
+    for stmt in &block.statements {
+        visitor.visit_statement(stmt);
+    }
+}
+
+pub fn walk_statement<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a StatementNode) {
+    match stmt {
+        StatementNode::Let(let_stmt)
+        | StatementNode::Infix(InfixDeclaration {
+            binding: let_stmt, ..
+        })
+        | StatementNode::Export(ExportStatement {
+            binding: let_stmt, ..
+        }) => {
+            visitor.visit_pattern(&let_stmt.name);
+            if let Some(value) = &let_stmt.value {
+                visitor.visit_expression(value);
+            }
+        }
+        StatementNode::Return(ret_stmt) => {
+            if let Some(value) = &ret_stmt.ret_value {
+                visitor.visit_expression(value);
+            }
+        }
+        StatementNode::Expression(exp_stmt) => {
+            if let Some(expression) = &exp_stmt.expression {
+                visitor.visit_expression(expression);
+            }
+        }
+        StatementNode::Throw(throw) => visitor.visit_expression(&throw.value),
+        StatementNode::Yield(yield_stmt) => visitor.visit_expression(&yield_stmt.value),
+        StatementNode::For(for_stmt) => {
+            visitor.visit_expression(&for_stmt.iterable);
+            visitor.visit_block(&for_stmt.body);
+        }
+        StatementNode::Try(try_stmt) => {
+            visitor.visit_block(&try_stmt.body);
+            if let Some(catch) = &try_stmt.catch {
+                visitor.visit_block(&catch.body);
+            }
+            if let Some(finally) = &try_stmt.finally {
+                visitor.visit_block(finally);
+            }
+        }
+        StatementNode::Import(_) | StatementNode::Struct(_) | StatementNode::Enum(_) => {}
+    }
+}
+
+pub fn walk_expression<'a, V: Visitor<'a> + ?Sized>(
+    visitor: &mut V,
+    expression: &'a ExpressionNode,
+) {
+    match expression {
+        ExpressionNode::Array(array) => {
+            for element in &array.elements {
+                visitor.visit_expression(element);
+            }
+        }
+        ExpressionNode::Hash(hash) => {
+            for (key, value) in &hash.pairs {
+                visitor.visit_expression(key);
+                visitor.visit_expression(value);
+            }
+        }
+        ExpressionNode::Prefix(prefix) => visitor.visit_expression(&prefix.right),
+        ExpressionNode::Infix(infix) => {
+            visitor.visit_expression(&infix.left);
+            visitor.visit_expression(&infix.right);
+        }
+        ExpressionNode::Match(match_exp) => {
+            visitor.visit_expression(&match_exp.subject);
+            for arm in &match_exp.arms {
+                visitor.visit_pattern(&arm.pattern);
+                if let Some(guard) = &arm.guard {
+                    visitor.visit_expression(guard);
+                }
+                visitor.visit_expression(&arm.body);
+            }
+        }
+        ExpressionNode::If(if_exp) => {
+            visitor.visit_expression(&if_exp.condition);
+            visitor.visit_block(&if_exp.consequence);
+            if let Some(alternative) = &if_exp.alternative {
+                visitor.visit_block(alternative);
+            }
+        }
+        ExpressionNode::Function(func) => {
+            for param in &func.parameters {
+                if let Some(default) = &param.default {
+                    visitor.visit_expression(default);
+                }
+            }
+            visitor.visit_block(&func.body);
+        }
+        ExpressionNode::Call(call) => {
+            visitor.visit_expression(&call.function);
+            for arg in &call.arguments {
+                visitor.visit_expression(&arg.value);
+            }
+        }
+        ExpressionNode::Member(member) => visitor.visit_expression(&member.object),
+        ExpressionNode::Struct(literal) => {
+            for (_, value) in &literal.fields {
+                visitor.visit_expression(value);
+            }
+        }
+        ExpressionNode::Index(index) => {
+            visitor.visit_expression(&index.left);
+            visitor.visit_expression(&index.index);
+        }
+        ExpressionNode::Range(range) => {
+            for bound in [&range.start, &range.end].into_iter().flatten() {
+                visitor.visit_expression(bound);
+            }
+        }
+        ExpressionNode::Assign(assign) => {
+            visitor.visit_identifier(&assign.name);
+            visitor.visit_expression(&assign.value);
+        }
+        ExpressionNode::IdentifierNode(ident) => visitor.visit_identifier(ident),
+        ExpressionNode::Integer(_)
+        | ExpressionNode::Boolean(_)
+        | ExpressionNode::Null(_)
+        | ExpressionNode::StringNode(_)
+        | ExpressionNode::Macro(_) => {}
+    }
+}
+
+pub fn walk_pattern<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, pattern: &'a Pattern) {
+    match pattern {
+        Pattern::Array(array) => {
+            for element in &array.elements {
+                visitor.visit_pattern(element);
+            }
+        }
+        Pattern::Hash(hash) => {
+            for (_, value) in &hash.pairs {
+                visitor.visit_pattern(value);
+            }
+        }
+        Pattern::Variant(variant) => {
+            for element in &variant.payload {
+                visitor.visit_pattern(element);
+            }
+        }
+        Pattern::Integer(_)
+        | Pattern::Boolean(_)
+        | Pattern::StringNode(_)
+        | Pattern::Binding(_)
+        | Pattern::Wildcard(_) => {}
+    }
+}
+
+/// Rewriting traversal of the AST, each method takes a node and returns its
+/// replacement.
+///
+/// By default every method rebuilds its node from folded children, so a
+/// rewrite overrides only the nodes it changes and calls the matching
+/// `fold_*` function to fold their children. Macro bodies are left as written.
+///
+/// Like [`Visitor::visit_identifier`], [`Fold::fold_identifier`] gets the
+/// names that refer to a binding and [`Fold::fold_pattern`] the ones bound.
+pub trait Fold {
+    fn fold_program(&mut self, program: Program) -> Program {
+        fold_program(self, program)
+    }
+
+    fn fold_statement(&mut self, stmt: StatementNode) -> StatementNode {
+        fold_statement(self, stmt)
+    }
+
+    fn fold_block(&mut self, block: BlockStatement) -> BlockStatement {
+        fold_block(self, block)
+    }
+
+    fn fold_expression(&mut self, expression: ExpressionNode) -> ExpressionNode {
+        fold_expression(self, expression)
+    }
+
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        fold_pattern(self, pattern)
+    }
+
+    fn fold_identifier(&mut self, ident: Identifier) -> Identifier {
+        ident
+    }
+}
+
+pub fn fold_program<F: Fold + ?Sized>(folder: &mut F, program: Program) -> Program {
+    Program {
+        statements: program
+            .statements
+            .into_iter()
+            .map(|stmt| folder.fold_statement(stmt))
+            .collect(),
+    }
+}
+
+pub fn fold_block<F: Fold + ?Sized>(folder: &mut F, mut block: BlockStatement) -> BlockStatement {
+    block.statements = block
+        .statements
+        .into_iter()
+        .map(|stmt| folder.fold_statement(stmt))
+        .collect();
+    block
+}
+
+pub fn fold_statement<F: Fold + ?Sized>(folder: &mut F, stmt: StatementNode) -> StatementNode {
+    match stmt {
+        StatementNode::Let(let_stmt) => StatementNode::Let(fold_let(folder, let_stmt)),
+        StatementNode::Infix(mut infix) => {
+            infix.binding = fold_let(folder, infix.binding);
+            StatementNode::Infix(infix)
+        }
+        StatementNode::Export(mut export) => {
+            export.binding = fold_let(folder, export.binding);
+            StatementNode::Export(export)
+        }
+        StatementNode::Return(mut ret_stmt) => {
+            ret_stmt.ret_value = fold_optional(folder, ret_stmt.ret_value);
+            StatementNode::Return(ret_stmt)
+        }
+        StatementNode::Expression(mut exp_stmt) => {
+            exp_stmt.expression = fold_optional(folder, exp_stmt.expression);
+            StatementNode::Expression(exp_stmt)
+        }
+        StatementNode::Throw(mut throw) => {
+            throw.value = folder.fold_expression(throw.value);
+            StatementNode::Throw(throw)
+        }
+        StatementNode::Yield(mut yield_stmt) => {
+            yield_stmt.value = folder.fold_expression(yield_stmt.value);
+            StatementNode::Yield(yield_stmt)
+        }
+        StatementNode::For(mut for_stmt) => {
+            for_stmt.iterable = folder.fold_expression(for_stmt.iterable);
+            for_stmt.body = folder.fold_block(for_stmt.body);
+            StatementNode::For(for_stmt)
+        }
+        StatementNode::Try(mut try_stmt) => {
+            try_stmt.body = folder.fold_block(try_stmt.body);
+            try_stmt.catch = try_stmt.catch.map(|mut catch| {
+                catch.body = folder.fold_block(catch.body);
+                catch
+            });
+            try_stmt.finally = try_stmt.finally.map(|finally| folder.fold_block(finally));
+            StatementNode::Try(try_stmt)
+        }
+        stmt @ (StatementNode::Import(_) | StatementNode::Struct(_) | StatementNode::Enum(_)) => {
+            stmt
+        }
+    }
+}
+
+fn fold_let<F: Fold + ?Sized>(folder: &mut F, mut let_stmt: LetStatement) -> LetStatement {
+    let_stmt.name = folder.fold_pattern(let_stmt.name);
+    let_stmt.value = fold_optional(folder, let_stmt.value);
+    let_stmt
+}
+
+pub fn fold_expression<F: Fold + ?Sized>(
+    folder: &mut F,
+    expression: ExpressionNode,
+) -> ExpressionNode {
+    match expression {
+        ExpressionNode::Array(mut array) => {
+            array.elements = array
+                .elements
+                .into_iter()
+                .map(|element| folder.fold_expression(element))
+                .collect();
+            ExpressionNode::Array(array)
+        }
+        ExpressionNode::Hash(mut hash) => {
+            hash.pairs = hash
+                .pairs
+                .into_iter()
+                .map(|(key, value)| (folder.fold_expression(key), folder.fold_expression(value)))
+                .collect();
+            ExpressionNode::Hash(hash)
+        }
+        ExpressionNode::Prefix(mut prefix) => {
+            prefix.right = Box::new(folder.fold_expression(*prefix.right));
+            ExpressionNode::Prefix(prefix)
+        }
+        ExpressionNode::Infix(mut infix) => {
+            infix.left = Box::new(folder.fold_expression(*infix.left));
+            infix.right = Box::new(folder.fold_expression(*infix.right));
+            ExpressionNode::Infix(infix)
+        }
+        ExpressionNode::Match(mut match_exp) => {
+            match_exp.subject = Box::new(folder.fold_expression(*match_exp.subject));
+            match_exp.arms = match_exp
+                .arms
+                .into_iter()
+                .map(|mut arm| {
+                    arm.pattern = folder.fold_pattern(arm.pattern);
+                    arm.guard = fold_optional(folder, arm.guard);
+                    arm.body = folder.fold_expression(arm.body);
+                    arm
+                })
+                .collect();
+            ExpressionNode::Match(match_exp)
+        }
+        ExpressionNode::If(mut if_exp) => {
+            if_exp.condition = Box::new(folder.fold_expression(*if_exp.condition));
+            if_exp.consequence = folder.fold_block(if_exp.consequence);
+            if_exp.alternative = if_exp
+                .alternative
+                .map(|alternative| folder.fold_block(alternative));
+            ExpressionNode::If(if_exp)
+        }
+        ExpressionNode::Function(mut func) => {
+            func.parameters = func
+                .parameters
+                .into_iter()
+                .map(|mut param| {
+                    param.default = fold_optional(folder, param.default);
+                    param
+                })
+                .collect();
+            func.body = folder.fold_block(func.body);
+            ExpressionNode::Function(func)
+        }
+        ExpressionNode::Call(mut call) => {
+            call.function = Box::new(folder.fold_expression(*call.function));
+            call.arguments = call
+                .arguments
+                .into_iter()
+                .map(|mut arg| {
+                    arg.value = folder.fold_expression(arg.value);
+                    arg
+                })
+                .collect();
+            ExpressionNode::Call(call)
+        }
+        ExpressionNode::Member(mut member) => {
+            member.object = Box::new(folder.fold_expression(*member.object));
+            ExpressionNode::Member(member)
+        }
+        ExpressionNode::Struct(mut literal) => {
+            literal.fields = literal
+                .fields
+                .into_iter()
+                .map(|(name, value)| (name, folder.fold_expression(value)))
+                .collect();
+            ExpressionNode::Struct(literal)
+        }
+        ExpressionNode::Index(mut index) => {
+            index.left = Box::new(folder.fold_expression(*index.left));
+            index.index = Box::new(folder.fold_expression(*index.index));
+            ExpressionNode::Index(index)
+        }
+        ExpressionNode::Range(mut range) => {
+            range.start = range
+                .start
+                .map(|start| Box::new(folder.fold_expression(*start)));
+            range.end = range.end.map(|end| Box::new(folder.fold_expression(*end)));
+            ExpressionNode::Range(range)
+        }
+        ExpressionNode::Assign(mut assign) => {
+            assign.name = folder.fold_identifier(assign.name);
+            assign.value = Box::new(folder.fold_expression(*assign.value));
+            ExpressionNode::Assign(assign)
+        }
+        ExpressionNode::IdentifierNode(ident) => {
+            ExpressionNode::IdentifierNode(folder.fold_identifier(ident))
+        }
+        leaf @ (ExpressionNode::Integer(_)
+        | ExpressionNode::Boolean(_)
+        | ExpressionNode::Null(_)
+        | ExpressionNode::StringNode(_)
+        | ExpressionNode::Macro(_)) => leaf,
+    }
+}
+
+pub fn fold_pattern<F: Fold + ?Sized>(folder: &mut F, pattern: Pattern) -> Pattern {
+    match pattern {
+        Pattern::Array(mut array) => {
+            array.elements = array
+                .elements
+                .into_iter()
+                .map(|element| folder.fold_pattern(element))
+                .collect();
+            Pattern::Array(array)
+        }
+        Pattern::Hash(mut hash) => {
+            hash.pairs = hash
+                .pairs
+                .into_iter()
+                .map(|(key, value)| (key, folder.fold_pattern(value)))
+                .collect();
+            Pattern::Hash(hash)
+        }
+        Pattern::Variant(mut variant) => {
+            variant.payload = variant
+                .payload
+                .into_iter()
+                .map(|element| folder.fold_pattern(element))
+                .collect();
+            Pattern::Variant(variant)
+        }
+        leaf @ (Pattern::Integer(_)
+        | Pattern::Boolean(_)
+        | Pattern::StringNode(_)
+        | Pattern::Binding(_)
+        | Pattern::Wildcard(_)) => leaf,
+    }
+}
+
+fn fold_optional<F: Fold + ?Sized>(
+    folder: &mut F,
+    expression: Option<ExpressionNode>,
+) -> Option<ExpressionNode> {
+    expression.map(|expression| folder.fold_expression(expression))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ast::Node,
+        build::{call, expr_stmt, ident, infix, int, let_stmt, program},
+        parse,
+    };
+
+    use super::{fold_pattern, walk_pattern, Fold, Identifier, Pattern, Visitor};
+
+    /// Collects the names used and bound, in traversal order.
+    #[derive(Default)]
+    struct Names {
+        used: Vec<String>,
+        bound: Vec<String>,
+    }
+
+    impl<'a> Visitor<'a> for Names {
+        fn visit_identifier(&mut self, ident: &'a Identifier) {
+            self.used.push(ident.value.clone());
+        }
+
+        fn visit_pattern(&mut self, pattern: &'a Pattern) {
+            if let Pattern::Binding(ident) = pattern {
+                self.bound.push(ident.value.clone());
+            }
+            walk_pattern(self, pattern);
+        }
+    }
+
+    /// Renames every `x` to `y`, both where it is bound and where it is used.
+    struct Rename;
+
+    impl Rename {
+        fn rename(mut ident: Identifier) -> Identifier {
+            if ident.value == "x" {
+                ident.value = String::from("y");
+                ident.token.literal = String::from("y");
+            }
+            ident
+        }
+    }
+
+    impl Fold for Rename {
+        fn fold_identifier(&mut self, ident: Identifier) -> Identifier {
+            Self::rename(ident)
+        }
+
+        fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+            match pattern {
+                Pattern::Binding(ident) => Pattern::Binding(Self::rename(ident)),
+                other => fold_pattern(self, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_walks_every_child() {
+        let program = crate::parse(
+            "let [a, ...b] = xs; \
+             let f = fn(p = d) { for i in r { try { yield i?.[j]; } catch (e) { throw e; } } }; \
+             match (s) { Some(v) if g => h(k, n: m), _ => [t, {u: w}] }; \
+             z = P { q: 1..end } ?? -o.field; \
+             macro(in_macro) { in_macro };",
+        )
+        .unwrap();
+
+        let mut names = Names::default();
+        names.visit_program(&program);
+
+        assert_eq!(
+            names.used,
+            vec![
+                "xs", "d", "r", "i", "j", "e", "s", "g", "h", "k", "m", "t", "u", "w", "z", "end",
+                "o"
+            ]
+        );
+        assert_eq!(names.bound, vec!["a", "f", "v"]);
+    }
+
+    #[test]
+    fn test_fold_rewrites_every_child() {
+        let program = crate::parse(
+            "let a = fn(p = x) { for i in x { yield x[x..]; } }; \
+             let [_, {k: x}] = match (x) { [x] if x => f(x, n: x) }; \
+             x = P { q: x } ?? {x: [-x]}; \
+             macro(x) { x };",
+        )
+        .unwrap();
+
+        let program = Rename.fold_program(program);
+
+        assert_eq!(
+            program.print_string(),
+            "let a = fn(p = y) for i in y { yield (y[(y..)]); };\
+             let [_, {\"k\": y}] = match (y) { [y] if y => f(y, n: y) };\
+             (y = (P { q: y } ?? {y: [(-y)]}))\
+             macro(x) x"
+        );
+    }
+
+    #[test]
+    fn test_print_string() {
+        let program = program(vec![let_stmt("myVar", ident("anotherVar"))]);
+
+        assert_eq!(
+            program.print_string(),
+            String::from("let myVar = anotherVar;"),
+            "print string wrong. got = {}",
+            program.print_string()
+        )
+    }
+
+    #[test]
+    fn test_equality_ignores_spans() {
+        let parsed = parse("let x = 1 + f(y);\nx").unwrap();
+
+        assert_eq!(parsed, parse("let   x =\n  1+f( y )\n\nx").unwrap());
+        assert_eq!(
+            parsed,
+            program(vec![
+                let_stmt("x", infix(int(1), "+", call(ident("f"), vec![ident("y")]))),
+                expr_stmt(ident("x")),
+            ])
+        );
+        assert_ne!(parsed, parse("let x = 1 + f(y);\ny").unwrap());
+        assert_ne!(parsed, parse("const x = 1 + f(y);\nx").unwrap());
+        assert_eq!(parsed.clone(), parsed);
     }
 }
@@ -0,0 +1,243 @@
+use crate::{
+    ast::{
+        Argument, ArrayLiteral, AssignExpression, BlockStatement, BooleanLiteral, CallExpression,
+        ExpressionNode, ExpressionStatement, FunctionLiteral, HashLiteral, Identifier,
+        IndexExpression, InfixExpression, IntegerLiteral, LetStatement, MemberExpression,
+        NullLiteral, Parameter, Pattern, PrefixExpression, Program, ReturnStatement, StatementNode,
+        StringLiteral,
+    },
+    token::{Span, Token, TokenKind},
+};
+
+fn token(kind: TokenKind, literal: &str) -> Token {
+    Token {
+        kind,
+        literal: literal.to_string(),
+        ..Default::default()
+    }
+}
+
+fn name(value: &str) -> Identifier {
+    Identifier {
+        token: token(TokenKind::Ident, value),
+        value: value.to_string(),
+    }
+}
+
+/// The functions here build nodes with the tokens the parser would give them,
+/// so that tests can compare a parsed program to one written out, like
+/// `program(vec![let_stmt("x", int(5))])` for `let x = 5;`.
+///
+/// The spans are left empty, which [`PartialEq`] on the tree ignores.
+pub fn program(statements: Vec<StatementNode>) -> Program {
+    Program { statements }
+}
+
+pub fn let_stmt(name: &str, value: ExpressionNode) -> StatementNode {
+    binding(token(TokenKind::Let, "let"), name, value)
+}
+
+pub fn const_stmt(name: &str, value: ExpressionNode) -> StatementNode {
+    binding(token(TokenKind::Const, "const"), name, value)
+}
+
+fn binding(token: Token, name: &str, value: ExpressionNode) -> StatementNode {
+    StatementNode::Let(LetStatement {
+        token,
+        span: Span::default(),
+        name: Pattern::Binding(self::name(name)),
+        annotation: None,
+        value: Some(value),
+    })
+}
+
+pub fn return_stmt(value: ExpressionNode) -> StatementNode {
+    StatementNode::Return(ReturnStatement {
+        token: token(TokenKind::Return, "return"),
+        span: Span::default(),
+        ret_value: Some(value),
+    })
+}
+
+/// An expression statement, with the token the expression starts with when
+/// it is not wrapped in parentheses.
+pub fn expr_stmt(expression: ExpressionNode) -> StatementNode {
+    StatementNode::Expression(ExpressionStatement {
+        token: first_token(&expression).clone(),
+        span: Span::default(),
+        expression: Some(expression),
+    })
+}
+
+fn first_token(expression: &ExpressionNode) -> &Token {
+    match expression {
+        ExpressionNode::Infix(infix) => first_token(&infix.left),
+        ExpressionNode::Call(call) => first_token(&call.function),
+        ExpressionNode::Member(member) => first_token(&member.object),
+        ExpressionNode::Index(index) => first_token(&index.left),
+        ExpressionNode::Range(range) => match &range.start {
+            Some(start) => first_token(start),
+            None => &range.token,
+        },
+        ExpressionNode::Assign(assign) => &assign.name.token,
+        ExpressionNode::Struct(literal) => &literal.name.token,
+        expression => expression.token(),
+    }
+}
+
+pub fn block(statements: Vec<StatementNode>) -> BlockStatement {
+    BlockStatement {
+        token: token(TokenKind::Lbrace, "{"),
+        span: Span::default(),
+        statements,
+    }
+}
+
+pub fn ident(value: &str) -> ExpressionNode {
+    ExpressionNode::IdentifierNode(name(value))
+}
+
+pub fn int(value: i64) -> ExpressionNode {
+    ExpressionNode::Integer(IntegerLiteral {
+        token: token(TokenKind::Int, &value.to_string()),
+        value,
+    })
+}
+
+pub fn boolean(value: bool) -> ExpressionNode {
+    let token = match value {
+        true => token(TokenKind::True, "true"),
+        false => token(TokenKind::False, "false"),
+    };
+
+    ExpressionNode::Boolean(BooleanLiteral { token, value })
+}
+
+pub fn string(value: &str) -> ExpressionNode {
+    ExpressionNode::StringNode(StringLiteral {
+        token: token(TokenKind::String, value),
+        value: value.to_string(),
+    })
+}
+
+pub fn null() -> ExpressionNode {
+    ExpressionNode::Null(NullLiteral {
+        token: token(TokenKind::Null, "null"),
+    })
+}
+
+pub fn array(elements: Vec<ExpressionNode>) -> ExpressionNode {
+    ExpressionNode::Array(ArrayLiteral {
+        token: token(TokenKind::Lbracket, "["),
+        span: Span::default(),
+        elements,
+    })
+}
+
+pub fn hash(pairs: Vec<(ExpressionNode, ExpressionNode)>) -> ExpressionNode {
+    ExpressionNode::Hash(HashLiteral {
+        token: token(TokenKind::Lbrace, "{"),
+        span: Span::default(),
+        pairs,
+    })
+}
+
+/// `-` or `!` applied to `right`.
+pub fn prefix(operator: &str, right: ExpressionNode) -> ExpressionNode {
+    let kind = match operator {
+        "-" => TokenKind::Minus,
+        "!" => TokenKind::Bang,
+        _ => panic!("`{}` is not a prefix operator", operator),
+    };
+
+    ExpressionNode::Prefix(PrefixExpression {
+        token: token(kind, operator),
+        span: Span::default(),
+        operator: operator.to_string(),
+        right: Box::new(right),
+    })
+}
+
+/// One of the built in binary operators, declared ones parse into calls.
+pub fn infix(left: ExpressionNode, operator: &str, right: ExpressionNode) -> ExpressionNode {
+    let kind = match operator {
+        "+" => TokenKind::Plus,
+        "-" => TokenKind::Minus,
+        "*" => TokenKind::Asteriks,
+        "/" => TokenKind::Slash,
+        "<" => TokenKind::Lt,
+        ">" => TokenKind::Gt,
+        "==" => TokenKind::Eq,
+        "!=" => TokenKind::NotEq,
+        "??" => TokenKind::QuestionQuestion,
+        _ => panic!("`{}` is not an infix operator", operator),
+    };
+
+    ExpressionNode::Infix(InfixExpression {
+        token: token(kind, operator),
+        span: Span::default(),
+        left: Box::new(left),
+        operator: operator.to_string(),
+        right: Box::new(right),
+    })
+}
+
+pub fn assign(name: &str, value: ExpressionNode) -> ExpressionNode {
+    ExpressionNode::Assign(AssignExpression {
+        token: token(TokenKind::Assign, "="),
+        span: Span::default(),
+        name: self::name(name),
+        value: Box::new(value),
+    })
+}
+
+pub fn call(function: ExpressionNode, arguments: Vec<ExpressionNode>) -> ExpressionNode {
+    ExpressionNode::Call(CallExpression {
+        token: token(TokenKind::Lparen, "("),
+        span: Span::default(),
+        function: Box::new(function),
+        arguments: arguments
+            .into_iter()
+            .map(|value| Argument { name: None, value })
+            .collect(),
+    })
+}
+
+pub fn member(object: ExpressionNode, property: &str) -> ExpressionNode {
+    ExpressionNode::Member(MemberExpression {
+        token: token(TokenKind::Dot, "."),
+        span: Span::default(),
+        object: Box::new(object),
+        property: name(property),
+        optional: false,
+    })
+}
+
+pub fn index(left: ExpressionNode, index: ExpressionNode) -> ExpressionNode {
+    ExpressionNode::Index(IndexExpression {
+        token: token(TokenKind::Lbracket, "["),
+        span: Span::default(),
+        left: Box::new(left),
+        index: Box::new(index),
+        optional: false,
+    })
+}
+
+/// A function literal with plain parameters, without defaults or types.
+pub fn function(parameters: &[&str], body: Vec<StatementNode>) -> ExpressionNode {
+    ExpressionNode::Function(FunctionLiteral {
+        token: token(TokenKind::Function, "fn"),
+        span: Span::default(),
+        parameters: parameters
+            .iter()
+            .map(|parameter| Parameter {
+                name: name(parameter),
+                annotation: None,
+                default: None,
+                rest: false,
+            })
+            .collect(),
+        return_type: None,
+        body: block(body),
+    })
+}
@@ -0,0 +1,211 @@
+use crate::{
+    arity,
+    ast::{Program, StatementNode},
+    destructure, exhaustiveness,
+    infer::{self, Scheme},
+    resolve, typecheck,
+};
+
+/// What the static passes found in a program.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Inferred type of every top-level `let` binding, in source order.
+    pub bindings: Vec<(String, Scheme)>,
+    pub errors: Vec<String>,
+    /// What type inference rejects. A dynamic program like `puts([1, "a"])`
+    /// still runs, so only `check` treats these as errors.
+    pub type_errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Runs the arity, const, type annotation, destructuring, type inference and
+/// exhaustiveness passes, which the REPL, `check` and `run` report on.
+///
+/// A problem found by another pass is not reported again by inference, which
+/// words it differently but at the same span.
+/// Macros should be expanded first, the passes do not look into macro bodies.
+pub fn check_program(program: &Program) -> Report {
+    let inference = infer::infer_program(program);
+
+    let mut errors = arity::check_program(program);
+    errors.extend(resolve::check_program(program));
+    errors.extend(typecheck::check_program(program));
+    errors.extend(destructure::check_program(program));
+    let spans: Vec<&str> = errors.iter().filter_map(|error| span_of(error)).collect();
+    let type_errors = inference
+        .errors
+        .into_iter()
+        .filter(|error| {
+            !errors.contains(error) && span_of(error).is_none_or(|span| !spans.contains(&span))
+        })
+        .collect();
+
+    Report {
+        bindings: inference.bindings,
+        errors,
+        type_errors,
+        warnings: exhaustiveness::check_program(program),
+    }
+}
+
+/// Checks code entered a piece at a time, like the lines of the REPL, each
+/// piece together with the pieces accepted before it.
+#[derive(Default)]
+pub struct Session {
+    accepted: Vec<StatementNode>,
+    /// What the accepted pieces were reported for already.
+    reported: Report,
+    /// The last piece checked and the report on it with the accepted ones.
+    pending: Option<(Vec<StatementNode>, Report)>,
+}
+
+impl Session {
+    /// Checks `program` after the accepted pieces and reports what they were
+    /// not reported for, so it sees their declarations and not their problems.
+    pub fn check(&mut self, program: &Program) -> Report {
+        let mut statements = self.accepted.clone();
+        statements.extend(program.statements.iter().cloned());
+        let report = check_program(&Program {
+            statements: statements.clone(),
+        });
+
+        let new = Report {
+            bindings: report.bindings.clone(),
+            errors: unseen(&report.errors, &self.reported.errors),
+            type_errors: unseen(&report.type_errors, &self.reported.type_errors),
+            warnings: unseen(&report.warnings, &self.reported.warnings),
+        };
+        self.pending = Some((statements, report));
+        new
+    }
+
+    /// Keeps the piece checked last for the checks of the ones after it.
+    pub fn accept(&mut self) {
+        if let Some((statements, report)) = self.pending.take() {
+            self.accepted = statements;
+            self.reported = report;
+        }
+    }
+}
+
+/// The messages in `messages` less one of each in `seen`.
+fn unseen(messages: &[String], seen: &[String]) -> Vec<String> {
+    let mut seen = seen.to_vec();
+    messages
+        .iter()
+        .filter(|message| match seen.iter().position(|s| s == *message) {
+            Some(idx) => {
+                seen.swap_remove(idx);
+                false
+            }
+            None => true,
+        })
+        .cloned()
+        .collect()
+}
+
+/// The span an error starts with, like `1:5-6` in `1:5-6: unknown type`.
+fn span_of(error: &str) -> Option<&str> {
+    let (span, _) = error.split_once(": ")?;
+    span.starts_with(|c: char| c.is_ascii_digit())
+        .then_some(span)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse;
+
+    use super::{check_program, Session};
+
+    #[test]
+    fn test_check_program() {
+        let tests = vec![
+            (
+                "let f = fn() { 1 }; f(1);",
+                vec!["1:22-23: wrong number of arguments: expected 0, got 1 in call `f(1)`"],
+                vec![],
+                vec![],
+            ),
+            (
+                "let x = null; x.y;",
+                vec!["1:17-18: can not access `y` on null in `x.y`, use `?.` if it may be null"],
+                vec![],
+                vec![],
+            ),
+            (
+                "let [a, b, c] = [1, 2];",
+                vec!["1:1-4: destructuring failed for `c`: expected at least 3 elements, got 2 in `let [a, b, c] = [1, 2];`"],
+                vec![],
+                vec![],
+            ),
+            (
+                "const c = 1; c = 2; match (true) { true => 1 };",
+                vec!["1:14-15: can not assign to const `c`, it is declared at 1:7-8"],
+                vec![],
+                vec!["non-exhaustive match over booleans in `match (true) { true => 1 }`: false not covered"],
+            ),
+            (
+                "let x: int = \"a\";",
+                vec!["1:14-17: type mismatch: `x` is declared as int, but the value is string"],
+                vec![],
+                vec![],
+            ),
+            (
+                "puts([1, \"a\"]);",
+                vec![],
+                vec!["1:10-13: type mismatch between int and string: int from `1` at 1:7-8, string from `\"a\"` at 1:10-13"],
+                vec![],
+            ),
+        ];
+
+        for (input, errors, type_errors, warnings) in tests {
+            let report = check_program(&parse(input).unwrap());
+
+            assert_eq!(report.errors, errors, "wrong errors for {input}");
+            assert_eq!(
+                report.type_errors, type_errors,
+                "wrong type errors for {input}"
+            );
+            assert_eq!(report.warnings, warnings, "wrong warnings for {input}");
+        }
+    }
+    #[test]
+    fn test_session() {
+        let tests = vec![
+            (vec!["struct P { x }", "P { x: 1 }.x;"], vec![]),
+            (
+                vec!["const c = 1;", "c = 2;"],
+                vec!["1:1-2: can not assign to const `c`, it is declared at 1:7-8"],
+            ),
+            (
+                vec!["let f = fn() { 1 };", "f(1);", "f(2);"],
+                vec![
+                    "1:2-3: wrong number of arguments: expected 0, got 1 in call `f(1)`",
+                    "1:2-3: wrong number of arguments: expected 0, got 1 in call `f(2)`",
+                ],
+            ),
+            (
+                vec!["puts([1, \"a\"]);", "puts([1, \"a\"]);"],
+                vec![
+                    "1:10-13: type mismatch between int and string: int from `1` at 1:7-8, string from `\"a\"` at 1:10-13",
+                    "1:10-13: type mismatch between int and string: int from `1` at 1:7-8, string from `\"a\"` at 1:10-13",
+                ],
+            ),
+        ];
+
+        for (lines, expected) in tests {
+            let mut session = Session::default();
+            let mut reported = vec![];
+            for line in &lines {
+                let report = session.check(&parse(line).unwrap());
+                if report.errors.is_empty() {
+                    session.accept();
+                }
+                reported.extend(report.errors);
+                reported.extend(report.type_errors);
+            }
+
+            assert_eq!(reported, expected, "for {lines:?}");
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::ast::{
+    walk_statement, ArrayPattern, ExportStatement, ExpressionNode, HashPattern, Node, Pattern,
+    Program, StatementNode, Visitor,
+};
+
+/// Checks every destructuring `let` whose value is an array or hash literal,
+/// also the ones in function bodies and blocks, and returns an error naming
+/// the binding that could not be filled.
+///
+/// Values that are only known at runtime, like identifiers or calls, are skipped.
+pub fn check_program(program: &Program) -> Vec<String> {
+    let mut checker = DestructureChecker { errors: vec![] };
+
+    checker.visit_program(program);
+
+    checker.errors
+}
+
+struct DestructureChecker {
+    errors: Vec<String>,
+}
+
+impl<'a> Visitor<'a> for DestructureChecker {
+    fn visit_statement(&mut self, stmt: &'a StatementNode) {
+        if let StatementNode::Let(let_stmt)
+        | StatementNode::Export(ExportStatement {
+            binding: let_stmt, ..
+        }) = stmt
+        {
+            if let Some(value) = &let_stmt.value {
+                if let Err(msg) = check_pattern(&let_stmt.name, value) {
+                    self.errors.push(format!(
+                        "{}: {} in `{}`",
+                        let_stmt.token.span,
+                        msg,
+                        let_stmt.print_string()
+                    ));
+                }
+            }
+        }
+        walk_statement(self, stmt);
+    }
+}
+
+fn check_pattern(pattern: &Pattern, value: &ExpressionNode) -> Result<(), String> {
+    match pattern {
+        Pattern::Array(array) => check_array_pattern(array, value),
+        Pattern::Hash(hash) => check_hash_pattern(hash, value),
+        _ => Ok(()),
+    }
+}
+
+fn check_array_pattern(pattern: &ArrayPattern, value: &ExpressionNode) -> Result<(), String> {
+    let elements = match value {
+        ExpressionNode::Array(array) => &array.elements,
+        other if is_literal(other) => {
+            return Err(format!(
+                "destructuring failed for `{}`: expected an array, got {}",
+                pattern.print_string(),
+                other.print_string()
+            ))
+        }
+        _ => return Ok(()),
+    };
+
+    if let Some(missing) = pattern.elements.get(elements.len()) {
+        return Err(format!(
+            "destructuring failed for `{}`: expected at least {} elements, got {}",
+            missing.print_string(),
+            pattern.elements.len(),
+            elements.len()
+        ));
+    }
+
+    if pattern.rest.is_none() && elements.len() > pattern.elements.len() {
+        return Err(format!(
+            "destructuring failed for `{}`: expected {} elements, got {}",
+            pattern.print_string(),
+            pattern.elements.len(),
+            elements.len()
+        ));
+    }
+
+    for (element, value) in pattern.elements.iter().zip(elements) {
+        check_pattern(element, value)?;
+    }
+
+    Ok(())
+}
+
+fn check_hash_pattern(pattern: &HashPattern, value: &ExpressionNode) -> Result<(), String> {
+    let pairs = match value {
+        ExpressionNode::Hash(hash) => &hash.pairs,
+        other if is_literal(other) => {
+            return Err(format!(
+                "destructuring failed for `{}`: expected a hash, got {}",
+                pattern.print_string(),
+                other.print_string()
+            ))
+        }
+        _ => return Ok(()),
+    };
+
+    // keys computed at runtime could still provide the missing one
+    if !pairs
+        .iter()
+        .all(|(key, _)| matches!(key, ExpressionNode::StringNode(_)))
+    {
+        return Ok(());
+    }
+
+    for (key, sub_pattern) in &pattern.pairs {
+        let found = pairs.iter().find(|(pair_key, _)| match pair_key {
+            ExpressionNode::StringNode(string) => string.value == key.value,
+            _ => false,
+        });
+
+        match found {
+            Some((_, value)) => check_pattern(sub_pattern, value)?,
+            None => {
+                return Err(format!(
+                    "destructuring failed for `{}`: key {} not found",
+                    sub_pattern.print_string(),
+                    key.print_string()
+                ))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn is_literal(expression: &ExpressionNode) -> bool {
+    matches!(
+        expression,
+        ExpressionNode::Integer(_)
+            | ExpressionNode::Boolean(_)
+            | ExpressionNode::Null(_)
+            | ExpressionNode::StringNode(_)
+            | ExpressionNode::Array(_)
+            | ExpressionNode::Hash(_)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{lexer::Lexer, parser::Parser};
+
+    use super::check_program;
+
+    fn errors_for(input: &str) -> Vec<String> {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert!(
+            parser.errors().is_empty(),
+            "parser errors: {:?}",
+            parser.errors()
+        );
+
+        check_program(&program)
+    }
+
+    #[test]
+    fn test_valid_destructuring() {
+        let tests = vec![
+            "let [a, b] = [1, 2];",
+            "let [a, ...rest] = [1, 2, 3];",
+            "let [a, b, ...rest] = [1, 2];",
+            r#"let {name, age} = {"name": "Ali", "age": 30, "city": "Baku"};"#,
+            r#"let {"pos": [x, y]} = {"pos": [1, 2]};"#,
+            "let [a, b] = xs;",
+            "let {name} = {key: 1};",
+            "let f = fn() { let [a, b] = [1, 2]; };",
+        ];
+
+        for input in tests {
+            let errors = errors_for(input);
+            assert!(
+                errors.is_empty(),
+                "unexpected errors for {}: {:?}",
+                input,
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn test_destructuring_errors_name_the_binding() {
+        let tests = vec![
+            (
+                "let [a, b, ...rest] = [1];",
+                "1:1-4: destructuring failed for `b`: expected at least 2 elements, got 1 in `let [a, b, ...rest] = [1];`",
+            ),
+            (
+                "let [a, b] = [1, 2, 3];",
+                "1:1-4: destructuring failed for `[a, b]`: expected 2 elements, got 3 in `let [a, b] = [1, 2, 3];`",
+            ),
+            (
+                r#"let {name, age} = {"name": "Ali"};"#,
+                r#"1:1-4: destructuring failed for `age`: key "age" not found in `let {"name": name, "age": age} = {"name": "Ali"};`"#,
+            ),
+            (
+                r#"let {"pos": [x, y]} = {"pos": [1]};"#,
+                r#"1:1-4: destructuring failed for `y`: expected at least 2 elements, got 1 in `let {"pos": [x, y]} = {"pos": [1]};`"#,
+            ),
+            (
+                "let {a} = null;",
+                "1:1-4: destructuring failed for `{\"a\": a}`: expected a hash, got null in `let {\"a\": a} = null;`",
+            ),
+            (
+                "let [a] = 5;",
+                "1:1-4: destructuring failed for `[a]`: expected an array, got 5 in `let [a] = 5;`",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let errors = errors_for(input);
+            assert_eq!(errors, vec![expected], "wrong errors for {}", input);
+        }
+    }
+
+    #[test]
+    fn test_nested_destructuring_errors() {
+        let tests = vec![
+            (
+                "let f = fn() { let [a, b] = [1]; };",
+                "1:16-19: destructuring failed for `b`: expected at least 2 elements, got 1 in `let [a, b] = [1];`",
+            ),
+            (
+                "for x in xs { try { let [a] = [1, 2]; } catch (e) { 1 } }",
+                "1:21-24: destructuring failed for `[a]`: expected 1 elements, got 2 in `let [a] = [1, 2];`",
+            ),
+            (
+                "match (x) { _ => fn() { let {k} = {}; } };",
+                "1:25-28: destructuring failed for `k`: key \"k\" not found in `let {\"k\": k} = {};`",
+            ),
+            (
+                "let [a] = [fn() { let [b] = 5; }];",
+                "1:19-22: destructuring failed for `[b]`: expected an array, got 5 in `let [b] = 5;`",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let errors = errors_for(input);
+            assert_eq!(errors, vec![expected], "wrong errors for {}", input);
+        }
+    }
+}
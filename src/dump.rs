@@ -0,0 +1,547 @@
+use crate::{
+    ast::{
+        BlockStatement, ExpressionNode, Identifier, LetStatement, Node, Parameter, Pattern,
+        Program, StatementNode, TypeExpr,
+    },
+    serialize,
+    token::TokenKind,
+};
+
+/// Lines of an S-expression longer than this are broken, one child per line.
+const WIDTH: usize = 80;
+
+/// The ways a program can be dumped, next to [`Node::print_string`](crate::ast::Node).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    SExpr,
+    Dot,
+    Json,
+}
+
+impl Format {
+    /// The format called `name`, one of `sexpr`, `dot` and `json`.
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "sexpr" => Some(Format::SExpr),
+            "dot" => Some(Format::Dot),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    pub fn render(&self, program: &Program) -> String {
+        match self {
+            Format::SExpr => to_sexpr(program),
+            Format::Dot => to_dot(program),
+            Format::Json => serialize::to_json(program).to_string(),
+        }
+    }
+}
+
+/// Renders every statement as an S-expression, like `(let x (+ 1 (* 2 3)))`,
+/// one per line. Expressions too long for a line are indented.
+pub fn to_sexpr(program: &Program) -> String {
+    let lines: Vec<String> = program
+        .statements
+        .iter()
+        .map(|stmt| statement(stmt).sexpr(0))
+        .collect();
+
+    lines.join("\n")
+}
+
+/// Renders the program as a Graphviz DOT tree, with a `program` node as its root.
+pub fn to_dot(program: &Program) -> String {
+    let root = Tree::list(
+        "program",
+        program.statements.iter().map(statement).collect(),
+    );
+
+    let mut out = String::from("digraph ast {\n    node [shape=box];\n");
+    root.dot(&mut 0, &mut out);
+    out.push('}');
+
+    out
+}
+
+/// The shape both printers share: an atom, or a list headed by a label.
+enum Tree {
+    Atom(String),
+    List(String, Vec<Tree>),
+}
+
+impl Tree {
+    fn atom(label: impl Into<String>) -> Tree {
+        Tree::Atom(label.into())
+    }
+
+    fn list(label: impl Into<String>, children: Vec<Tree>) -> Tree {
+        Tree::List(label.into(), children)
+    }
+
+    fn sexpr(&self, indent: usize) -> String {
+        let flat = self.flat();
+        let Tree::List(label, children) = self else {
+            return flat;
+        };
+        if indent + flat.len() <= WIDTH || children.is_empty() {
+            return flat;
+        }
+
+        let mut out = format!("({}", label);
+        for child in children {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent + 2));
+            out.push_str(&child.sexpr(indent + 2));
+        }
+        out.push(')');
+
+        out
+    }
+
+    fn flat(&self) -> String {
+        match self {
+            Tree::Atom(label) => label.clone(),
+            Tree::List(label, children) => {
+                let mut out = format!("({}", label);
+                for child in children {
+                    out.push(' ');
+                    out.push_str(&child.flat());
+                }
+                out.push(')');
+                out
+            }
+        }
+    }
+
+    /// Writes this node and its edges, numbering nodes in preorder from `next`.
+    fn dot(&self, next: &mut usize, out: &mut String) -> usize {
+        let id = *next;
+        *next += 1;
+
+        let (label, children) = match self {
+            Tree::Atom(label) => (label, &[][..]),
+            Tree::List(label, children) => (label, &children[..]),
+        };
+        let label = label.replace('\\', "\\\\").replace('"', "\\\"");
+        out.push_str(&format!("    n{} [label=\"{}\"];\n", id, label));
+
+        for child in children {
+            let child_id = child.dot(next, out);
+            out.push_str(&format!("    n{} -> n{};\n", id, child_id));
+        }
+
+        id
+    }
+}
+
+fn name(ident: &Identifier) -> Tree {
+    Tree::atom(&ident.value)
+}
+
+fn optional(label: &str, value: Option<Tree>) -> Tree {
+    Tree::list(label, value.into_iter().collect())
+}
+
+fn statement(stmt: &StatementNode) -> Tree {
+    match stmt {
+        StatementNode::Let(let_stmt) => let_statement(let_stmt),
+        StatementNode::Return(ret_stmt) => {
+            optional("return", ret_stmt.ret_value.as_ref().map(expression))
+        }
+        StatementNode::Expression(exp_stmt) => match &exp_stmt.expression {
+            Some(expression) => self::expression(expression),
+            None => Tree::list("empty", vec![]),
+        },
+        StatementNode::Infix(infix) => Tree::list(
+            "infix",
+            vec![
+                Tree::atom(infix.binding.name.names().join(", ")),
+                Tree::atom(infix.precedence.to_string()),
+                Tree::atom(format!("{:?}", infix.associativity).to_lowercase()),
+                optional("=", infix.binding.value.as_ref().map(expression)),
+            ],
+        ),
+        StatementNode::Import(import) => Tree::list(
+            "import",
+            vec![Tree::atom(import.path.print_string()), name(&import.alias)],
+        ),
+        StatementNode::Export(export) => Tree::list("export", vec![let_statement(&export.binding)]),
+        StatementNode::Struct(declaration) => {
+            let mut children = vec![name(&declaration.name)];
+            children.extend(declaration.fields.iter().map(name));
+            Tree::list("struct", children)
+        }
+        StatementNode::Enum(declaration) => {
+            let mut children = vec![name(&declaration.name)];
+            children.extend(declaration.variants.iter().map(|variant| {
+                if variant.fields.is_empty() {
+                    return name(&variant.name);
+                }
+                Tree::list(
+                    &variant.name.value,
+                    variant.fields.iter().map(name).collect(),
+                )
+            }));
+            Tree::list("enum", children)
+        }
+        StatementNode::Throw(throw) => Tree::list("throw", vec![expression(&throw.value)]),
+        StatementNode::Try(try_stmt) => {
+            let mut children = vec![block(&try_stmt.body)];
+            if let Some(catch) = &try_stmt.catch {
+                children.push(Tree::list(
+                    "catch",
+                    vec![name(&catch.parameter), block(&catch.body)],
+                ));
+            }
+            if let Some(finally) = &try_stmt.finally {
+                children.push(Tree::list("finally", vec![block(finally)]));
+            }
+            Tree::list("try", children)
+        }
+        StatementNode::Yield(yield_stmt) => {
+            Tree::list("yield", vec![expression(&yield_stmt.value)])
+        }
+        StatementNode::For(for_stmt) => Tree::list(
+            "for",
+            vec![
+                name(&for_stmt.variable),
+                expression(&for_stmt.iterable),
+                block(&for_stmt.body),
+            ],
+        ),
+    }
+}
+
+fn let_statement(let_stmt: &LetStatement) -> Tree {
+    let mut target = pattern(&let_stmt.name);
+    if let Some(annotation) = &let_stmt.annotation {
+        target = Tree::list(":", vec![target, type_expr(annotation)]);
+    }
+
+    let mut children = vec![target];
+    children.extend(let_stmt.value.as_ref().map(expression));
+    Tree::list(&let_stmt.token.literal, children)
+}
+
+fn block(block: &BlockStatement) -> Tree {
+    Tree::list("block", block.statements.iter().map(statement).collect())
+}
+
+fn parameter(param: &Parameter) -> Tree {
+    let mut tree = name(&param.name);
+    if param.rest {
+        tree = Tree::list("...", vec![tree]);
+    }
+    if let Some(annotation) = &param.annotation {
+        tree = Tree::list(":", vec![tree, type_expr(annotation)]);
+    }
+    if let Some(default) = &param.default {
+        tree = Tree::list("=", vec![tree, expression(default)]);
+    }
+
+    tree
+}
+
+fn expression(expression: &ExpressionNode) -> Tree {
+    match expression {
+        ExpressionNode::IdentifierNode(ident) => name(ident),
+        ExpressionNode::Integer(int) => Tree::atom(int.value.to_string()),
+        ExpressionNode::Boolean(boolean) => Tree::atom(boolean.value.to_string()),
+        ExpressionNode::StringNode(string) => Tree::atom(string.print_string()),
+        ExpressionNode::Null(_) => Tree::atom("null"),
+        ExpressionNode::Array(array) => Tree::list(
+            "array",
+            array.elements.iter().map(self::expression).collect(),
+        ),
+        ExpressionNode::Hash(hash) => Tree::list(
+            "hash",
+            hash.pairs
+                .iter()
+                .map(|(key, value)| {
+                    Tree::list(":", vec![self::expression(key), self::expression(value)])
+                })
+                .collect(),
+        ),
+        ExpressionNode::Struct(literal) => {
+            let mut children = vec![name(&literal.name)];
+            children.extend(
+                literal.fields.iter().map(|(field, value)| {
+                    Tree::list(":", vec![name(field), self::expression(value)])
+                }),
+            );
+            Tree::list("new", children)
+        }
+        ExpressionNode::Prefix(prefix) => {
+            Tree::list(&prefix.operator, vec![self::expression(&prefix.right)])
+        }
+        ExpressionNode::Infix(infix) => Tree::list(
+            &infix.operator,
+            vec![
+                self::expression(&infix.left),
+                self::expression(&infix.right),
+            ],
+        ),
+        ExpressionNode::Assign(assign) => Tree::list(
+            "=",
+            vec![name(&assign.name), self::expression(&assign.value)],
+        ),
+        ExpressionNode::Function(func) => {
+            let mut children = vec![Tree::list(
+                "params",
+                func.parameters.iter().map(parameter).collect(),
+            )];
+            if let Some(return_type) = &func.return_type {
+                children.push(Tree::list("->", vec![type_expr(return_type)]));
+            }
+            children.push(block(&func.body));
+            Tree::list("fn", children)
+        }
+        ExpressionNode::Macro(macro_lit) => Tree::list(
+            "macro",
+            vec![
+                Tree::list("params", macro_lit.parameters.iter().map(name).collect()),
+                block(&macro_lit.body),
+            ],
+        ),
+        ExpressionNode::Call(call) => {
+            let arguments = call.arguments.iter().map(|arg| match &arg.name {
+                Some(arg_name) => {
+                    Tree::list(":", vec![name(arg_name), self::expression(&arg.value)])
+                }
+                None => self::expression(&arg.value),
+            });
+
+            // a custom operator reads like the builtin ones
+            if call.token.kind == TokenKind::Operator {
+                return Tree::list(&call.token.literal, arguments.collect());
+            }
+
+            let mut children = vec![self::expression(&call.function)];
+            children.extend(arguments);
+            Tree::list("call", children)
+        }
+        ExpressionNode::Member(member) => Tree::list(
+            if member.optional { "?." } else { "." },
+            vec![self::expression(&member.object), name(&member.property)],
+        ),
+        ExpressionNode::Index(index) => Tree::list(
+            if index.optional { "?.[]" } else { "[]" },
+            vec![
+                self::expression(&index.left),
+                self::expression(&index.index),
+            ],
+        ),
+        ExpressionNode::Range(range) => {
+            let bound = |bound: &Option<Box<ExpressionNode>>| match bound {
+                Some(bound) => self::expression(bound),
+                None => Tree::atom("_"),
+            };
+            Tree::list(
+                if range.inclusive { "..=" } else { ".." },
+                vec![bound(&range.start), bound(&range.end)],
+            )
+        }
+        ExpressionNode::Match(match_exp) => {
+            let mut children = vec![self::expression(&match_exp.subject)];
+            children.extend(match_exp.arms.iter().map(|arm| {
+                let mut arm_children = vec![pattern(&arm.pattern)];
+                if let Some(guard) = &arm.guard {
+                    arm_children.push(Tree::list("if", vec![self::expression(guard)]));
+                }
+                arm_children.push(self::expression(&arm.body));
+                Tree::list("=>", arm_children)
+            }));
+            Tree::list("match", children)
+        }
+        ExpressionNode::If(if_exp) => {
+            let mut children = vec![self::expression(&if_exp.condition)];
+            children.extend(if_exp.blocks().map(block));
+            Tree::list("if", children)
+        }
+    }
+}
+
+fn pattern(pattern: &Pattern) -> Tree {
+    match pattern {
+        Pattern::Integer(int) => Tree::atom(int.value.to_string()),
+        Pattern::Boolean(boolean) => Tree::atom(boolean.value.to_string()),
+        Pattern::StringNode(string) => Tree::atom(string.print_string()),
+        Pattern::Binding(ident) => name(ident),
+        Pattern::Wildcard(_) => Tree::atom("_"),
+        Pattern::Array(array) => {
+            let mut children: Vec<Tree> = array.elements.iter().map(self::pattern).collect();
+            if let Some(rest) = &array.rest {
+                children.push(Tree::list("...", vec![name(rest)]));
+            }
+            Tree::list("array", children)
+        }
+        Pattern::Hash(hash) => Tree::list(
+            "hash",
+            hash.pairs
+                .iter()
+                .map(|(key, value)| {
+                    Tree::list(
+                        ":",
+                        vec![Tree::atom(key.print_string()), self::pattern(value)],
+                    )
+                })
+                .collect(),
+        ),
+        Pattern::Variant(variant) => Tree::list(
+            &variant.name.value,
+            variant.payload.iter().map(self::pattern).collect(),
+        ),
+    }
+}
+
+fn type_expr(type_expr: &TypeExpr) -> Tree {
+    match type_expr {
+        TypeExpr::Named(type_name) => name(type_name),
+        TypeExpr::Array(array) => Tree::list("array", vec![self::type_expr(&array.element)]),
+        TypeExpr::Hash(hash) => Tree::list(
+            "hash",
+            vec![self::type_expr(&hash.key), self::type_expr(&hash.value)],
+        ),
+        TypeExpr::Function(func) => Tree::list(
+            "fn",
+            vec![
+                Tree::list(
+                    "params",
+                    func.parameters.iter().map(self::type_expr).collect(),
+                ),
+                Tree::list("->", vec![self::type_expr(&func.return_type)]),
+            ],
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{lexer::Lexer, parser::Parser};
+
+    use super::{to_dot, to_sexpr, Format};
+
+    fn parse(input: &str) -> crate::ast::Program {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert!(
+            parser.errors().is_empty(),
+            "parser errors: {:?}",
+            parser.errors()
+        );
+
+        program
+    }
+
+    #[test]
+    fn test_sexpr() {
+        let tests = vec![
+            ("let x = 1 + 2 * 3;", "(let x (+ 1 (* 2 3)))"),
+            ("const x: int = -a;", "(const (: x int) (- a))"),
+            ("return 5;", "(return 5)"),
+            (r#"let dir = "C:\tmp";"#, r#"(let dir "C:\tmp")"#),
+            (
+                "let f = fn(a, b: int = 1, ...rest) -> int { a };",
+                "(let f (fn (params a (= (: b int) 1) (... rest)) (-> int) (block a)))",
+            ),
+            (
+                r#"f(1, y: "two")?.z[0..]"#,
+                r#"([] (?. (call f 1 (: y "two")) z) (.. 0 _))"#,
+            ),
+            (
+                "let [a, ...t] = xs; let {k} = {\"k\": null};",
+                "(let (array a (... t)) xs)\n(let (hash (: \"k\" k)) (hash (: \"k\" null)))",
+            ),
+            (
+                "infix <+> 3 left = fn(a, b) { a }; 1 <+> 2",
+                "(infix <+> 3 left (= (fn (params a b) (block a))))\n(<+> 1 2)",
+            ),
+            (
+                "enum O { None, Some(x) } match (o) { Some(v) if v > 1 => v, _ => 0 }",
+                "(enum O None (Some x))\n(match o (=> (Some v) (if (> v 1)) v) (=> _ 0))",
+            ),
+            (
+                "try { throw 1; } catch (e) { e } finally { for x in xs { puts(x); } }",
+                "(try\n  (block (throw 1))\n  (catch e (block e))\n  (finally (block (for x xs (block (call puts x))))))",
+            ),
+            (
+                "struct P { x } let p: {string: [int]} = P { x: 1 };",
+                "(struct P x)\n(let (: p (hash string (array int))) (new P (: x 1)))",
+            ),
+            (
+                "if (a) { 1 } else if (b) { 2 }",
+                "(if a (block 1) (block (if b (block 2))))",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                to_sexpr(&parse(input)),
+                expected,
+                "wrong s-expression for {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_sexpr_breaks_long_lines() {
+        let program = parse(
+            "let total = fn(first, second, third) { first * second + second * third + third * first };",
+        );
+
+        assert_eq!(
+            to_sexpr(&program),
+            [
+                "(let",
+                "  total",
+                "  (fn",
+                "    (params first second third)",
+                "    (block (+ (+ (* first second) (* second third)) (* third first)))))",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_dot() {
+        let program = parse(r#"let s = "a\" + b;"#);
+
+        assert_eq!(
+            to_dot(&program),
+            [
+                "digraph ast {",
+                "    node [shape=box];",
+                "    n0 [label=\"program\"];",
+                "    n1 [label=\"let\"];",
+                "    n2 [label=\"s\"];",
+                "    n1 -> n2;",
+                "    n3 [label=\"+\"];",
+                r#"    n4 [label="\"a\\\""];"#,
+                "    n3 -> n4;",
+                "    n5 [label=\"b\"];",
+                "    n3 -> n5;",
+                "    n1 -> n3;",
+                "    n0 -> n1;",
+                "}",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_format_names() {
+        let program = parse("x");
+
+        assert_eq!(Format::from_name("sexpr"), Some(Format::SExpr));
+        assert_eq!(Format::from_name("xml"), None);
+        assert_eq!(Format::SExpr.render(&program), "x");
+        assert!(Format::Dot.render(&program).starts_with("digraph ast {"));
+        assert!(Format::Json
+            .render(&program)
+            .starts_with(r#"{"type":"Program""#));
+    }
+}
@@ -0,0 +1,1952 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Display, hint, io::Write, rc::Rc};
+
+use crate::{
+    ast::{
+        AssignExpression, BlockStatement, CallExpression, ExportStatement, ExpressionNode,
+        ForStatement, HashLiteral, IfExpression, ImportStatement, IndexExpression,
+        InfixDeclaration, InfixExpression, LetStatement, MatchExpression, MemberExpression, Node,
+        Parameter, Pattern, PrefixExpression, Program, RangeExpression, StatementNode,
+        StructLiteral, TryStatement, Variant,
+    },
+    module::Module,
+    token::{Span, TokenKind},
+    value::{Builtin, Closure, Env, ErrorValue, HashKey, Range, StructValue, Value, VariantValue},
+};
+
+/// How deeply calls may nest before evaluation fails.
+///
+/// The stack is guarded by [`DEFAULT_MAX_STACK`], this keeps runaway
+/// recursion short. See [`Evaluator::with_max_call_depth`].
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 64;
+
+/// How much stack evaluation may take before it fails instead of overflowing.
+///
+/// Leaves a quarter of the 2 MiB stack of a spawned thread for the frames
+/// below the evaluator and for what runs between two checks. See
+/// [`Evaluator::with_max_stack`] for running on a larger one.
+pub const DEFAULT_MAX_STACK: usize = 1536 * 1024;
+
+/// An error that stopped evaluation and the source range it was raised at.
+///
+/// `try` catches it as a [`Value::Error`].
+#[derive(Debug, Clone)]
+pub struct RuntimeError<'a> {
+    pub message: String,
+    pub span: Span,
+    /// The calls the error left, innermost first, each as the name of the
+    /// function and where it was called.
+    pub stack: Vec<String>,
+    /// What `throw` threw, null for an error raised by running the code.
+    pub value: Value<'a>,
+}
+
+impl Display for RuntimeError<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.span, self.message)
+    }
+}
+
+impl<'a> RuntimeError<'a> {
+    fn new(span: Span, message: String) -> RuntimeError<'a> {
+        RuntimeError {
+            message,
+            span,
+            stack: vec![],
+            value: Value::Null,
+        }
+    }
+}
+
+fn error<'a, T>(span: Span, message: String) -> Result<T, RuntimeError<'a>> {
+    Err(RuntimeError::new(span, message))
+}
+
+/// Runs programs, keeping the top-level bindings of one run for the next.
+///
+/// Values borrow the code of their functions, so the programs have to live
+/// as long as the evaluator.
+pub struct Evaluator<'a> {
+    globals: Env<'a>,
+    /// The module whose statements are running, its imports are loaded.
+    module: Option<&'a Module>,
+    /// The exports of every module run so far, by its address.
+    modules: HashMap<*const Module, Value<'a>>,
+    /// The functions running and where each was called, innermost last.
+    calls: Vec<(Rc<Closure<'a>>, Span)>,
+    max_depth: usize,
+    /// Where the stack was when the running program started.
+    stack_start: Option<usize>,
+    max_stack: usize,
+    output: Box<dyn Write + 'a>,
+}
+
+impl<'a> Evaluator<'a> {
+    /// An evaluator whose `puts` writes to `output`.
+    pub fn new(output: impl Write + 'a) -> Evaluator<'a> {
+        Evaluator {
+            globals: Env::new(),
+            module: None,
+            modules: HashMap::new(),
+            calls: vec![],
+            max_depth: DEFAULT_MAX_CALL_DEPTH,
+            stack_start: None,
+            max_stack: DEFAULT_MAX_STACK,
+            output: Box::new(output),
+        }
+    }
+
+    /// Limits how deeply calls may nest.
+    pub fn with_max_call_depth(mut self, max_depth: usize) -> Evaluator<'a> {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Limits how many bytes of stack evaluation may take, counted from where
+    /// [`Evaluator::eval_program`] or [`Evaluator::eval_module`] is called.
+    ///
+    /// The thread needs room beyond it for the frames between two checks and
+    /// for printing the code an error names.
+    pub fn with_max_stack(mut self, max_stack: usize) -> Evaluator<'a> {
+        self.max_stack = max_stack;
+        self
+    }
+
+    /// Runs `program` in the global scope and returns the value of its last
+    /// statement, if that is an expression.
+    ///
+    /// Macros should be expanded first. Imports fail, only [`Evaluator::eval_module`]
+    /// has the modules they name.
+    pub fn eval_program(&mut self, program: &'a Program) -> Result<Value<'a>, RuntimeError<'a>> {
+        let env = self.globals.clone();
+        self.count_stack(|evaluator| Machine::new(&program.statements, env).run(evaluator))
+    }
+
+    /// Runs a loaded module in the global scope, running each module it
+    /// imports once, before its first import.
+    pub fn eval_module(&mut self, module: &'a Module) -> Result<Value<'a>, RuntimeError<'a>> {
+        let env = self.globals.clone();
+        self.count_stack(|evaluator| evaluator.run_module(module, env))
+    }
+
+    /// Runs `run` with the stack counted from the current frame.
+    fn count_stack<T>(&mut self, run: impl FnOnce(&mut Evaluator<'a>) -> T) -> T {
+        let outer = self.stack_start.replace(stack_position());
+        let result = run(self);
+        self.stack_start = outer;
+        result
+    }
+
+    fn run_module(
+        &mut self,
+        module: &'a Module,
+        env: Env<'a>,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        let outer = self.module.replace(module);
+        let result = Machine::new(&module.program.statements, env).run(self);
+        self.module = outer;
+        result
+    }
+
+    fn import(&mut self, import: &'a ImportStatement) -> Result<Value<'a>, RuntimeError<'a>> {
+        let Some(module) = self
+            .module
+            .and_then(|module| module.imports.get(&import.alias.value))
+        else {
+            let msg = format!(
+                "can not import {} here, only a file that is run can import",
+                import.path.print_string()
+            );
+            return error(import.path.token.span, msg);
+        };
+
+        let key = Rc::as_ptr(module);
+        if let Some(exports) = self.modules.get(&key) {
+            return Ok(exports.clone());
+        }
+
+        let env = Env::new();
+        self.run_module(module, env.clone())?;
+        let exports = module
+            .exports
+            .iter()
+            .map(|name| (name.clone(), env.get(name).unwrap_or(Value::Null)))
+            .collect();
+        let exports = Value::Module(Rc::new(exports));
+        self.modules.insert(key, exports.clone());
+
+        Ok(exports)
+    }
+
+    /// The value `catch` binds for `err`, whose stack goes on with the calls
+    /// that are still running.
+    fn caught(&self, err: RuntimeError<'a>) -> Value<'a> {
+        if let Value::Error(caught) = err.value {
+            return Value::Error(caught);
+        }
+
+        let mut stack = err.stack;
+        for (closure, span) in self.calls.iter().rev() {
+            stack.push(stack_entry(closure, *span));
+        }
+
+        Value::Error(Rc::new(ErrorValue {
+            message: err.message,
+            stack,
+            value: err.value,
+        }))
+    }
+
+    /// Runs one statement, leaving statements that contain blocks to the [`Machine`].
+    fn exec_statement(
+        &mut self,
+        stmt: &'a StatementNode,
+        env: &Env<'a>,
+    ) -> Result<Step<'a>, RuntimeError<'a>> {
+        match stmt {
+            StatementNode::Let(let_stmt)
+            | StatementNode::Infix(InfixDeclaration {
+                binding: let_stmt, ..
+            })
+            | StatementNode::Export(ExportStatement {
+                binding: let_stmt, ..
+            }) => {
+                self.exec_let(let_stmt, env)?;
+                Ok(Step::Done(Value::Null))
+            }
+            StatementNode::Return(ret_stmt) => {
+                let value = match &ret_stmt.ret_value {
+                    Some(value) => self.eval_expression(value, env)?,
+                    None => Value::Null,
+                };
+                Ok(Step::Return(value))
+            }
+            StatementNode::Expression(exp_stmt) => match &exp_stmt.expression {
+                Some(ExpressionNode::If(if_exp)) => self.exec_if(if_exp, env),
+                Some(expression) => Ok(Step::Done(self.eval_expression(expression, env)?)),
+                None => Ok(Step::Done(Value::Null)),
+            },
+            StatementNode::Import(import) => {
+                let exports = self.import(import)?;
+                env.define(&import.alias.value, exports);
+                Ok(Step::Done(Value::Null))
+            }
+            StatementNode::Struct(declaration) => {
+                env.define(&declaration.name.value, Value::StructType(declaration));
+                Ok(Step::Done(Value::Null))
+            }
+            StatementNode::For(for_stmt) => {
+                let iterable = self.eval_expression(&for_stmt.iterable, env)?;
+                let items = Items::of(iterable, for_stmt)?;
+                Ok(Step::Enter(Frame::For {
+                    stmt: for_stmt,
+                    items,
+                    env: env.clone(),
+                }))
+            }
+            StatementNode::Enum(declaration) => {
+                for variant in &declaration.variants {
+                    // a variant without a payload is a value on its own
+                    let value = if variant.fields.is_empty() {
+                        Value::Variant(Rc::new(VariantValue {
+                            variant,
+                            payload: vec![],
+                        }))
+                    } else {
+                        Value::Constructor(variant)
+                    };
+                    env.define(&variant.name.value, value);
+                }
+                Ok(Step::Done(Value::Null))
+            }
+            StatementNode::Throw(throw) => {
+                let value = self.eval_expression(&throw.value, env)?;
+                let err = match value {
+                    // throwing a caught error again keeps where it came from
+                    Value::Error(caught) => RuntimeError {
+                        message: caught.message.clone(),
+                        span: throw.token.span,
+                        stack: caught.stack.clone(),
+                        value: Value::Error(caught),
+                    },
+                    value => RuntimeError {
+                        message: value.to_string(),
+                        span: throw.token.span,
+                        stack: vec![],
+                        value,
+                    },
+                };
+                Err(err)
+            }
+            StatementNode::Try(try_stmt) => Ok(Step::Enter(Frame::Try {
+                stmt: try_stmt,
+                env: env.clone(),
+                stage: TryStage::Start,
+            })),
+            StatementNode::Yield(yield_stmt) => {
+                Ok(Step::Yield(self.eval_expression(&yield_stmt.value, env)?))
+            }
+        }
+    }
+
+    /// Enters the block the condition picks, as a statement it can return
+    /// from the function.
+    fn exec_if(
+        &mut self,
+        if_exp: &'a IfExpression,
+        env: &Env<'a>,
+    ) -> Result<Step<'a>, RuntimeError<'a>> {
+        match self.branch(if_exp, env)? {
+            Some(block) => Ok(Step::Enter(Frame::block(block, env))),
+            None => Ok(Step::Done(Value::Null)),
+        }
+    }
+
+    fn exec_let(
+        &mut self,
+        let_stmt: &'a LetStatement,
+        env: &Env<'a>,
+    ) -> Result<(), RuntimeError<'a>> {
+        let value = match (&let_stmt.name, &let_stmt.value) {
+            // a function remembers the name it is bound to for error messages
+            (Pattern::Binding(name), Some(ExpressionNode::Function(literal))) => {
+                Value::Function(Rc::new(Closure {
+                    literal,
+                    env: env.clone(),
+                    name: Some(name.value.clone()),
+                }))
+            }
+            (_, Some(value)) => self.eval_expression(value, env)?,
+            (_, None) => Value::Null,
+        };
+
+        let mut bindings = vec![];
+        if let Err(msg) = destructure(&let_stmt.name, &value, &mut bindings) {
+            let msg = format!("{} in `{}`", msg, let_stmt.print_string());
+            return error(let_stmt.token.span, msg);
+        }
+        for (name, value) in bindings {
+            if let_stmt.is_const() {
+                env.define_const(name, value);
+            } else {
+                env.define(name, value);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn eval_expression(
+        &mut self,
+        expression: &'a ExpressionNode,
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        self.check_stack(expression.span())?;
+
+        match expression {
+            ExpressionNode::IdentifierNode(ident) => {
+                let value = env.get(&ident.value);
+                match value.or_else(|| Builtin::lookup(&ident.value).map(Value::Builtin)) {
+                    Some(value) => Ok(value),
+                    None => {
+                        let msg = format!("unknown identifier `{}`", ident.value);
+                        error(ident.token.span, msg)
+                    }
+                }
+            }
+            ExpressionNode::Integer(int) => Ok(Value::Int(int.value)),
+            ExpressionNode::Boolean(boolean) => Ok(Value::Bool(boolean.value)),
+            ExpressionNode::StringNode(string) => Ok(Value::String(string.value.as_str().into())),
+            ExpressionNode::Array(array) => {
+                let elements = array
+                    .elements
+                    .iter()
+                    .map(|element| self.eval_expression(element, env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Array(Rc::new(elements)))
+            }
+            ExpressionNode::Hash(hash) => self.eval_hash(hash, env),
+            ExpressionNode::Prefix(prefix) => {
+                let right = self.eval_expression(&prefix.right, env)?;
+                eval_prefix(prefix, right)
+            }
+            ExpressionNode::Infix(infix) => self.eval_infix(infix, env),
+            ExpressionNode::Match(match_exp) => self.eval_match(match_exp, env),
+            ExpressionNode::If(if_exp) => self.eval_if(if_exp, env),
+            ExpressionNode::Function(literal) => Ok(Value::Function(Rc::new(Closure {
+                literal,
+                env: env.clone(),
+                name: None,
+            }))),
+            ExpressionNode::Call(call) => self.eval_call(call, env),
+            ExpressionNode::Macro(literal) => {
+                let msg = String::from("macros are expanded before running, this one was not");
+                error(literal.token.span, msg)
+            }
+            ExpressionNode::Member(member) => {
+                let object = self.eval_expression(&member.object, env)?;
+                self.member(member, object)
+            }
+            ExpressionNode::Struct(literal) => self.eval_struct(literal, env),
+            ExpressionNode::Index(index) => self.eval_index(index, env),
+            ExpressionNode::Null(_) => Ok(Value::Null),
+            ExpressionNode::Assign(assign) => self.eval_assign(assign, env),
+            ExpressionNode::Range(range) => self.eval_range(range, env),
+        }
+    }
+
+    fn eval_hash(
+        &mut self,
+        hash: &'a HashLiteral,
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        let mut pairs: Vec<(HashKey, Value<'a>)> = vec![];
+
+        for (key_exp, value_exp) in &hash.pairs {
+            let key = self.eval_expression(key_exp, env)?;
+            let Some(key) = HashKey::from_value(&key) else {
+                let msg = format!(
+                    "can not use {} as a hash key in `{}`",
+                    key.type_name(),
+                    key_exp.print_string()
+                );
+                return error(key_exp.token().span, msg);
+            };
+            let value = self.eval_expression(value_exp, env)?;
+            match pairs.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, slot)) => *slot = value,
+                None => pairs.push((key, value)),
+            }
+        }
+
+        Ok(Value::Hash(Rc::new(pairs)))
+    }
+
+    /// Runs the first arm whose pattern matches and whose guard holds.
+    fn eval_match(
+        &mut self,
+        match_exp: &'a MatchExpression,
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        let subject = self.eval_expression(&match_exp.subject, env)?;
+
+        for arm in &match_exp.arms {
+            let mut bindings = vec![];
+            if destructure(&arm.pattern, &subject, &mut bindings).is_err() {
+                continue;
+            }
+            let arm_env = env.enclosed();
+            for (name, value) in bindings {
+                arm_env.define(name, value);
+            }
+            if let Some(guard) = &arm.guard {
+                if !self.eval_expression(guard, &arm_env)?.is_truthy() {
+                    continue;
+                }
+            }
+            return self.eval_expression(&arm.body, &arm_env);
+        }
+
+        let msg = format!(
+            "no arm matches {} in `match ({})`",
+            subject,
+            match_exp.subject.print_string()
+        );
+        error(match_exp.token.span, msg)
+    }
+
+    /// Runs the block the condition picks, which `return` and `yield` can
+    /// not leave since the `if` is used as a value.
+    fn eval_if(
+        &mut self,
+        if_exp: &'a IfExpression,
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        match self.branch(if_exp, env)? {
+            Some(block) => Machine::branch(&block.statements, env.enclosed()).run(self),
+            None => Ok(Value::Null),
+        }
+    }
+
+    /// The block the condition of `if_exp` picks, if any.
+    fn branch(
+        &mut self,
+        if_exp: &'a IfExpression,
+        env: &Env<'a>,
+    ) -> Result<Option<&'a BlockStatement>, RuntimeError<'a>> {
+        let condition = self.eval_expression(&if_exp.condition, env)?;
+        if condition.is_truthy() {
+            Ok(Some(&if_exp.consequence))
+        } else {
+            Ok(if_exp.alternative.as_ref())
+        }
+    }
+
+    fn eval_assign(
+        &mut self,
+        assign: &'a AssignExpression,
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        let value = self.eval_expression(&assign.value, env)?;
+        if env.is_const(&assign.name.value) {
+            let msg = format!("can not assign to const `{}`", assign.name.value);
+            return error(assign.name.token.span, msg);
+        }
+        if !env.assign(&assign.name.value, value.clone()) {
+            let msg = format!("can not assign to undeclared `{}`", assign.name.value);
+            return error(assign.name.token.span, msg);
+        }
+
+        Ok(value)
+    }
+
+    fn eval_infix(
+        &mut self,
+        infix: &'a InfixExpression,
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        let left = self.eval_expression(&infix.left, env)?;
+
+        // the right side of `??` only runs when it is needed
+        if infix.token.kind == TokenKind::QuestionQuestion {
+            return match left {
+                Value::Null => self.eval_expression(&infix.right, env),
+                left => Ok(left),
+            };
+        }
+
+        let right = self.eval_expression(&infix.right, env)?;
+
+        // a custom operator calls the function bound to it
+        if infix.token.kind == TokenKind::Operator {
+            let Some(function) = env.get(&infix.operator) else {
+                let msg = format!("operator `{}` is not bound to a function", infix.operator);
+                return error(infix.token.span, msg);
+            };
+            let arguments = vec![(None, left), (None, right)];
+            return self.call(function, arguments, infix.token.span, infix);
+        }
+
+        eval_operator(infix, left, right)
+    }
+
+    fn eval_call(
+        &mut self,
+        call: &'a CallExpression,
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        let function = self.eval_expression(&call.function, env)?;
+
+        let mut arguments = vec![];
+        for argument in &call.arguments {
+            let value = self.eval_expression(&argument.value, env)?;
+            arguments.push((
+                argument.name.as_ref().map(|name| name.value.as_str()),
+                value,
+            ));
+        }
+
+        self.call(function, arguments, call.token.span, call)
+    }
+
+    /// Calls `function`, `site` is the call or operator expression errors name.
+    fn call(
+        &mut self,
+        function: Value<'a>,
+        arguments: Vec<(Option<&'a str>, Value<'a>)>,
+        span: Span,
+        site: &dyn Node,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        let closure = match function {
+            Value::Function(closure) => closure,
+            Value::Builtin(builtin) => return self.call_builtin(builtin, arguments, span, site),
+            Value::Constructor(variant) => return construct(variant, arguments, span, site),
+            Value::GeneratorNext(generator) => {
+                if !arguments.is_empty() {
+                    let msg = format!(
+                        "wrong number of arguments: expected 0, got {} in call `{}`",
+                        arguments.len(),
+                        site.print_string()
+                    );
+                    return error(span, msg);
+                }
+                let value = self.resume(&generator, span)?;
+                let done = value.is_none();
+                let pairs = vec![
+                    (
+                        HashKey::String("value".into()),
+                        value.unwrap_or(Value::Null),
+                    ),
+                    (HashKey::String("done".into()), Value::Bool(done)),
+                ];
+                return Ok(Value::Hash(Rc::new(pairs)));
+            }
+            other => {
+                let msg = format!(
+                    "can not call {} in `{}`",
+                    other.type_name(),
+                    site.print_string()
+                );
+                return error(span, msg);
+            }
+        };
+
+        self.check_depth(span)?;
+
+        let env = closure.env.enclosed();
+        self.bind_arguments(&closure.literal.parameters, arguments, &env, span, site)?;
+
+        // a generator's body runs only when it is asked for values
+        if closure.literal.is_generator() {
+            let machine = Machine::generator(&closure.literal.body.statements, env);
+            return Ok(Value::Generator(Rc::new(Generator {
+                closure,
+                state: RefCell::new(GeneratorState::Suspended(machine)),
+            })));
+        }
+
+        self.calls.push((Rc::clone(&closure), span));
+        let result = Machine::new(&closure.literal.body.statements, env).run(self);
+        self.calls.pop();
+
+        result.map_err(|mut err| {
+            err.stack.push(stack_entry(&closure, span));
+            err
+        })
+    }
+
+    fn check_depth(&self, span: Span) -> Result<(), RuntimeError<'a>> {
+        if self.calls.len() >= self.max_depth {
+            let msg = format!("calls nest deeper than the maximum of {}", self.max_depth);
+            return error(span, msg);
+        }
+
+        Ok(())
+    }
+
+    /// Fails once evaluation takes more than `max_stack`, every call and
+    /// nested expression goes through here.
+    fn check_stack(&self, span: Span) -> Result<(), RuntimeError<'a>> {
+        let used = self
+            .stack_start
+            .map_or(0, |start| start.abs_diff(stack_position()));
+        if used > self.max_stack {
+            let msg = format!(
+                "nesting exceeds the stack limit of {} KiB",
+                self.max_stack / 1024
+            );
+            return error(span, msg);
+        }
+
+        Ok(())
+    }
+
+    /// Runs `generator` up to its next `yield` and returns the value yielded,
+    /// `None` once its body is done. `span` is where it was asked to go on.
+    fn resume(
+        &mut self,
+        generator: &Generator<'a>,
+        span: Span,
+    ) -> Result<Option<Value<'a>>, RuntimeError<'a>> {
+        let mut machine = match generator.state.replace(GeneratorState::Running) {
+            GeneratorState::Suspended(machine) => machine,
+            GeneratorState::Running => {
+                let msg = String::from("a generator can not go on while it is running");
+                return error(span, msg);
+            }
+            GeneratorState::Done => {
+                generator.state.replace(GeneratorState::Done);
+                return Ok(None);
+            }
+        };
+        if let Err(err) = self.check_depth(span) {
+            generator.state.replace(GeneratorState::Suspended(machine));
+            return Err(err);
+        }
+
+        self.calls.push((Rc::clone(&generator.closure), span));
+        let result = machine.resume(self);
+        self.calls.pop();
+
+        match result {
+            Ok(Pause::Yield(value)) => {
+                generator.state.replace(GeneratorState::Suspended(machine));
+                Ok(Some(value))
+            }
+            Ok(Pause::Finish(_)) => {
+                generator.state.replace(GeneratorState::Done);
+                Ok(None)
+            }
+            Err(mut err) => {
+                generator.state.replace(GeneratorState::Done);
+                err.stack.push(stack_entry(&generator.closure, span));
+                Err(err)
+            }
+        }
+    }
+
+    /// Binds positional arguments in order and named ones by name, fills the
+    /// parameters left out with their defaults and collects the extra
+    /// positional arguments in the rest parameter.
+    fn bind_arguments(
+        &mut self,
+        parameters: &'a [Parameter],
+        arguments: Vec<(Option<&'a str>, Value<'a>)>,
+        env: &Env<'a>,
+        span: Span,
+        site: &dyn Node,
+    ) -> Result<(), RuntimeError<'a>> {
+        let positional: Vec<&Parameter> = parameters.iter().filter(|p| !p.rest).collect();
+        let rest = parameters.iter().find(|p| p.rest);
+        let count = arguments.len();
+        let fail = |msg: String| error(span, format!("{} in call `{}`", msg, site.print_string()));
+
+        let mut slots: Vec<Option<Value<'a>>> = vec![None; positional.len()];
+        let mut extra = vec![];
+        let mut given = 0;
+
+        for (name, value) in arguments {
+            let idx = match name {
+                None => {
+                    given += 1;
+                    if given > positional.len() {
+                        extra.push(value);
+                        continue;
+                    }
+                    given - 1
+                }
+                Some(name) => match positional.iter().position(|p| p.name.value == name) {
+                    Some(idx) => idx,
+                    None => return fail(format!("unknown named argument `{}`", name)),
+                },
+            };
+            if slots[idx].is_some() {
+                let name = &positional[idx].name.value;
+                return fail(format!("argument `{}` given more than once", name));
+            }
+            slots[idx] = Some(value);
+        }
+
+        let wrong_count = || {
+            let required = positional.iter().filter(|p| p.default.is_none()).count();
+            let expected = if rest.is_some() {
+                format!("at least {}", required)
+            } else if required == positional.len() {
+                format!("{}", required)
+            } else {
+                format!("{} to {}", required, positional.len())
+            };
+            fail(format!(
+                "wrong number of arguments: expected {}, got {}",
+                expected, count
+            ))
+        };
+
+        if rest.is_none() && !extra.is_empty() {
+            return wrong_count();
+        }
+
+        // defaults see the parameters before them
+        for (param, slot) in positional.iter().zip(slots) {
+            let value = match (slot, &param.default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => self.eval_expression(default, env)?,
+                (None, None) => return wrong_count(),
+            };
+            env.define(&param.name.value, value);
+        }
+        if let Some(rest) = rest {
+            env.define(&rest.name.value, Value::Array(Rc::new(extra)));
+        }
+
+        Ok(())
+    }
+
+    fn call_builtin(
+        &mut self,
+        builtin: Builtin,
+        arguments: Vec<(Option<&'a str>, Value<'a>)>,
+        span: Span,
+        site: &dyn Node,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        if let Some((Some(name), _)) = arguments.iter().find(|(name, _)| name.is_some()) {
+            let msg = format!(
+                "unknown named argument `{}` in call `{}`",
+                name,
+                site.print_string()
+            );
+            return error(span, msg);
+        }
+
+        match builtin {
+            Builtin::Puts => {
+                for (_, value) in arguments {
+                    // output that can not be written has nowhere to report to
+                    let _ = writeln!(self.output, "{}", value);
+                }
+                Ok(Value::Null)
+            }
+            Builtin::Len => {
+                let len = match arguments.as_slice() {
+                    [(_, Value::String(value))] => value.chars().count(),
+                    [(_, Value::Array(elements))] => elements.len(),
+                    [(_, Value::Hash(pairs))] => pairs.len(),
+                    [(_, other)] => {
+                        let msg = format!(
+                            "len is not defined for {} in `{}`",
+                            other.type_name(),
+                            site.print_string()
+                        );
+                        return error(span, msg);
+                    }
+                    _ => {
+                        let msg = format!(
+                            "wrong number of arguments: expected 1, got {} in call `{}`",
+                            arguments.len(),
+                            site.print_string()
+                        );
+                        return error(span, msg);
+                    }
+                };
+                Ok(Value::Int(len as i64))
+            }
+        }
+    }
+
+    fn member(
+        &mut self,
+        member: &'a MemberExpression,
+        object: Value<'a>,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        let name = &member.property.value;
+        let span = member.property.token.span;
+
+        match &object {
+            Value::Null if member.optional => Ok(Value::Null),
+            Value::Null => {
+                let msg = format!(
+                    "can not access `{}` on null in `{}`, use `?.` if it may be null",
+                    name,
+                    member.print_string()
+                );
+                error(span, msg)
+            }
+            Value::Struct(value) => match value.fields.iter().find(|(field, _)| field == name) {
+                Some((_, field)) => Ok(field.clone()),
+                None => {
+                    let msg = format!("struct `{}` has no field `{}`", value.name, name);
+                    error(span, msg)
+                }
+            },
+            Value::Generator(generator) if name == "next" => {
+                Ok(Value::GeneratorNext(Rc::clone(generator)))
+            }
+            Value::Error(err) => match err.field(name) {
+                Some(value) => Ok(value),
+                None => {
+                    let msg = format!(
+                        "an error has no field `{}`, only message, stack and value",
+                        name
+                    );
+                    error(span, msg)
+                }
+            },
+            Value::Module(exports) => match exports.get(name) {
+                Some(value) => Ok(value.clone()),
+                None => {
+                    let msg = format!(
+                        "module `{}` does not export `{}`",
+                        member.object.print_string(),
+                        name
+                    );
+                    error(span, msg)
+                }
+            },
+            other => {
+                let msg = format!(
+                    "can not access `{}` on {} in `{}`",
+                    name,
+                    other.type_name(),
+                    member.print_string()
+                );
+                error(span, msg)
+            }
+        }
+    }
+
+    fn eval_struct(
+        &mut self,
+        literal: &'a StructLiteral,
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        let declaration = match env.get(&literal.name.value) {
+            Some(Value::StructType(declaration)) => declaration,
+            _ => {
+                let msg = format!("unknown struct `{}`", literal.name.value);
+                return error(literal.name.token.span, msg);
+            }
+        };
+        if let Some((span, msg)) = declaration.check_literal(literal).into_iter().next() {
+            return error(span, msg);
+        }
+
+        let mut fields = vec![];
+        for field in &declaration.fields {
+            let value = literal
+                .fields
+                .iter()
+                .find(|(name, _)| name.value == field.value)
+                .map(|(_, value)| value);
+            if let Some(value) = value {
+                fields.push((field.value.clone(), self.eval_expression(value, env)?));
+            }
+        }
+
+        Ok(Value::Struct(Rc::new(StructValue {
+            name: declaration.name.value.clone(),
+            fields,
+        })))
+    }
+
+    fn eval_range(
+        &mut self,
+        range: &'a RangeExpression,
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        let mut bound = |bound: &'a Option<Box<ExpressionNode>>| match bound {
+            None => Ok(None),
+            Some(exp) => match self.eval_expression(exp, env)? {
+                Value::Int(value) => Ok(Some(value)),
+                other => {
+                    let msg = format!(
+                        "a range bound must be an int, got {} in `{}`",
+                        other.type_name(),
+                        range.print_string()
+                    );
+                    error(exp.token().span, msg)
+                }
+            },
+        };
+
+        Ok(Value::Range(Range {
+            start: bound(&range.start)?,
+            end: bound(&range.end)?,
+            inclusive: range.inclusive,
+        }))
+    }
+
+    /// Indexing past the end gives null, like a hash key that is not there,
+    /// and a slice is cut to the elements there are.
+    fn eval_index(
+        &mut self,
+        index: &'a IndexExpression,
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        let left = self.eval_expression(&index.left, env)?;
+        if index.optional && matches!(left, Value::Null) {
+            return Ok(Value::Null);
+        }
+        let key = self.eval_expression(&index.index, env)?;
+
+        match (&left, &key) {
+            (Value::Array(elements), Value::Int(idx)) => Ok(usize::try_from(*idx)
+                .ok()
+                .and_then(|idx| elements.get(idx))
+                .cloned()
+                .unwrap_or(Value::Null)),
+            (Value::String(value), Value::Int(idx)) => Ok(usize::try_from(*idx)
+                .ok()
+                .and_then(|idx| value.chars().nth(idx))
+                .map(|ch| Value::String(ch.to_string().into()))
+                .unwrap_or(Value::Null)),
+            (Value::Array(elements), Value::Range(range)) => {
+                let (start, end) = range.slice(elements.len());
+                Ok(Value::Array(Rc::new(elements[start..end].to_vec())))
+            }
+            (Value::String(value), Value::Range(range)) => {
+                let (start, end) = range.slice(value.chars().count());
+                let slice: String = value.chars().skip(start).take(end - start).collect();
+                Ok(Value::String(slice.into()))
+            }
+            (Value::Hash(pairs), key) if HashKey::from_value(key).is_some() => {
+                let key = HashKey::from_value(key);
+                Ok(pairs
+                    .iter()
+                    .find(|(k, _)| Some(k) == key.as_ref())
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or(Value::Null))
+            }
+            (left, key) => {
+                let msg = format!(
+                    "can not index {} with {} in `{}`",
+                    left.type_name(),
+                    key.type_name(),
+                    index.print_string()
+                );
+                error(index.token.span, msg)
+            }
+        }
+    }
+}
+
+/// The address of a local, the distance between two of them is the stack
+/// taken by the frames in between.
+#[inline(never)]
+fn stack_position() -> usize {
+    let local = 0u8;
+    hint::black_box(&local) as *const u8 as usize
+}
+
+fn stack_entry(closure: &Closure, span: Span) -> String {
+    format!("{} ({})", closure.name.as_deref().unwrap_or("fn"), span)
+}
+
+/// Builds a value of `variant` from one positional argument per field.
+fn construct<'a>(
+    variant: &'a Variant,
+    arguments: Vec<(Option<&'a str>, Value<'a>)>,
+    span: Span,
+    site: &dyn Node,
+) -> Result<Value<'a>, RuntimeError<'a>> {
+    let msg = match arguments.iter().find_map(|(name, _)| *name) {
+        Some(name) => format!("unknown named argument `{}`", name),
+        None if arguments.len() != variant.fields.len() => format!(
+            "wrong number of arguments: expected {}, got {}",
+            variant.fields.len(),
+            arguments.len()
+        ),
+        None => {
+            let payload = arguments.into_iter().map(|(_, value)| value).collect();
+            return Ok(Value::Variant(Rc::new(VariantValue { variant, payload })));
+        }
+    };
+
+    error(span, format!("{} in call `{}`", msg, site.print_string()))
+}
+
+/// Applies a built-in infix operator to its evaluated operands.
+fn eval_operator<'a>(
+    infix: &InfixExpression,
+    left: Value<'a>,
+    right: Value<'a>,
+) -> Result<Value<'a>, RuntimeError<'a>> {
+    let operands = (infix.operator.as_str(), &left, &right);
+    let result = match operands {
+        ("==", left, right) => Some(Value::Bool(left.equals(right))),
+        ("!=", left, right) => Some(Value::Bool(!left.equals(right))),
+        ("+", Value::String(left), Value::String(right)) => {
+            Some(Value::String(format!("{}{}", left, right).into()))
+        }
+        (operator, Value::Int(left), Value::Int(right)) => {
+            let (left, right) = (*left, *right);
+            match operator {
+                "<" => Some(Value::Bool(left < right)),
+                ">" => Some(Value::Bool(left > right)),
+                "/" if right == 0 => {
+                    let msg = format!("division by zero in `{}`", infix.print_string());
+                    return error(infix.token.span, msg);
+                }
+                "+" | "-" | "*" | "/" => {
+                    let value = match operator {
+                        "+" => left.checked_add(right),
+                        "-" => left.checked_sub(right),
+                        "*" => left.checked_mul(right),
+                        _ => left.checked_div(right),
+                    };
+                    let Some(value) = value else {
+                        let msg = format!("integer overflow in `{}`", infix.print_string());
+                        return error(infix.token.span, msg);
+                    };
+                    Some(Value::Int(value))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+
+    match result {
+        Some(value) => Ok(value),
+        None => {
+            let msg = format!(
+                "operator {} is not defined for {} and {} in `{}`",
+                infix.operator,
+                left.type_name(),
+                right.type_name(),
+                infix.print_string()
+            );
+            error(infix.token.span, msg)
+        }
+    }
+}
+
+fn eval_prefix<'a>(
+    prefix: &'a PrefixExpression,
+    right: Value<'a>,
+) -> Result<Value<'a>, RuntimeError<'a>> {
+    match (prefix.operator.as_str(), &right) {
+        ("!", right) => Ok(Value::Bool(!right.is_truthy())),
+        ("-", Value::Int(value)) => match value.checked_neg() {
+            Some(value) => Ok(Value::Int(value)),
+            None => {
+                let msg = format!("integer overflow in `{}`", prefix.print_string());
+                error(prefix.token.span, msg)
+            }
+        },
+        (operator, right) => {
+            let msg = format!(
+                "operator {} is not defined for {} in `{}`",
+                operator,
+                right.type_name(),
+                prefix.print_string()
+            );
+            error(prefix.token.span, msg)
+        }
+    }
+}
+
+/// Matches `value` against `pattern`, collecting the values of the names it
+/// binds, or says why it does not match.
+fn destructure<'a>(
+    pattern: &'a Pattern,
+    value: &Value<'a>,
+    bindings: &mut Vec<(&'a str, Value<'a>)>,
+) -> Result<(), String> {
+    let mismatch = |expected: &str| {
+        Err(format!(
+            "destructuring failed for `{}`: expected {}, got {}",
+            pattern.print_string(),
+            expected,
+            value
+        ))
+    };
+
+    match pattern {
+        Pattern::Wildcard(_) => Ok(()),
+        Pattern::Binding(name) => {
+            bindings.push((&name.value, value.clone()));
+            Ok(())
+        }
+        Pattern::Integer(int) => match value {
+            Value::Int(value) if *value == int.value => Ok(()),
+            _ => mismatch(&int.print_string()),
+        },
+        Pattern::Boolean(boolean) => match value {
+            Value::Bool(value) if *value == boolean.value => Ok(()),
+            _ => mismatch(&boolean.print_string()),
+        },
+        Pattern::StringNode(string) => match value {
+            Value::String(value) if **value == *string.value => Ok(()),
+            _ => mismatch(&string.print_string()),
+        },
+        Pattern::Array(array) => {
+            let Value::Array(elements) = value else {
+                return mismatch("an array");
+            };
+
+            if let Some(missing) = array.elements.get(elements.len()) {
+                return Err(format!(
+                    "destructuring failed for `{}`: expected at least {} elements, got {}",
+                    missing.print_string(),
+                    array.elements.len(),
+                    elements.len()
+                ));
+            }
+            if array.rest.is_none() && elements.len() > array.elements.len() {
+                return Err(format!(
+                    "destructuring failed for `{}`: expected {} elements, got {}",
+                    pattern.print_string(),
+                    array.elements.len(),
+                    elements.len()
+                ));
+            }
+
+            for (element, value) in array.elements.iter().zip(elements.iter()) {
+                destructure(element, value, bindings)?;
+            }
+            if let Some(rest) = &array.rest {
+                let rest_elements = elements[array.elements.len()..].to_vec();
+                bindings.push((&rest.value, Value::Array(Rc::new(rest_elements))));
+            }
+            Ok(())
+        }
+        Pattern::Hash(hash) => {
+            let Value::Hash(pairs) = value else {
+                return mismatch("a hash");
+            };
+
+            for (key, sub_pattern) in &hash.pairs {
+                let found = pairs
+                    .iter()
+                    .find(|(k, _)| matches!(k, HashKey::String(k) if **k == *key.value));
+                match found {
+                    Some((_, value)) => destructure(sub_pattern, value, bindings)?,
+                    None => {
+                        return Err(format!(
+                            "destructuring failed for `{}`: key {} not found",
+                            sub_pattern.print_string(),
+                            key.print_string()
+                        ))
+                    }
+                }
+            }
+            Ok(())
+        }
+        Pattern::Variant(pattern) => match value {
+            Value::Variant(value)
+                if value.variant.name.value == pattern.name.value
+                    && value.payload.len() == pattern.payload.len() =>
+            {
+                for (sub_pattern, value) in pattern.payload.iter().zip(&value.payload) {
+                    destructure(sub_pattern, value, bindings)?;
+                }
+                Ok(())
+            }
+            _ => mismatch(&pattern.print_string()),
+        },
+    }
+}
+
+/// Statements still to run, the innermost block last.
+///
+/// They are kept off the Rust stack, so that a generator can stop at a
+/// `yield` and continue later.
+struct Machine<'a> {
+    frames: Vec<Frame<'a>>,
+    /// Whether `yield` pauses it, elsewhere `yield` is an error.
+    generator: bool,
+    /// Whether it runs a block of an `if` used as a value, which `return`
+    /// and `yield` can not leave.
+    branch: bool,
+}
+
+enum Frame<'a> {
+    /// A block part way through, `next` is the index of the statement to run next.
+    Block {
+        statements: &'a [StatementNode],
+        next: usize,
+        env: Env<'a>,
+        /// Whether the value of its last statement is what the machine finishes with.
+        tail: bool,
+    },
+    /// A `for` loop between two runs of its body.
+    For {
+        stmt: &'a ForStatement,
+        items: Items<'a>,
+        env: Env<'a>,
+    },
+    /// A `try` statement, below the block of its body, `catch` or `finally`.
+    Try {
+        stmt: &'a TryStatement,
+        env: Env<'a>,
+        stage: TryStage<'a>,
+    },
+}
+
+/// How far a `try` statement has run.
+enum TryStage<'a> {
+    Start,
+    Body,
+    Catch,
+    /// The `finally` block, and how the statement is left once it is done,
+    /// if not by going on with the next statement.
+    Finally(Option<Exit<'a>>),
+}
+
+/// What running one statement leads to.
+enum Step<'a> {
+    /// The statement is done, with the value of an expression statement.
+    Done(Value<'a>),
+    /// The function returns.
+    Return(Value<'a>),
+    /// The statement goes on in a new frame, like the body of a loop.
+    Enter(Frame<'a>),
+    /// The generator pauses with a value.
+    Yield(Value<'a>),
+}
+
+/// Where a machine stopped running.
+enum Pause<'a> {
+    /// At a `yield`, it goes on with the next statement when resumed.
+    Yield(Value<'a>),
+    /// With every frame done, and the value [`Machine::run`] returns.
+    Finish(Value<'a>),
+}
+
+/// A way of leaving frames before their statements are done.
+enum Exit<'a> {
+    Return(Value<'a>),
+    Throw(RuntimeError<'a>),
+}
+
+impl<'a> Machine<'a> {
+    fn new(statements: &'a [StatementNode], env: Env<'a>) -> Machine<'a> {
+        Machine {
+            frames: vec![Frame::Block {
+                statements,
+                next: 0,
+                env,
+                tail: true,
+            }],
+            generator: false,
+            branch: false,
+        }
+    }
+
+    fn generator(statements: &'a [StatementNode], env: Env<'a>) -> Machine<'a> {
+        Machine {
+            generator: true,
+            ..Machine::new(statements, env)
+        }
+    }
+
+    fn branch(statements: &'a [StatementNode], env: Env<'a>) -> Machine<'a> {
+        Machine {
+            branch: true,
+            ..Machine::new(statements, env)
+        }
+    }
+
+    /// Runs every frame and returns the value of a `return`, or else the
+    /// value of the outermost block's last statement if that is an expression,
+    /// or an `if` whose block ends in one.
+    fn run(&mut self, evaluator: &mut Evaluator<'a>) -> Result<Value<'a>, RuntimeError<'a>> {
+        match self.resume(evaluator)? {
+            Pause::Finish(value) => Ok(value),
+            Pause::Yield(_) => unreachable!("only a generator pauses"),
+        }
+    }
+
+    /// Runs frames until every one is done or a generator yields.
+    fn resume(&mut self, evaluator: &mut Evaluator<'a>) -> Result<Pause<'a>, RuntimeError<'a>> {
+        let mut result = Value::Null;
+
+        loop {
+            let step = match self.frames.last_mut() {
+                None => return Ok(Pause::Finish(result)),
+                Some(Frame::Block {
+                    statements,
+                    next,
+                    env,
+                    tail,
+                }) => {
+                    let statements: &'a [StatementNode] = statements;
+                    let Some(stmt) = statements.get(*next) else {
+                        self.frames.pop();
+                        continue;
+                    };
+                    *next += 1;
+                    let last = *tail && *next == statements.len();
+                    let env = env.clone();
+
+                    match evaluator.exec_statement(stmt, &env) {
+                        Ok(Step::Done(value)) if last => {
+                            result = value;
+                            continue;
+                        }
+                        Ok(Step::Done(_)) => continue,
+                        Ok(Step::Yield(value)) if self.generator => return Ok(Pause::Yield(value)),
+                        Ok(Step::Yield(_)) => Err(self.refuse(stmt, "yield")),
+                        Ok(Step::Return(_)) if self.branch => Err(self.refuse(stmt, "return")),
+                        Ok(mut step) => {
+                            if last {
+                                result = Value::Null;
+                                // the block of a last `if` statement gives the value
+                                if let Step::Enter(Frame::Block { tail, .. }) = &mut step {
+                                    *tail = true;
+                                }
+                            }
+                            Ok(step)
+                        }
+                        Err(err) => Err(Exit::Throw(err)),
+                    }
+                }
+                Some(Frame::For { stmt, items, env }) => match items.next(evaluator) {
+                    Ok(Some(item)) => {
+                        let body_env = env.enclosed();
+                        body_env.define(&stmt.variable.value, item);
+                        Ok(Step::Enter(Frame::Block {
+                            statements: &stmt.body.statements,
+                            next: 0,
+                            env: body_env,
+                            tail: false,
+                        }))
+                    }
+                    Ok(None) => {
+                        self.frames.pop();
+                        continue;
+                    }
+                    Err(err) => Err(Exit::Throw(err)),
+                },
+                // the block above the `try` is done without leaving early
+                Some(Frame::Try { stmt, env, stage }) => match (&*stage, &stmt.finally) {
+                    (TryStage::Start, _) => {
+                        *stage = TryStage::Body;
+                        Ok(Step::Enter(Frame::block(&stmt.body, env)))
+                    }
+                    (TryStage::Body | TryStage::Catch, Some(finally)) => {
+                        let frame = Frame::block(finally, env);
+                        *stage = TryStage::Finally(None);
+                        Ok(Step::Enter(frame))
+                    }
+                    (TryStage::Body | TryStage::Catch, None) => {
+                        self.frames.pop();
+                        continue;
+                    }
+                    (TryStage::Finally(_), _) => {
+                        let Some(Frame::Try {
+                            stage: TryStage::Finally(exit),
+                            ..
+                        }) = self.frames.pop()
+                        else {
+                            unreachable!("matched above");
+                        };
+                        match exit {
+                            Some(exit) => Err(exit),
+                            None => continue,
+                        }
+                    }
+                },
+            };
+
+            let exit = match step {
+                Ok(Step::Enter(frame)) => {
+                    self.frames.push(frame);
+                    continue;
+                }
+                Ok(Step::Return(value)) => Exit::Return(value),
+                Ok(Step::Done(_) | Step::Yield(_)) => unreachable!("handled with the statement"),
+                Err(exit) => exit,
+            };
+            match self.unwind(exit, evaluator) {
+                None => continue,
+                Some(Exit::Return(value)) => return Ok(Pause::Finish(value)),
+                Some(Exit::Throw(err)) => return Err(err),
+            }
+        }
+    }
+
+    /// The error for a `return` or `yield` statement this machine can not be left by.
+    fn refuse(&self, stmt: &StatementNode, keyword: &str) -> Exit<'a> {
+        let msg = match self.branch {
+            true => format!("{} can not leave an `if` used as a value", keyword),
+            false => format!("{} is only allowed inside a function", keyword),
+        };
+        Exit::Throw(RuntimeError::new(stmt.span(), msg))
+    }
+
+    /// Pops frames until a `try` handles `exit` by running its `catch` or
+    /// `finally` block, or returns `exit` once every frame is gone.
+    fn unwind(&mut self, mut exit: Exit<'a>, evaluator: &Evaluator<'a>) -> Option<Exit<'a>> {
+        while let Some(frame) = self.frames.pop() {
+            let Frame::Try { stmt, env, stage } = frame else {
+                continue;
+            };
+
+            if let TryStage::Body = stage {
+                match (exit, &stmt.catch) {
+                    (Exit::Throw(err), Some(catch)) => {
+                        let catch_env = env.enclosed();
+                        catch_env.define(&catch.parameter.value, evaluator.caught(err));
+                        self.frames.push(Frame::Try {
+                            stmt,
+                            env,
+                            stage: TryStage::Catch,
+                        });
+                        self.frames.push(Frame::Block {
+                            statements: &catch.body.statements,
+                            next: 0,
+                            env: catch_env,
+                            tail: false,
+                        });
+                        return None;
+                    }
+                    (other, _) => exit = other,
+                }
+            }
+
+            // leaving the `finally` block early replaces the exit it ran for
+            if let (TryStage::Body | TryStage::Catch, Some(finally)) = (&stage, &stmt.finally) {
+                let frame = Frame::block(finally, &env);
+                self.frames.push(Frame::Try {
+                    stmt,
+                    env,
+                    stage: TryStage::Finally(Some(exit)),
+                });
+                self.frames.push(frame);
+                return None;
+            }
+        }
+
+        Some(exit)
+    }
+}
+
+impl<'a> Frame<'a> {
+    /// The frame of `block` in a new scope inside `env`.
+    fn block(block: &'a BlockStatement, env: &Env<'a>) -> Frame<'a> {
+        Frame::Block {
+            statements: &block.statements,
+            next: 0,
+            env: env.enclosed(),
+            tail: false,
+        }
+    }
+}
+
+/// A call of a function that yields, which runs its body a bit at a time.
+///
+/// A `finally` block is skipped if the generator is not run to its end.
+pub struct Generator<'a> {
+    closure: Rc<Closure<'a>>,
+    state: RefCell<GeneratorState<'a>>,
+}
+
+enum GeneratorState<'a> {
+    Suspended(Machine<'a>),
+    Running,
+    Done,
+}
+
+// the frames hold scopes that may hold the generator itself
+impl std::fmt::Debug for Generator<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Generator")
+            .field("closure", &self.closure)
+            .finish()
+    }
+}
+
+/// The elements a `for` loop has not visited yet.
+enum Items<'a> {
+    Array(Rc<Vec<Value<'a>>>, usize),
+    /// Asked for each value, `Span` is the iterable's.
+    Generator(Rc<Generator<'a>>, Span),
+    /// The next number, `None` past `i64::MAX`, and the last one, `None`
+    /// for a range without an end.
+    Range(Option<i64>, Option<i64>),
+}
+
+impl<'a> Items<'a> {
+    fn of(iterable: Value<'a>, stmt: &ForStatement) -> Result<Items<'a>, RuntimeError<'a>> {
+        match iterable {
+            Value::Array(elements) => Ok(Items::Array(elements, 0)),
+            Value::Range(Range {
+                start: Some(start),
+                end,
+                inclusive,
+            }) => {
+                let last = match (end, inclusive) {
+                    (Some(end), true) => Some(end),
+                    (Some(end), false) => match end.checked_sub(1) {
+                        Some(last) => Some(last),
+                        None => return Ok(Items::Range(None, None)),
+                    },
+                    (None, _) => None,
+                };
+                Ok(Items::Range(Some(start), last))
+            }
+            Value::Generator(generator) => {
+                Ok(Items::Generator(generator, stmt.iterable.token().span))
+            }
+            other => {
+                let msg = format!(
+                    "can not iterate over {} in `{}`",
+                    other.type_name(),
+                    stmt.iterable.print_string()
+                );
+                error(stmt.iterable.token().span, msg)
+            }
+        }
+    }
+
+    fn next(
+        &mut self,
+        evaluator: &mut Evaluator<'a>,
+    ) -> Result<Option<Value<'a>>, RuntimeError<'a>> {
+        match self {
+            Items::Array(elements, idx) => {
+                let element = elements.get(*idx).cloned();
+                *idx += 1;
+                Ok(element)
+            }
+            Items::Generator(generator, span) => evaluator.resume(generator, *span),
+            Items::Range(next, last) => match *next {
+                Some(value) if last.is_none_or(|last| value <= last) => {
+                    *next = value.checked_add(1);
+                    Ok(Some(Value::Int(value)))
+                }
+                _ => Ok(None),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::{macro_expansion::expand_macros, module::Loader, parse};
+
+    use super::Evaluator;
+
+    /// Runs `input` and returns what it printed followed by its value, or its error.
+    fn run(input: &str) -> Result<String, String> {
+        let program = expand_macros(parse(input).unwrap()).unwrap();
+        let program = Box::leak(Box::new(program));
+
+        let mut output = vec![];
+        let value = Evaluator::new(&mut output).eval_program(program);
+        let value = value.map_err(|error| error.to_string())?.to_string();
+        Ok(format!("{}{}", String::from_utf8(output).unwrap(), value))
+    }
+
+    #[test]
+    fn test_eval_program() {
+        let tests = vec![
+            ("1 + 2 * 3", "7"),
+            ("-(4 - 10) / 2", "3"),
+            ("1 < 2 == true; !null", "true"),
+            ("\"a\" + \"b\"", "ab"),
+            ("let x = 5; x = x + 1; x", "6"),
+            ("[1, \"a\", [true]]", "[1, \"a\", [true]]"),
+            ("{\"a\": 1, 2: [null]}[\"a\"]", "1"),
+            ("{\"a\": 1, 2: [null]}", "{\"a\": 1, 2: [null]}"),
+            ("[1, 2][5]; \"abc\"[1]", "b"),
+            ("len(\"abc\") + len([1]) + len({})", "4"),
+            ("puts(1, \"a\"); 2", "1\na\n2"),
+            ("let add = fn(a, b) { a + b }; add(1, 2)", "3"),
+            ("let f = fn() { return 1; 2 }; f()", "1"),
+            ("let f = fn() { let x = 1; }; f()", "null"),
+            (
+                "let counter = fn() { let n = 0; fn() { n = n + 1 } }; let c = counter(); c(); c()",
+                "2",
+            ),
+            (
+                "let fib = fn(n) { match (n) { 0 => 0, 1 => 1, _ => fib(n - 1) + fib(n - 2) } }; fib(15)",
+                "610",
+            ),
+            ("let f = fn(a, b = a * 2) { [a, b] }; f(1)", "[1, 2]"),
+            ("let f = fn(a, b = 2) { [a, b] }; f(b: 3, a: 1)", "[1, 3]"),
+            ("let f = fn(a, ...rest) { rest }; f(1, 2, 3)", "[2, 3]"),
+            ("let f = fn(a, ...rest) { rest }; f(1)", "[]"),
+            ("infix <+> 3 left = fn(a, b) { a * 10 + b }; 1 <+> 2 <+> 3", "123"),
+            ("infix ^^ 4 right = fn(a, b) { [a, b] }; 1 ^^ 2 ^^ 3", "[1, [2, 3]]"),
+            ("match ([1, 2]) { [a] => a, [a, b] if a > b => a, [_, b] => b }", "2"),
+            ("match ({\"k\": 7}) { {\"k\": v} => v }", "7"),
+            ("let [a, ...rest] = [1, 2, 3]; let {\"b\": b} = {\"b\": rest}; b", "[2, 3]"),
+            ("let s = 0; for x in [1, 2, 3] { s = s + x; } s", "6"),
+            (
+                "let f = fn(xs) { for x in xs { return x; } 0 }; f([4, 5])",
+                "4",
+            ),
+            ("struct P { x, y } P { y: 2, x: 1 }", "P { x: 1, y: 2 }"),
+            ("struct P { x, y } let p = P { x: 1, y: \"a\" }; p.y", "a"),
+            ("struct P { x } P { x: 1 } == P { x: 1 }", "true"),
+            ("let f = fn(a, b) { a }; f", "fn(a, b)"),
+            ("len", "builtin len"),
+            ("let len = fn(x) { 0 }; len([1])", "0"),
+            ("enum Shape { Circle(r), Rect(w, h) } [Circle(1), Rect(2, \"a\")]", "[Circle(1), Rect(2, \"a\")]"),
+            ("enum Option { Some(x), None } None", "None"),
+            ("enum Option { Some(x), None } Some", "variant Some(x)"),
+            (
+                "enum Shape { Circle(r), Rect(w, h), Dot } let area = fn(s) { match (s) { Circle(r) => 3 * r * r, Rect(w, h) => w * h, Dot => 0 } }; [area(Circle(2)), area(Rect(2, 3)), area(Dot)]",
+                "[12, 6, 0]",
+            ),
+            ("enum O { Some(x), None } match (Some(Some(1))) { Some(None) => 0, Some(Some(x)) => x }", "1"),
+            ("enum O { Some(x), None } [Some(1) == Some(1), Some(1) == Some(2), None == None]", "[true, false, true]"),
+            ("let f = fn(s) { match (s) { None => 0, _ => 1 } }; enum O { Some(x), None } [f(Some(5)), f(None)]", "[1, 0]"),
+            ("const c = 1; let f = fn() { let c = 2; c = 3; c }; [f(), c]", "[3, 1]"),
+            ("try { puts(1) } catch (e) { puts(2) } finally { puts(3) } 4", "1\n3\n4"),
+            ("let f = fn() { try { throw \"boom\" } catch (e) { return e; } }; f()", "error: boom"),
+            (
+                "let f = fn() { try { [1] + 1 } catch (e) { return e.message; } }; f()",
+                "operator + is not defined for array and int in `([1] + 1)`",
+            ),
+            (
+                "let div = fn(a, b) { a / b }; let f = fn() { try { div(1, 0) } catch (e) { return [e.stack, e.value]; } }; f()",
+                "[[\"div (1:55-56)\", \"f (1:109-110)\"], null]",
+            ),
+            (
+                "let f = fn() { try { throw {\"code\": 2} } catch (e) { return e.value[\"code\"]; } }; f()",
+                "2",
+            ),
+            (
+                "let f = fn() { try { return 1 } finally { puts(\"finally\") } }; f()",
+                "finally\n1",
+            ),
+            ("let f = fn() { try { return 1 } finally { return 2 } }; f()", "2"),
+            (
+                "let f = fn() { try { try { throw 1 } finally { puts(2) } } catch (e) { return e.value + 1; } }; f()",
+                "2\n2",
+            ),
+            (
+                "let f = fn() { try { throw 1 } catch (e) { throw e } }; let g = fn() { try { f() } catch (e) { return [e.message, e.stack]; } }; g()",
+                "[\"1\", [\"f (1:79-80)\", \"g (1:131-132)\"]]",
+            ),
+            ("let f = fn(xs) { for x in xs { try { return x } finally { puts(0) } } }; f([7, 8])", "0\n7"),
+            (
+                "let count = fn(n) { for i in [1, 2, 3] { match (i > n) { true => null, _ => i }; yield i * 10; } }; let g = count(2); [g.next(), g.next()]",
+                "[{\"value\": 10, \"done\": false}, {\"value\": 20, \"done\": false}]",
+            ),
+            (
+                "let g = fn() { yield 1; return 5; yield 2; }(); [g.next()[\"value\"], g.next(), g.next()[\"done\"]]",
+                "[1, {\"value\": null, \"done\": true}, true]",
+            ),
+            (
+                "let gen = fn(xs) { puts(\"start\"); for x in xs { yield x; } }; let g = gen([1, 2]); puts(\"made\"); for x in g { puts(x); } 0",
+                "made\nstart\n1\n2\n0",
+            ),
+            (
+                "let naturals = fn() { let n = 0; for _ in [1, 1, 1] { n = n + 1; yield n; } }; let g = naturals(); [g.next()[\"value\"], g.next()[\"value\"]]",
+                "[1, 2]",
+            ),
+            (
+                "let inner = fn() { yield 1; yield 2; }; let outer = fn() { for x in inner() { yield x * 2; } }; let s = 0; for x in outer() { s = s + x; } s",
+                "6",
+            ),
+            (
+                "let g = fn() { try { yield 1; throw \"stop\"; } catch (e) { yield e.message; } }(); for x in g { puts(x); } g.next()[\"done\"]",
+                "1\nstop\ntrue",
+            ),
+            ("fn() { yield 1; }()", "generator"),
+            ("[1..3, 1..=3, ..5, 2..]", "[1..3, 1..=3, ..5, 2..]"),
+            ("let s = 0; for i in 1..=4 { s = s + i; } s", "10"),
+            ("let s = 0; for i in 3..3 { s = s + 1; } s", "0"),
+            (
+                "let g = fn() { for i in 0..1_000_000_000_000 { yield i; } }(); g.next(); g.next()[\"value\"]",
+                "1",
+            ),
+            ("let f = fn() { for i in 5.. { return i; } }; f()", "5"),
+            (
+                "let s = 0; for i in 9223372036854775806..=9223372036854775807 { s = s + 1; } s",
+                "2",
+            ),
+            ("let xs = [1, 2, 3, 4]; [xs[1..3], xs[..2], xs[2..], xs[1..=2]]", "[[2, 3], [1, 2], [3, 4], [2, 3]]"),
+            ("[1, 2][-5..10]; [1, 2][2..1]", "[]"),
+            ("let r = 1..; [\"héllo\"[r], \"héllo\"[..=1], \"ab\"[5..]]", "[\"éllo\", \"hé\", \"\"]"),
+            ("(1..3) == (1..3)", "true"),
+            ("let n = null; [n?.x, n?.[0], n?.x?.y]", "[null, null, null]"),
+            ("struct P { x } let p = P { x: 1 }; [p?.x, [5]?.[0]]", "[1, 5]"),
+            ("[null ?? 1, 2 ?? 3, false ?? 4, null ?? null ?? 5]", "[1, 2, false, 5]"),
+            ("let n = null; n?.[puts(1)]; 1 ?? puts(2)", "1"),
+            ("let m = macro(x) { quote(unquote(x) + 1) }; m(2)", "3"),
+            ("if (1 < 2) { 10 } else { 20 }", "10"),
+            ("let x = if (false) { 1 }; [x, if (null) { 1 } else { 2 }]", "[null, 2]"),
+            (
+                "let sign = fn(n) { if (n > 0) { 1 } else if (n < 0) { -1 } else { 0 } }; [sign(5), sign(-5), sign(0)]",
+                "[1, -1, 0]",
+            ),
+            ("let f = fn(n) { if (n > 0) { return \"pos\"; } \"other\" }; f(1) + f(0)", "posother"),
+            ("let x = 1; if (true) { let x = 2; }; x", "1"),
+            ("if (true) { let y = 1; }", "null"),
+            (
+                "let g = fn() { for i in 1..4 { if (i == 2) { yield i * 10; } else { yield i; } } }; let it = g(); [it.next()[\"value\"], it.next()[\"value\"], it.next()[\"value\"]]",
+                "[1, 20, 3]",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Ok(expected.to_string()), "for {input}");
+        }
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let tests = vec![
+            ("1 / 0", "1:3-4: division by zero in `(1 / 0)`"),
+            ("9223372036854775807 + 1", "1:21-22: integer overflow in `(9223372036854775807 + 1)`"),
+            ("1 + true", "1:3-4: operator + is not defined for int and bool in `(1 + true)`"),
+            ("-\"a\"", "1:1-2: operator - is not defined for string in `(-\"a\")`"),
+            ("x", "1:1-2: unknown identifier `x`"),
+            ("y = 1", "1:1-2: can not assign to undeclared `y`"),
+            ("const c = 1; c = 2", "1:14-15: can not assign to const `c`"),
+            ("const c = 1; let f = fn() { c = 2 }; f()", "1:29-30: can not assign to const `c`"),
+            ("1(2)", "1:2-3: can not call int in `1(2)`"),
+            (
+                "let f = fn(a, b) { a }; f(1)",
+                "1:26-27: wrong number of arguments: expected 2, got 1 in call `f(1)`",
+            ),
+            (
+                "let f = fn(a, b = 1) { a }; f(1, 2, 3)",
+                "1:30-31: wrong number of arguments: expected 1 to 2, got 3 in call `f(1, 2, 3)`",
+            ),
+            (
+                "let f = fn(a, b, ...r) { a }; f()",
+                "1:32-33: wrong number of arguments: expected at least 2, got 0 in call `f()`",
+            ),
+            (
+                "let f = fn(a) { a }; f(b: 1)",
+                "1:23-24: unknown named argument `b` in call `f(b: 1)`",
+            ),
+            (
+                "let f = fn(a) { a }; f(1, a: 1)",
+                "1:23-24: argument `a` given more than once in call `f(1, a: 1)`",
+            ),
+            (
+                "infix <+> 3 left = fn(a) { a }; 1 <+> 2",
+                "1:35-38: wrong number of arguments: expected 1, got 2 in call `(1 <+> 2)`",
+            ),
+            (
+                "let [a, b] = [1];",
+                "1:1-4: destructuring failed for `b`: expected at least 2 elements, got 1 in `let [a, b] = [1];`",
+            ),
+            ("match (3) { 1 => 1 }", "1:1-6: no arm matches 3 in `match (3)`"),
+            (
+                "let f = fn() { let v = if (true) { return 1; }; v }; f()",
+                "1:36-45: return can not leave an `if` used as a value",
+            ),
+            (
+                "let g = fn() { yield 1; [if (true) { yield 2; }] }(); g.next(); g.next()",
+                "1:38-46: yield can not leave an `if` used as a value",
+            ),
+            ("struct P { x } P { x: 1 }.y", "1:27-28: struct `P` has no field `y`"),
+            ("{}[[1]]", "1:3-4: can not index hash with array in `({}[[1]])`"),
+            ("for x in 1 { }", "1:10-11: can not iterate over int in `1`"),
+            (
+                "enum O { Some(x), None } Some(1, 2)",
+                "1:30-31: wrong number of arguments: expected 1, got 2 in call `Some(1, 2)`",
+            ),
+            (
+                "enum O { Some(x), None } match (None) { Some(x) => x }",
+                "1:26-31: no arm matches None in `match (None)`",
+            ),
+            (
+                "let g = fn() { yield g.next(); }(); g.next()",
+                "1:28-29: a generator can not go on while it is running",
+            ),
+            (
+                "let g = fn() { yield 1; }(); g.next(1)",
+                "1:36-37: wrong number of arguments: expected 0, got 1 in call `g.next(1)`",
+            ),
+            (
+                "1..true",
+                "1:4-8: a range bound must be an int, got bool in `(1..true)`",
+            ),
+            ("for i in ..3 { }", "1:10-12: can not iterate over range in `(..3)`"),
+            (
+                "let x = null; x.y",
+                "1:17-18: can not access `y` on null in `x.y`, use `?.` if it may be null",
+            ),
+            (
+                "let n = null; n?.a.b",
+                "1:20-21: can not access `b` on null in `n?.a.b`, use `?.` if it may be null",
+            ),
+            ("len(1)", "1:4-5: len is not defined for int in `len(1)`"),
+            (
+                "let f = fn(n) { f(n + 1) }; f(0)",
+                "1:18-19: calls nest deeper than the maximum of 64",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Err(expected.to_string()), "for {input}");
+        }
+    }
+
+    #[test]
+    fn test_uncaught_errors() {
+        let tests = vec![
+            ("throw \"boom\";", "1:1-6: boom", vec![]),
+            (
+                "let f = fn(x) { throw x; }; let g = fn() { f(1) }; g()",
+                "1:17-22: 1",
+                vec!["f (1:45-46)", "g (1:53-54)"],
+            ),
+            (
+                "let f = fn() { try { 1 / 0 } finally { puts(1) } }; f()",
+                "1:24-25: division by zero in `(1 / 0)`",
+                vec!["f (1:54-55)"],
+            ),
+            ("fn() { throw 1; }()", "1:8-13: 1", vec!["fn (1:18-19)"]),
+        ];
+
+        for (input, message, stack) in tests {
+            let program = Box::leak(Box::new(parse(input).unwrap()));
+            let error = Evaluator::new(std::io::sink())
+                .eval_program(program)
+                .unwrap_err();
+
+            assert_eq!(error.to_string(), message, "for {input}");
+            assert_eq!(error.stack, stack, "for {input}");
+        }
+    }
+
+    #[test]
+    fn test_max_call_depth() {
+        let program = parse("let f = fn(n) { match (n) { 0 => 0, _ => 1 + f(n - 1) } }; f(10)");
+        let program = Box::leak(Box::new(program.unwrap()));
+
+        let mut evaluator = Evaluator::new(std::io::sink()).with_max_call_depth(5);
+        let error = evaluator.eval_program(program).unwrap_err();
+
+        assert_eq!(error.message, "calls nest deeper than the maximum of 5");
+    }
+
+    #[test]
+    fn test_max_stack() {
+        let nested = format!("{}f(n - 1){}", "1 + (".repeat(25), ")".repeat(25));
+        let tests = vec![
+            String::from("let f = fn(n) { match (n) { 0 => 0, _ => 1 + f(n - 1) } }; f(100000)"),
+            format!("let f = fn(n) {{ match (n) {{ 0 => 0, _ => {nested} }} }}; f(100000)"),
+            String::from("let f = fn(n) { yield f(n - 1).next(); }; f(100000).next()"),
+            String::from("let f = fn(n) { for x in 0..n { return f(n - 1); } }; f(100000)"),
+        ];
+
+        // on the 2 MiB stack of a test thread, close to the default limit
+        for input in tests {
+            let program = Box::leak(Box::new(parse(&input).unwrap()));
+
+            let mut evaluator = Evaluator::new(std::io::sink()).with_max_call_depth(usize::MAX);
+            let error = evaluator.eval_program(program).unwrap_err();
+
+            assert_eq!(
+                error.message, "nesting exceeds the stack limit of 1536 KiB",
+                "for {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_module() {
+        let dir = std::env::temp_dir().join(format!("eval-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let files = [
+            (
+                "main.mk",
+                "import \"a.mk\" as a; import \"b.mk\" as b; puts(a.x); [a.twice(b.y), a]",
+            ),
+            (
+                "a.mk",
+                "import \"b.mk\" as b; export let x = b.y; export let twice = fn(n) { n * 2 };",
+            ),
+            (
+                "b.mk",
+                "puts(\"b runs\"); export let y = 21; let hidden = 0;",
+            ),
+        ];
+        for (path, input) in files {
+            fs::write(dir.join(path), input).unwrap();
+        }
+
+        let module = Loader::new().load(&dir.join("main.mk")).unwrap();
+        let module = Box::leak(Box::new(module));
+        let mut output = vec![];
+        let value = Evaluator::new(&mut output)
+            .eval_module(module)
+            .unwrap()
+            .to_string();
+
+        assert_eq!(value, "[42, module { twice, x }]");
+        assert_eq!(String::from_utf8(output).unwrap(), "b runs\n21\n");
+
+        let program = Box::leak(Box::new(parse("import \"b.mk\" as b;").unwrap()));
+        let error = Evaluator::new(std::io::sink())
+            .eval_program(program)
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "can not import \"b.mk\" here, only a file that is run can import"
+        );
+    }
+}
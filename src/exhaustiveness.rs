@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use crate::ast::{
+    walk_expression, walk_statement, EnumDeclaration, ExpressionNode, MatchExpression, Node,
+    Pattern, Program, StatementNode, Visitor,
+};
+
+/// Walks the program and returns a warning for every `match` over booleans
+/// or enum variants that leaves a value uncovered and has no catch-all arm.
+pub fn check_program(program: &Program) -> Vec<String> {
+    let mut checker = ExhaustivenessChecker {
+        enums: HashMap::new(),
+        warnings: vec![],
+    };
+
+    checker.visit_program(program);
+
+    checker.warnings
+}
+
+struct ExhaustivenessChecker<'a> {
+    /// The declaring enum of every variant seen so far.
+    enums: HashMap<&'a str, &'a EnumDeclaration>,
+    warnings: Vec<String>,
+}
+
+impl<'a> Visitor<'a> for ExhaustivenessChecker<'a> {
+    fn visit_statement(&mut self, stmt: &'a StatementNode) {
+        if let StatementNode::Enum(declaration) = stmt {
+            for variant in &declaration.variants {
+                self.enums.insert(&variant.name.value, declaration);
+            }
+        }
+        walk_statement(self, stmt);
+    }
+
+    fn visit_expression(&mut self, expression: &'a ExpressionNode) {
+        walk_expression(self, expression);
+
+        if let ExpressionNode::Match(match_exp) = expression {
+            if let Some(warning) = check_boolean_match(match_exp) {
+                self.warnings.push(warning);
+            }
+            if let Some(warning) = self.check_enum_match(match_exp) {
+                self.warnings.push(warning);
+            }
+        }
+    }
+}
+
+impl ExhaustivenessChecker<'_> {
+    fn check_enum_match(&self, match_exp: &MatchExpression) -> Option<String> {
+        let mut declaration = None;
+        let mut covered: Vec<&str> = vec![];
+
+        for arm in &match_exp.arms {
+            match &arm.pattern {
+                Pattern::Variant(variant) => {
+                    declaration = declaration.or(self.enums.get(variant.name.value.as_str()));
+                    // a guarded arm or a refutable payload may still fall through
+                    if arm.guard.is_none() && variant.payload.iter().all(irrefutable) {
+                        covered.push(&variant.name.value);
+                    }
+                }
+                Pattern::Wildcard(_) | Pattern::Binding(_) if arm.guard.is_none() => return None,
+                _ => {}
+            }
+        }
+
+        let declaration = declaration?;
+        let missing: Vec<&str> = declaration
+            .variants
+            .iter()
+            .map(|variant| variant.name.value.as_str())
+            .filter(|name| !covered.contains(name))
+            .collect();
+
+        if missing.is_empty() {
+            return None;
+        }
+
+        Some(format!(
+            "non-exhaustive match over `{}` in `{}`: {} not covered",
+            declaration.name.value,
+            match_exp.print_string(),
+            missing.join(" and ")
+        ))
+    }
+}
+
+/// Whether the pattern matches every value.
+fn irrefutable(pattern: &Pattern) -> bool {
+    matches!(pattern, Pattern::Wildcard(_) | Pattern::Binding(_))
+}
+
+fn check_boolean_match(match_exp: &MatchExpression) -> Option<String> {
+    let mut over_booleans = false;
+    let mut covers_true = false;
+    let mut covers_false = false;
+
+    for arm in &match_exp.arms {
+        match &arm.pattern {
+            Pattern::Boolean(boolean) => {
+                over_booleans = true;
+                // a guarded arm may still fall through, so it covers nothing
+                if arm.guard.is_none() {
+                    covers_true |= boolean.value;
+                    covers_false |= !boolean.value;
+                }
+            }
+            Pattern::Wildcard(_) | Pattern::Binding(_) if arm.guard.is_none() => return None,
+            _ => {}
+        }
+    }
+
+    if !over_booleans {
+        return None;
+    }
+
+    let missing: Vec<&str> = [(covers_true, "true"), (covers_false, "false")]
+        .into_iter()
+        .filter(|(covered, _)| !covered)
+        .map(|(_, literal)| literal)
+        .collect();
+
+    if missing.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "non-exhaustive match over booleans in `{}`: {} not covered",
+        match_exp.print_string(),
+        missing.join(" and ")
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{lexer::Lexer, parser::Parser};
+
+    use super::check_program;
+
+    fn warnings_for(input: &str) -> Vec<String> {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert!(
+            parser.errors().is_empty(),
+            "parser errors: {:?}",
+            parser.errors()
+        );
+
+        check_program(&program)
+    }
+
+    #[test]
+    fn test_boolean_match_exhaustiveness() {
+        let tests = vec![
+            ("match (x) { true => 1, false => 0 }", 0),
+            ("match (x) { true => 1, _ => 0 }", 0),
+            ("match (x) { true => 1, other => 0 }", 0),
+            ("match (x) { 1 => 1, 2 => 0 }", 0),
+            ("match (x) { true => 1 }", 1),
+            ("match (x) { true => 1, false if y => 0 }", 1),
+            ("match (x) { true => 1, _ if y => 0 }", 1),
+            ("-match (x) { false => 1 } + match (y) { true => 1 }", 2),
+        ];
+
+        for (input, expected) in tests {
+            let warnings = warnings_for(input);
+            assert_eq!(
+                warnings.len(),
+                expected,
+                "wrong number of warnings for {}. got = {:?}",
+                input,
+                warnings
+            );
+        }
+    }
+
+    #[test]
+    fn test_enum_match_exhaustiveness() {
+        let enums = "enum Shape { Circle(r), Rect(w, h), Empty }";
+        let tests = vec![
+            (
+                "match (s) { Circle(r) => r, Rect(w, h) => w, Empty => 0 }",
+                0,
+            ),
+            ("match (s) { Circle(_) => 1, _ => 0 }", 0),
+            ("match (s) { Circle(r) => r, Rect(w, h) => w }", 1),
+            (
+                "match (s) { Circle(1) => 1, Rect(w, h) => w, Empty => 0 }",
+                1,
+            ),
+            (
+                "match (s) { Circle(r) if r > 1 => r, Rect(w, h) => w, Empty => 0 }",
+                1,
+            ),
+            ("match (s) { 1 => 1, 2 => 0 }", 0),
+        ];
+
+        for (input, expected) in tests {
+            let input = format!("{} {}", enums, input);
+            let warnings = warnings_for(&input);
+            assert_eq!(
+                warnings.len(),
+                expected,
+                "wrong number of warnings for {}. got = {:?}",
+                input,
+                warnings
+            );
+        }
+    }
+
+    #[test]
+    fn test_enum_match_warning_names_missing_variants() {
+        let warnings = warnings_for(
+            "enum Shape { Circle(r), Rect(w, h), Empty } match (s) { Circle(r) => r }",
+        );
+
+        assert_eq!(
+            warnings,
+            vec![
+                "non-exhaustive match over `Shape` in `match (s) { Circle(r) => r }`: Rect and Empty not covered"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_boolean_match_warning_names_missing_value() {
+        let warnings = warnings_for("match (x) { true => 1 }");
+
+        assert_eq!(
+            warnings[0],
+            "non-exhaustive match over booleans in `match (x) { true => 1 }`: false not covered"
+        );
+    }
+}
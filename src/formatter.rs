@@ -0,0 +1,1073 @@
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use crate::{
+    ast::{
+        self, Argument, Associativity, BlockStatement, ExpressionNode, ExpressionStatement,
+        LetStatement, Node, Parameter, Pattern, Program, StatementNode, Visitor,
+    },
+    lexer::Lexer,
+    parser::{ParseError, Parser},
+    token::{Comment, Position, TokenKind},
+};
+
+/// How [`format_program`] and [`format_source`] lay out code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    /// Spaces per nesting level.
+    pub indent_width: usize,
+    /// Lines are broken to stay within this many columns where the code allows it.
+    pub line_length: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            indent_width: 4,
+            line_length: 80,
+        }
+    }
+}
+
+/// Prints the program in its canonical layout, with as few parentheses as
+/// the operator precedences allow.
+pub fn format_program(program: &Program, config: &Config) -> String {
+    let mut formatter = Formatter::new(program, &[], "");
+
+    render(&formatter.program(program), config)
+}
+
+/// Formats `input`, keeping its comments and the single blank lines between
+/// statements. Formatting the output again gives the same output.
+///
+/// A comment is printed before the statement it precedes, or after the
+/// statement when it is on one of the lines that statement spans, and not
+/// before one of its inner statements. After a statement broken over
+/// several lines it lands on the last one, where it is found again.
+pub fn format_source(input: &str, config: &Config) -> Result<String, Vec<ParseError>> {
+    let lexer = Lexer::new(input).with_comments();
+    let mut parser = Parser::new(lexer);
+    let program = parser.parse_program();
+
+    match program {
+        Some(program) if parser.errors().is_empty() => {
+            let mut formatter = Formatter::new(&program, parser.comments(), input);
+            Ok(render(&formatter.program(&program), config))
+        }
+        _ => Err(parser.errors().clone()),
+    }
+}
+
+/// The layout of some code, before it is fitted to the line length.
+enum Doc {
+    Text(String),
+    /// A space, or a line break when the enclosing group does not fit.
+    Line,
+    /// Nothing, or a line break when the enclosing group does not fit.
+    SoftLine,
+    /// Always a line break.
+    HardLine,
+    /// Keeps the enclosing groups from being put on one line.
+    BreakParent,
+    /// Indents the lines broken inside by one level.
+    Nest(Box<Doc>),
+    /// Put on one line if it fits, otherwise every line in it is broken.
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+fn text(value: impl Into<String>) -> Doc {
+    Doc::Text(value.into())
+}
+
+fn nest(doc: Doc) -> Doc {
+    Doc::Nest(Box::new(doc))
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+fn concat(docs: Vec<Doc>) -> Doc {
+    Doc::Concat(docs)
+}
+
+/// `open items close` separated by commas, one item per line when too long.
+fn delimited(open: &str, items: Vec<Doc>, close: &str, padding: fn() -> Doc) -> Doc {
+    if items.is_empty() {
+        return text(format!("{}{}", open, close));
+    }
+
+    let mut inner = vec![padding()];
+    for (idx, item) in items.into_iter().enumerate() {
+        if idx > 0 {
+            inner.push(text(","));
+            inner.push(Doc::Line);
+        }
+        inner.push(item);
+    }
+
+    group(concat(vec![
+        text(open),
+        nest(concat(inner)),
+        padding(),
+        text(close),
+    ]))
+}
+
+fn render(doc: &Doc, config: &Config) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    // (indent, flat, doc), the next one to print on top
+    let mut stack = vec![(0, false, doc)];
+
+    while let Some((indent, flat, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(value) => {
+                out.push_str(value);
+                column += value.chars().count();
+            }
+            Doc::Line if flat => {
+                out.push(' ');
+                column += 1;
+            }
+            Doc::SoftLine if flat => {}
+            Doc::BreakParent => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                out.truncate(out.trim_end_matches(' ').len());
+                out.push('\n');
+                out.push_str(&" ".repeat(indent));
+                column = indent;
+            }
+            Doc::Nest(doc) => stack.push((indent + config.indent_width, flat, doc)),
+            Doc::Group(doc) => {
+                let width = config.line_length as isize - column as isize;
+                let flat = flat || fits(width, doc, &stack);
+                stack.push((indent, flat, doc));
+            }
+            Doc::Concat(docs) => {
+                for doc in docs.iter().rev() {
+                    stack.push((indent, flat, doc));
+                }
+            }
+        }
+    }
+
+    let mut out = out.trim_end().to_string();
+    out.push('\n');
+    out
+}
+
+/// Whether `doc` fits in `width` columns on one line, together with what
+/// follows it on `rest` up to the next line break.
+fn fits(mut width: isize, doc: &Doc, rest: &[(usize, bool, &Doc)]) -> bool {
+    let mut todo = vec![(true, doc)];
+    let mut rest = rest.iter().rev();
+
+    while width >= 0 {
+        let (flat, doc) = match todo.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, flat, doc)) => (*flat, *doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Text(value) => width -= value.chars().count() as isize,
+            Doc::Line if flat => width -= 1,
+            Doc::SoftLine if flat => {}
+            Doc::HardLine | Doc::BreakParent if flat => return false,
+            Doc::BreakParent => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+            Doc::Nest(doc) | Doc::Group(doc) => todo.push((flat, doc)),
+            Doc::Concat(docs) => todo.extend(docs.iter().rev().map(|doc| (flat, doc))),
+        }
+    }
+
+    false
+}
+
+/// How tightly an expression binds, mirroring the parser's precedence levels.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+enum Binding {
+    Lowest,
+    Assign,
+    Range,
+    Coalesce,
+    Equals,
+    LessGreater,
+    Sum,
+    Product,
+    Prefix,
+    Call,
+    Index,
+    Atom,
+}
+
+impl Binding {
+    /// The level an `infix` declaration asks for, `1` binds like `==` and `4` like `*`.
+    fn declared(precedence: u8) -> Binding {
+        match precedence {
+            1 => Binding::Equals,
+            2 => Binding::LessGreater,
+            3 => Binding::Sum,
+            _ => Binding::Product,
+        }
+    }
+
+    fn of_operator(operator: &str) -> Binding {
+        match operator {
+            "==" | "!=" => Binding::Equals,
+            "<" | ">" => Binding::LessGreater,
+            "+" | "-" => Binding::Sum,
+            "*" | "/" => Binding::Product,
+            "??" => Binding::Coalesce,
+            _ => Binding::Lowest,
+        }
+    }
+
+    /// One level tighter, an operand on the side an operator does not
+    /// associate to needs it to go without parentheses.
+    fn tighter(self) -> Binding {
+        match self {
+            Binding::Lowest => Binding::Assign,
+            Binding::Assign => Binding::Range,
+            Binding::Range => Binding::Coalesce,
+            Binding::Coalesce => Binding::Equals,
+            Binding::Equals => Binding::LessGreater,
+            Binding::LessGreater => Binding::Sum,
+            Binding::Sum => Binding::Product,
+            Binding::Product => Binding::Prefix,
+            Binding::Prefix => Binding::Call,
+            Binding::Call => Binding::Index,
+            Binding::Index | Binding::Atom => Binding::Atom,
+        }
+    }
+}
+
+/// Collects the operators declared with `infix`, anywhere in the program.
+struct Operators(HashMap<String, (Binding, Associativity)>);
+
+impl<'a> Visitor<'a> for Operators {
+    fn visit_statement(&mut self, stmt: &'a StatementNode) {
+        if let StatementNode::Infix(infix) = stmt {
+            let level = Binding::declared(infix.precedence);
+            for name in infix.binding.name.names() {
+                self.0.insert(name, (level, infix.associativity));
+            }
+        }
+        ast::walk_statement(self, stmt);
+    }
+}
+
+/// Where a statement is printed, which decides if it ends with a `;`.
+#[derive(PartialEq, Clone, Copy)]
+enum Placement {
+    /// Anywhere but the end of a block.
+    Inner,
+    /// The last statement of a block, an expression there is its value.
+    Last,
+}
+
+struct Formatter<'a> {
+    operators: HashMap<String, (Binding, Associativity)>,
+    comments: &'a [Comment],
+    /// How many of the `comments` are printed already.
+    printed: usize,
+    lines: Vec<&'a str>,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(program: &Program, comments: &'a [Comment], source: &'a str) -> Formatter<'a> {
+        let mut operators = Operators(HashMap::new());
+        ast::walk_program(&mut operators, program);
+
+        Formatter {
+            operators: operators.0,
+            comments,
+            printed: 0,
+            lines: source.lines().collect(),
+        }
+    }
+
+    fn program(&mut self, program: &Program) -> Doc {
+        let mut docs = vec![];
+        self.statements(&mut docs, &program.statements, Placement::Inner);
+
+        // comments after the last statement
+        let end = Position {
+            line: usize::MAX,
+            column: 0,
+        };
+        for comment in self.comments_before(end) {
+            self.separate(&mut docs, comment.span.start);
+            docs.push(text(&comment.text));
+        }
+
+        concat(docs)
+    }
+
+    /// Adds the statements one per line to `docs`, the last one placed at `last`.
+    fn statements(&mut self, docs: &mut Vec<Doc>, statements: &[StatementNode], last: Placement) {
+        for (idx, stmt) in statements.iter().enumerate() {
+            let start = statement_start(stmt);
+
+            for comment in self.comments_before(start) {
+                self.separate(docs, comment.span.start);
+                docs.push(text(&comment.text));
+            }
+            self.separate(docs, start);
+
+            let placement = if idx + 1 == statements.len() {
+                last
+            } else {
+                Placement::Inner
+            };
+            docs.push(self.statement(stmt, placement));
+
+            if let Some(comment) = self.trailing_comment(start.line..=stmt.span().end.line) {
+                docs.push(text(format!(" {}", comment.text)));
+                docs.push(Doc::BreakParent);
+            }
+        }
+    }
+
+    /// Starts a new line in `docs` for what is at `position`, after a blank
+    /// line if the source has one there.
+    fn separate(&self, docs: &mut Vec<Doc>, position: Position) {
+        if docs.is_empty() {
+            return;
+        }
+
+        docs.push(Doc::HardLine);
+        if self.blank_line_before(position) {
+            docs.push(Doc::HardLine);
+        }
+    }
+
+    /// Takes the comments not printed yet that start before `position`.
+    fn comments_before(&mut self, position: Position) -> Vec<&'a Comment> {
+        let start = self.printed;
+        while self.comments.get(self.printed).is_some_and(|comment| {
+            let at = comment.span.start;
+            (at.line, at.column) < (position.line, position.column)
+        }) {
+            self.printed += 1;
+        }
+
+        self.comments[start..self.printed].iter().collect()
+    }
+
+    /// Takes the next comment if it is on one of `lines`.
+    fn trailing_comment(&mut self, lines: RangeInclusive<usize>) -> Option<&'a Comment> {
+        let comment = self.comments.get(self.printed)?;
+        if !lines.contains(&comment.span.start.line) {
+            return None;
+        }
+
+        self.printed += 1;
+        Some(comment)
+    }
+
+    /// Whether `position` starts its line in the source, right below an empty line.
+    fn blank_line_before(&self, position: Position) -> bool {
+        let Some(line) = self.lines.get(position.line - 1) else {
+            return false;
+        };
+        let starts_line = line
+            .chars()
+            .take(position.column - 1)
+            .all(|ch| ch.is_whitespace());
+
+        starts_line && position.line >= 2 && self.lines[position.line - 2].trim().is_empty()
+    }
+
+    fn statement(&mut self, stmt: &StatementNode, placement: Placement) -> Doc {
+        match stmt {
+            StatementNode::Let(let_stmt) => self.let_statement(let_stmt),
+            StatementNode::Return(ret_stmt) => match &ret_stmt.ret_value {
+                Some(value) => concat(vec![text("return "), self.expression(value), text(";")]),
+                None => text("return;"),
+            },
+            StatementNode::Expression(exp_stmt) => {
+                let Some(expression) = &exp_stmt.expression else {
+                    return text(";");
+                };
+                let mut docs = vec![self.expression(expression)];
+                if placement == Placement::Inner {
+                    docs.push(text(";"));
+                }
+                concat(docs)
+            }
+            StatementNode::Infix(infix) => {
+                let associativity = match infix.associativity {
+                    Associativity::Left => "left",
+                    Associativity::Right => "right",
+                };
+                concat(vec![
+                    text(format!(
+                        "infix {} {} {} = ",
+                        infix.binding.name.print_string(),
+                        infix.precedence,
+                        associativity
+                    )),
+                    self.optional_value(&infix.binding.value),
+                    text(";"),
+                ])
+            }
+            StatementNode::Import(import) => text(format!(
+                "import {} as {};",
+                import.path.print_string(),
+                import.alias.value
+            )),
+            StatementNode::Export(export) => {
+                concat(vec![text("export "), self.let_statement(&export.binding)])
+            }
+            StatementNode::Struct(declaration) => {
+                let fields = declaration
+                    .fields
+                    .iter()
+                    .map(|field| text(&field.value))
+                    .collect();
+                concat(vec![
+                    text(format!("struct {} ", declaration.name.value)),
+                    delimited("{", fields, "}", || Doc::Line),
+                ])
+            }
+            StatementNode::Enum(declaration) => {
+                let variants = declaration
+                    .variants
+                    .iter()
+                    .map(|variant| text(variant.print_string()))
+                    .collect();
+                concat(vec![
+                    text(format!("enum {} ", declaration.name.value)),
+                    delimited("{", variants, "}", || Doc::Line),
+                ])
+            }
+            StatementNode::Throw(throw) => concat(vec![
+                text("throw "),
+                self.expression(&throw.value),
+                text(";"),
+            ]),
+            StatementNode::Yield(yield_stmt) => concat(vec![
+                text("yield "),
+                self.expression(&yield_stmt.value),
+                text(";"),
+            ]),
+            StatementNode::Try(try_stmt) => {
+                let mut docs = vec![text("try "), self.block(&try_stmt.body, true)];
+                if let Some(catch) = &try_stmt.catch {
+                    docs.push(text(format!(" catch ({}) ", catch.parameter.value)));
+                    docs.push(self.block(&catch.body, true));
+                }
+                if let Some(finally) = &try_stmt.finally {
+                    docs.push(text(" finally "));
+                    docs.push(self.block(finally, true));
+                }
+                concat(docs)
+            }
+            StatementNode::For(for_stmt) => {
+                // parentheses keep `P { x: 1 }` from being read as the loop body
+                let mut iterable = self.expression(&for_stmt.iterable);
+                if contains_struct_literal(&for_stmt.iterable) {
+                    iterable = concat(vec![text("("), iterable, text(")")]);
+                }
+                concat(vec![
+                    text(format!("for {} in ", for_stmt.variable.value)),
+                    iterable,
+                    text(" "),
+                    self.block(&for_stmt.body, true),
+                ])
+            }
+        }
+    }
+
+    fn let_statement(&mut self, let_stmt: &LetStatement) -> Doc {
+        let mut head = format!("{} {}", let_stmt.token.literal, pattern(&let_stmt.name));
+        if let Some(annotation) = &let_stmt.annotation {
+            head.push_str(&format!(": {}", annotation.print_string()));
+        }
+
+        concat(vec![
+            text(format!("{} = ", head)),
+            self.optional_value(&let_stmt.value),
+            text(";"),
+        ])
+    }
+
+    fn optional_value(&mut self, value: &Option<ExpressionNode>) -> Doc {
+        match value {
+            Some(value) => self.expression(value),
+            None => text(""),
+        }
+    }
+
+    /// A block on one line when it is a single short expression, unless
+    /// `broken` asks for its own lines anyway.
+    fn block(&mut self, block: &BlockStatement, broken: bool) -> Doc {
+        if block.statements.is_empty() {
+            return text("{}");
+        }
+
+        let single_expression =
+            matches!(block.statements.as_slice(), [StatementNode::Expression(_)]);
+        let line: fn() -> Doc = if broken || !single_expression {
+            || Doc::HardLine
+        } else {
+            || Doc::Line
+        };
+
+        let mut body = vec![];
+        self.statements(&mut body, &block.statements, Placement::Last);
+
+        group(concat(vec![
+            text("{"),
+            nest(concat(vec![line(), concat(body)])),
+            line(),
+            text("}"),
+        ]))
+    }
+
+    fn binding(&self, expression: &ExpressionNode) -> Binding {
+        match expression {
+            ExpressionNode::Assign(_) => Binding::Assign,
+            ExpressionNode::Range(_) => Binding::Range,
+            ExpressionNode::Infix(infix) => Binding::of_operator(&infix.operator),
+            ExpressionNode::Prefix(_) => Binding::Prefix,
+            ExpressionNode::Call(call) if call.token.kind == TokenKind::Operator => self
+                .operators
+                .get(&call.token.literal)
+                .map_or(Binding::Lowest, |(level, _)| *level),
+            ExpressionNode::Call(_) | ExpressionNode::Member(_) => Binding::Call,
+            ExpressionNode::Index(_) => Binding::Index,
+            _ => Binding::Atom,
+        }
+    }
+
+    /// The expression, in parentheses if it binds looser than `min`.
+    fn operand(&mut self, expression: &ExpressionNode, min: Binding) -> Doc {
+        let doc = self.expression(expression);
+        if self.binding(expression) < min {
+            return concat(vec![text("("), doc, text(")")]);
+        }
+        doc
+    }
+
+    fn binary(&mut self, left: Doc, operator: &str, right: Doc) -> Doc {
+        group(concat(vec![
+            left,
+            text(format!(" {}", operator)),
+            nest(concat(vec![Doc::Line, right])),
+        ]))
+    }
+
+    fn expression(&mut self, expression: &ExpressionNode) -> Doc {
+        match expression {
+            ExpressionNode::IdentifierNode(ident) => text(&ident.value),
+            ExpressionNode::Integer(int) => text(&int.token.literal),
+            ExpressionNode::Boolean(boolean) => text(boolean.value.to_string()),
+            ExpressionNode::Null(_) => text("null"),
+            ExpressionNode::StringNode(string) => text(string.print_string()),
+            ExpressionNode::Array(array) => {
+                let elements = array
+                    .elements
+                    .iter()
+                    .map(|element| self.expression(element))
+                    .collect();
+                delimited("[", elements, "]", || Doc::SoftLine)
+            }
+            ExpressionNode::Hash(hash) => {
+                let pairs = hash
+                    .pairs
+                    .iter()
+                    .map(|(key, value)| {
+                        // `{1..: 2}` would read the `:` as the end of the range
+                        let key = match key {
+                            ExpressionNode::Range(range) if range.end.is_none() => {
+                                concat(vec![text("("), self.expression(key), text(")")])
+                            }
+                            _ => self.expression(key),
+                        };
+                        concat(vec![key, text(": "), self.expression(value)])
+                    })
+                    .collect();
+                delimited("{", pairs, "}", || Doc::SoftLine)
+            }
+            ExpressionNode::Struct(literal) => {
+                let fields = literal
+                    .fields
+                    .iter()
+                    .map(|(name, value)| {
+                        concat(vec![
+                            text(format!("{}: ", name.value)),
+                            self.expression(value),
+                        ])
+                    })
+                    .collect();
+                concat(vec![
+                    text(format!("{} ", literal.name.value)),
+                    delimited("{", fields, "}", || Doc::Line),
+                ])
+            }
+            ExpressionNode::Prefix(prefix) => {
+                let mut right = self.operand(&prefix.right, Binding::Prefix);
+                // `- -a` must not lex as a declared `--`
+                if let ExpressionNode::Prefix(inner) = prefix.right.as_ref() {
+                    let joined = format!("{}{}", prefix.operator, inner.operator);
+                    if self.operators.keys().any(|op| op.starts_with(&joined)) {
+                        right = concat(vec![text("("), right, text(")")]);
+                    }
+                }
+                concat(vec![text(&prefix.operator), right])
+            }
+            ExpressionNode::Infix(infix) => {
+                let level = Binding::of_operator(&infix.operator);
+                let left = self.operand(&infix.left, level);
+                let right = self.operand(&infix.right, level.tighter());
+                self.binary(left, &infix.operator, right)
+            }
+            ExpressionNode::Assign(assign) => {
+                let value = self.operand(&assign.value, Binding::Assign);
+                self.binary(text(&assign.name.value), "=", value)
+            }
+            ExpressionNode::Function(func) => {
+                let parameters = func
+                    .parameters
+                    .iter()
+                    .map(|param| self.parameter(param))
+                    .collect();
+                let mut docs = vec![
+                    text("fn"),
+                    delimited("(", parameters, ")", || Doc::SoftLine),
+                ];
+                if let Some(return_type) = &func.return_type {
+                    docs.push(text(format!(" -> {}", return_type.print_string())));
+                }
+                docs.push(text(" "));
+                docs.push(self.block(&func.body, false));
+                concat(docs)
+            }
+            ExpressionNode::Macro(macro_lit) => {
+                let parameters = macro_lit
+                    .parameters
+                    .iter()
+                    .map(|param| text(&param.value))
+                    .collect();
+                concat(vec![
+                    text("macro"),
+                    delimited("(", parameters, ")", || Doc::SoftLine),
+                    text(" "),
+                    self.block(&macro_lit.body, false),
+                ])
+            }
+            ExpressionNode::Call(call) => {
+                if let (TokenKind::Operator, [left, right]) =
+                    (&call.token.kind, call.arguments.as_slice())
+                {
+                    let (level, associativity) = self
+                        .operators
+                        .get(&call.token.literal)
+                        .copied()
+                        .unwrap_or((Binding::Lowest, Associativity::Left));
+                    let (left_min, right_min) = match associativity {
+                        Associativity::Left => (level, level.tighter()),
+                        Associativity::Right => (level.tighter(), level),
+                    };
+                    let left = self.operand(&left.value, left_min);
+                    let right = self.operand(&right.value, right_min);
+                    return self.binary(left, &call.token.literal, right);
+                }
+
+                let function = self.operand(&call.function, Binding::Call);
+                let arguments = call
+                    .arguments
+                    .iter()
+                    .map(|arg| self.argument(arg))
+                    .collect();
+                concat(vec![
+                    function,
+                    delimited("(", arguments, ")", || Doc::SoftLine),
+                ])
+            }
+            ExpressionNode::Member(member) => {
+                let access = if member.optional { "?." } else { "." };
+                concat(vec![
+                    self.operand(&member.object, Binding::Call),
+                    text(format!("{}{}", access, member.property.value)),
+                ])
+            }
+            ExpressionNode::Index(index) => {
+                let open = if index.optional { "?.[" } else { "[" };
+                concat(vec![
+                    self.operand(&index.left, Binding::Call),
+                    text(open),
+                    self.expression(&index.index),
+                    text("]"),
+                ])
+            }
+            ExpressionNode::Range(range) => {
+                let mut docs = vec![];
+                if let Some(start) = &range.start {
+                    docs.push(self.operand(start, Binding::Range.tighter()));
+                }
+                docs.push(text(if range.inclusive { "..=" } else { ".." }));
+                if let Some(end) = &range.end {
+                    docs.push(self.operand(end, Binding::Range.tighter()));
+                }
+                concat(docs)
+            }
+            ExpressionNode::Match(match_exp) => {
+                let arms = match_exp
+                    .arms
+                    .iter()
+                    .map(|arm| {
+                        let mut head = pattern(&arm.pattern);
+                        let mut docs = vec![];
+                        if let Some(guard) = &arm.guard {
+                            docs.push(text(format!("{} if ", head)));
+                            docs.push(self.expression(guard));
+                            head = String::new();
+                        }
+                        docs.push(text(format!("{} => ", head)));
+                        docs.push(self.expression(&arm.body));
+                        concat(docs)
+                    })
+                    .collect();
+                concat(vec![
+                    text("match ("),
+                    self.expression(&match_exp.subject),
+                    text(") "),
+                    delimited("{", arms, "}", || Doc::Line),
+                ])
+            }
+            ExpressionNode::If(if_exp) => {
+                let mut docs = vec![
+                    text("if ("),
+                    self.expression(&if_exp.condition),
+                    text(") "),
+                    self.block(&if_exp.consequence, false),
+                ];
+                if let Some(alternative) = &if_exp.alternative {
+                    docs.push(text(" else "));
+                    docs.push(match else_if(alternative) {
+                        Some(nested) => self.expression(nested),
+                        None => self.block(alternative, false),
+                    });
+                }
+                concat(docs)
+            }
+        }
+    }
+
+    fn parameter(&mut self, param: &Parameter) -> Doc {
+        let mut head = String::new();
+        if param.rest {
+            head.push_str("...");
+        }
+        head.push_str(&param.name.value);
+        if let Some(annotation) = &param.annotation {
+            head.push_str(&format!(": {}", annotation.print_string()));
+        }
+
+        match &param.default {
+            Some(default) => concat(vec![text(format!("{} = ", head)), self.expression(default)]),
+            None => text(head),
+        }
+    }
+
+    fn argument(&mut self, arg: &Argument) -> Doc {
+        match &arg.name {
+            Some(name) => concat(vec![
+                text(format!("{}: ", name.value)),
+                self.expression(&arg.value),
+            ]),
+            None => self.expression(&arg.value),
+        }
+    }
+}
+
+/// Where the statement starts in the source.
+fn statement_start(stmt: &StatementNode) -> Position {
+    let token = match stmt {
+        StatementNode::Let(let_stmt) => &let_stmt.token,
+        StatementNode::Return(ret_stmt) => &ret_stmt.token,
+        StatementNode::Expression(exp_stmt) => &exp_stmt.token,
+        StatementNode::Infix(infix) => &infix.token,
+        StatementNode::Import(import) => &import.token,
+        StatementNode::Export(export) => &export.token,
+        StatementNode::Struct(declaration) => &declaration.token,
+        StatementNode::Enum(declaration) => &declaration.token,
+        StatementNode::Throw(throw) => &throw.token,
+        StatementNode::Try(try_stmt) => &try_stmt.token,
+        StatementNode::Yield(yield_stmt) => &yield_stmt.token,
+        StatementNode::For(for_stmt) => &for_stmt.token,
+    };
+
+    token.span.start
+}
+
+/// The `if` an `else if` was written with, a block written around it stays.
+fn else_if(alternative: &BlockStatement) -> Option<&ExpressionNode> {
+    match alternative.statements.as_slice() {
+        [StatementNode::Expression(ExpressionStatement {
+            expression: Some(nested @ ExpressionNode::If(if_exp)),
+            ..
+        })] if if_exp.token.span == alternative.token.span => Some(nested),
+        _ => None,
+    }
+}
+
+/// Patterns are short, they are printed on one line.
+fn pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Array(array) => {
+            let mut elements: Vec<String> = array.elements.iter().map(self::pattern).collect();
+            if let Some(rest) = &array.rest {
+                elements.push(format!("...{}", rest.value));
+            }
+            format!("[{}]", elements.join(", "))
+        }
+        Pattern::Hash(hash) => {
+            let pairs: Vec<String> = hash
+                .pairs
+                .iter()
+                .map(|(key, value)| match value {
+                    Pattern::Binding(name) if name.value == key.value => name.value.clone(),
+                    _ => format!("{}: {}", key.print_string(), self::pattern(value)),
+                })
+                .collect();
+            format!("{{{}}}", pairs.join(", "))
+        }
+        Pattern::Variant(variant) => {
+            if variant.payload.is_empty() {
+                return variant.name.value.clone();
+            }
+            let payload: Vec<String> = variant.payload.iter().map(self::pattern).collect();
+            format!("{}({})", variant.name.value, payload.join(", "))
+        }
+        _ => pattern.print_string(),
+    }
+}
+
+struct StructLiterals(bool);
+
+impl<'a> Visitor<'a> for StructLiterals {
+    fn visit_expression(&mut self, expression: &'a ExpressionNode) {
+        self.0 |= matches!(expression, ExpressionNode::Struct(_));
+        ast::walk_expression(self, expression);
+    }
+}
+
+fn contains_struct_literal(expression: &ExpressionNode) -> bool {
+    let mut found = StructLiterals(false);
+    found.visit_expression(expression);
+    found.0
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ast::Node, lexer::Lexer, parser::Parser};
+
+    use super::{format_program, format_source, Config};
+
+    fn print_string(input: &str) -> String {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert!(
+            parser.errors().is_empty(),
+            "parser errors for {}: {:?}",
+            input,
+            parser.errors()
+        );
+
+        program.print_string()
+    }
+
+    #[test]
+    fn test_format() {
+        let tests = vec![
+            ("let x= 1+2*3", "let x = 1 + 2 * 3;\n"),
+            ("let y = (1 + 2) * 3;", "let y = (1 + 2) * 3;\n"),
+            ("a - (b - c); (a - b) - c", "a - (b - c);\na - b - c;\n"),
+            ("-(a + b) * -c[0]", "-(a + b) * -c[0];\n"),
+            ("(a ?? b) == (c ?? d)", "(a ?? b) == (c ?? d);\n"),
+            ("(1..n)[0]; xs[(1..)]; f(..2)", "(1..n)[0];\nxs[1..];\nf(..2);\n"),
+            ("a = (b = c)", "a = b = c;\n"),
+            ("(a + b).c; (f)(x)?.[0]", "(a + b).c;\nf(x)?.[0];\n"),
+            (
+                "infix ^^ 4 right = fn(a, b) { a }; (1 ^^ 2) ^^ 3; 1 ^^ (2 ^^ 3)",
+                "infix ^^ 4 right = fn(a, b) { a };\n(1 ^^ 2) ^^ 3;\n1 ^^ 2 ^^ 3;\n",
+            ),
+            (
+                "let f = fn(a, b: int = 1, ...rest) -> int { let s = a + b; return s; }",
+                "let f = fn(a, b: int = 1, ...rest) -> int {\n    let s = a + b;\n    return s;\n};\n",
+            ),
+            ("fn() {}; macro(x) { quote(x) }", "fn() {};\nmacro(x) { quote(x) };\n"),
+            (
+                "struct P { x, y, } enum O { None, Some(v), } P { x: 1, y: 2 }",
+                "struct P { x, y }\nenum O { None, Some(v) }\nP { x: 1, y: 2 };\n",
+            ),
+            (
+                "for x in (P { x: [1] }).x { puts(x); }",
+                "for x in (P { x: [1] }.x) {\n    puts(x)\n}\n",
+            ),
+            (
+                "try { risky(); } catch (e) { throw e; }",
+                "try {\n    risky()\n} catch (e) {\n    throw e;\n}\n",
+            ),
+            (
+                "let s = if (x>0) {1} else if (x<0) {-1} else {0}; if (ok) { return 1; } else { if (a) { 2 } }",
+                "let s = if (x > 0) { 1 } else if (x < 0) { -1 } else { 0 };\nif (ok) {\n    return 1;\n} else { if (a) { 2 } };\n",
+            ),
+            (
+                r#"match (x) { 0 => "zero", n if n > 1 => n, {"k": v, "name": name} => v, [head, ...tail] => head }"#,
+                "match (x) {\n    0 => \"zero\",\n    n if n > 1 => n,\n    {\"k\": v, name} => v,\n    [head, ...tail] => head\n};\n",
+            ),
+            (
+                r#"import "lib/vec" as vec; export const n: int = vec.len({"a": 1..});"#,
+                "import \"lib/vec\" as vec;\nexport const n: int = vec.len({\"a\": 1..});\n",
+            ),
+            (
+                "let total = fn(first, second, third) { first * second + second * third + third * first };",
+                "let total = fn(first, second, third) {\n    first * second + second * third + third * first\n};\n",
+            ),
+            (
+                "let names = [\"alice\", \"bob\", \"carol\", \"dave\", \"erin\", \"frank\", \"grace\", \"heidi\"];",
+                "let names = [\n    \"alice\",\n    \"bob\",\n    \"carol\",\n    \"dave\",\n    \"erin\",\n    \"frank\",\n    \"grace\",\n    \"heidi\"\n];\n",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let formatted = format_source(input, &Config::default()).unwrap();
+            assert_eq!(formatted, expected, "wrong format for {}", input);
+        }
+    }
+
+    #[test]
+    fn test_format_keeps_program_and_is_idempotent() {
+        let tests = vec![
+            "let x= 5;\nlet y= 10;\nlet foobar = 838383;",
+            "const x = 5; export const n = 1; x = 5; a = b = 1 + 2; f(x = 1)",
+            r#"let {"pos": [x, y], id: key} = {"pos": [1, 2], "id": 3}; let [a, _, ...rest] = xs;"#,
+            "fn(x = 1 + 2, y = fn() { 3 }) { x }; macro(x, y) { quote(unquote(x) + y); }",
+            "let apply: fn(fn(int) -> int, int) -> int = f; let {a}: {string: int} = h;",
+            "-a * b; !-a; a + b * c + d / e - f; 5 > 4 == 3 < 4; !(true == true)",
+            "a * [1, 2][b * c] * d; add(a * b[2], b[1]); -xs[0]; -(-(a))",
+            "1..n + 1; 0..=n == m; xs[1..3]; s[..5]; xs[..]; f(1.., ..2); 1_000_000",
+            "a ?? b ?? c; a ?? (b ?? c); a?.b?.c ?? d; -a?.b; f(x)?.y.z; null ?? 0",
+            "infix <+> 3 left = fn(a, b) { a }; 1 + 2 <+> 3 * 4; a < b <+> c; 1 <+> (2 <+> 3)",
+            "enum Option { None, Some(x) } match (o) { Some(Some(x)) => x, None => 0, _ => 1 }",
+            "try { throw 1; } catch (e) { throw e; } finally { 2 } try { a } finally { b }",
+            "fn(rows) { for row in rows.next() { yield row * 2; } }; for x in 0.. { x }",
+            "Line { a: P { x: 1 } }.a.x; {\"a\": 1, true: [2], (1..): 3}; fn(x) { x }(5)",
+            "let g = match (1) { _ => fn() { 1 } }; (match (x) { _ => 1 }).y; (fn() {})()",
+            "if (a) { 1 } else if (b) { f(); 2 } else { if (c) { 3 } }; (if (a) { 1 }).x; -if (a) { 1 }",
+            "let s = \"multi\nline\";\n\n\nlet t = s; // done\n// bye",
+            "let x = someFunction(argumentNumberOne, argumentNumberTwo, argumentNumberThree, argument); // c",
+            "let f = fn() {\n    let x = someFunction(argumentNumberOne, argumentNumberTwo, argumentNumberThree); // d\n};\nf();",
+            "let g = fn() { // e\n};\nf(a, // f\n  b); // g",
+        ];
+
+        let configs = [
+            Config::default(),
+            Config {
+                indent_width: 2,
+                line_length: 20,
+            },
+            Config {
+                indent_width: 8,
+                line_length: 0,
+            },
+        ];
+
+        for input in tests {
+            for config in &configs {
+                let formatted = format_source(input, config).unwrap();
+                assert_eq!(
+                    print_string(&formatted),
+                    print_string(input),
+                    "formatting changed the program {}, got\n{}",
+                    input,
+                    formatted
+                );
+                assert_eq!(
+                    format_source(&formatted, config).unwrap(),
+                    formatted,
+                    "formatting is not idempotent for {}",
+                    input
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = r#"// header
+
+// about x
+let x = 1;   // one
+let f = fn() {
+    // before the body
+    let y = x; // y
+    y
+};
+
+
+// trailing
+"#;
+
+        assert_eq!(
+            format_source(input, &Config::default()).unwrap(),
+            r#"// header
+
+// about x
+let x = 1; // one
+let f = fn() {
+    // before the body
+    let y = x; // y
+    y
+};
+
+// trailing
+"#
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let input = "let f = fn(a, b) { if_(a, [b, b], fn() { a }) };";
+        let config = Config {
+            indent_width: 2,
+            line_length: 24,
+        };
+
+        assert_eq!(
+            format_source(input, &config).unwrap(),
+            "let f = fn(a, b) {\n  if_(\n    a,\n    [b, b],\n    fn() { a }\n  )\n};\n"
+        );
+    }
+
+    #[test]
+    fn test_format_program_without_source() {
+        let lexer = Lexer::new("// gone\nlet x = (1);\n\nx");
+        let program = Parser::new(lexer).parse_program().unwrap();
+
+        assert_eq!(
+            format_program(&program, &Config::default()),
+            "let x = 1;\nx;\n"
+        );
+    }
+
+    #[test]
+    fn test_format_errors() {
+        let errors = format_source("let = 5;", &Config::default()).unwrap_err();
+
+        assert_eq!(
+            errors[0].to_string(),
+            "1:5-6: expected a pattern, got Assign instead"
+        );
+    }
+}
@@ -0,0 +1,1866 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use crate::{
+    ast::{
+        BlockStatement, CallExpression, EnumDeclaration, ExportStatement, ExpressionNode,
+        FunctionLiteral, IfExpression, IndexExpression, InfixDeclaration, InfixExpression,
+        MatchExpression, Node, Pattern, Program, RangeExpression, StatementNode, StructDeclaration,
+        TypeExpr, Variant,
+    },
+    token::Span,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ty {
+    Var(u32),
+    Int,
+    Bool,
+    String,
+    Array(Box<Ty>),
+    Hash(Box<Ty>, Box<Ty>),
+    Function(Vec<Param>, Box<Ty>),
+    /// A value of a `struct` or `enum` declaration, compared by name.
+    Named(String),
+    /// What calling a function that yields returns.
+    Generator(Box<Ty>),
+    /// `a..b`, a lazy sequence of ints.
+    Range,
+}
+
+/// A parameter of a function type, only its type takes part in unification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub ty: Ty,
+    /// Known when the type comes from a function literal, named arguments
+    /// are matched against it.
+    pub name: Option<String>,
+    /// Whether an argument for the parameter can be left out.
+    pub default: bool,
+    /// Whether this is a rest parameter, whose `ty` is the type of each
+    /// extra argument.
+    pub rest: bool,
+}
+
+impl Param {
+    fn positional(ty: Ty) -> Param {
+        Param {
+            ty,
+            name: None,
+            default: false,
+            rest: false,
+        }
+    }
+
+    fn apply(&self, subst: &Subst) -> Param {
+        Param {
+            ty: self.ty.apply(subst),
+            ..self.clone()
+        }
+    }
+
+    fn display(&self, ty: String) -> String {
+        match self.rest {
+            true => format!("...[{}]", ty),
+            false => ty,
+        }
+    }
+}
+
+impl Ty {
+    fn free_vars(&self, vars: &mut HashSet<u32>) {
+        match self {
+            Ty::Var(var) => {
+                vars.insert(*var);
+            }
+            Ty::Int | Ty::Bool | Ty::String | Ty::Range | Ty::Named(_) => {}
+            Ty::Array(element) | Ty::Generator(element) => element.free_vars(vars),
+            Ty::Hash(key, value) => {
+                key.free_vars(vars);
+                value.free_vars(vars);
+            }
+            Ty::Function(params, ret) => {
+                for param in params {
+                    param.ty.free_vars(vars);
+                }
+                ret.free_vars(vars);
+            }
+        }
+    }
+
+    fn apply(&self, subst: &Subst) -> Ty {
+        match self {
+            Ty::Var(var) => match subst.get(var) {
+                Some(ty) => ty.apply(subst),
+                None => self.clone(),
+            },
+            Ty::Int | Ty::Bool | Ty::String | Ty::Range | Ty::Named(_) => self.clone(),
+            Ty::Array(element) => Ty::Array(Box::new(element.apply(subst))),
+            Ty::Generator(element) => Ty::Generator(Box::new(element.apply(subst))),
+            Ty::Hash(key, value) => {
+                Ty::Hash(Box::new(key.apply(subst)), Box::new(value.apply(subst)))
+            }
+            Ty::Function(params, ret) => Ty::Function(
+                params.iter().map(|p| p.apply(subst)).collect(),
+                Box::new(ret.apply(subst)),
+            ),
+        }
+    }
+}
+
+impl Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ty::Var(var) => write!(f, "'t{}", var),
+            Ty::Int => write!(f, "int"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::String => write!(f, "string"),
+            Ty::Range => write!(f, "range"),
+            Ty::Named(name) => write!(f, "{}", name),
+            Ty::Array(element) => write!(f, "[{}]", element),
+            Ty::Generator(element) => write!(f, "generator({})", element),
+            Ty::Hash(key, value) => write!(f, "{{{}: {}}}", key, value),
+            Ty::Function(params, ret) => {
+                let params: Vec<String> =
+                    params.iter().map(|p| p.display(p.ty.to_string())).collect();
+                write!(f, "fn({}) -> {}", params.join(", "), ret)
+            }
+        }
+    }
+}
+
+/// A type generalised over `vars`, `fn('a) -> 'a` for the identity function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scheme {
+    pub vars: Vec<u32>,
+    pub ty: Ty,
+}
+
+impl Scheme {
+    fn mono(ty: Ty) -> Scheme {
+        Scheme { vars: vec![], ty }
+    }
+
+    fn apply(&self, subst: &Subst) -> Scheme {
+        let subst: Subst = subst
+            .iter()
+            .filter(|(var, _)| !self.vars.contains(var))
+            .map(|(var, ty)| (*var, ty.clone()))
+            .collect();
+
+        Scheme {
+            vars: self.vars.clone(),
+            ty: self.ty.apply(&subst),
+        }
+    }
+}
+
+impl Display for Scheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // name the quantified variables 'a, 'b, ... in order of appearance
+        let mut order = vec![];
+        collect_vars(&self.ty, &mut order);
+
+        let names: HashMap<u32, String> = order
+            .into_iter()
+            .filter(|var| self.vars.contains(var))
+            .enumerate()
+            .map(|(idx, var)| (var, format!("'{}", (b'a' + (idx % 26) as u8) as char)))
+            .collect();
+
+        write!(f, "{}", rename(&self.ty, &names))
+    }
+}
+
+fn collect_vars(ty: &Ty, order: &mut Vec<u32>) {
+    match ty {
+        Ty::Var(var) => {
+            if !order.contains(var) {
+                order.push(*var);
+            }
+        }
+        Ty::Int | Ty::Bool | Ty::String | Ty::Range | Ty::Named(_) => {}
+        Ty::Array(element) | Ty::Generator(element) => collect_vars(element, order),
+        Ty::Hash(key, value) => {
+            collect_vars(key, order);
+            collect_vars(value, order);
+        }
+        Ty::Function(params, ret) => {
+            for param in params {
+                collect_vars(&param.ty, order);
+            }
+            collect_vars(ret, order);
+        }
+    }
+}
+
+fn rename(ty: &Ty, names: &HashMap<u32, String>) -> String {
+    match ty {
+        Ty::Var(var) => match names.get(var) {
+            Some(name) => name.clone(),
+            None => ty.to_string(),
+        },
+        Ty::Array(element) => format!("[{}]", rename(element, names)),
+        Ty::Generator(element) => format!("generator({})", rename(element, names)),
+        Ty::Hash(key, value) => format!("{{{}: {}}}", rename(key, names), rename(value, names)),
+        Ty::Function(params, ret) => {
+            let params: Vec<String> = params
+                .iter()
+                .map(|p| p.display(rename(&p.ty, names)))
+                .collect();
+            format!("fn({}) -> {}", params.join(", "), rename(ret, names))
+        }
+        _ => ty.to_string(),
+    }
+}
+
+type Subst = HashMap<u32, Ty>;
+
+/// `first` applied after `second`.
+fn compose(first: &Subst, second: &Subst) -> Subst {
+    let mut subst: Subst = second
+        .iter()
+        .map(|(var, ty)| (*var, ty.apply(first)))
+        .collect();
+    for (var, ty) in first {
+        subst.entry(*var).or_insert_with(|| ty.clone());
+    }
+    subst
+}
+
+fn unify(a: &Ty, b: &Ty) -> Result<Subst, ()> {
+    match (a, b) {
+        (Ty::Var(x), Ty::Var(y)) if x == y => Ok(Subst::new()),
+        (Ty::Var(var), ty) | (ty, Ty::Var(var)) => {
+            let mut vars = HashSet::new();
+            ty.free_vars(&mut vars);
+            if vars.contains(var) {
+                return Err(());
+            }
+            Ok(Subst::from([(*var, ty.clone())]))
+        }
+        (Ty::Int, Ty::Int)
+        | (Ty::Bool, Ty::Bool)
+        | (Ty::String, Ty::String)
+        | (Ty::Range, Ty::Range) => Ok(Subst::new()),
+        (Ty::Named(a), Ty::Named(b)) if a == b => Ok(Subst::new()),
+        (Ty::Array(a), Ty::Array(b)) | (Ty::Generator(a), Ty::Generator(b)) => unify(a, b),
+        (Ty::Hash(ak, av), Ty::Hash(bk, bv)) => {
+            let s1 = unify(ak, bk)?;
+            let s2 = unify(&av.apply(&s1), &bv.apply(&s1))?;
+            Ok(compose(&s2, &s1))
+        }
+        (Ty::Function(a_params, a_ret), Ty::Function(b_params, b_ret))
+            if a_params.len() == b_params.len()
+                && a_params.iter().zip(b_params).all(|(a, b)| a.rest == b.rest) =>
+        {
+            let mut subst = Subst::new();
+            for (a, b) in a_params.iter().zip(b_params) {
+                let s = unify(&a.ty.apply(&subst), &b.ty.apply(&subst))?;
+                subst = compose(&s, &subst);
+            }
+            let s = unify(&a_ret.apply(&subst), &b_ret.apply(&subst))?;
+            Ok(compose(&s, &subst))
+        }
+        _ => Err(()),
+    }
+}
+
+/// Where a type in a unification came from.
+struct Origin {
+    what: String,
+    span: Span,
+}
+
+impl Origin {
+    fn of_expression(expression: &ExpressionNode) -> Origin {
+        Origin {
+            what: expression.print_string(),
+            span: expression.token().span,
+        }
+    }
+
+    fn of_pattern(pattern: &Pattern) -> Origin {
+        Origin {
+            what: pattern.print_string(),
+            span: pattern.token().span,
+        }
+    }
+
+    fn of_type_expr(type_expr: &TypeExpr, span: Span) -> Origin {
+        Origin {
+            what: type_expr.print_string(),
+            span,
+        }
+    }
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` at {}", self.what, self.span)
+    }
+}
+
+type Env = HashMap<String, Scheme>;
+
+fn apply_env(env: &Env, subst: &Subst) -> Env {
+    env.iter()
+        .map(|(name, scheme)| (name.clone(), scheme.apply(subst)))
+        .collect()
+}
+
+pub struct Inference {
+    /// Generalised type of every top-level `let` binding, in source order.
+    pub bindings: Vec<(String, Scheme)>,
+    pub errors: Vec<String>,
+}
+
+/// Infers types for the program with Algorithm W, without running it.
+///
+/// Let-bound values are generalised, so `let id = fn(x) { x };` can be used
+/// at several types. Annotations act as extra constraints. Names that are
+/// never bound get a fresh type variable. A call to a function literal's
+/// type places named arguments by name and extra ones in the rest parameter,
+/// and reports arguments that fit no parameter or required ones left empty.
+pub fn infer_program(program: &Program) -> Inference {
+    let mut infer = Infer {
+        next_var: 0,
+        structs: HashMap::new(),
+        enums: top_level_enums(program),
+        errors: vec![],
+    };
+
+    let mut env = Env::new();
+    let mut subst = Subst::new();
+    let mut bindings = vec![];
+
+    for stmt in &program.statements {
+        let (s, new_env) = infer.infer_statement(&env, stmt, None);
+        subst = compose(&s, &subst);
+        env = new_env;
+
+        if let StatementNode::Let(let_stmt)
+        | StatementNode::Infix(InfixDeclaration {
+            binding: let_stmt, ..
+        })
+        | StatementNode::Export(ExportStatement {
+            binding: let_stmt, ..
+        }) = stmt
+        {
+            for name in let_stmt.name.names() {
+                if let Some(scheme) = env.get(&name) {
+                    bindings.push((name, scheme.clone()));
+                }
+            }
+        }
+    }
+
+    // later statements can still pin down variables of earlier bindings
+    Inference {
+        bindings: bindings
+            .into_iter()
+            .map(|(name, scheme)| (name, scheme.apply(&subst)))
+            .collect(),
+        errors: infer.errors,
+    }
+}
+
+struct Infer {
+    next_var: u32,
+    structs: HashMap<String, StructDeclaration>,
+    enums: HashMap<String, EnumDeclaration>,
+    errors: Vec<String>,
+}
+
+impl Infer {
+    fn fresh(&mut self) -> Ty {
+        self.next_var += 1;
+        Ty::Var(self.next_var)
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Ty {
+        let subst: Subst = scheme.vars.iter().map(|var| (*var, self.fresh())).collect();
+        scheme.ty.apply(&subst)
+    }
+
+    fn generalize(env: &Env, ty: &Ty) -> Scheme {
+        let mut env_vars = HashSet::new();
+        for scheme in env.values() {
+            let mut vars = HashSet::new();
+            scheme.ty.free_vars(&mut vars);
+            env_vars.extend(vars.into_iter().filter(|var| !scheme.vars.contains(var)));
+        }
+
+        let mut order = vec![];
+        collect_vars(ty, &mut order);
+
+        Scheme {
+            vars: order
+                .into_iter()
+                .filter(|var| !env_vars.contains(var))
+                .collect(),
+            ty: ty.clone(),
+        }
+    }
+
+    /// Unifies `expected` with `actual`, recording where each side came from
+    /// when they conflict.
+    fn unify(
+        &mut self,
+        expected: &Ty,
+        expected_from: &Origin,
+        actual: &Ty,
+        actual_from: &Origin,
+    ) -> Subst {
+        match unify(expected, actual) {
+            Ok(subst) => subst,
+            Err(()) => {
+                let msg = format!(
+                    "{}: type mismatch between {} and {}: {} from {}, {} from {}",
+                    actual_from.span,
+                    expected,
+                    actual,
+                    expected,
+                    expected_from,
+                    actual,
+                    actual_from
+                );
+                self.errors.push(msg);
+                Subst::new()
+            }
+        }
+    }
+
+    fn resolve(&mut self, type_expr: &TypeExpr) -> Ty {
+        match type_expr {
+            TypeExpr::Named(name) => match name.value.as_str() {
+                "int" => Ty::Int,
+                "bool" => Ty::Bool,
+                "string" => Ty::String,
+                other if self.structs.contains_key(other) || self.enums.contains_key(other) => {
+                    Ty::Named(other.to_string())
+                }
+                other => {
+                    let msg = format!("{}: unknown type `{}`", name.token.span, other);
+                    self.errors.push(msg);
+                    self.fresh()
+                }
+            },
+            TypeExpr::Array(array) => Ty::Array(Box::new(self.resolve(&array.element))),
+            TypeExpr::Hash(hash) => Ty::Hash(
+                Box::new(self.resolve(&hash.key)),
+                Box::new(self.resolve(&hash.value)),
+            ),
+            TypeExpr::Function(func) => {
+                let params = func
+                    .parameters
+                    .iter()
+                    .map(|p| Param::positional(self.resolve(p)))
+                    .collect();
+                Ty::Function(params, Box::new(self.resolve(&func.return_type)))
+            }
+        }
+    }
+
+    /// Infers one statement, returning the substitution and the environment
+    /// for the statements after it. `ret` is the return type of the
+    /// enclosing function, if any.
+    fn infer_statement(
+        &mut self,
+        env: &Env,
+        stmt: &StatementNode,
+        ret: Option<(&Ty, &Origin)>,
+    ) -> (Subst, Env) {
+        match stmt {
+            StatementNode::Let(let_stmt)
+            | StatementNode::Infix(InfixDeclaration {
+                binding: let_stmt, ..
+            })
+            | StatementNode::Export(ExportStatement {
+                binding: let_stmt, ..
+            }) => {
+                let value = match &let_stmt.value {
+                    Some(value) => value,
+                    None => return (Subst::new(), env.clone()),
+                };
+
+                // bind the name first so function literals can call themselves
+                let mut value_env = env.clone();
+                let self_type = self.fresh();
+                if let (Pattern::Binding(name), ExpressionNode::Function(_)) =
+                    (&let_stmt.name, value)
+                {
+                    value_env.insert(name.value.clone(), Scheme::mono(self_type.clone()));
+                }
+
+                let (mut subst, value_type) = self.infer_expression(&value_env, value);
+                let value_from = Origin::of_expression(value);
+
+                let s = self.unify(
+                    &self_type.apply(&subst),
+                    &value_from,
+                    &value_type,
+                    &value_from,
+                );
+                subst = compose(&s, &subst);
+
+                if let Some(annotation) = &let_stmt.annotation {
+                    let declared = self.resolve(annotation);
+                    let declared_from =
+                        Origin::of_type_expr(annotation, let_stmt.name.token().span);
+                    let s = self.unify(
+                        &declared,
+                        &declared_from,
+                        &value_type.apply(&subst),
+                        &value_from,
+                    );
+                    subst = compose(&s, &subst);
+                }
+
+                let (s, pattern_type, bindings) = self.infer_pattern(&let_stmt.name);
+                subst = compose(&s, &subst);
+                let s = self.unify(
+                    &pattern_type.apply(&subst),
+                    &Origin::of_pattern(&let_stmt.name),
+                    &value_type.apply(&subst),
+                    &value_from,
+                );
+                subst = compose(&s, &subst);
+
+                let mut new_env = apply_env(env, &subst);
+                let generalize_env = new_env.clone();
+                for (name, ty) in bindings {
+                    let scheme = Self::generalize(&generalize_env, &ty.apply(&subst));
+                    new_env.insert(name, scheme);
+                }
+
+                (subst, new_env)
+            }
+            StatementNode::Struct(declaration) => {
+                self.structs
+                    .insert(declaration.name.value.clone(), declaration.clone());
+                (Subst::new(), env.clone())
+            }
+            StatementNode::Enum(declaration) => {
+                self.enums
+                    .insert(declaration.name.value.clone(), declaration.clone());
+
+                let mut new_env = env.clone();
+                for variant in &declaration.variants {
+                    let scheme = self.constructor(&declaration.name.value, variant);
+                    new_env.insert(variant.name.value.clone(), scheme);
+                }
+                (Subst::new(), new_env)
+            }
+            // in a generator the return slot holds the type of the yielded values
+            StatementNode::Yield(yield_stmt) => {
+                let (mut subst, value_type) = self.infer_expression(env, &yield_stmt.value);
+                if let Some((ret, ret_from)) = ret {
+                    let s = self.unify(
+                        &ret.apply(&subst),
+                        ret_from,
+                        &value_type,
+                        &Origin::of_expression(&yield_stmt.value),
+                    );
+                    subst = compose(&s, &subst);
+                }
+                (subst.clone(), apply_env(env, &subst))
+            }
+            StatementNode::For(for_stmt) => {
+                let (mut subst, iterable) = self.infer_expression(env, &for_stmt.iterable);
+                let element = match iterable.apply(&subst) {
+                    Ty::Array(element) | Ty::Generator(element) => *element,
+                    Ty::Range => Ty::Int,
+                    Ty::Var(_) => self.fresh(),
+                    other => {
+                        let msg = format!(
+                            "{}: can not iterate over {} in `{}`",
+                            for_stmt.iterable.token().span,
+                            other,
+                            for_stmt.iterable.print_string()
+                        );
+                        self.errors.push(msg);
+                        self.fresh()
+                    }
+                };
+
+                let mut body_env = apply_env(env, &subst);
+                body_env.insert(for_stmt.variable.value.clone(), Scheme::mono(element));
+                let s = self.infer_statements(&body_env, &for_stmt.body.statements, ret);
+                subst = compose(&s, &subst);
+                (subst.clone(), apply_env(env, &subst))
+            }
+            StatementNode::Throw(throw) => {
+                let (subst, _) = self.infer_expression(env, &throw.value);
+                (subst.clone(), apply_env(env, &subst))
+            }
+            // any value can be thrown, so the catch parameter is fresh
+            StatementNode::Try(try_stmt) => {
+                let mut subst = self.infer_statements(env, &try_stmt.body.statements, ret);
+                if let Some(catch) = &try_stmt.catch {
+                    let mut catch_env = apply_env(env, &subst);
+                    catch_env.insert(catch.parameter.value.clone(), Scheme::mono(self.fresh()));
+                    let s = self.infer_statements(&catch_env, &catch.body.statements, ret);
+                    subst = compose(&s, &subst);
+                }
+                if let Some(finally) = &try_stmt.finally {
+                    let s =
+                        self.infer_statements(&apply_env(env, &subst), &finally.statements, ret);
+                    subst = compose(&s, &subst);
+                }
+                (subst.clone(), apply_env(env, &subst))
+            }
+            // modules are inferred on their own, their members are fresh here
+            StatementNode::Import(import) => {
+                let mut new_env = env.clone();
+                new_env.insert(import.alias.value.clone(), Scheme::mono(self.fresh()));
+                (Subst::new(), new_env)
+            }
+            StatementNode::Return(ret_stmt) => {
+                let value = match &ret_stmt.ret_value {
+                    Some(value) => value,
+                    None => return (Subst::new(), env.clone()),
+                };
+
+                let (mut subst, value_type) = self.infer_expression(env, value);
+                if let Some((ret, ret_from)) = ret {
+                    let s = self.unify(
+                        &ret.apply(&subst),
+                        ret_from,
+                        &value_type,
+                        &Origin::of_expression(value),
+                    );
+                    subst = compose(&s, &subst);
+                }
+
+                (subst.clone(), apply_env(env, &subst))
+            }
+            StatementNode::Expression(exp_stmt) => match &exp_stmt.expression {
+                Some(expression) => {
+                    let (subst, _) = self.infer_value(env, expression, ret);
+                    (subst.clone(), apply_env(env, &subst))
+                }
+                None => (Subst::new(), env.clone()),
+            },
+        }
+    }
+
+    /// Infers a block that is not a function body, its bindings end with it.
+    fn infer_statements(
+        &mut self,
+        env: &Env,
+        statements: &[StatementNode],
+        ret: Option<(&Ty, &Origin)>,
+    ) -> Subst {
+        let mut subst = Subst::new();
+        let mut env = env.clone();
+
+        for stmt in statements {
+            let ret = ret.map(|(ret, from)| (ret.apply(&subst), from));
+            let (s, new_env) =
+                self.infer_statement(&env, stmt, ret.as_ref().map(|(ret, from)| (ret, *from)));
+            subst = compose(&s, &subst);
+            env = new_env;
+        }
+
+        subst
+    }
+
+    /// `Rect(w, h)` of `enum Shape` is `fn('a, 'b) -> Shape`, payloads are untyped.
+    fn constructor(&mut self, enum_name: &str, variant: &Variant) -> Scheme {
+        let named = Ty::Named(enum_name.to_string());
+        if variant.fields.is_empty() {
+            return Scheme::mono(named);
+        }
+
+        let params: Vec<Ty> = variant.fields.iter().map(|_| self.fresh()).collect();
+        let mut vars = HashSet::new();
+        for param in &params {
+            param.free_vars(&mut vars);
+        }
+
+        Scheme {
+            vars: vars.into_iter().collect(),
+            ty: Ty::Function(
+                params.into_iter().map(Param::positional).collect(),
+                Box::new(named),
+            ),
+        }
+    }
+
+    /// Types a pattern with fresh variables, returning the type it matches
+    /// and the type of every name it binds.
+    fn infer_pattern(&mut self, pattern: &Pattern) -> (Subst, Ty, Vec<(String, Ty)>) {
+        match pattern {
+            Pattern::Integer(_) => (Subst::new(), Ty::Int, vec![]),
+            Pattern::Boolean(_) => (Subst::new(), Ty::Bool, vec![]),
+            Pattern::StringNode(_) => (Subst::new(), Ty::String, vec![]),
+            Pattern::Wildcard(_) => (Subst::new(), self.fresh(), vec![]),
+            Pattern::Binding(name) => {
+                let ty = self.fresh();
+                (Subst::new(), ty.clone(), vec![(name.value.clone(), ty)])
+            }
+            Pattern::Array(array) => {
+                let element = self.fresh();
+                let mut subst = Subst::new();
+                let mut bindings = vec![];
+
+                for el in &array.elements {
+                    let (s, el_type, el_bindings) = self.infer_pattern(el);
+                    subst = compose(&s, &subst);
+                    let from = Origin::of_pattern(el);
+                    let s = self.unify(
+                        &element.apply(&subst),
+                        &Origin::of_pattern(pattern),
+                        &el_type,
+                        &from,
+                    );
+                    subst = compose(&s, &subst);
+                    bindings.extend(el_bindings);
+                }
+
+                let element = element.apply(&subst);
+                if let Some(rest) = &array.rest {
+                    bindings.push((rest.value.clone(), Ty::Array(Box::new(element.clone()))));
+                }
+
+                (subst, Ty::Array(Box::new(element)), bindings)
+            }
+            Pattern::Hash(hash) => {
+                let value = self.fresh();
+                let mut subst = Subst::new();
+                let mut bindings = vec![];
+
+                for (_, el) in &hash.pairs {
+                    let (s, el_type, el_bindings) = self.infer_pattern(el);
+                    subst = compose(&s, &subst);
+                    let from = Origin::of_pattern(el);
+                    let s = self.unify(
+                        &value.apply(&subst),
+                        &Origin::of_pattern(pattern),
+                        &el_type,
+                        &from,
+                    );
+                    subst = compose(&s, &subst);
+                    bindings.extend(el_bindings);
+                }
+
+                (
+                    subst.clone(),
+                    Ty::Hash(Box::new(Ty::String), Box::new(value.apply(&subst))),
+                    bindings,
+                )
+            }
+            Pattern::Variant(variant) => {
+                let mut subst = Subst::new();
+                let mut bindings = vec![];
+                for el in &variant.payload {
+                    let (s, _, el_bindings) = self.infer_pattern(el);
+                    subst = compose(&s, &subst);
+                    bindings.extend(el_bindings);
+                }
+
+                let span = variant.name.token.span;
+                let declaration = self
+                    .enums
+                    .values()
+                    .find(|declaration| declaration.variant(&variant.name.value).is_some());
+                let ty = match declaration {
+                    Some(declaration) => {
+                        let name = declaration.name.value.clone();
+                        if let Some(msg) = declaration
+                            .variant(&variant.name.value)
+                            .and_then(|v| v.check_pattern(variant))
+                        {
+                            self.errors.push(format!("{}: {}", span, msg));
+                        }
+                        Ty::Named(name)
+                    }
+                    None => {
+                        let msg = format!("{}: unknown variant `{}`", span, variant.name.value);
+                        self.errors.push(msg);
+                        self.fresh()
+                    }
+                };
+
+                (subst, ty, bindings)
+            }
+        }
+    }
+
+    fn infer_expression(&mut self, env: &Env, expression: &ExpressionNode) -> (Subst, Ty) {
+        match expression {
+            ExpressionNode::IdentifierNode(ident) => match env.get(&ident.value) {
+                Some(scheme) => (Subst::new(), self.instantiate(scheme)),
+                None => (Subst::new(), self.fresh()),
+            },
+            ExpressionNode::Integer(_) => (Subst::new(), Ty::Int),
+            ExpressionNode::Boolean(_) => (Subst::new(), Ty::Bool),
+            ExpressionNode::StringNode(_) => (Subst::new(), Ty::String),
+            // every type includes null
+            ExpressionNode::Null(_) => (Subst::new(), self.fresh()),
+            // macros are expanded before inference, a leftover one is opaque
+            ExpressionNode::Macro(_) => (Subst::new(), self.fresh()),
+            ExpressionNode::Array(array) => {
+                let element = self.fresh();
+                let (subst, element) = self.infer_all(env, array.elements.iter(), element);
+                (subst, Ty::Array(Box::new(element)))
+            }
+            ExpressionNode::Hash(hash) => {
+                let key = self.fresh();
+                let (subst, key) = self.infer_all(env, hash.pairs.iter().map(|(k, _)| k), key);
+
+                let value = self.fresh();
+                let (s, value) = self.infer_all(
+                    &apply_env(env, &subst),
+                    hash.pairs.iter().map(|(_, v)| v),
+                    value,
+                );
+
+                let subst = compose(&s, &subst);
+                (
+                    subst.clone(),
+                    Ty::Hash(Box::new(key.apply(&subst)), Box::new(value)),
+                )
+            }
+            // fields are untyped, only their names are checked
+            ExpressionNode::Member(member) => {
+                let (subst, object) = self.infer_expression(env, &member.object);
+                let object = object.apply(&subst);
+                if let (Ty::Generator(element), "next") = (&object, member.property.value.as_str())
+                {
+                    return (subst, Ty::Function(vec![], element.clone()));
+                }
+                if let Ty::Named(name) = object {
+                    let declaration = self.structs.get(&name);
+                    if declaration.is_some_and(|d| !d.has_field(&member.property.value)) {
+                        let msg = format!(
+                            "{}: struct `{}` has no field `{}`",
+                            member.property.token.span, name, member.property.value
+                        );
+                        self.errors.push(msg);
+                    }
+                }
+                (subst, self.fresh())
+            }
+            ExpressionNode::Assign(assign) => {
+                let (mut subst, value) = self.infer_expression(env, &assign.value);
+                if let Some(scheme) = env.get(&assign.name.value) {
+                    let target = self.instantiate(scheme);
+                    let s = self.unify(
+                        &target.apply(&subst),
+                        &Origin {
+                            what: assign.name.value.clone(),
+                            span: assign.name.token.span,
+                        },
+                        &value,
+                        &Origin::of_expression(&assign.value),
+                    );
+                    subst = compose(&s, &subst);
+                }
+                let value = value.apply(&subst);
+                (subst, value)
+            }
+            ExpressionNode::Struct(literal) => {
+                let mut subst = Subst::new();
+                for (_, value) in &literal.fields {
+                    let (s, _) = self.infer_expression(&apply_env(env, &subst), value);
+                    subst = compose(&s, &subst);
+                }
+
+                let errors = match self.structs.get(&literal.name.value) {
+                    Some(declaration) => declaration.check_literal(literal),
+                    None => {
+                        let msg = format!(
+                            "{}: unknown struct `{}`",
+                            literal.name.token.span, literal.name.value
+                        );
+                        self.errors.push(msg);
+                        return (subst, self.fresh());
+                    }
+                };
+                for (span, msg) in errors {
+                    self.errors.push(format!("{}: {}", span, msg));
+                }
+
+                (subst, Ty::Named(literal.name.value.clone()))
+            }
+            ExpressionNode::Prefix(prefix) => {
+                let (subst, right) = self.infer_expression(env, &prefix.right);
+
+                match prefix.operator.as_str() {
+                    "-" => {
+                        let from = Origin {
+                            what: prefix.operator.clone(),
+                            span: prefix.token.span,
+                        };
+                        let s = self.unify(
+                            &Ty::Int,
+                            &from,
+                            &right,
+                            &Origin::of_expression(&prefix.right),
+                        );
+                        (compose(&s, &subst), Ty::Int)
+                    }
+                    _ => (subst, Ty::Bool),
+                }
+            }
+            ExpressionNode::Infix(infix) => self.infer_infix(env, infix),
+            ExpressionNode::Match(match_exp) => self.infer_match(env, match_exp),
+            ExpressionNode::If(if_exp) => self.infer_if(env, if_exp, None),
+            ExpressionNode::Index(index) => self.infer_index(env, index),
+            ExpressionNode::Range(range) => self.infer_range(env, range),
+            ExpressionNode::Function(func) => self.infer_function(env, func),
+            ExpressionNode::Call(call) => self.infer_call(env, call),
+        }
+    }
+
+    /// Indexing needs to know the collection, an index into a value of
+    /// unknown type is left unconstrained.
+    fn infer_index(&mut self, env: &Env, index: &IndexExpression) -> (Subst, Ty) {
+        let (s1, left) = self.infer_expression(env, &index.left);
+        let (s2, index_type) = self.infer_expression(&apply_env(env, &s1), &index.index);
+        let mut subst = compose(&s2, &s1);
+        let left = left.apply(&subst);
+
+        if index.is_slice() {
+            return match left {
+                Ty::Array(_) | Ty::String | Ty::Var(_) => (subst, left),
+                other => {
+                    let msg = format!(
+                        "{}: can not slice {} in `{}`",
+                        index.token.span,
+                        other,
+                        index.print_string()
+                    );
+                    self.errors.push(msg);
+                    (subst, self.fresh())
+                }
+            };
+        }
+
+        let (key, value) = match left {
+            Ty::Array(element) => (Ty::Int, *element),
+            Ty::String => (Ty::Int, Ty::String),
+            Ty::Hash(key, value) => (*key, *value),
+            Ty::Var(_) => return (subst, self.fresh()),
+            other => {
+                let msg = format!(
+                    "{}: can not index {} in `{}`",
+                    index.token.span,
+                    other,
+                    index.print_string()
+                );
+                self.errors.push(msg);
+                return (subst, self.fresh());
+            }
+        };
+
+        let s = self.unify(
+            &key,
+            &Origin::of_expression(&index.left),
+            &index_type.apply(&subst),
+            &Origin::of_expression(&index.index),
+        );
+        subst = compose(&s, &subst);
+
+        (subst.clone(), value.apply(&subst))
+    }
+
+    fn infer_range(&mut self, env: &Env, range: &RangeExpression) -> (Subst, Ty) {
+        let mut subst = Subst::new();
+        let range_from = Origin {
+            what: range.token.literal.clone(),
+            span: range.token.span,
+        };
+
+        for bound in [&range.start, &range.end].into_iter().flatten() {
+            let (s, ty) = self.infer_expression(&apply_env(env, &subst), bound);
+            subst = compose(&s, &subst);
+            let s = self.unify(&Ty::Int, &range_from, &ty, &Origin::of_expression(bound));
+            subst = compose(&s, &subst);
+        }
+
+        (subst, Ty::Range)
+    }
+
+    /// Infers every expression and unifies them all with `element`.
+    fn infer_all<'a>(
+        &mut self,
+        env: &Env,
+        expressions: impl Iterator<Item = &'a ExpressionNode>,
+        element: Ty,
+    ) -> (Subst, Ty) {
+        let mut subst = Subst::new();
+        let mut first: Option<Origin> = None;
+
+        for expression in expressions {
+            let (s, ty) = self.infer_expression(&apply_env(env, &subst), expression);
+            subst = compose(&s, &subst);
+
+            let from = Origin::of_expression(expression);
+            let s = match &first {
+                Some(first_from) => self.unify(&element.apply(&subst), first_from, &ty, &from),
+                None => self.unify(&element.apply(&subst), &from, &ty, &from),
+            };
+            subst = compose(&s, &subst);
+            first.get_or_insert(from);
+        }
+
+        (subst.clone(), element.apply(&subst))
+    }
+
+    fn infer_infix(&mut self, env: &Env, infix: &InfixExpression) -> (Subst, Ty) {
+        let (s1, left) = self.infer_expression(env, &infix.left);
+        let (s2, right) = self.infer_expression(&apply_env(env, &s1), &infix.right);
+        let mut subst = compose(&s2, &s1);
+        let left = left.apply(&subst);
+
+        let left_from = Origin::of_expression(&infix.left);
+        let right_from = Origin::of_expression(&infix.right);
+        let operator_from = Origin {
+            what: infix.operator.clone(),
+            span: infix.token.span,
+        };
+
+        let result = match infix.operator.as_str() {
+            "-" | "*" | "/" | "<" | ">" => {
+                let s = self.unify(&Ty::Int, &operator_from, &left, &left_from);
+                subst = compose(&s, &subst);
+                let s = self.unify(&Ty::Int, &operator_from, &right.apply(&subst), &right_from);
+                subst = compose(&s, &subst);
+
+                if infix.operator == "<" || infix.operator == ">" {
+                    Ty::Bool
+                } else {
+                    Ty::Int
+                }
+            }
+            "??" => {
+                let s = self.unify(&left, &left_from, &right.apply(&subst), &right_from);
+                subst = compose(&s, &subst);
+                left.apply(&subst)
+            }
+            "+" | "==" | "!=" => {
+                let s = self.unify(&left, &left_from, &right.apply(&subst), &right_from);
+                subst = compose(&s, &subst);
+
+                if infix.operator != "+" {
+                    Ty::Bool
+                } else {
+                    let sum = left.apply(&subst);
+                    // `+` is only defined for ints and strings
+                    if !matches!(sum, Ty::Int | Ty::String | Ty::Var(_)) {
+                        let msg = format!(
+                            "{}: operator + is not defined for {}",
+                            infix.token.span, sum
+                        );
+                        self.errors.push(msg);
+                    }
+                    sum
+                }
+            }
+            _ => self.fresh(),
+        };
+
+        (subst, result)
+    }
+
+    fn infer_match(&mut self, env: &Env, match_exp: &MatchExpression) -> (Subst, Ty) {
+        let (mut subst, subject) = self.infer_expression(env, &match_exp.subject);
+        let subject_from = Origin::of_expression(&match_exp.subject);
+
+        let result = self.fresh();
+        let mut first: Option<Origin> = None;
+
+        for arm in &match_exp.arms {
+            let (s, pattern_type, bindings) = self.infer_pattern(&arm.pattern);
+            subst = compose(&s, &subst);
+            let s = self.unify(
+                &subject.apply(&subst),
+                &subject_from,
+                &pattern_type.apply(&subst),
+                &Origin::of_pattern(&arm.pattern),
+            );
+            subst = compose(&s, &subst);
+
+            let mut arm_env = apply_env(env, &subst);
+            for (name, ty) in bindings {
+                arm_env.insert(name, Scheme::mono(ty.apply(&subst)));
+            }
+
+            if let Some(guard) = &arm.guard {
+                let (s, guard_type) = self.infer_expression(&arm_env, guard);
+                subst = compose(&s, &subst);
+                let from = Origin {
+                    what: String::from("if"),
+                    span: guard.token().span,
+                };
+                let s = self.unify(&Ty::Bool, &from, &guard_type, &Origin::of_expression(guard));
+                subst = compose(&s, &subst);
+                arm_env = apply_env(&arm_env, &subst);
+            }
+
+            let (s, body_type) = self.infer_expression(&arm_env, &arm.body);
+            subst = compose(&s, &subst);
+
+            let body_from = Origin::of_expression(&arm.body);
+            let s = match &first {
+                Some(first_from) => {
+                    self.unify(&result.apply(&subst), first_from, &body_type, &body_from)
+                }
+                None => self.unify(&result.apply(&subst), &body_from, &body_type, &body_from),
+            };
+            subst = compose(&s, &subst);
+            first.get_or_insert(body_from);
+        }
+
+        (subst.clone(), result.apply(&subst))
+    }
+
+    /// An expression a statement is made of, where an `if` can return from
+    /// the enclosing function.
+    fn infer_value(
+        &mut self,
+        env: &Env,
+        expression: &ExpressionNode,
+        ret: Option<(&Ty, &Origin)>,
+    ) -> (Subst, Ty) {
+        match expression {
+            ExpressionNode::If(if_exp) => self.infer_if(env, if_exp, ret),
+            expression => self.infer_expression(env, expression),
+        }
+    }
+
+    /// The blocks take the type of their last expression statement, a block
+    /// without one and a missing `else` are null.
+    fn infer_if(
+        &mut self,
+        env: &Env,
+        if_exp: &IfExpression,
+        ret: Option<(&Ty, &Origin)>,
+    ) -> (Subst, Ty) {
+        let (mut subst, condition) = self.infer_expression(env, &if_exp.condition);
+        let from = Origin {
+            what: String::from("if"),
+            span: if_exp.token.span,
+        };
+        let s = self.unify(
+            &Ty::Bool,
+            &from,
+            &condition,
+            &Origin::of_expression(&if_exp.condition),
+        );
+        subst = compose(&s, &subst);
+
+        let result = self.fresh();
+        let mut first: Option<Origin> = None;
+
+        for block in if_exp.blocks() {
+            let mut block_env = apply_env(env, &subst);
+            let mut value = None;
+
+            for (idx, stmt) in block.statements.iter().enumerate() {
+                let ret = ret.map(|(ret, from)| (ret.apply(&subst), from));
+                let ret = ret.as_ref().map(|(ret, from)| (ret, *from));
+
+                let is_last = idx + 1 == block.statements.len();
+                if let (true, StatementNode::Expression(exp_stmt)) = (is_last, stmt) {
+                    if let Some(expression) = &exp_stmt.expression {
+                        let (s, ty) = self.infer_value(&block_env, expression, ret);
+                        subst = compose(&s, &subst);
+                        value = Some((ty, Origin::of_expression(expression)));
+                        continue;
+                    }
+                }
+
+                let (s, new_env) = self.infer_statement(&block_env, stmt, ret);
+                subst = compose(&s, &subst);
+                block_env = new_env;
+            }
+
+            let Some((ty, value_from)) = value else {
+                continue;
+            };
+            let expected_from = first.as_ref().unwrap_or(&value_from);
+            let s = self.unify(&result.apply(&subst), expected_from, &ty, &value_from);
+            subst = compose(&s, &subst);
+            first.get_or_insert(value_from);
+        }
+
+        (subst.clone(), result.apply(&subst))
+    }
+
+    fn infer_function(&mut self, env: &Env, func: &FunctionLiteral) -> (Subst, Ty) {
+        let mut subst = Subst::new();
+        let mut fn_env = env.clone();
+        let mut params = vec![];
+
+        for param in &func.parameters {
+            let mut ty = if param.rest {
+                Ty::Array(Box::new(self.fresh()))
+            } else {
+                self.fresh()
+            };
+            let param_from = Origin {
+                what: param.print_string(),
+                span: param.name.token.span,
+            };
+
+            if let Some(annotation) = &param.annotation {
+                let declared = self.resolve(annotation);
+                let from = Origin::of_type_expr(annotation, param.name.token.span);
+                let s = self.unify(&declared, &from, &ty, &param_from);
+                subst = compose(&s, &subst);
+                ty = ty.apply(&subst);
+            }
+
+            if let Some(default) = &param.default {
+                let (s, default_type) = self.infer_expression(&apply_env(&fn_env, &subst), default);
+                subst = compose(&s, &subst);
+                let s = self.unify(
+                    &ty.apply(&subst),
+                    &param_from,
+                    &default_type,
+                    &Origin::of_expression(default),
+                );
+                subst = compose(&s, &subst);
+                ty = ty.apply(&subst);
+            }
+
+            // a rest parameter is an array inside the body, its type takes
+            // the type of each extra argument
+            let each = match (&ty, param.rest) {
+                (Ty::Array(element), true) => element.as_ref().clone(),
+                (ty, _) => ty.clone(),
+            };
+            params.push(Param {
+                ty: each,
+                name: Some(param.name.value.clone()),
+                default: param.default.is_some(),
+                rest: param.rest,
+            });
+            fn_env.insert(param.name.value.clone(), Scheme::mono(ty));
+        }
+
+        let ret = self.fresh();
+        let ret_from = match &func.return_type {
+            Some(return_type) => {
+                let declared = self.resolve(return_type);
+                let from = Origin::of_type_expr(return_type, func.token.span);
+                let s = self.unify(&declared, &from, &ret, &from);
+                subst = compose(&s, &subst);
+                from
+            }
+            None => Origin {
+                what: func.print_string(),
+                span: func.token.span,
+            },
+        };
+
+        // a generator has no implicit return value, `ret` is what it yields
+        let is_generator = func.is_generator();
+        let s = if is_generator {
+            let ret = ret.apply(&subst);
+            self.infer_statements(
+                &apply_env(&fn_env, &subst),
+                &func.body.statements,
+                Some((&ret, &ret_from)),
+            )
+        } else {
+            self.infer_block(
+                &apply_env(&fn_env, &subst),
+                &func.body,
+                &ret.apply(&subst),
+                &ret_from,
+            )
+        };
+        subst = compose(&s, &subst);
+
+        let params = params.iter().map(|p| p.apply(&subst)).collect();
+        let ret = ret.apply(&subst);
+        let ret = if is_generator {
+            Ty::Generator(Box::new(ret))
+        } else {
+            ret
+        };
+        (subst.clone(), Ty::Function(params, Box::new(ret)))
+    }
+
+    fn infer_block(
+        &mut self,
+        env: &Env,
+        block: &BlockStatement,
+        ret: &Ty,
+        ret_from: &Origin,
+    ) -> Subst {
+        let mut subst = Subst::new();
+        let mut env = env.clone();
+
+        for (idx, stmt) in block.statements.iter().enumerate() {
+            let ret = ret.apply(&subst);
+
+            // the last expression statement is the implicit return value
+            let is_last = idx + 1 == block.statements.len();
+            if let (true, StatementNode::Expression(exp_stmt)) = (is_last, stmt) {
+                if let Some(value) = &exp_stmt.expression {
+                    let (s, value_type) = self.infer_value(&env, value, Some((&ret, ret_from)));
+                    subst = compose(&s, &subst);
+                    let s = self.unify(
+                        &ret.apply(&subst),
+                        ret_from,
+                        &value_type,
+                        &Origin::of_expression(value),
+                    );
+                    subst = compose(&s, &subst);
+                    continue;
+                }
+            }
+
+            let (s, new_env) = self.infer_statement(&env, stmt, Some((&ret, ret_from)));
+            subst = compose(&s, &subst);
+            env = new_env;
+        }
+
+        subst
+    }
+
+    fn infer_call(&mut self, env: &Env, call: &CallExpression) -> (Subst, Ty) {
+        let (mut subst, function) = self.infer_expression(env, &call.function);
+
+        let mut arguments = vec![];
+        for arg in &call.arguments {
+            let (s, ty) = self.infer_expression(&apply_env(env, &subst), &arg.value);
+            subst = compose(&s, &subst);
+            arguments.push((arg, ty, Origin::of_expression(&arg.value)));
+        }
+
+        let function_from = Origin::of_expression(&call.function);
+        let call_from = Origin {
+            what: call.print_string(),
+            span: call.token.span,
+        };
+
+        match function.apply(&subst) {
+            Ty::Function(params, ret) => {
+                let targets = self.match_arguments(call, &params);
+                for ((_, ty, arg_from), param) in arguments.iter().zip(targets) {
+                    let Some(param) = param else {
+                        continue;
+                    };
+                    let s = self.unify(
+                        &param.ty.apply(&subst),
+                        &function_from,
+                        &ty.apply(&subst),
+                        arg_from,
+                    );
+                    subst = compose(&s, &subst);
+                }
+                (subst.clone(), ret.apply(&subst))
+            }
+            // without a known signature only positional arguments can be placed
+            function => {
+                let ret = self.fresh();
+                let expected = Ty::Function(
+                    arguments
+                        .iter()
+                        .filter(|(arg, _, _)| arg.name.is_none())
+                        .map(|(_, ty, _)| Param::positional(ty.apply(&subst)))
+                        .collect(),
+                    Box::new(ret.clone()),
+                );
+                let s = self.unify(&function, &function_from, &expected, &call_from);
+                subst = compose(&s, &subst);
+                (subst.clone(), ret.apply(&subst))
+            }
+        }
+    }
+
+    /// The parameter each argument of the call fills: positional ones in
+    /// order with the extra ones going to the rest parameter, named ones by
+    /// name. Reports an argument that fits no parameter or a required
+    /// parameter left without one, in the words of [`crate::arity`].
+    fn match_arguments<'p>(
+        &mut self,
+        call: &CallExpression,
+        params: &'p [Param],
+    ) -> Vec<Option<&'p Param>> {
+        let positional: Vec<&Param> = params.iter().filter(|p| !p.rest).collect();
+        let rest = params.iter().find(|p| p.rest);
+        // a type without parameter names can not place named arguments, nor
+        // tell which parameters they leave empty
+        let named = positional.iter().any(|p| p.name.is_some());
+        let placeable = named || call.arguments.iter().all(|arg| arg.name.is_none());
+
+        let mut filled = vec![false; positional.len()];
+        let mut targets = vec![];
+        let mut errors = vec![];
+        let mut given = 0;
+
+        for arg in &call.arguments {
+            let idx = match &arg.name {
+                None => {
+                    given += 1;
+                    match positional.get(given - 1) {
+                        Some(_) => Some(given - 1),
+                        None => {
+                            targets.push(rest);
+                            continue;
+                        }
+                    }
+                }
+                Some(name) => {
+                    let idx = positional
+                        .iter()
+                        .position(|p| p.name.as_ref() == Some(&name.value));
+                    match idx {
+                        Some(idx) if filled[idx] => {
+                            errors.push(format!("argument `{}` given more than once", name.value))
+                        }
+                        None if named => {
+                            errors.push(format!("unknown named argument `{}`", name.value))
+                        }
+                        _ => {}
+                    }
+                    idx
+                }
+            };
+
+            if let Some(idx) = idx {
+                filled[idx] = true;
+            }
+            targets.push(idx.map(|idx| positional[idx]));
+        }
+
+        let required = positional.iter().filter(|p| !p.default).count();
+        let missing = positional
+            .iter()
+            .zip(&filled)
+            .any(|(param, filled)| !param.default && !filled);
+        if placeable && (missing || (rest.is_none() && given > positional.len())) {
+            let expected = if rest.is_some() {
+                format!("at least {}", required)
+            } else if required == positional.len() {
+                format!("{}", required)
+            } else {
+                format!("{} to {}", required, positional.len())
+            };
+            errors.push(format!(
+                "wrong number of arguments: expected {}, got {}",
+                expected,
+                call.arguments.len()
+            ));
+        }
+
+        // like the arity check, only the first problem of a call is reported
+        if let Some(msg) = errors.into_iter().next() {
+            self.errors.push(format!(
+                "{}: {} in call `{}`",
+                call.token.span,
+                msg,
+                call.print_string()
+            ));
+        }
+
+        targets
+    }
+}
+
+/// The enums declared at the top level, a match may use their variants
+/// before the declaration.
+fn top_level_enums(program: &Program) -> HashMap<String, EnumDeclaration> {
+    program
+        .statements
+        .iter()
+        .filter_map(|stmt| match stmt {
+            StatementNode::Enum(declaration) => {
+                Some((declaration.name.value.clone(), declaration.clone()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{lexer::Lexer, parser::Parser};
+
+    use super::{infer_program, Inference};
+
+    fn infer(input: &str) -> Inference {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert!(
+            parser.errors().is_empty(),
+            "parser errors: {:?}",
+            parser.errors()
+        );
+
+        infer_program(&program)
+    }
+
+    #[test]
+    fn test_inferred_types() {
+        let tests = vec![
+            ("let x = 5;", "x", "int"),
+            ("let s = \"a\" + \"b\";", "s", "string"),
+            ("let b = 1 < 2;", "b", "bool"),
+            ("let id = fn(x) { x };", "id", "fn('a) -> 'a"),
+            ("let add = fn(a, b) { a + b };", "add", "fn('a, 'a) -> 'a"),
+            ("let inc = fn(n) { n + 1 };", "inc", "fn(int) -> int"),
+            ("let k = fn(a, b) { a };", "k", "fn('a, 'b) -> 'a"),
+            ("let xs = [1, 2, 3];", "xs", "[int]"),
+            ("let h = {\"a\": true};", "h", "{string: bool}"),
+            (
+                "let apply = fn(f, x) { f(x) };",
+                "apply",
+                "fn(fn('a) -> 'b, 'a) -> 'b",
+            ),
+            (
+                "let compose = fn(f, g) { fn(x) { f(g(x)) } };",
+                "compose",
+                "fn(fn('a) -> 'b, fn('c) -> 'a) -> fn('c) -> 'b",
+            ),
+            (
+                "let fact = fn(n) { match (n) { 0 => 1, _ => n * fact(n - 1) } };",
+                "fact",
+                "fn(int) -> int",
+            ),
+            (
+                "infix |> 1 left = fn(x, f) { f(x) }; let r = 1 |> fn(n) { n < 2 };",
+                "r",
+                "bool",
+            ),
+            ("let [a, ...rest] = [1, 2];", "rest", "[int]"),
+            ("let {name} = {\"name\": \"Ali\"};", "name", "string"),
+            (
+                "let f = fn(x, y = true) { y };",
+                "f",
+                "fn('a, bool) -> bool",
+            ),
+            (
+                "let f = fn(x: int) -> int { return x; };",
+                "f",
+                "fn(int) -> int",
+            ),
+            (
+                "struct Point { x, y } let p = Point { x: 1, y: 2 };",
+                "p",
+                "Point",
+            ),
+            (
+                "enum Shape { Circle(r), Rect(w, h) } let r = Rect(1, true);",
+                "r",
+                "Shape",
+            ),
+            (
+                "enum Shape { Circle(r), Empty } let f = fn(s) { match (s) { Circle(r) => r + 1, Empty => 0 } };",
+                "f",
+                "fn(Shape) -> int",
+            ),
+            (
+                "let f = fn(x) { try { return x + 1; } catch (e) { throw e; } };",
+                "f",
+                "fn(int) -> int",
+            ),
+            (
+                "let f = fn(x) { try { g(); } catch (e) { return e; } finally { x } };",
+                "f",
+                "fn('a) -> 'b",
+            ),
+            (
+                "let count = fn(n) { yield n; yield n + 1; };",
+                "count",
+                "fn(int) -> generator(int)",
+            ),
+            (
+                "let g = fn() { for x in [\"a\"] { yield x; } }; let first = g().next();",
+                "first",
+                "string",
+            ),
+            (
+                "let gen = fn() { yield true; }; let f = fn() { for b in gen() { return b; } false };",
+                "f",
+                "fn() -> bool",
+            ),
+            ("let r = 0..=10;", "r", "range"),
+            (
+                "let total = fn(n) { let t = 0; for i in 0..n { puts(i * 2); } t };",
+                "total",
+                "fn(int) -> int",
+            ),
+            ("let xs = [true]; let x = xs[0];", "x", "bool"),
+            ("let s = \"hello\"[1..3];", "s", "string"),
+            ("let v = {\"a\": 1}[\"a\"];", "v", "int"),
+            ("let first = fn(xs) { xs[0] };", "first", "fn('a) -> 'b"),
+            (
+                "struct P { x } let get = fn(p: P) { p.x };",
+                "get",
+                "fn(P) -> 'a",
+            ),
+            ("let n = null;", "n", "'a"),
+            ("let or = fn(a) { a ?? 0 };", "or", "fn(int) -> int"),
+            ("let v = [null, 1][0] ?? 2;", "v", "int"),
+            ("let count = 0; let inc = fn() { count = count + 1 };", "inc", "fn() -> int"),
+            ("const c = \"a\";", "c", "string"),
+            (
+                "let f = fn(x, ...rest) { rest };",
+                "f",
+                "fn('a, ...['b]) -> ['b]",
+            ),
+            (
+                "let f = fn(x, ...rest) { rest }; let r = f(1, true, false);",
+                "r",
+                "[bool]",
+            ),
+            (
+                "let f = fn(a, b = \"\") { b }; let y = f(b: \"s\", a: 1);",
+                "y",
+                "string",
+            ),
+            (
+                "let sign = fn(n) { if (n > 0) { 1 } else if (n < 0) { -1 } else { 0 } };",
+                "sign",
+                "fn(int) -> int",
+            ),
+            (
+                "let f = fn(ok) { if (ok) { return \"a\"; } \"b\" };",
+                "f",
+                "fn(bool) -> string",
+            ),
+            ("let v = if (true) { let a = 1; a };", "v", "int"),
+        ];
+
+        for (input, name, expected) in tests {
+            let inference = infer(input);
+            assert!(
+                inference.errors.is_empty(),
+                "unexpected errors for {}: {:?}",
+                input,
+                inference.errors
+            );
+
+            let scheme = inference
+                .bindings
+                .iter()
+                .find(|(binding, _)| binding == name)
+                .map(|(_, scheme)| scheme.to_string());
+            assert_eq!(
+                scheme.as_deref(),
+                Some(expected),
+                "wrong type for {} in {}",
+                name,
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_let_polymorphism() {
+        let inference = infer("let id = fn(x) { x }; let a = id(1); let b = id(true);");
+
+        assert!(
+            inference.errors.is_empty(),
+            "errors: {:?}",
+            inference.errors
+        );
+        let types: Vec<String> = inference
+            .bindings
+            .iter()
+            .map(|(name, scheme)| format!("{}: {}", name, scheme))
+            .collect();
+        assert_eq!(types, vec!["id: fn('a) -> 'a", "a: int", "b: bool"]);
+    }
+
+    #[test]
+    fn test_unification_errors_name_both_origins() {
+        let tests = vec![
+            (
+                "let x = 1 + \"a\";",
+                "1:13-16: type mismatch between int and string: int from `1` at 1:9-10, string from `\"a\"` at 1:13-16",
+            ),
+            (
+                "let xs = [1, true];",
+                "1:14-18: type mismatch between int and bool: int from `1` at 1:11-12, bool from `true` at 1:14-18",
+            ),
+            (
+                "let f = fn(x) { x * 2 };\nlet y = f(\"a\");",
+                "2:11-14: type mismatch between int and string: int from `f` at 2:9-10, string from `\"a\"` at 2:11-14",
+            ),
+            (
+                "let x: string = 5;",
+                "1:17-18: type mismatch between string and int: string from `string` at 1:5-6, int from `5` at 1:17-18",
+            ),
+            (
+                "let m = match (x) { 1 => \"one\", _ => 0 };",
+                "1:38-39: type mismatch between string and int: string from `\"one\"` at 1:26-31, int from `0` at 1:38-39",
+            ),
+            (
+                "let g = fn(b) { match (b) { true => 1, 0 => 2 } };",
+                "1:40-41: type mismatch between bool and int: bool from `b` at 1:24-25, int from `0` at 1:40-41",
+            ),
+            (
+                "let self = fn(f) { f(f) };",
+                "1:21-22: type mismatch between 't2 and fn('t2) -> 't4: 't2 from `f` at 1:20-21, fn('t2) -> 't4 from `f(f)` at 1:21-22",
+            ),
+            (
+                "let g = fn() { yield 1; yield \"a\"; };",
+                "1:31-34: type mismatch between int and string: int from `fn() yield 1;yield \"a\";` at 1:9-11, string from `\"a\"` at 1:31-34",
+            ),
+            (
+                "let v = if (1) { 2 } else { 3 };",
+                "1:13-14: type mismatch between bool and int: bool from `if` at 1:9-11, int from `1` at 1:13-14",
+            ),
+            (
+                "let v = if (true) { 2 } else { \"a\" };",
+                "1:32-35: type mismatch between int and string: int from `2` at 1:21-22, string from `\"a\"` at 1:32-35",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let inference = infer(input);
+            assert_eq!(
+                inference.errors,
+                vec![expected],
+                "wrong errors for {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_call_errors() {
+        let tests = vec![
+            (
+                "let f = fn(a: int) { a }; f(a: \"s\");",
+                "1:32-35: type mismatch between int and string: int from `f` at 1:27-28, string from `\"s\"` at 1:32-35",
+            ),
+            (
+                "let f = fn(a, ...r) { a }; f(1, 2, \"c\");",
+                "1:36-39: type mismatch between int and string: int from `f` at 1:28-29, string from `\"c\"` at 1:36-39",
+            ),
+            (
+                "let f = fn(a) { a }; f(1, 2);",
+                "1:23-24: wrong number of arguments: expected 1, got 2 in call `f(1, 2)`",
+            ),
+            (
+                "let f = fn(a, b = 1) { a }; f(b: 2);",
+                "1:30-31: wrong number of arguments: expected 1 to 2, got 1 in call `f(b: 2)`",
+            ),
+            (
+                "let f = fn(a, ...r) { a }; f();",
+                "1:29-30: wrong number of arguments: expected at least 1, got 0 in call `f()`",
+            ),
+            (
+                "let f = fn(a) { a }; f(1, b: 2);",
+                "1:23-24: unknown named argument `b` in call `f(1, b: 2)`",
+            ),
+            (
+                "fn(a) { a }(1, a: 2);",
+                "1:12-13: argument `a` given more than once in call `fn(a) a(1, a: 2)`",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let inference = infer(input);
+            assert_eq!(
+                inference.errors,
+                vec![expected],
+                "wrong errors for {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_index_errors() {
+        let tests = vec![
+            (
+                "let x = [1][\"a\"];",
+                "1:13-16: type mismatch between int and string: int from `[1]` at 1:9-10, string from `\"a\"` at 1:13-16",
+            ),
+            ("let x = true[0];", "1:13-14: can not index bool in `(true[0])`"),
+            (
+                "let x = 1; x = true;",
+                "1:16-20: type mismatch between int and bool: int from `x` at 1:12-13, bool from `true` at 1:16-20",
+            ),
+            (
+                "let x = 1 ?? \"a\";",
+                "1:14-17: type mismatch between int and string: int from `1` at 1:9-10, string from `\"a\"` at 1:14-17",
+            ),
+            ("let x = 5[1..];", "1:10-11: can not slice int in `(5[(1..)])`"),
+            (
+                "let r = 1..true;",
+                "1:12-16: type mismatch between int and bool: int from `..` at 1:10-12, bool from `true` at 1:12-16",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let inference = infer(input);
+            assert_eq!(
+                inference.errors,
+                vec![expected],
+                "wrong errors for {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_iterating_over_non_sequence() {
+        let inference = infer("let f = fn() { for x in 5 { x } };");
+
+        assert_eq!(
+            inference.errors,
+            vec!["1:25-26: can not iterate over int in `5`"]
+        );
+    }
+
+    #[test]
+    fn test_struct_errors() {
+        let inference = infer("struct P { x } let p = P { x: 1 };\nlet y = p.y;");
+
+        assert_eq!(
+            inference.errors,
+            vec!["2:11-12: struct `P` has no field `y`"]
+        );
+    }
+
+    #[test]
+    fn test_variant_pattern_errors() {
+        let tests = vec![
+            (
+                "enum E { A(x), B } let f = fn(e) { match (e) { A(x, y) => x, B => 0 } };",
+                "1:48-49: variant `A` has 1 values, but the pattern `A(x, y)` has 2",
+            ),
+            (
+                "let f = fn(e) { match (e) { C(x) => x } };",
+                "1:29-30: unknown variant `C`",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let inference = infer(input);
+            assert_eq!(
+                inference.errors,
+                vec![expected],
+                "wrong errors for {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_operator_restrictions() {
+        let inference = infer("let t = true + false;");
+
+        assert_eq!(
+            inference.errors,
+            vec!["1:14-15: operator + is not defined for bool"]
+        );
+    }
+}
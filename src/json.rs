@@ -0,0 +1,392 @@
+use std::fmt::Display;
+
+/// How deep arrays and objects may nest, deeper input fails instead of overflowing the stack.
+pub const MAX_DEPTH: usize = 512;
+
+/// A JSON value. Numbers are integers, which is all the AST needs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    /// Keeps the keys in insertion order.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// The value of `key` if this is an object that has it.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(pairs) => pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Parses a single JSON value, surrounded by nothing but whitespace.
+    pub fn parse(input: &str) -> Result<Json, String> {
+        let mut parser = JsonParser {
+            input: input.chars().collect(),
+            position: 0,
+            depth: 0,
+        };
+
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.position < parser.input.len() {
+            return Err(parser.error("end of input"));
+        }
+
+        Ok(value)
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => write_string(f, value),
+            Json::Array(elements) => {
+                write!(f, "[")?;
+                for (idx, element) in elements.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(pairs) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in pairs.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, value: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for ch in value.chars() {
+        match ch {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            ch if ch.is_control() => write!(f, "\\u{:04x}", ch as u32)?,
+            ch => write!(f, "{}", ch)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct JsonParser {
+    input: Vec<char>,
+    position: usize,
+    depth: usize,
+}
+
+impl JsonParser {
+    fn error(&self, expected: &str) -> String {
+        match self.input.get(self.position) {
+            Some(ch) => format!(
+                "expected {} at offset {}, got `{}`",
+                expected, self.position, ch
+            ),
+            None => format!(
+                "expected {} at offset {}, got the end",
+                expected, self.position
+            ),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .input
+            .get(self.position)
+            .is_some_and(|ch| ch.is_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input.get(self.position).copied()
+    }
+
+    /// Consumes `word` if the input continues with it.
+    fn eat(&mut self, word: &str) -> bool {
+        let found = word
+            .chars()
+            .enumerate()
+            .all(|(idx, ch)| self.input.get(self.position + idx) == Some(&ch));
+        if found {
+            self.position += word.chars().count();
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.peek() != Some(expected) {
+            return Err(self.error(&format!("`{}`", expected)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn parse_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('{') => self.nested(Self::parse_object),
+            Some('[') => self.nested(Self::parse_array),
+            Some('"') => self.parse_string().map(Json::String),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some(_) => {
+                for (word, value) in [
+                    ("null", Json::Null),
+                    ("true", Json::Bool(true)),
+                    ("false", Json::Bool(false)),
+                ] {
+                    if self.eat(word) {
+                        return Ok(value);
+                    }
+                }
+                Err(self.error("a value"))
+            }
+            None => Err(self.error("a value")),
+        }
+    }
+
+    /// Runs `parse` one nesting level deeper, or fails past [`MAX_DEPTH`].
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Json, String>) -> Result<Json, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(format!(
+                "nesting exceeds the maximum depth of {} at offset {}",
+                MAX_DEPTH, self.position
+            ));
+        }
+
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+
+        value
+    }
+
+    fn parse_object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut pairs = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(Json::Object(pairs));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("a key"));
+            }
+            let key = self.parse_string()?;
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            pairs.push((key, value));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => {
+                    self.position += 1;
+                    return Ok(Json::Object(pairs));
+                }
+                _ => return Err(self.error("`,` or `}`")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut elements = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(Json::Array(elements));
+        }
+
+        loop {
+            elements.push(self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(']') => {
+                    self.position += 1;
+                    return Ok(Json::Array(elements));
+                }
+                _ => return Err(self.error("`,` or `]`")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.position += 1;
+                    return Ok(string);
+                }
+                Some('\\') => {
+                    self.position += 1;
+                    let escape = self.peek();
+                    self.position += 1;
+                    let escaped = match escape {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => self.parse_unicode_escape()?,
+                        _ => {
+                            self.position -= 1;
+                            return Err(self.error("an escape sequence"));
+                        }
+                    };
+                    string.push(escaped);
+                }
+                Some(ch) => {
+                    string.push(ch);
+                    self.position += 1;
+                }
+                None => return Err(self.error("`\"`")),
+            }
+        }
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char, String> {
+        let digits: String = self.input.iter().skip(self.position).take(4).collect();
+        let code = u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| digits.len() == 4)
+            .ok_or_else(|| self.error("four hex digits"))?;
+        self.position += 4;
+
+        char::from_u32(code).ok_or_else(|| self.error("a unicode scalar value"))
+    }
+
+    fn parse_number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+        if self.peek() == Some('-') {
+            self.position += 1;
+        }
+        while self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+            self.position += 1;
+        }
+
+        let literal: String = self.input[start..self.position].iter().collect();
+        literal
+            .parse()
+            .map(Json::Number)
+            .map_err(|_| format!("expected an integer at offset {}, got `{}`", start, literal))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Json, MAX_DEPTH};
+
+    #[test]
+    fn test_parse_and_print() {
+        let tests = vec![
+            ("null", "null"),
+            (" true ", "true"),
+            ("-42", "-42"),
+            (r#""a\"b\\c\ndA\/""#, r#""a\"b\\c\ndA/""#),
+            ("[ 1, [], {} ]", "[1,[],{}]"),
+            (
+                r#"{ "b": 1, "a": [false, null], "c": {"d": "e"} }"#,
+                r#"{"b":1,"a":[false,null],"c":{"d":"e"}}"#,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let json = Json::parse(input).unwrap();
+            assert_eq!(json.to_string(), expected, "wrong json for {}", input);
+            assert_eq!(Json::parse(&json.to_string()).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_get() {
+        let json = Json::parse(r#"{"a": 1, "b": [2]}"#).unwrap();
+
+        assert_eq!(json.get("a"), Some(&Json::Number(1)));
+        assert_eq!(json.get("c"), None);
+        assert_eq!(Json::Number(1).get("a"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let tests = vec![
+            ("", "expected a value at offset 0, got the end"),
+            ("[1 2]", "expected `,` or `]` at offset 3, got `2`"),
+            ("{1: 2}", "expected a key at offset 1, got `1`"),
+            (r#"{"a" 1}"#, "expected `:` at offset 5, got `1`"),
+            (r#""abc"#, "expected `\"` at offset 4, got the end"),
+            (
+                r#""\x""#,
+                "expected an escape sequence at offset 2, got `x`",
+            ),
+            (r#""\u12""#, "expected four hex digits at offset 3, got `1`"),
+            ("1.5", "expected end of input at offset 1, got `.`"),
+            ("-", "expected an integer at offset 0, got `-`"),
+            ("nul", "expected a value at offset 0, got `n`"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                Json::parse(input),
+                Err(expected.to_string()),
+                "wrong error for {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_nesting_depth_limit() {
+        let tests = vec![
+            ("[".repeat(100_000), 512),
+            ("{\"a\":".repeat(100_000), 2560),
+        ];
+
+        for (input, offset) in tests {
+            assert_eq!(
+                Json::parse(&input),
+                Err(format!(
+                    "nesting exceeds the maximum depth of {} at offset {}",
+                    MAX_DEPTH, offset
+                )),
+            );
+        }
+    }
+}
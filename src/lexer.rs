@@ -1,10 +1,18 @@
-use crate::token::{lookup_ident, Token, TokenKind};
+use crate::token::{lookup_ident, Comment, Position, Span, Token, TokenKind};
 
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
     read_position: usize,
     ch: char,
+    line: usize,
+    column: usize,
+    /// Symbols declared with `infix`, longest first.
+    operators: Vec<String>,
+    after_infix: bool,
+    /// Comments skipped so far, collected only when `keep_comments` is set.
+    comments: Vec<Comment>,
+    keep_comments: bool,
 }
 
 impl Lexer {
@@ -14,6 +22,12 @@ impl Lexer {
             position: 0,
             read_position: 0,
             ch: Default::default(),
+            line: 1,
+            column: 0,
+            operators: vec![],
+            after_infix: false,
+            comments: vec![],
+            keep_comments: false,
         };
 
         lexer.read_char();
@@ -21,7 +35,25 @@ impl Lexer {
         lexer
     }
 
+    /// Keeps the comments the lexer skips, for tools like the formatter that
+    /// print them again.
+    pub fn with_comments(mut self) -> Lexer {
+        self.keep_comments = true;
+        self
+    }
+
+    /// The comments skipped so far, empty unless made [`with_comments`](Lexer::with_comments).
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
     fn read_char(&mut self) {
+        if self.ch == '\n' {
+            self.line += 1;
+            self.column = 0;
+        }
+        self.column += 1;
+
         if self.read_position >= self.input.len() {
             self.ch = '\0';
         } else {
@@ -31,9 +63,30 @@ impl Lexer {
         self.read_position += 1;
     }
 
+    /// Makes later occurrences of `symbol` lex as a single operator token.
+    pub fn register_operator(&mut self, symbol: &str) {
+        if !self.operators.iter().any(|op| op == symbol) {
+            self.operators.push(symbol.to_string());
+            self.operators.sort_by_key(|op| std::cmp::Reverse(op.len()));
+        }
+    }
+
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
 
+        let start = self.cur_position();
+
+        // the symbol after `infix` is being declared, so it is not registered yet
+        let after_infix = std::mem::take(&mut self.after_infix);
+        if let Some(literal) = self.read_operator(after_infix) {
+            let token = Token {
+                kind: TokenKind::Operator,
+                literal,
+                ..Default::default()
+            };
+            return self.with_span(token, start);
+        }
+
         let token = match self.ch {
             '=' => {
                 if self.peek_char() == '=' {
@@ -41,6 +94,14 @@ impl Lexer {
                     Token {
                         kind: TokenKind::Eq,
                         literal: String::from("=="),
+                        ..Default::default()
+                    }
+                } else if self.peek_char() == '>' {
+                    self.read_char();
+                    Token {
+                        kind: TokenKind::FatArrow,
+                        literal: String::from("=>"),
+                        ..Default::default()
                     }
                 } else {
                     Lexer::new_token(TokenKind::Assign, self.ch)
@@ -50,20 +111,71 @@ impl Lexer {
             '(' => Lexer::new_token(TokenKind::Lparen, self.ch),
             ')' => Lexer::new_token(TokenKind::Rparen, self.ch),
             ',' => Lexer::new_token(TokenKind::Comma, self.ch),
+            ':' => Lexer::new_token(TokenKind::Colon, self.ch),
+            '.' if self.peek_char() == '.' => {
+                self.read_char();
+                let (kind, literal) = match self.peek_char() {
+                    '.' => (TokenKind::Ellipsis, "..."),
+                    '=' => (TokenKind::DotDotEq, "..="),
+                    _ => (TokenKind::DotDot, ".."),
+                };
+                if kind != TokenKind::DotDot {
+                    self.read_char();
+                }
+                Token {
+                    kind,
+                    literal: String::from(literal),
+                    ..Default::default()
+                }
+            }
+            '.' => Lexer::new_token(TokenKind::Dot, self.ch),
+            '?' if matches!(self.peek_char(), '.' | '?') => {
+                self.read_char();
+                let (kind, literal) = if self.ch == '.' {
+                    (TokenKind::QuestionDot, "?.")
+                } else {
+                    (TokenKind::QuestionQuestion, "??")
+                };
+                Token {
+                    kind,
+                    literal: String::from(literal),
+                    ..Default::default()
+                }
+            }
             '+' => Lexer::new_token(TokenKind::Plus, self.ch),
             '{' => Lexer::new_token(TokenKind::Lbrace, self.ch),
             '}' => Lexer::new_token(TokenKind::Rbrace, self.ch),
+            '[' => Lexer::new_token(TokenKind::Lbracket, self.ch),
+            ']' => Lexer::new_token(TokenKind::Rbracket, self.ch),
+            '"' => Token {
+                kind: TokenKind::String,
+                literal: self.read_string(),
+                ..Default::default()
+            },
             '\0' => Token {
                 kind: TokenKind::Eof,
                 literal: "".to_string(),
+                ..Default::default()
             },
-            '-' => Lexer::new_token(TokenKind::Minus, self.ch),
+            '-' => {
+                if self.peek_char() == '>' {
+                    self.read_char();
+                    Token {
+                        kind: TokenKind::Arrow,
+                        literal: String::from("->"),
+                        ..Default::default()
+                    }
+                } else {
+                    Lexer::new_token(TokenKind::Minus, self.ch)
+                }
+            }
             '!' => {
                 if self.peek_char() == '=' {
                     self.read_char();
                     Token {
                         kind: TokenKind::NotEq,
                         literal: String::from("!="),
+                        ..Default::default()
                     }
                 } else {
                     Lexer::new_token(TokenKind::Bang, self.ch)
@@ -76,44 +188,128 @@ impl Lexer {
             '>' => Lexer::new_token(TokenKind::Gt, self.ch),
 
             _ => {
-                return if Lexer::is_letter(self.ch) {
+                let token = if Lexer::is_letter(self.ch) {
                     let literal = self.read_identifier();
                     let kind = lookup_ident(&literal);
-                    Token { kind, literal }
+                    self.after_infix = kind == TokenKind::Infix;
+                    Token {
+                        kind,
+                        literal,
+                        ..Default::default()
+                    }
                 } else if Lexer::is_digit(self.ch) {
                     let kind = TokenKind::Int;
                     let literal = self.read_number();
-                    Token { kind, literal }
+                    Token {
+                        kind,
+                        literal,
+                        ..Default::default()
+                    }
                 } else {
-                    Lexer::new_token(TokenKind::Illegal, self.ch)
-                }
+                    let token = Lexer::new_token(TokenKind::Illegal, self.ch);
+                    self.read_char();
+                    token
+                };
+
+                return self.with_span(token, start);
             }
         };
 
         self.read_char();
 
-        return token;
+        self.with_span(token, start)
     }
 
+    fn cur_position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn with_span(&self, mut token: Token, start: Position) -> Token {
+        token.span = Span {
+            start,
+            end: self.cur_position(),
+        };
+        token
+    }
+
+    /// Skips whitespace and `//` comments, which run to the end of the line.
     fn skip_whitespace(&mut self) {
-        while self.ch.is_ascii_whitespace() {
-            self.read_char();
+        loop {
+            while self.ch.is_ascii_whitespace() {
+                self.read_char();
+            }
+
+            if self.ch != '/' || self.peek_char() != '/' {
+                return;
+            }
+
+            let start = self.cur_position();
+            let mut text = String::new();
+            while self.ch != '\n' && self.ch != '\0' {
+                text.push(self.ch);
+                self.read_char();
+            }
+
+            if self.keep_comments {
+                let comment = Comment {
+                    text: text.trim_end().to_string(),
+                    span: Span {
+                        start,
+                        end: self.cur_position(),
+                    },
+                };
+                self.comments.push(comment);
+            }
         }
     }
 
     fn peek_char(&self) -> char {
-        return if self.read_position >= self.input.len() {
+        if self.read_position >= self.input.len() {
             '\0'
         } else {
             self.input[self.read_position]
-        };
+        }
     }
 
     fn new_token(kind: TokenKind, ch: char) -> Token {
         Token {
             kind,
             literal: ch.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn is_operator_char(ch: char) -> bool {
+        "+-*/<>=!&|^%~?@$".contains(ch)
+    }
+
+    /// Reads a registered operator, or with `any` the whole run of operator characters.
+    fn read_operator(&mut self, any: bool) -> Option<String> {
+        let rest = &self.input[self.position.min(self.input.len())..];
+
+        let literal: String = if any {
+            rest.iter()
+                .take_while(|ch| Lexer::is_operator_char(**ch))
+                .collect()
+        } else {
+            self.operators
+                .iter()
+                .find(|op| op.chars().eq(rest.iter().copied().take(op.chars().count())))?
+                .clone()
+        };
+
+        if literal.is_empty() {
+            return None;
         }
+
+        for _ in literal.chars() {
+            self.read_char();
+        }
+
+        Some(literal)
     }
 
     fn is_letter(ch: char) -> bool {
@@ -131,13 +327,26 @@ impl Lexer {
         identifier
     }
 
+    fn read_string(&mut self) -> String {
+        let mut string = String::new();
+
+        self.read_char();
+        while self.ch != '"' && self.ch != '\0' {
+            string.push(self.ch);
+            self.read_char();
+        }
+
+        string
+    }
+
     fn is_digit(ch: char) -> bool {
         ch.is_numeric()
     }
 
+    /// Reads the digits of a number, `_` may separate them as in `1_000_000`.
     fn read_number(&mut self) -> String {
         let mut num = String::from("");
-        while Lexer::is_digit(self.ch) {
+        while Lexer::is_digit(self.ch) || (self.ch == '_' && Lexer::is_digit(self.peek_char())) {
             num.push(self.ch);
             self.read_char();
         }
@@ -174,6 +383,10 @@ mod test {
 
         10 == 10;
         10 !=9;
+        "foobar"
+        "foo bar"
+        match (x) { [a, _] => a, {"k": v} => v }
+        [first, ...rest]
         "#;
 
         // == != <>
@@ -182,306 +395,527 @@ mod test {
             Token {
                 kind: TokenKind::Let,
                 literal: "let".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Ident,
                 literal: "five".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Assign,
                 literal: "=".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Int,
                 literal: "5".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Semicolon,
                 literal: ";".to_string(),
+                ..Default::default()
             },
             //
             Token {
                 kind: TokenKind::Let,
                 literal: "let".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Ident,
                 literal: "ten".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Assign,
                 literal: "=".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Int,
                 literal: "10".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Semicolon,
                 literal: ";".to_string(),
+                ..Default::default()
             },
             //
             Token {
                 kind: TokenKind::Let,
                 literal: "let".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Ident,
                 literal: "add".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Assign,
                 literal: "=".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Function,
                 literal: "fn".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Lparen,
                 literal: "(".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Ident,
                 literal: "x".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Comma,
                 literal: ",".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Ident,
                 literal: "y".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Rparen,
                 literal: ")".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Lbrace,
                 literal: "{".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Ident,
                 literal: "x".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Plus,
                 literal: "+".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Ident,
                 literal: "y".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Semicolon,
                 literal: ";".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Rbrace,
                 literal: "}".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Semicolon,
                 literal: ";".to_string(),
+                ..Default::default()
             },
             //
             Token {
                 kind: TokenKind::Let,
                 literal: "let".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Ident,
                 literal: "result".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Assign,
                 literal: "=".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Ident,
                 literal: "add".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Lparen,
                 literal: "(".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Ident,
                 literal: "five".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Comma,
                 literal: ",".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Ident,
                 literal: "ten".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Rparen,
                 literal: ")".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Semicolon,
                 literal: ";".to_string(),
+                ..Default::default()
             },
             //
             Token {
                 kind: TokenKind::Bang,
                 literal: "!".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Minus,
                 literal: "-".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Slash,
                 literal: "/".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Asteriks,
                 literal: "*".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Int,
                 literal: "5".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Semicolon,
                 literal: ";".to_string(),
+                ..Default::default()
             },
             //
             Token {
                 kind: TokenKind::Int,
                 literal: "5".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Lt,
                 literal: "<".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Int,
                 literal: "10".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Gt,
                 literal: ">".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Int,
                 literal: "5".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Semicolon,
                 literal: ";".to_string(),
+                ..Default::default()
             },
             //
             Token {
                 kind: TokenKind::If,
                 literal: "if".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Lparen,
                 literal: "(".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Int,
                 literal: "5".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Lt,
                 literal: "<".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Int,
                 literal: "10".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Rparen,
                 literal: ")".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Lbrace,
                 literal: "{".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Return,
                 literal: "return".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::True,
                 literal: "true".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Semicolon,
                 literal: ";".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Rbrace,
                 literal: "}".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Else,
                 literal: "else".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Lbrace,
                 literal: "{".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Return,
                 literal: "return".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::False,
                 literal: "false".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Semicolon,
                 literal: ";".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Rbrace,
                 literal: "}".to_string(),
+                ..Default::default()
             },
             //
             Token {
                 kind: TokenKind::Int,
                 literal: "10".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Eq,
                 literal: "==".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Int,
                 literal: "10".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Semicolon,
                 literal: ";".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Int,
                 literal: "10".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::NotEq,
                 literal: "!=".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Int,
                 literal: "9".to_string(),
+                ..Default::default()
             },
             Token {
                 kind: TokenKind::Semicolon,
                 literal: ";".to_string(),
+                ..Default::default()
+            },
+            //
+            Token {
+                kind: TokenKind::String,
+                literal: "foobar".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::String,
+                literal: "foo bar".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Match,
+                literal: "match".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Lparen,
+                literal: "(".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Ident,
+                literal: "x".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Rparen,
+                literal: ")".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Lbrace,
+                literal: "{".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Lbracket,
+                literal: "[".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Ident,
+                literal: "a".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Comma,
+                literal: ",".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Ident,
+                literal: "_".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Rbracket,
+                literal: "]".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::FatArrow,
+                literal: "=>".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Ident,
+                literal: "a".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Comma,
+                literal: ",".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Lbrace,
+                literal: "{".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::String,
+                literal: "k".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Colon,
+                literal: ":".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Ident,
+                literal: "v".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Rbrace,
+                literal: "}".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::FatArrow,
+                literal: "=>".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Ident,
+                literal: "v".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Rbrace,
+                literal: "}".to_string(),
+                ..Default::default()
+            },
+            //
+            Token {
+                kind: TokenKind::Lbracket,
+                literal: "[".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Ident,
+                literal: "first".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Comma,
+                literal: ",".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Ellipsis,
+                literal: "...".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Ident,
+                literal: "rest".to_string(),
+                ..Default::default()
+            },
+            Token {
+                kind: TokenKind::Rbracket,
+                literal: "]".to_string(),
+                ..Default::default()
             },
             //
             Token {
                 kind: TokenKind::Eof,
                 literal: "".to_string(),
+                ..Default::default()
             },
         ];
 
@@ -502,4 +936,220 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_token_spans() {
+        let input = "let five = 5;\n  add(x) -> @\n";
+
+        let expected = vec![
+            (TokenKind::Let, "1:1-4"),
+            (TokenKind::Ident, "1:5-9"),
+            (TokenKind::Assign, "1:10-11"),
+            (TokenKind::Int, "1:12-13"),
+            (TokenKind::Semicolon, "1:13-14"),
+            (TokenKind::Ident, "2:3-6"),
+            (TokenKind::Lparen, "2:6-7"),
+            (TokenKind::Ident, "2:7-8"),
+            (TokenKind::Rparen, "2:8-9"),
+            (TokenKind::Arrow, "2:10-12"),
+            (TokenKind::Illegal, "2:13-14"),
+            (TokenKind::Eof, "3:1-2"),
+        ];
+
+        let mut lexer = Lexer::new(input);
+
+        for (idx, (kind, span)) in expected.into_iter().enumerate() {
+            let recv_token = lexer.next_token();
+            assert_eq!(
+                kind, recv_token.kind,
+                "tests[{idx}] - token type wrong, expected={}, got={}",
+                kind, recv_token.kind
+            );
+            assert_eq!(
+                span,
+                recv_token.span.to_string(),
+                "tests[{idx}] - token span wrong, expected={}, got={}",
+                span,
+                recv_token.span
+            );
+        }
+    }
+
+    #[test]
+    fn test_operator_tokens() {
+        let input = "a <+> b; infix <+> 3 left; a <+> b <+>> c;";
+
+        let expected = vec![
+            (TokenKind::Ident, "a"),
+            (TokenKind::Lt, "<"),
+            (TokenKind::Plus, "+"),
+            (TokenKind::Gt, ">"),
+            (TokenKind::Ident, "b"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Infix, "infix"),
+            (TokenKind::Operator, "<+>"),
+            (TokenKind::Int, "3"),
+            (TokenKind::Ident, "left"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Ident, "a"),
+            (TokenKind::Operator, "<+>"),
+            (TokenKind::Ident, "b"),
+            (TokenKind::Operator, "<+>"),
+            (TokenKind::Gt, ">"),
+            (TokenKind::Ident, "c"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Eof, ""),
+        ];
+
+        let mut lexer = Lexer::new(input);
+
+        for (idx, (kind, literal)) in expected.into_iter().enumerate() {
+            // the parser registers the operator once it has read the declaration
+            if idx == 9 {
+                lexer.register_operator("<+>");
+            }
+
+            let recv_token = lexer.next_token();
+            assert_eq!(
+                kind, recv_token.kind,
+                "tests[{idx}] - token type wrong, expected={}, got={}",
+                kind, recv_token.kind
+            );
+            assert_eq!(
+                literal, recv_token.literal,
+                "tests[{idx}] - literal wrong, expected={}, got={}",
+                literal, recv_token.literal
+            );
+        }
+    }
+
+    #[test]
+    fn test_optional_tokens() {
+        let input = "let a = null; a?.b ?? c; a?.[0]; ?";
+
+        let expected = vec![
+            (TokenKind::Let, "let"),
+            (TokenKind::Ident, "a"),
+            (TokenKind::Assign, "="),
+            (TokenKind::Null, "null"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Ident, "a"),
+            (TokenKind::QuestionDot, "?."),
+            (TokenKind::Ident, "b"),
+            (TokenKind::QuestionQuestion, "??"),
+            (TokenKind::Ident, "c"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Ident, "a"),
+            (TokenKind::QuestionDot, "?."),
+            (TokenKind::Lbracket, "["),
+            (TokenKind::Int, "0"),
+            (TokenKind::Rbracket, "]"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Illegal, "?"),
+            (TokenKind::Eof, ""),
+        ];
+
+        let mut lexer = Lexer::new(input);
+
+        for (idx, (kind, literal)) in expected.into_iter().enumerate() {
+            let recv_token = lexer.next_token();
+            assert_eq!(
+                kind, recv_token.kind,
+                "tests[{idx}] - token type wrong, expected={}, got={}",
+                kind, recv_token.kind
+            );
+            assert_eq!(
+                literal, recv_token.literal,
+                "tests[{idx}] - literal wrong, expected={}, got={}",
+                literal, recv_token.literal
+            );
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "// lead\nlet a = 1 / 2; // trail  \n//\na";
+
+        let expected = vec![
+            (TokenKind::Let, "let"),
+            (TokenKind::Ident, "a"),
+            (TokenKind::Assign, "="),
+            (TokenKind::Int, "1"),
+            (TokenKind::Slash, "/"),
+            (TokenKind::Int, "2"),
+            (TokenKind::Semicolon, ";"),
+            (TokenKind::Ident, "a"),
+            (TokenKind::Eof, ""),
+        ];
+
+        let mut plain = Lexer::new(input);
+        let mut lexer = Lexer::new(input).with_comments();
+
+        for (idx, (kind, literal)) in expected.into_iter().enumerate() {
+            for recv_token in [plain.next_token(), lexer.next_token()] {
+                assert_eq!(kind, recv_token.kind, "tests[{idx}] - token type wrong");
+                assert_eq!(literal, recv_token.literal, "tests[{idx}] - literal wrong");
+            }
+        }
+
+        assert!(plain.comments().is_empty());
+        let comments: Vec<(String, String)> = lexer
+            .comments()
+            .iter()
+            .map(|comment| (comment.text.clone(), comment.span.to_string()))
+            .collect();
+        assert_eq!(
+            comments,
+            vec![
+                (String::from("// lead"), String::from("1:1-8")),
+                (String::from("// trail"), String::from("2:16-26")),
+                (String::from("//"), String::from("3:1-3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_range_tokens() {
+        let input = "0..1_000_000 a..=b [...rest] xs[..2] m.x 1_";
+
+        let expected = vec![
+            (TokenKind::Int, "0"),
+            (TokenKind::DotDot, ".."),
+            (TokenKind::Int, "1_000_000"),
+            (TokenKind::Ident, "a"),
+            (TokenKind::DotDotEq, "..="),
+            (TokenKind::Ident, "b"),
+            (TokenKind::Lbracket, "["),
+            (TokenKind::Ellipsis, "..."),
+            (TokenKind::Ident, "rest"),
+            (TokenKind::Rbracket, "]"),
+            (TokenKind::Ident, "xs"),
+            (TokenKind::Lbracket, "["),
+            (TokenKind::DotDot, ".."),
+            (TokenKind::Int, "2"),
+            (TokenKind::Rbracket, "]"),
+            (TokenKind::Ident, "m"),
+            (TokenKind::Dot, "."),
+            (TokenKind::Ident, "x"),
+            (TokenKind::Int, "1"),
+            (TokenKind::Ident, "_"),
+            (TokenKind::Eof, ""),
+        ];
+
+        let mut lexer = Lexer::new(input);
+
+        for (idx, (kind, literal)) in expected.into_iter().enumerate() {
+            let recv_token = lexer.next_token();
+            assert_eq!(
+                kind, recv_token.kind,
+                "tests[{idx}] - token type wrong, expected={}, got={}",
+                kind, recv_token.kind
+            );
+            assert_eq!(
+                literal, recv_token.literal,
+                "tests[{idx}] - literal wrong, expected={}, got={}",
+                literal, recv_token.literal
+            );
+        }
+    }
 }
@@ -0,0 +1,76 @@
+//! Lexer, parser, static analyses and evaluator for the Monkey language.
+//!
+//! ```
+//! use interpreter_kurs::{ast::Node, parse};
+//!
+//! let program = parse("let x = 1 + 2;").unwrap();
+//! assert_eq!(program.print_string(), "let x = (1 + 2);");
+//! ```
+
+pub mod arity;
+pub mod ast;
+pub mod build;
+pub mod check;
+pub mod destructure;
+pub mod dump;
+pub mod eval;
+pub mod exhaustiveness;
+pub mod formatter;
+pub mod infer;
+pub mod json;
+pub mod lexer;
+pub mod macro_expansion;
+pub mod modify;
+pub mod module;
+pub mod optimize;
+pub mod parser;
+pub mod resolve;
+pub mod serialize;
+pub mod token;
+pub mod typecheck;
+pub mod value;
+
+pub use ast::Program;
+pub use lexer::Lexer;
+pub use parser::{ParseError, Parser};
+
+/// Parses `input` into a program, or returns every syntax error found.
+pub fn parse(input: &str) -> Result<Program, Vec<ParseError>> {
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+    let program = parser.parse_program();
+
+    match program {
+        Some(program) if parser.errors().is_empty() => Ok(program),
+        _ => Err(parser.errors().clone()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ast::Node, parse};
+
+    #[test]
+    fn test_parse() {
+        let program = parse("let add = fn(a, b) { a + b }; add(1, 2);").unwrap();
+
+        assert_eq!(
+            program.print_string(),
+            "let add = fn(a, b) (a + b);add(1, 2)"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let errors = parse("let = 5;\nlet x 5;").unwrap_err();
+
+        let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "1:5-6: expected a pattern, got Assign instead",
+                "2:7-8: expected next token to be Assign, got Int intead",
+            ]
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{
+        CallExpression, ExpressionNode, LetStatement, MacroLiteral, Node, Pattern, Program,
+        StatementNode,
+    },
+    modify::{modify_expression, modify_program},
+};
+
+/// Removes the top-level `let name = macro(...) { ... };` definitions and
+/// replaces every call of them with the expansion of the macro's template.
+///
+/// There is no evaluator yet, so a macro body must be a single
+/// `quote(...)`. Inside the quote, `unquote(expr)` splices `expr` with the
+/// macro's parameters replaced by the call's argument expressions. The
+/// result of an expansion is not expanded again.
+pub fn expand_macros(program: Program) -> Result<Program, Vec<String>> {
+    let mut errors = vec![];
+    let (macros, program) = define_macros(program, &mut errors);
+
+    let program = modify_program(program, &mut |expression| match expression {
+        ExpressionNode::Call(call) => match macro_call(&call, &macros) {
+            Some(mac) => mac.expand(call, &mut errors),
+            None => ExpressionNode::Call(call),
+        },
+        expression => expression,
+    });
+
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(errors)
+    }
+}
+
+struct Macro {
+    name: String,
+    literal: MacroLiteral,
+    /// The expression inside `quote(...)`, `None` if the body is not a quote.
+    template: Option<ExpressionNode>,
+}
+
+fn define_macros(program: Program, errors: &mut Vec<String>) -> (HashMap<String, Macro>, Program) {
+    let mut macros = HashMap::new();
+    let mut statements = vec![];
+
+    for stmt in program.statements {
+        match stmt {
+            StatementNode::Let(let_stmt) => match (let_stmt.name, let_stmt.value) {
+                (Pattern::Binding(name), Some(ExpressionNode::Macro(literal))) => {
+                    let template = quoted(&literal);
+                    if template.is_none() {
+                        errors.push(format!(
+                            "{}: macro `{}` must return quote(...)",
+                            literal.token.span, name.value
+                        ));
+                    }
+                    macros.insert(
+                        name.value.clone(),
+                        Macro {
+                            name: name.value,
+                            literal,
+                            template,
+                        },
+                    );
+                }
+                (name, value) => statements.push(StatementNode::Let(LetStatement {
+                    name,
+                    value,
+                    ..let_stmt
+                })),
+            },
+            stmt => statements.push(stmt),
+        }
+    }
+
+    (macros, Program { statements })
+}
+
+/// The single expression quoted by the macro body.
+fn quoted(literal: &MacroLiteral) -> Option<ExpressionNode> {
+    match literal.body.statements.as_slice() {
+        [StatementNode::Expression(exp_stmt)] => match &exp_stmt.expression {
+            Some(ExpressionNode::Call(call)) => builtin_argument(call, "quote").cloned(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The argument of a `quote(x)` or `unquote(x)` call named `builtin`.
+fn builtin_argument<'a>(call: &'a CallExpression, builtin: &str) -> Option<&'a ExpressionNode> {
+    match (call.function.as_ref(), call.arguments.as_slice()) {
+        (ExpressionNode::IdentifierNode(ident), [arg])
+            if ident.value == builtin && arg.name.is_none() =>
+        {
+            Some(&arg.value)
+        }
+        _ => None,
+    }
+}
+
+fn macro_call<'a>(call: &CallExpression, macros: &'a HashMap<String, Macro>) -> Option<&'a Macro> {
+    match call.function.as_ref() {
+        ExpressionNode::IdentifierNode(ident) => macros.get(&ident.value),
+        _ => None,
+    }
+}
+
+impl Macro {
+    fn expand(&self, call: CallExpression, errors: &mut Vec<String>) -> ExpressionNode {
+        let span = call.function.token().span;
+
+        if call.arguments.iter().any(|arg| arg.name.is_some()) {
+            errors.push(format!(
+                "{span}: macro `{}` does not take named arguments in `{}`",
+                self.name,
+                call.print_string()
+            ));
+            return ExpressionNode::Call(call);
+        }
+        if call.arguments.len() != self.literal.parameters.len() {
+            errors.push(format!(
+                "{span}: wrong number of arguments to macro `{}`: expected {}, got {}",
+                self.name,
+                self.literal.parameters.len(),
+                call.arguments.len()
+            ));
+            return ExpressionNode::Call(call);
+        }
+
+        let template = match &self.template {
+            Some(template) => template.clone(),
+            None => return ExpressionNode::Call(call),
+        };
+
+        let arguments: HashMap<&str, ExpressionNode> = self
+            .literal
+            .parameters
+            .iter()
+            .map(|param| param.value.as_str())
+            .zip(call.arguments.into_iter().map(|arg| arg.value))
+            .collect();
+
+        modify_expression(template, &mut |expression| match expression {
+            ExpressionNode::Call(unquote) => match builtin_argument(&unquote, "unquote") {
+                Some(spliced) => substitute(spliced.clone(), &arguments),
+                None => ExpressionNode::Call(unquote),
+            },
+            expression => expression,
+        })
+    }
+}
+
+/// Replaces the macro parameters in an unquoted expression by the call's arguments.
+fn substitute(
+    expression: ExpressionNode,
+    arguments: &HashMap<&str, ExpressionNode>,
+) -> ExpressionNode {
+    modify_expression(expression, &mut |expression| match expression {
+        ExpressionNode::IdentifierNode(ident) => match arguments.get(ident.value.as_str()) {
+            Some(argument) => argument.clone(),
+            None => ExpressionNode::IdentifierNode(ident),
+        },
+        expression => expression,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ast::Node, parse};
+
+    use super::expand_macros;
+
+    #[test]
+    fn test_expand_macros() {
+        let tests = vec![
+            (
+                "let flip = macro(a, b) { quote(unquote(b) - unquote(a)) }; flip(2 + 2, 10 - 5);",
+                "((10 - 5) - (2 + 2))",
+            ),
+            (
+                "let reverse = macro(a, b) { quote(unquote(b) - unquote(a)) };\
+                 let x = reverse(2, reverse(3, 1));",
+                "let x = ((1 - 3) - 2);",
+            ),
+            (
+                "let unless = macro(cond, yes, no) {
+                    quote(match (!(unquote(cond))) { true => unquote(yes), _ => unquote(no) })
+                 };
+                 unless(10 > 5, puts(\"not greater\"), puts(\"greater\"));",
+                "match ((!(10 > 5))) { true => puts(\"not greater\"), _ => puts(\"greater\") }",
+            ),
+            (
+                "let plus_one = macro(x) { quote(unquote(x + 1) * x) }; plus_one(y);",
+                "((y + 1) * x)",
+            ),
+            ("let m = macro() { quote(1) }; fn() { m() };", "fn() 1"),
+            ("let f = fn(x) { x }; f(1);", "let f = fn(x) x;f(1)"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input).unwrap();
+            let expanded = expand_macros(program).unwrap();
+
+            assert_eq!(expanded.print_string(), expected, "for {input}");
+        }
+    }
+
+    #[test]
+    fn test_expand_macro_errors() {
+        let tests = vec![
+            (
+                "let m = macro(a, b) { quote(a) };\nm(1);",
+                "2:1-2: wrong number of arguments to macro `m`: expected 2, got 1",
+            ),
+            (
+                "let m = macro(a) { quote(a) }; m(a: 1);",
+                "1:32-33: macro `m` does not take named arguments in `m(a: 1)`",
+            ),
+            (
+                "let m = macro(a) { a + 1 }; m(1);",
+                "1:9-14: macro `m` must return quote(...)",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input).unwrap();
+            let errors = expand_macros(program).unwrap_err();
+
+            assert_eq!(errors, vec![expected], "for {input}");
+        }
+    }
+}
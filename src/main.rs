@@ -1,15 +1,225 @@
-use std::io;
+use std::{env, fs, io, path::Path, process, thread};
 
+use interpreter_kurs::{
+    check, dump::Format, eval::Evaluator, formatter, module::Loader, optimize, parse,
+};
 use repl::start;
 
-pub mod ast;
-pub mod lexer;
-pub mod parser;
-pub mod repl;
-pub mod token;
+mod repl;
+
+/// Stack of the thread that parses, checks and runs code, each nested call
+/// and each level of the syntax tree takes some of it.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// How deeply calls may nest, the stack is guarded by [`MAX_STACK`].
+const MAX_CALL_DEPTH: usize = 10_000;
+
+/// How much of [`STACK_SIZE`] evaluation may take, the rest leaves room for
+/// what runs between two checks of the evaluator.
+const MAX_STACK: usize = STACK_SIZE - 64 * 1024 * 1024;
 
 fn main() {
-    println!("Salam! Bu oyrenmek ucun yazdigim interpereterdir!");
-    println!("Feel free to type in the code");
-    start(io::stdin(), io::stdout());
+    let args: Vec<String> = env::args().collect();
+    with_big_stack(move || dispatch(&args));
+}
+
+fn dispatch(args: &[String]) {
+    match args {
+        [_, mode, path] if mode == "check" => check(path),
+        [_, mode, path] if mode == "run" => run(path),
+        [_, mode, format, path] if mode == "dump" => match Format::from_name(format) {
+            Some(format) => dump(format, path),
+            None => {
+                eprintln!("unknown format `{format}`, expected sexpr, dot or json");
+                process::exit(2);
+            }
+        },
+        [_, mode, options @ ..] if mode == "fmt" => fmt(options),
+        [_] => {
+            println!("Salam! Bu oyrenmek ucun yazdigim interpereterdir!");
+            println!("Feel free to type in the code");
+            start(io::stdin(), io::stdout(), MAX_CALL_DEPTH, MAX_STACK);
+        }
+        _ => {
+            eprintln!(
+                "usage: {} [check <file> | run <file> | dump <sexpr|dot|json> <file> | fmt [--check] [--indent <n>] [--line-length <n>] <file>...]",
+                args[0]
+            );
+            process::exit(2);
+        }
+    }
+}
+
+/// Infers the types of a file's top-level bindings without running it, and
+/// runs the same static checks as the REPL.
+///
+/// Imported modules are loaded and checked for syntax and unknown exports.
+fn check(path: &str) {
+    let module = match Loader::new().load(Path::new(path)) {
+        Ok(module) => module,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{error}");
+            }
+            process::exit(1);
+        }
+    };
+
+    let report = check::check_program(&module.program);
+
+    for (name, scheme) in &report.bindings {
+        println!("{name}: {scheme}");
+    }
+    for warning in &report.warnings {
+        eprintln!("{path}: warning: {warning}");
+    }
+
+    if !report.errors.is_empty() || !report.type_errors.is_empty() {
+        for error in report.errors.iter().chain(&report.type_errors) {
+            eprintln!("{path}:{error}");
+        }
+        process::exit(1);
+    }
+}
+
+/// Runs `f` on a thread with a stack of [`STACK_SIZE`].
+fn with_big_stack(f: impl FnOnce() + Send + 'static) {
+    let thread = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(f)
+        .expect("should have spawned a thread");
+    if thread.join().is_err() {
+        process::exit(101);
+    }
+}
+
+/// Runs a file and the modules it imports, after the same checks as `check`.
+///
+/// Only type inference errors do not stop it, they are printed as warnings.
+/// The checked code is optimized before it runs.
+fn run(path: &str) {
+    let module = match Loader::new().load(Path::new(path)) {
+        Ok(module) => module,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{error}");
+            }
+            process::exit(1);
+        }
+    };
+
+    // a program inference rejects may still run, like `puts([1, "a"])`
+    let report = check::check_program(&module.program);
+    for warning in report.type_errors.iter().chain(&report.warnings) {
+        eprintln!("{path}: warning: {warning}");
+    }
+    if !report.errors.is_empty() {
+        for error in &report.errors {
+            eprintln!("{path}:{error}");
+        }
+        process::exit(1);
+    }
+
+    let module = optimize::optimize_module(&module);
+    let mut evaluator = Evaluator::new(io::stdout())
+        .with_max_call_depth(MAX_CALL_DEPTH)
+        .with_max_stack(MAX_STACK);
+    if let Err(error) = evaluator.eval_module(&module) {
+        eprintln!("{path}:{error}");
+        for entry in &error.stack {
+            eprintln!("\tat {entry}");
+        }
+        process::exit(1);
+    }
+}
+
+/// Prints the syntax tree of a file in `format`, without expanding macros.
+fn dump(format: Format, path: &str) {
+    let input = match fs::read_to_string(path) {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("{path}: {err}");
+            process::exit(1);
+        }
+    };
+
+    match parse(&input) {
+        Ok(program) => println!("{}", format.render(&program)),
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{path}:{error}");
+            }
+            process::exit(1);
+        }
+    }
+}
+
+/// Formats files in place, or with `--check` only reports the ones that are
+/// not formatted.
+fn fmt(options: &[String]) {
+    let mut config = formatter::Config::default();
+    let mut check = false;
+    let mut paths = vec![];
+
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--check" => check = true,
+            "--indent" | "--line-length" => {
+                let Some(value) = options.next().and_then(|value| value.parse().ok()) else {
+                    eprintln!("{option} needs a number");
+                    process::exit(2);
+                };
+                if option == "--indent" {
+                    config.indent_width = value;
+                } else {
+                    config.line_length = value;
+                }
+            }
+            path => paths.push(path),
+        }
+    }
+
+    if paths.is_empty() {
+        eprintln!("fmt needs at least one file");
+        process::exit(2);
+    }
+
+    let mut failed = false;
+    for path in paths {
+        let input = match fs::read_to_string(path) {
+            Ok(input) => input,
+            Err(err) => {
+                eprintln!("{path}: {err}");
+                failed = true;
+                continue;
+            }
+        };
+
+        let formatted = match formatter::format_source(&input, &config) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("{path}:{error}");
+                }
+                failed = true;
+                continue;
+            }
+        };
+
+        if formatted == input {
+            continue;
+        }
+        if check {
+            eprintln!("{path}: not formatted");
+            failed = true;
+        } else if let Err(err) = fs::write(path, formatted) {
+            eprintln!("{path}: {err}");
+            failed = true;
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
 }
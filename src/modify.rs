@@ -0,0 +1,95 @@
+use crate::ast::{self, ExpressionNode, Fold, Program};
+
+/// Rebuilds the program with every expression passed through `modifier`.
+///
+/// Children are modified before their parent, so the modifier sees a node
+/// whose subexpressions are already rewritten. Macro literals are templates
+/// and are left untouched.
+pub fn modify_program<F>(program: Program, modifier: &mut F) -> Program
+where
+    F: FnMut(ExpressionNode) -> ExpressionNode,
+{
+    Modifier(modifier).fold_program(program)
+}
+
+pub fn modify_expression<F>(expression: ExpressionNode, modifier: &mut F) -> ExpressionNode
+where
+    F: FnMut(ExpressionNode) -> ExpressionNode,
+{
+    Modifier(modifier).fold_expression(expression)
+}
+
+struct Modifier<'m, F>(&'m mut F);
+
+impl<F> Fold for Modifier<'_, F>
+where
+    F: FnMut(ExpressionNode) -> ExpressionNode,
+{
+    fn fold_expression(&mut self, expression: ExpressionNode) -> ExpressionNode {
+        let expression = ast::fold_expression(self, expression);
+        (self.0)(expression)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ast::{ExpressionNode, IntegerLiteral, Node},
+        parse,
+        token::Token,
+    };
+
+    use super::modify_program;
+
+    #[test]
+    fn test_modify_program() {
+        let tests = vec![
+            ("1", "2"),
+            ("1 + 2", "(2 + 2)"),
+            ("-1", "(-2)"),
+            ("let x = 1;", "let x = 2;"),
+            ("fn(x = 1) { return 1; }", "fn(x = 2) return 2;"),
+            ("[1, 2]", "[2, 2]"),
+            ("{1: 1}", "{2: 2}"),
+            (
+                "match (1) { 1 if 1 => 1, _ => 2 }",
+                "match (2) { 1 if 2 => 2, _ => 2 }",
+            ),
+            ("f(1, y: 1)", "f(2, y: 2)"),
+            ("xs[1][..1]", "((xs[2])[(..2)])"),
+            (
+                "infix <+> 3 left = fn(a, b) { 1 };",
+                "infix <+> 3 left = fn(a, b) 2;",
+            ),
+            ("macro(a) { 1 }", "macro(a) 1"),
+            (
+                "try { throw 1; } catch (e) { 1 } finally { 1 }",
+                "try { throw 2; } catch (e) { 2 } finally { 2 }",
+            ),
+            (
+                "fn() { for x in [1] { yield 1; } }",
+                "fn() for x in [2] { yield 2; }",
+            ),
+        ];
+
+        let mut one_to_two = |expression| match expression {
+            ExpressionNode::Integer(int) if int.value == 1 => {
+                ExpressionNode::Integer(IntegerLiteral {
+                    token: Token {
+                        literal: String::from("2"),
+                        ..int.token
+                    },
+                    value: 2,
+                })
+            }
+            expression => expression,
+        };
+
+        for (input, expected) in tests {
+            let program = parse(input).unwrap();
+            let modified = modify_program(program, &mut one_to_two);
+
+            assert_eq!(modified.print_string(), expected, "for {input}");
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    ast::{
+        walk_block, walk_expression, walk_statement, BlockStatement, ExpressionNode, Identifier,
+        LetStatement, Pattern, Program, StatementNode, Visitor,
+    },
+    macro_expansion, parse,
+};
+
+/// A parsed source file with its macros expanded and its imports loaded.
+#[derive(Debug)]
+pub struct Module {
+    /// The path the module was first loaded by, used in diagnostics.
+    pub path: PathBuf,
+    pub program: Program,
+    /// Names bound by `export let`.
+    pub exports: Vec<String>,
+    /// The modules imported by this one, by alias.
+    pub imports: HashMap<String, Rc<Module>>,
+}
+
+/// Loads modules and everything they import, each file only once.
+///
+/// Import paths are relative to the directory of the importing file.
+#[derive(Default)]
+pub struct Loader {
+    cache: HashMap<PathBuf, Rc<Module>>,
+    /// Canonical paths of the files whose imports are being loaded.
+    loading: Vec<PathBuf>,
+}
+
+impl Loader {
+    pub fn new() -> Loader {
+        Loader::default()
+    }
+
+    /// Loads the module at `path`, or returns every error found in it and its imports.
+    pub fn load(&mut self, path: &Path) -> Result<Rc<Module>, Vec<String>> {
+        let canonical = fs::canonicalize(path)
+            .map_err(|e| vec![format!("could not read {}: {}", path.display(), e)])?;
+
+        if let Some(module) = self.cache.get(&canonical) {
+            return Ok(Rc::clone(module));
+        }
+
+        // canonical paths, so that `lib/../a.mk` is reported as the `a.mk` it is
+        if let Some(start) = self.loading.iter().position(|c| *c == canonical) {
+            let cycle: Vec<String> = self.loading[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|path| path.display().to_string())
+                .collect();
+            return Err(vec![format!("import cycle: {}", cycle.join(" -> "))]);
+        }
+
+        self.loading.push(canonical.clone());
+        let module = self.load_uncached(path);
+        self.loading.pop();
+
+        let module = Rc::new(module?);
+        self.cache.insert(canonical, Rc::clone(&module));
+
+        Ok(module)
+    }
+
+    fn load_uncached(&mut self, path: &Path) -> Result<Module, Vec<String>> {
+        let input = fs::read_to_string(path)
+            .map_err(|e| vec![format!("could not read {}: {}", path.display(), e)])?;
+
+        let in_file = |errors: Vec<String>| -> Vec<String> {
+            errors
+                .into_iter()
+                .map(|error| format!("{}:{}", path.display(), error))
+                .collect()
+        };
+
+        let program = parse(input.as_str())
+            .map_err(|errors| in_file(errors.iter().map(|e| e.to_string()).collect()))?;
+        let program = macro_expansion::expand_macros(program).map_err(in_file)?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut errors = vec![];
+        let mut imports = HashMap::new();
+        let mut exports = vec![];
+
+        for stmt in &program.statements {
+            match stmt {
+                StatementNode::Import(import) => match self.load(&dir.join(&import.path.value)) {
+                    Ok(module) => {
+                        imports.insert(import.alias.value.clone(), module);
+                    }
+                    Err(import_errors) => errors.extend(import_errors),
+                },
+                StatementNode::Export(export) => exports.extend(export.binding.name.names()),
+                _ => {}
+            }
+        }
+
+        let mut checker = ExportChecker {
+            imports: &imports,
+            scopes: vec![HashMap::new()],
+            errors: vec![],
+        };
+        checker.visit_program(&program);
+        errors.extend(in_file(checker.errors));
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(Module {
+            path: path.to_path_buf(),
+            program,
+            exports,
+            imports,
+        })
+    }
+}
+
+/// Reports each `alias.name` whose module does not export `name`, skipping
+/// the places where a local binding shadows the alias.
+struct ExportChecker<'a> {
+    imports: &'a HashMap<String, Rc<Module>>,
+    /// Every binding in scope, `true` for the aliases of imported modules.
+    scopes: Vec<HashMap<&'a str, bool>>,
+    errors: Vec<String>,
+}
+
+impl<'a> Visitor<'a> for ExportChecker<'a> {
+    fn visit_statement(&mut self, stmt: &'a StatementNode) {
+        match stmt {
+            StatementNode::Let(let_stmt) => self.check_let(let_stmt),
+            StatementNode::Infix(infix) => self.check_let(&infix.binding),
+            StatementNode::Export(export) => self.check_let(&export.binding),
+            StatementNode::Import(import) => self.declare(&import.alias, true),
+            StatementNode::Enum(declaration) => {
+                for variant in &declaration.variants {
+                    self.declare(&variant.name, false);
+                }
+            }
+            StatementNode::For(for_stmt) => {
+                self.visit_expression(&for_stmt.iterable);
+                self.scoped(|checker| {
+                    checker.declare(&for_stmt.variable, false);
+                    walk_block(checker, &for_stmt.body);
+                });
+            }
+            StatementNode::Try(try_stmt) => {
+                self.visit_block(&try_stmt.body);
+                if let Some(catch) = &try_stmt.catch {
+                    self.scoped(|checker| {
+                        checker.declare(&catch.parameter, false);
+                        walk_block(checker, &catch.body);
+                    });
+                }
+                if let Some(finally) = &try_stmt.finally {
+                    self.visit_block(finally);
+                }
+            }
+            _ => walk_statement(self, stmt),
+        }
+    }
+
+    fn visit_block(&mut self, block: &'a BlockStatement) {
+        self.scoped(|checker| walk_block(checker, block));
+    }
+
+    fn visit_expression(&mut self, expression: &'a ExpressionNode) {
+        match expression {
+            ExpressionNode::Member(member) => {
+                walk_expression(self, expression);
+                let ExpressionNode::IdentifierNode(alias) = member.object.as_ref() else {
+                    return;
+                };
+                if !self.is_alias(&alias.value) {
+                    return;
+                }
+                let exported = self
+                    .imports
+                    .get(&alias.value)
+                    .map(|module| module.exports.contains(&member.property.value));
+                if exported == Some(false) {
+                    self.errors.push(format!(
+                        "{}: module `{}` does not export `{}`",
+                        member.property.token.span, alias.value, member.property.value
+                    ));
+                }
+            }
+            ExpressionNode::Function(func) => self.scoped(|checker| {
+                for param in &func.parameters {
+                    if let Some(default) = &param.default {
+                        checker.visit_expression(default);
+                    }
+                    checker.declare(&param.name, false);
+                }
+                walk_block(checker, &func.body);
+            }),
+            ExpressionNode::Match(match_exp) => {
+                self.visit_expression(&match_exp.subject);
+                for arm in &match_exp.arms {
+                    self.scoped(|checker| {
+                        checker.declare_pattern(&arm.pattern);
+                        if let Some(guard) = &arm.guard {
+                            checker.visit_expression(guard);
+                        }
+                        checker.visit_expression(&arm.body);
+                    });
+                }
+            }
+            _ => walk_expression(self, expression),
+        }
+    }
+}
+
+impl<'a> ExportChecker<'a> {
+    fn check_let(&mut self, let_stmt: &'a LetStatement) {
+        // a function sees its own name
+        if let Some(value @ ExpressionNode::Function(_)) = &let_stmt.value {
+            self.declare_pattern(&let_stmt.name);
+            self.visit_expression(value);
+            return;
+        }
+
+        if let Some(value) = &let_stmt.value {
+            self.visit_expression(value);
+        }
+        self.declare_pattern(&let_stmt.name);
+    }
+
+    /// Runs `check` in a new scope, whose bindings end with it.
+    fn scoped(&mut self, check: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        check(self);
+        self.scopes.pop();
+    }
+
+    fn declare_pattern(&mut self, pattern: &'a Pattern) {
+        for name in pattern.bindings() {
+            self.declare(name, false);
+        }
+    }
+
+    fn declare(&mut self, name: &'a Identifier, alias: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(&name.value, alias);
+        }
+    }
+
+    /// Whether the innermost binding of `name` is an import alias.
+    fn is_alias(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf, rc::Rc};
+
+    use super::Loader;
+
+    /// Writes `files` into a fresh directory under the system temp dir.
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("module-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        for (path, input) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, input).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn test_load_modules_once() {
+        let dir = write_files(
+            "cache",
+            &[
+                (
+                    "main.mk",
+                    "import \"lib/a.mk\" as a; import \"lib/b.mk\" as b; a.x + b.y;",
+                ),
+                ("lib/a.mk", "import \"shared.mk\" as s; export let x = s.z;"),
+                ("lib/b.mk", "import \"shared.mk\" as s; export let y = s.z;"),
+                ("lib/shared.mk", "export let z = 1; let hidden = 2;"),
+            ],
+        );
+
+        let module = Loader::new().load(&dir.join("main.mk")).unwrap();
+
+        let a = &module.imports["a"];
+        let b = &module.imports["b"];
+        assert_eq!(a.exports, vec!["x"]);
+        assert_eq!(b.exports, vec!["y"]);
+        assert!(Rc::ptr_eq(&a.imports["s"], &b.imports["s"]));
+        assert_eq!(a.imports["s"].exports, vec!["z"]);
+    }
+
+    #[test]
+    fn test_local_bindings_shadow_imports() {
+        let dir = write_files(
+            "shadow",
+            &[
+                (
+                    "main.mk",
+                    "import \"lib.mk\" as m;
+                    let f = fn(m) { m.hidden };
+                    let g = fn() { for m in [{}] { m.hidden; } };
+                    match (1) { m => m.hidden };
+                    try { throw 1; } catch (m) { m.hidden }
+                    let m = {}; m.hidden;",
+                ),
+                ("lib.mk", "export let x = 1; let hidden = 2;"),
+            ],
+        );
+
+        let result = Loader::new().load(&dir.join("main.mk"));
+
+        assert!(result.is_ok(), "unexpected errors: {:?}", result.err());
+    }
+
+    #[test]
+    fn test_module_errors() {
+        let dir = write_files(
+            "errors",
+            &[
+                ("cycle.mk", "import \"lib/a.mk\" as a;"),
+                ("lib/a.mk", "import \"../lib/b.mk\" as b;"),
+                ("lib/b.mk", "import \"a.mk\" as a;"),
+                ("private.mk", "import \"lib/c.mk\" as c;\nc.hidden;"),
+                ("lib/c.mk", "let hidden = 1;"),
+                (
+                    "nested.mk",
+                    "import \"lib/c.mk\" as c;\nlet f = fn(x) { x + c.hidden };",
+                ),
+                ("broken.mk", "import \"lib/d.mk\" as d;"),
+                ("lib/d.mk", "let = 1;"),
+                ("missing.mk", "import \"nope.mk\" as n;"),
+            ],
+        );
+        let canonical = fs::canonicalize(&dir).unwrap();
+        let path = |name: &str| dir.join(name).display().to_string();
+        let canonical_path = |name: &str| canonical.join(name).display().to_string();
+
+        let tests = vec![
+            (
+                "cycle.mk",
+                format!(
+                    "import cycle: {} -> {} -> {}",
+                    canonical_path("lib/a.mk"),
+                    canonical_path("lib/b.mk"),
+                    canonical_path("lib/a.mk")
+                ),
+            ),
+            (
+                "private.mk",
+                format!(
+                    "{}:2:3-9: module `c` does not export `hidden`",
+                    path("private.mk")
+                ),
+            ),
+            (
+                "nested.mk",
+                format!(
+                    "{}:2:23-29: module `c` does not export `hidden`",
+                    path("nested.mk")
+                ),
+            ),
+            (
+                "broken.mk",
+                format!(
+                    "{}:1:5-6: expected a pattern, got Assign instead",
+                    path("lib/d.mk")
+                ),
+            ),
+            ("missing.mk", format!("could not read {}", path("nope.mk"))),
+        ];
+
+        for (file, expected) in tests {
+            let errors = Loader::new().load(&dir.join(file)).unwrap_err();
+
+            assert!(
+                errors[0].starts_with(&expected),
+                "expected {:?} for {}, got = {:?}",
+                expected,
+                file,
+                errors
+            );
+        }
+    }
+}
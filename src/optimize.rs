@@ -0,0 +1,599 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    ast::{
+        self, BooleanLiteral, ExpressionNode, ExpressionStatement, Fold, IfExpression,
+        InfixExpression, IntegerLiteral, MatchExpression, NullLiteral, Pattern, PrefixExpression,
+        Program, StatementNode, Visitor,
+    },
+    infer::{self, Ty},
+    module::Module,
+    token::{Span, Token, TokenKind},
+};
+
+/// Folds constant integer and boolean expressions, so `2 * 3 + 1` becomes
+/// `7`, and drops operations that can not change a value, like `x * 1` on an
+/// integer, `!!x` on a boolean, `if (true)` and a match guard that is always
+/// `true`.
+///
+/// Nothing that can fail at runtime is folded away: division by zero and
+/// overflowing arithmetic are kept, and so is `x * 1` when `x` may not be an
+/// integer. Neither is a result that no literal can write, like `i64::MIN`.
+/// Macro literals are templates and are left as they are.
+///
+/// A name is known to be an integer or a boolean when type inference says so
+/// for its top-level `let`, it is bound nowhere else and never assigned, and
+/// its value can not be null, which inference allows for every type. Only
+/// the uses after the `let` count on it.
+pub fn optimize_program(program: Program) -> Program {
+    let inference = infer::infer_program(&program);
+    let mut binders = Binders::default();
+    binders.visit_program(&program);
+
+    let candidates = match inference.errors.is_empty() {
+        true => inference
+            .bindings
+            .into_iter()
+            .filter(|(name, scheme)| {
+                scheme.vars.is_empty()
+                    && matches!(scheme.ty, Ty::Int | Ty::Bool)
+                    && binders.counts.get(name) == Some(&1)
+            })
+            .map(|(name, scheme)| (name, scheme.ty))
+            .collect(),
+        false => HashMap::new(),
+    };
+
+    Optimizer {
+        candidates,
+        known: HashMap::new(),
+    }
+    .fold_program(program)
+}
+
+/// Optimizes a loaded module and the modules it imports, a module imported
+/// more than once stays a single module.
+pub fn optimize_module(module: &Rc<Module>) -> Rc<Module> {
+    fn optimize(module: &Rc<Module>, done: &mut HashMap<*const Module, Rc<Module>>) -> Rc<Module> {
+        if let Some(optimized) = done.get(&Rc::as_ptr(module)) {
+            return Rc::clone(optimized);
+        }
+
+        let imports = module
+            .imports
+            .iter()
+            .map(|(alias, import)| (alias.clone(), optimize(import, done)))
+            .collect();
+        let optimized = Rc::new(Module {
+            path: module.path.clone(),
+            program: optimize_program(module.program.clone()),
+            exports: module.exports.clone(),
+            imports,
+        });
+        done.insert(Rc::as_ptr(module), Rc::clone(&optimized));
+
+        optimized
+    }
+
+    optimize(module, &mut HashMap::new())
+}
+
+struct Optimizer {
+    /// Top-level bindings whose inferred type is `int` or `bool`.
+    candidates: HashMap<String, Ty>,
+    /// The candidates whose `let` has been passed and whose value is never null.
+    known: HashMap<String, Ty>,
+}
+
+impl Fold for Optimizer {
+    fn fold_program(&mut self, program: Program) -> Program {
+        let mut statements = vec![];
+
+        for stmt in program.statements {
+            let stmt = self.fold_statement(stmt);
+            if let StatementNode::Let(let_stmt)
+            | StatementNode::Export(ast::ExportStatement {
+                binding: let_stmt, ..
+            }) = &stmt
+            {
+                if let (Pattern::Binding(name), Some(value)) = (&let_stmt.name, &let_stmt.value) {
+                    if let Some(ty) = self.candidates.get(&name.value) {
+                        if self.is_never_null(value) {
+                            self.known.insert(name.value.clone(), ty.clone());
+                        }
+                    }
+                }
+            }
+            statements.push(stmt);
+        }
+
+        Program { statements }
+    }
+
+    fn fold_expression(&mut self, expression: ExpressionNode) -> ExpressionNode {
+        match ast::fold_expression(self, expression) {
+            ExpressionNode::Prefix(prefix) => self.simplify_prefix(prefix),
+            ExpressionNode::Infix(infix) => self.simplify_infix(infix),
+            ExpressionNode::Match(match_exp) => simplify_match(match_exp),
+            ExpressionNode::If(if_exp) => simplify_if(if_exp),
+            expression => expression,
+        }
+    }
+}
+
+/// Counts how often each name is bound or assigned anywhere in a program.
+#[derive(Default)]
+struct Binders {
+    counts: HashMap<String, usize>,
+}
+
+impl Binders {
+    fn count(&mut self, name: &str) {
+        *self.counts.entry(name.to_string()).or_default() += 1;
+    }
+}
+
+impl<'a> Visitor<'a> for Binders {
+    fn visit_statement(&mut self, stmt: &'a StatementNode) {
+        match stmt {
+            StatementNode::For(for_stmt) => self.count(&for_stmt.variable.value),
+            StatementNode::Try(try_stmt) => {
+                if let Some(catch) = &try_stmt.catch {
+                    self.count(&catch.parameter.value);
+                }
+            }
+            StatementNode::Import(import) => self.count(&import.alias.value),
+            StatementNode::Struct(declaration) => self.count(&declaration.name.value),
+            StatementNode::Enum(declaration) => {
+                for variant in &declaration.variants {
+                    self.count(&variant.name.value);
+                }
+            }
+            _ => {}
+        }
+        ast::walk_statement(self, stmt);
+    }
+
+    fn visit_expression(&mut self, expression: &'a ExpressionNode) {
+        match expression {
+            ExpressionNode::Function(func) => {
+                for param in &func.parameters {
+                    self.count(&param.name.value);
+                }
+            }
+            ExpressionNode::Macro(macro_lit) => {
+                for param in &macro_lit.parameters {
+                    self.count(&param.value);
+                }
+            }
+            ExpressionNode::Assign(assign) => self.count(&assign.name.value),
+            _ => {}
+        }
+        ast::walk_expression(self, expression);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'a Pattern) {
+        for name in pattern.names() {
+            self.count(&name);
+        }
+    }
+}
+
+impl Optimizer {
+    fn simplify_prefix(&self, prefix: PrefixExpression) -> ExpressionNode {
+        let PrefixExpression {
+            token,
+            span,
+            operator,
+            right,
+        } = prefix;
+
+        match (operator.as_str(), *right) {
+            ("-", ExpressionNode::Integer(int)) if int.value.checked_neg().is_some() => {
+                integer(-int.value, span)
+            }
+            ("!", ExpressionNode::Boolean(value)) => boolean(!value.value, span),
+            ("!", ExpressionNode::Prefix(inner))
+                if inner.operator == "!" && self.is_bool(&inner.right) =>
+            {
+                *inner.right
+            }
+            (_, right) => ExpressionNode::Prefix(PrefixExpression {
+                token,
+                span,
+                operator,
+                right: Box::new(right),
+            }),
+        }
+    }
+
+    fn simplify_infix(&self, infix: InfixExpression) -> ExpressionNode {
+        let folded = match (&*infix.left, &*infix.right) {
+            (ExpressionNode::Integer(left), ExpressionNode::Integer(right)) => {
+                fold_integers(left.value, &infix.operator, right.value, infix.span)
+            }
+            (ExpressionNode::Boolean(left), ExpressionNode::Boolean(right)) => {
+                match infix.operator.as_str() {
+                    "==" => Some(boolean(left.value == right.value, infix.span)),
+                    "!=" => Some(boolean(left.value != right.value, infix.span)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(folded) = folded {
+            return folded;
+        }
+
+        let InfixExpression {
+            token,
+            span,
+            left,
+            operator,
+            right,
+        } = infix;
+
+        match (*left, operator.as_str(), *right) {
+            (left, "*" | "/", right) if is_int_literal(&right, 1) && self.is_int(&left) => left,
+            (left, "*", right) if is_int_literal(&left, 1) && self.is_int(&right) => right,
+            (left, "+" | "-", right) if is_int_literal(&right, 0) && self.is_int(&left) => left,
+            (left, "+", right) if is_int_literal(&left, 0) && self.is_int(&right) => right,
+            (left, _, right) => ExpressionNode::Infix(InfixExpression {
+                token,
+                span,
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+            }),
+        }
+    }
+
+    /// Whether the expression is an integer whenever it does not fail itself,
+    /// since only integers can be negated, subtracted, multiplied, divided or
+    /// added to an integer.
+    fn is_int(&self, expression: &ExpressionNode) -> bool {
+        match expression {
+            ExpressionNode::Integer(_) => true,
+            ExpressionNode::IdentifierNode(ident) => self.known.get(&ident.value) == Some(&Ty::Int),
+            ExpressionNode::Prefix(prefix) => prefix.operator == "-",
+            ExpressionNode::Infix(infix) => match infix.operator.as_str() {
+                "-" | "*" | "/" => true,
+                "+" => self.is_int(&infix.left) || self.is_int(&infix.right),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether the expression is a boolean whenever it does not fail itself.
+    fn is_bool(&self, expression: &ExpressionNode) -> bool {
+        match expression {
+            ExpressionNode::Boolean(_) => true,
+            ExpressionNode::IdentifierNode(ident) => {
+                self.known.get(&ident.value) == Some(&Ty::Bool)
+            }
+            ExpressionNode::Prefix(prefix) => prefix.operator == "!",
+            ExpressionNode::Infix(infix) => {
+                matches!(infix.operator.as_str(), "==" | "!=" | "<" | ">")
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the expression has a value other than null whenever it does
+    /// not fail, like the value of an operator that is not `??`.
+    fn is_never_null(&self, expression: &ExpressionNode) -> bool {
+        match expression {
+            ExpressionNode::Integer(_) | ExpressionNode::Boolean(_) | ExpressionNode::Prefix(_) => {
+                true
+            }
+            ExpressionNode::IdentifierNode(ident) => self.known.contains_key(&ident.value),
+            ExpressionNode::Infix(infix) => {
+                infix.token.kind != TokenKind::Operator && infix.operator != "??"
+            }
+            _ => false,
+        }
+    }
+}
+
+/// `None` when the result is not known before running, as for division by
+/// zero or an overflow, or when it has no literal.
+fn fold_integers(left: i64, operator: &str, right: i64, span: Span) -> Option<ExpressionNode> {
+    let value = match operator {
+        "+" => left.checked_add(right)?,
+        "-" => left.checked_sub(right)?,
+        "*" => left.checked_mul(right)?,
+        "/" => left.checked_div(right)?,
+        "<" => return Some(boolean(left < right, span)),
+        ">" => return Some(boolean(left > right, span)),
+        "==" => return Some(boolean(left == right, span)),
+        "!=" => return Some(boolean(left != right, span)),
+        _ => return None,
+    };
+
+    // the digits of `i64::MIN` overflow before its `-` applies
+    (value != i64::MIN).then(|| integer(value, span))
+}
+
+/// Keeps only the block a constant condition picks, or just the expression
+/// in it when that is all the block holds.
+fn simplify_if(if_exp: IfExpression) -> ExpressionNode {
+    let ExpressionNode::Boolean(condition) = if_exp.condition.as_ref() else {
+        return ExpressionNode::If(if_exp);
+    };
+    let taken = match condition.value {
+        true => Some(if_exp.consequence),
+        false => if_exp.alternative,
+    };
+
+    let Some(mut block) = taken else {
+        return null(if_exp.span);
+    };
+    match block.statements.as_mut_slice() {
+        [] => null(if_exp.span),
+        [StatementNode::Expression(ExpressionStatement {
+            expression: Some(value),
+            ..
+        })] => std::mem::replace(value, null(if_exp.span)),
+        _ => ExpressionNode::If(IfExpression {
+            condition: Box::new(boolean(true, if_exp.condition.span())),
+            consequence: block,
+            alternative: None,
+            ..if_exp
+        }),
+    }
+}
+
+/// Drops guards that always hold and arms whose guard never does.
+fn simplify_match(mut match_exp: MatchExpression) -> ExpressionNode {
+    match_exp.arms.retain(
+        |arm| !matches!(arm.guard, Some(ExpressionNode::Boolean(ref guard)) if !guard.value),
+    );
+
+    for arm in &mut match_exp.arms {
+        if matches!(arm.guard, Some(ExpressionNode::Boolean(ref guard)) if guard.value) {
+            arm.guard = None;
+        }
+    }
+
+    ExpressionNode::Match(match_exp)
+}
+
+fn is_int_literal(expression: &ExpressionNode, value: i64) -> bool {
+    matches!(expression, ExpressionNode::Integer(int) if int.value == value)
+}
+
+fn integer(value: i64, span: Span) -> ExpressionNode {
+    ExpressionNode::Integer(IntegerLiteral {
+        token: Token {
+            kind: TokenKind::Int,
+            literal: value.to_string(),
+            span,
+        },
+        value,
+    })
+}
+
+fn null(span: Span) -> ExpressionNode {
+    ExpressionNode::Null(NullLiteral {
+        token: Token {
+            kind: TokenKind::Null,
+            literal: String::from("null"),
+            span,
+        },
+    })
+}
+
+fn boolean(value: bool, span: Span) -> ExpressionNode {
+    let (kind, literal) = match value {
+        true => (TokenKind::True, "true"),
+        false => (TokenKind::False, "false"),
+    };
+
+    ExpressionNode::Boolean(BooleanLiteral {
+        token: Token {
+            kind,
+            literal: literal.to_string(),
+            span,
+        },
+        value,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, rc::Rc};
+
+    use crate::{
+        ast::{ExpressionNode, Node, StatementNode},
+        module::Loader,
+        parse,
+    };
+
+    use super::{optimize_module, optimize_program};
+
+    #[test]
+    fn test_optimize_program() {
+        let tests = vec![
+            ("2 * 3 + 1", "7"),
+            ("(2 + 3) * x", "(5 * x)"),
+            ("1 + 2 * x", "(1 + (2 * x))"),
+            ("-(2 - 5)", "3"),
+            ("10 / 3; 7 - 10", "3-3"),
+            ("1 < 2 == true; 3 > 4; 2 == 2; 2 != 2", "truefalsetruefalse"),
+            ("true == false; !true; true != (1 > 0)", "falsefalsefalse"),
+            ("1 == true; true < false", "(1 == true)(true < false)"),
+            ("!!(a < b); !!!x; !!true", "(a < b)(!x)true"),
+            ("!!x; !-x", "(!(!x))(!(-x))"),
+            ("x * 1; s + 0; 1 * f(x)", "(x * 1)(s + 0)(1 * f(x))"),
+            ("-x * 1; 1 * (a - b); (a + 1) / 1", "(-x)(a - b)(a + 1)"),
+            ("0 + n * 2; (a * b) - 0", "(n * 2)(a * b)"),
+            (
+                "let x = fn() { return 2 * 21; };",
+                "let x = fn() return 42;;",
+            ),
+            ("f(1 + 1, y: [2 * 2])", "f(2, y: [4])"),
+            (
+                "match (x) { n if 1 < 2 => n, _ if false => 0, _ => 1 }",
+                "match (x) { n => n, _ => 1 }",
+            ),
+            ("macro(a) { 1 + 2 }", "macro(a) (1 + 2)"),
+            (
+                "if (1 < 2) { 10 } else { 20 }; if (x) { 1 + 1 }",
+                "10if (x) { 2 }",
+            ),
+            (
+                "if (false) { 1 }; if (true) {}; !if (true) { false }",
+                "nullnulltrue",
+            ),
+            (
+                "if (false) { 1 } else if (true) { f(); 2 } else { 3 }",
+                "if (true) { f()2 }",
+            ),
+            (
+                "let f = fn() { if (true) { return 1; } else { 2 } };",
+                "let f = fn() if (true) { return 1; };",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let program = optimize_program(parse(input).unwrap());
+
+            assert_eq!(program.print_string(), expected, "for {input}");
+        }
+    }
+
+    #[test]
+    fn test_inferred_types() {
+        let tests = vec![
+            ("let x = 5; x * 1; 1 * x + 0", "let x = 5;xx"),
+            (
+                "let x = 2 * 3; let y = x - 1; y / 1",
+                "let x = 6;let y = (x - 1);y",
+            ),
+            (
+                "let b = 1 < 2; let c = !b; !!c",
+                "let b = true;let c = (!b);c",
+            ),
+            (
+                "export const n = 1; let f = fn() { n * 1 };",
+                "export const n = 1;let f = fn() n;",
+            ),
+            // only after the `let`, and only while nothing else binds the name
+            ("x * 1; let x = 5;", "(x * 1)let x = 5;"),
+            (
+                "let x = 5; let f = fn(x) { x * 1 };",
+                "let x = 5;let f = fn(x) (x * 1);",
+            ),
+            ("let x = 5; x = 6; x * 1", "let x = 5;(x = 6)(x * 1)"),
+            (
+                "let x = 5; for x in xs { x * 1 }",
+                "let x = 5;for x in xs { (x * 1) }",
+            ),
+            // inference allows null for an int
+            ("let x = null; x * 1", "let x = null;(x * 1)"),
+            (
+                "let f = fn() { 1 }; let n = f(); n * 1",
+                "let f = fn() 1;let n = f();(n * 1)",
+            ),
+            (
+                "let b = if (c) { true }; !!b",
+                "let b = if (c) { true };(!(!b))",
+            ),
+            ("let s = \"a\"; s + 0", "let s = \"a\";(s + 0)"),
+            (
+                "let x = 5; x * 1; let y = x + \"a\";",
+                "let x = 5;(x * 1)let y = (x + \"a\");",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let program = optimize_program(parse(input).unwrap());
+
+            assert_eq!(program.print_string(), expected, "for {input}");
+        }
+    }
+
+    #[test]
+    fn test_optimize_module() {
+        let dir = std::env::temp_dir().join(format!("optimize-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("main.mk"),
+            "import \"a.mk\" as a; import \"b.mk\" as b; a.x + 1 * 2;",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("a.mk"),
+            "import \"c.mk\" as c; export let x = 2 + 2;",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b.mk"),
+            "import \"c.mk\" as c; export let y = c.z;",
+        )
+        .unwrap();
+        fs::write(dir.join("c.mk"), "export let z = 3 * 3;").unwrap();
+
+        let module = Loader::new().load(&dir.join("main.mk")).unwrap();
+        let optimized = optimize_module(&module);
+
+        assert_eq!(
+            optimized.program.print_string(),
+            "import \"a.mk\" as a;import \"b.mk\" as b;(a.x + 2)"
+        );
+        let (a, b) = (&optimized.imports["a"], &optimized.imports["b"]);
+        assert_eq!(
+            a.program.print_string(),
+            "import \"c.mk\" as c;export let x = 4;"
+        );
+        assert!(Rc::ptr_eq(&a.imports["c"], &b.imports["c"]));
+        assert_eq!(a.imports["c"].program.print_string(), "export let z = 9;");
+    }
+
+    #[test]
+    fn test_runtime_errors_are_kept() {
+        let tests = vec![
+            ("10 / 0", "(10 / 0)"),
+            ("10 / (5 - 5)", "(10 / 0)"),
+            ("(1 / 0) * 1", "(1 / 0)"),
+            ("0 * (1 / 0)", "(0 * (1 / 0))"),
+            ("9223372036854775807 + 1", "(9223372036854775807 + 1)"),
+            ("-9223372036854775807 - 2", "(-9223372036854775807 - 2)"),
+            ("-9223372036854775807 - 1", "(-9223372036854775807 - 1)"),
+            (
+                "(0 - 4611686018427387904) * 2",
+                "(-4611686018427387904 * 2)",
+            ),
+            ("3037000500 * 3037000500", "(3037000500 * 3037000500)"),
+            (
+                "\"a\" * 1; [1] + 0; f() / 1",
+                "(\"a\" * 1)([1] + 0)(f() / 1)",
+            ),
+            ("if (true) { 1 / 0 } else { 2 }", "(1 / 0)"),
+        ];
+
+        for (input, expected) in tests {
+            let program = optimize_program(parse(input).unwrap());
+
+            assert_eq!(program.print_string(), expected, "for {input}");
+            assert!(parse(expected).is_ok(), "{expected} does not parse");
+        }
+    }
+
+    #[test]
+    fn test_folded_literal_spans() {
+        let program = optimize_program(parse("let x = (2 + 3) * 4;").unwrap());
+
+        let StatementNode::Let(let_stmt) = &program.statements[0] else {
+            panic!("expected a let statement, got {:?}", program.statements[0]);
+        };
+        let Some(ExpressionNode::Integer(int)) = &let_stmt.value else {
+            panic!("expected an integer, got {:?}", let_stmt.value);
+        };
+        assert_eq!(int.value, 20);
+        assert_eq!(int.token.literal, "20");
+        assert_eq!(int.token.span.to_string(), "1:9-20");
+    }
+}
@@ -1,35 +1,126 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
 
 use crate::{
     ast::{
-        ExpressionNode, ExpressionStatement, Identifier, IntegerLiteral, LetStatement, Program, ReturnStatement, StatementNode
+        self, Argument, ArrayLiteral, ArrayPattern, ArrayType, AssignExpression, Associativity,
+        BlockStatement, BooleanLiteral, CallExpression, CatchClause, EnumDeclaration,
+        ExportStatement, ExpressionNode, ExpressionStatement, Fold, ForStatement, FunctionLiteral,
+        FunctionType, HashLiteral, HashPattern, HashType, Identifier, IfExpression,
+        ImportStatement, IndexExpression, InfixDeclaration, InfixExpression, IntegerLiteral,
+        LetStatement, MacroLiteral, MatchArm, MatchExpression, MemberExpression, Node, NullLiteral,
+        Parameter, Pattern, PrefixExpression, Program, RangeExpression, ReturnStatement,
+        StatementNode, StringLiteral, StructDeclaration, StructLiteral, ThrowStatement,
+        TryStatement, TypeExpr, Variant, VariantPattern, YieldStatement,
     },
     lexer::Lexer,
-    token::{Token, TokenKind},
+    token::{Comment, Position, Span, Token, TokenKind},
 };
 
-struct Parser {
+/// A syntax error and the source range of the token it was found at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.span, self.message)
+    }
+}
+
+/// How deeply expressions, blocks, patterns and types may nest before parsing gives up.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// How many operators, calls, member accesses and indexes may be chained, counting
+/// the chains the operands are nested in.
+///
+/// A chain nests its tree as deeply as real nesting does, but is far more common
+/// in ordinary code, so it gets its own, larger limit. The passes over a tree at
+/// both limits need more than the 2 MiB stack of a spawned thread in a debug
+/// build, the binary runs them on a bigger one.
+pub const DEFAULT_MAX_CHAIN: usize = 1024;
+
+pub struct Parser {
     lexer: Lexer,
     cur_token: Token,
     peek_token: Token,
-    errors: Vec<String>,
+    errors: Vec<ParseError>,
+    depth: usize,
+    max_depth: usize,
+    /// Chain links around the current token, see [`DEFAULT_MAX_CHAIN`].
+    chain: usize,
+    max_chain: usize,
+    too_deep: bool,
     prefix_parse_fns: HashMap<TokenKind, PrefixParseFn>,
     infix_parse_fns: HashMap<TokenKind, InfixParseFn>,
+    /// Operators declared with `infix` so far.
+    operators: HashMap<String, (PredenceLevel, Associativity)>,
+    /// Enum variants declared anywhere in the program, a bare name in a
+    /// match pattern matches one of them.
+    variants: HashSet<String>,
+    /// Function literals being parsed around the current token, `yield` needs one.
+    functions: usize,
+    /// Set in the head of a `for`, where `xs {` starts the body, not a struct literal.
+    no_struct_literal: bool,
 }
 
 type PrefixParseFn = fn(parser: &mut Parser) -> Option<ExpressionNode>;
 type InfixParseFn = fn(parser: &mut Parser, exp: ExpressionNode) -> Option<ExpressionNode>;
 
+#[derive(PartialEq, PartialOrd, Clone, Copy)]
 enum PredenceLevel {
     Lowest = 0,
-    Equals = 1,       // ==
-    LessGreather = 2, //> or <
-    Sum = 3,
-    Product = 4,
-    Prefix = 5,
-    Call = 6,
+    Assign = 1,       // =
+    Range = 2,        // .. or ..=
+    Coalesce = 3,     // ??
+    Equals = 4,       // ==
+    LessGreather = 5, //> or <
+    Sum = 6,
+    Product = 7,
+    Prefix = 8,
+    Call = 9, // also . and ?.
+    Index = 10,
 }
 
+impl PredenceLevel {
+    /// The level an `infix` declaration asks for, `1` binds like `==` and `4` like `*`.
+    fn declared(precedence: u8) -> Option<PredenceLevel> {
+        match precedence {
+            1 => Some(PredenceLevel::Equals),
+            2 => Some(PredenceLevel::LessGreather),
+            3 => Some(PredenceLevel::Sum),
+            4 => Some(PredenceLevel::Product),
+            _ => None,
+        }
+    }
+
+    /// One level looser, the right operand of a right associative operator is
+    /// parsed at it so that the same operator nests to the right.
+    fn looser(self) -> PredenceLevel {
+        match self {
+            PredenceLevel::Index => PredenceLevel::Call,
+            PredenceLevel::Call => PredenceLevel::Prefix,
+            PredenceLevel::Prefix => PredenceLevel::Product,
+            PredenceLevel::Product => PredenceLevel::Sum,
+            PredenceLevel::Sum => PredenceLevel::LessGreather,
+            PredenceLevel::LessGreather => PredenceLevel::Equals,
+            PredenceLevel::Equals => PredenceLevel::Coalesce,
+            PredenceLevel::Coalesce => PredenceLevel::Range,
+            PredenceLevel::Range => PredenceLevel::Assign,
+            PredenceLevel::Assign | PredenceLevel::Lowest => PredenceLevel::Lowest,
+        }
+    }
+}
+
+/// Symbols an `infix` declaration can not take over.
+const BUILTIN_OPERATORS: [&str; 14] = [
+    "=", "==", "!=", "=>", "->", "+", "-", "!", "*", "/", "<", ">", "?", "??",
+];
+
 impl Parser {
     pub fn new(lexer: Lexer) -> Parser {
         let mut parser = Parser {
@@ -37,126 +128,1772 @@ impl Parser {
             cur_token: Default::default(),
             peek_token: Default::default(),
             errors: vec![],
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            chain: 0,
+            max_chain: DEFAULT_MAX_CHAIN,
+            too_deep: false,
             prefix_parse_fns: HashMap::new(),
             infix_parse_fns: HashMap::new(),
+            operators: HashMap::new(),
+            variants: HashSet::new(),
+            functions: 0,
+            no_struct_literal: false,
+        };
+
+        parser.register_prefix(TokenKind::Ident, Self::parse_identifier);
+        parser.register_prefix(TokenKind::Int, Self::parse_integer_literal);
+        parser.register_prefix(TokenKind::True, Self::parse_boolean);
+        parser.register_prefix(TokenKind::False, Self::parse_boolean);
+        parser.register_prefix(TokenKind::Null, Self::parse_null);
+        parser.register_prefix(TokenKind::String, Self::parse_string_literal);
+        parser.register_prefix(TokenKind::Bang, Self::parse_prefix_expression);
+        parser.register_prefix(TokenKind::Minus, Self::parse_prefix_expression);
+        parser.register_prefix(TokenKind::Lparen, Self::parse_grouped_expression);
+        parser.register_prefix(TokenKind::Match, Self::parse_match_expression);
+        parser.register_prefix(TokenKind::If, Self::parse_if_expression);
+        parser.register_prefix(TokenKind::Lbracket, Self::parse_array_literal);
+        parser.register_prefix(TokenKind::Lbrace, Self::parse_hash_literal);
+        parser.register_prefix(TokenKind::Function, Self::parse_function_literal);
+        parser.register_prefix(TokenKind::Macro, Self::parse_macro_literal);
+        parser.register_prefix(TokenKind::DotDot, Self::parse_open_range);
+        parser.register_prefix(TokenKind::DotDotEq, Self::parse_open_range);
+
+        parser.register_infix(TokenKind::Plus, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::DotDot, Self::parse_range_expression);
+        parser.register_infix(TokenKind::DotDotEq, Self::parse_range_expression);
+        parser.register_infix(TokenKind::Lbracket, Self::parse_index_expression);
+        parser.register_infix(TokenKind::Minus, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Slash, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Asteriks, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Eq, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::NotEq, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Lt, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Gt, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Lparen, Self::parse_call_expression);
+        parser.register_infix(TokenKind::Operator, Self::parse_operator_expression);
+        parser.register_infix(TokenKind::Dot, Self::parse_member_expression);
+        parser.register_infix(TokenKind::QuestionDot, Self::parse_optional_chain);
+        parser.register_infix(TokenKind::QuestionQuestion, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Assign, Self::parse_assign_expression);
+
+        parser.next_token();
+        parser.next_token();
+
+        parser
+    }
+
+    /// Limits nesting so that hostile input fails with an error instead of overflowing the stack.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Parser {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Limits chains like `a + a + ...` the same way, see [`DEFAULT_MAX_CHAIN`].
+    pub fn with_max_chain(mut self, max_chain: usize) -> Parser {
+        self.max_chain = max_chain;
+        self
+    }
+
+    /// Adds variants declared outside the input, like on earlier lines of the REPL.
+    pub fn with_variants(mut self, variants: HashSet<String>) -> Parser {
+        self.variants.extend(variants);
+        self
+    }
+
+    /// Runs `parse` one nesting level deeper, or reports an error past `max_depth`.
+    ///
+    /// Hitting the limit skips the rest of the input and silences later errors,
+    /// they would only be fallout of the unfinished nodes.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Parser) -> Option<T>) -> Option<T> {
+        if !self.descend() {
+            return None;
+        }
+
+        let node = parse(self);
+        self.depth -= 1;
+
+        node
+    }
+
+    /// Enters one nesting level, the caller leaves it again with `depth -= 1`.
+    fn descend(&mut self) -> bool {
+        if self.too_deep {
+            return false;
+        }
+        if self.depth >= self.max_depth {
+            let msg = format!("nesting exceeds the maximum depth of {}", self.max_depth);
+            self.give_up(msg);
+            return false;
+        }
+
+        self.depth += 1;
+        true
+    }
+
+    /// Adds one link to the current chain, the caller leaves it again by restoring `chain`.
+    fn link(&mut self) -> bool {
+        if self.too_deep {
+            return false;
+        }
+        if self.chain >= self.max_chain {
+            let msg = format!("chain exceeds the maximum length of {}", self.max_chain);
+            self.give_up(msg);
+            return false;
+        }
+
+        self.chain += 1;
+        true
+    }
+
+    /// Reports hitting a limit and skips the rest of the input.
+    fn give_up(&mut self, msg: String) {
+        self.push_error(msg);
+        self.too_deep = true;
+        while !self.cur_token_is(TokenKind::Eof) {
+            self.next_token();
+        }
+    }
+
+    fn parse_identifier(&mut self) -> Option<ExpressionNode> {
+        let ident = Identifier {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        };
+
+        if self.peek_token_is(TokenKind::Lbrace) && !self.no_struct_literal {
+            return self.parse_struct_literal(ident);
+        }
+
+        Some(ExpressionNode::IdentifierNode(ident))
+    }
+
+    /// Parses `Point { x: 1, y: 2 }` after the struct name.
+    fn parse_struct_literal(&mut self, name: Identifier) -> Option<ExpressionNode> {
+        self.next_token();
+        let token = name.token.clone();
+        let mut fields = vec![];
+
+        while !self.peek_token_is(TokenKind::Rbrace) {
+            let field = self.parse_field_name()?;
+
+            if !self.expect_peek(TokenKind::Colon) {
+                return None;
+            }
+            self.next_token();
+
+            let value = self.parse_expression(PredenceLevel::Lowest)?;
+            fields.push((field, value));
+
+            if !self.peek_token_is(TokenKind::Rbrace) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+        self.next_token();
+
+        Some(ExpressionNode::Struct(StructLiteral {
+            token,
+            span: Span::default(),
+            name,
+            fields,
+        }))
+    }
+
+    fn parse_field_name(&mut self) -> Option<Identifier> {
+        if !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+
+        Some(Identifier {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        })
+    }
+
+    fn parse_integer_literal(&mut self) -> Option<ExpressionNode> {
+        let mut literal = IntegerLiteral {
+            token: self.cur_token.clone(),
+            value: Default::default(),
+        };
+
+        match self.cur_token.literal.replace('_', "").parse::<i64>() {
+            Ok(value) => {
+                literal.value = value;
+                Some(ExpressionNode::Integer(literal))
+            }
+            Err(_) => {
+                let msg = format!("could not parse {} as integer", self.cur_token.literal);
+                self.push_error(msg);
+                None
+            }
+        }
+    }
+
+    fn parse_boolean(&mut self) -> Option<ExpressionNode> {
+        Some(ExpressionNode::Boolean(BooleanLiteral {
+            token: self.cur_token.clone(),
+            value: self.cur_token_is(TokenKind::True),
+        }))
+    }
+
+    fn parse_null(&mut self) -> Option<ExpressionNode> {
+        Some(ExpressionNode::Null(NullLiteral {
+            token: self.cur_token.clone(),
+        }))
+    }
+
+    fn parse_string_literal(&mut self) -> Option<ExpressionNode> {
+        Some(ExpressionNode::StringNode(StringLiteral {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        }))
+    }
+
+    fn parse_array_literal(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let elements = self.parse_expression_list(TokenKind::Rbracket)?;
+
+        Some(ExpressionNode::Array(ArrayLiteral {
+            token,
+            span: Span::default(),
+            elements,
+        }))
+    }
+
+    fn parse_hash_literal(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let mut pairs = vec![];
+
+        while !self.peek_token_is(TokenKind::Rbrace) {
+            self.next_token();
+            let key = self.parse_expression(PredenceLevel::Lowest)?;
+
+            if !self.expect_peek(TokenKind::Colon) {
+                return None;
+            }
+            self.next_token();
+
+            let value = self.parse_expression(PredenceLevel::Lowest)?;
+            pairs.push((key, value));
+
+            if !self.peek_token_is(TokenKind::Rbrace) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenKind::Rbrace) {
+            return None;
+        }
+
+        Some(ExpressionNode::Hash(HashLiteral {
+            token,
+            span: Span::default(),
+            pairs,
+        }))
+    }
+
+    fn parse_expression_list(&mut self, end: TokenKind) -> Option<Vec<ExpressionNode>> {
+        let mut list = vec![];
+
+        if self.peek_token_is(end.clone()) {
+            self.next_token();
+            return Some(list);
+        }
+
+        self.next_token();
+        list.push(self.parse_expression(PredenceLevel::Lowest)?);
+
+        while self.peek_token_is(TokenKind::Comma) {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(PredenceLevel::Lowest)?);
+        }
+
+        if !self.expect_peek(end) {
+            return None;
+        }
+
+        Some(list)
+    }
+
+    fn parse_prefix_expression(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let operator = self.cur_token.literal.clone();
+
+        self.next_token();
+
+        let right = self.parse_expression(PredenceLevel::Prefix)?;
+
+        Some(ExpressionNode::Prefix(PrefixExpression {
+            token,
+            span: Span::default(),
+            operator,
+            right: Box::new(right),
+        }))
+    }
+
+    fn parse_infix_expression(&mut self, left: ExpressionNode) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let operator = self.cur_token.literal.clone();
+        let precedence = self.cur_precedence();
+
+        self.next_token();
+
+        let right = self.parse_expression(precedence)?;
+
+        Some(ExpressionNode::Infix(InfixExpression {
+            token,
+            span: Span::default(),
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        }))
+    }
+
+    /// `x = 5`, right associative so that `a = b = 5` assigns both.
+    fn parse_assign_expression(&mut self, left: ExpressionNode) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let name = match left {
+            ExpressionNode::IdentifierNode(name) => name,
+            other => {
+                let msg = format!(
+                    "can not assign to `{}`, only to a name",
+                    other.print_string()
+                );
+                self.push_error(msg);
+                return None;
+            }
         };
 
-        parser.register_prefix(TokenKind::Ident, Self::parse_identifier);
-        parser.register_prefix(TokenKind::Int, Self::parse_integer_literal);
+        self.next_token();
+
+        let value = self.parse_expression(PredenceLevel::Assign.looser())?;
+
+        Some(ExpressionNode::Assign(AssignExpression {
+            token,
+            span: Span::default(),
+            name,
+            value: Box::new(value),
+        }))
+    }
+
+    fn parse_grouped_expression(&mut self) -> Option<ExpressionNode> {
+        self.next_token();
+
+        // parentheses end the ambiguity with a `for` body
+        let no_struct_literal = std::mem::replace(&mut self.no_struct_literal, false);
+        let exp = self.parse_expression(PredenceLevel::Lowest);
+        self.no_struct_literal = no_struct_literal;
+
+        if !self.expect_peek(TokenKind::Rparen) {
+            return None;
+        }
+
+        exp
+    }
+
+    fn parse_function_literal(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::Lparen) {
+            return None;
+        }
+
+        let parameters = self.parse_function_parameters()?;
+
+        let mut return_type = None;
+        if self.peek_token_is(TokenKind::Arrow) {
+            self.next_token();
+            self.next_token();
+            return_type = Some(self.parse_type_expr()?);
+        }
+
+        if !self.expect_peek(TokenKind::Lbrace) {
+            return None;
+        }
+
+        self.functions += 1;
+        let body = self.parse_block_statement();
+        self.functions -= 1;
+
+        Some(ExpressionNode::Function(FunctionLiteral {
+            token,
+            span: Span::default(),
+            parameters,
+            return_type,
+            body,
+        }))
+    }
+
+    fn parse_macro_literal(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::Lparen) {
+            return None;
+        }
+
+        let mut parameters = vec![];
+        for param in self.parse_function_parameters()? {
+            if param.rest || param.default.is_some() || param.annotation.is_some() {
+                let msg = format!(
+                    "macro parameter `{}` must be a plain name",
+                    param.print_string()
+                );
+                self.push_error(msg);
+                return None;
+            }
+            parameters.push(param.name);
+        }
+
+        if !self.expect_peek(TokenKind::Lbrace) {
+            return None;
+        }
+
+        let body = self.parse_block_statement();
+
+        Some(ExpressionNode::Macro(MacroLiteral {
+            token,
+            span: Span::default(),
+            parameters,
+            body,
+        }))
+    }
+
+    fn parse_function_parameters(&mut self) -> Option<Vec<Parameter>> {
+        let mut parameters: Vec<Parameter> = vec![];
+
+        while !self.peek_token_is(TokenKind::Rparen) {
+            if let Some(rest) = parameters.iter().find(|p| p.rest) {
+                let msg = format!(
+                    "rest parameter `...{}` must be the last parameter",
+                    rest.name.value
+                );
+                self.push_error(msg);
+                return None;
+            }
+
+            self.next_token();
+
+            let rest = self.cur_token_is(TokenKind::Ellipsis);
+            if rest {
+                self.next_token();
+            }
+
+            if !self.cur_token_is(TokenKind::Ident) {
+                let msg = format!(
+                    "expected a parameter name, got {} instead",
+                    self.cur_token.kind
+                );
+                self.push_error(msg);
+                return None;
+            }
+            let name = Identifier {
+                token: self.cur_token.clone(),
+                value: self.cur_token.literal.clone(),
+            };
+
+            if parameters.iter().any(|p| p.name.value == name.value) {
+                let msg = format!("duplicate parameter `{}`", name.value);
+                self.push_error(msg);
+                return None;
+            }
+
+            let annotation = self.parse_type_annotation()?;
+
+            let mut default = None;
+            if self.peek_token_is(TokenKind::Assign) {
+                if rest {
+                    let msg = format!("rest parameter `...{}` can not have a default", name.value);
+                    self.push_error(msg);
+                    return None;
+                }
+                self.next_token();
+                self.next_token();
+                default = Some(self.parse_expression(PredenceLevel::Lowest)?);
+            } else if !rest && parameters.iter().any(|p| p.default.is_some()) {
+                let msg = format!(
+                    "parameter `{}` without a default follows a parameter with a default",
+                    name.value
+                );
+                self.push_error(msg);
+                return None;
+            }
+
+            parameters.push(Parameter {
+                name,
+                annotation,
+                default,
+                rest,
+            });
+
+            if !self.peek_token_is(TokenKind::Rparen) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenKind::Rparen) {
+            return None;
+        }
+
+        Some(parameters)
+    }
+
+    /// Parses an optional `: type` after a binding, `Some(None)` when there is none.
+    fn parse_type_annotation(&mut self) -> Option<Option<TypeExpr>> {
+        if !self.peek_token_is(TokenKind::Colon) {
+            return Some(None);
+        }
+        self.next_token();
+        self.next_token();
+
+        Some(Some(self.parse_type_expr()?))
+    }
+
+    fn parse_type_expr(&mut self) -> Option<TypeExpr> {
+        self.nested(Self::parse_type_expr_unguarded)
+    }
+
+    fn parse_type_expr_unguarded(&mut self) -> Option<TypeExpr> {
+        let token = self.cur_token.clone();
+
+        match self.cur_token.kind {
+            TokenKind::Ident => Some(TypeExpr::Named(Identifier {
+                token,
+                value: self.cur_token.literal.clone(),
+            })),
+            TokenKind::Lbracket => {
+                self.next_token();
+                let element = self.parse_type_expr()?;
+
+                if !self.expect_peek(TokenKind::Rbracket) {
+                    return None;
+                }
+
+                Some(TypeExpr::Array(ArrayType {
+                    token,
+                    element: Box::new(element),
+                }))
+            }
+            TokenKind::Lbrace => {
+                self.next_token();
+                let key = self.parse_type_expr()?;
+
+                if !self.expect_peek(TokenKind::Colon) {
+                    return None;
+                }
+                self.next_token();
+
+                let value = self.parse_type_expr()?;
+
+                if !self.expect_peek(TokenKind::Rbrace) {
+                    return None;
+                }
+
+                Some(TypeExpr::Hash(HashType {
+                    token,
+                    key: Box::new(key),
+                    value: Box::new(value),
+                }))
+            }
+            TokenKind::Function => {
+                if !self.expect_peek(TokenKind::Lparen) {
+                    return None;
+                }
+
+                let mut parameters = vec![];
+                while !self.peek_token_is(TokenKind::Rparen) {
+                    self.next_token();
+                    parameters.push(self.parse_type_expr()?);
+
+                    if !self.peek_token_is(TokenKind::Rparen) && !self.expect_peek(TokenKind::Comma)
+                    {
+                        return None;
+                    }
+                }
+
+                if !self.expect_peek(TokenKind::Rparen) || !self.expect_peek(TokenKind::Arrow) {
+                    return None;
+                }
+                self.next_token();
+
+                let return_type = self.parse_type_expr()?;
+
+                Some(TypeExpr::Function(FunctionType {
+                    token,
+                    parameters,
+                    return_type: Box::new(return_type),
+                }))
+            }
+            _ => {
+                let msg = format!("expected a type, got {} instead", self.cur_token.kind);
+                self.push_error(msg);
+                None
+            }
+        }
+    }
+
+    fn parse_call_expression(&mut self, function: ExpressionNode) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let arguments = self.parse_call_arguments()?;
+
+        Some(ExpressionNode::Call(CallExpression {
+            token,
+            span: Span::default(),
+            function: Box::new(function),
+            arguments,
+        }))
+    }
+
+    fn parse_call_arguments(&mut self) -> Option<Vec<Argument>> {
+        let mut arguments: Vec<Argument> = vec![];
+
+        while !self.peek_token_is(TokenKind::Rparen) {
+            self.next_token();
+
+            let mut name = None;
+            if self.cur_token_is(TokenKind::Ident) && self.peek_token_is(TokenKind::Colon) {
+                name = Some(Identifier {
+                    token: self.cur_token.clone(),
+                    value: self.cur_token.literal.clone(),
+                });
+                self.next_token();
+                self.next_token();
+            } else if let Some(named) = arguments.iter().find_map(|a| a.name.as_ref()) {
+                let msg = format!(
+                    "positional argument can not follow the named argument `{}`",
+                    named.value
+                );
+                self.push_error(msg);
+                return None;
+            }
+
+            let value = self.parse_expression(PredenceLevel::Lowest)?;
+            arguments.push(Argument { name, value });
+
+            if !self.peek_token_is(TokenKind::Rparen) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenKind::Rparen) {
+            return None;
+        }
+
+        Some(arguments)
+    }
+
+    fn parse_match_expression(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::Lparen) {
+            return None;
+        }
+        self.next_token();
+
+        let subject = self.parse_expression(PredenceLevel::Lowest)?;
+
+        if !self.expect_peek(TokenKind::Rparen) {
+            return None;
+        }
+        if !self.expect_peek(TokenKind::Lbrace) {
+            return None;
+        }
+
+        let mut arms = vec![];
+
+        while !self.peek_token_is(TokenKind::Rbrace) {
+            self.next_token();
+            arms.push(self.parse_match_arm()?);
+
+            if !self.peek_token_is(TokenKind::Rbrace) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenKind::Rbrace) {
+            return None;
+        }
+
+        Some(ExpressionNode::Match(MatchExpression {
+            token,
+            span: Span::default(),
+            subject: Box::new(subject),
+            arms,
+        }))
+    }
+
+    fn parse_if_expression(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::Lparen) {
+            return None;
+        }
+        self.next_token();
+
+        let condition = self.parse_expression(PredenceLevel::Lowest)?;
+
+        if !self.expect_peek(TokenKind::Rparen) {
+            return None;
+        }
+        if !self.expect_peek(TokenKind::Lbrace) {
+            return None;
+        }
+        let consequence = self.parse_block_statement();
+
+        let mut alternative = None;
+        if self.peek_token_is(TokenKind::Else) {
+            self.next_token();
+            alternative = Some(self.parse_else()?);
+        }
+
+        Some(ExpressionNode::If(IfExpression {
+            token,
+            span: Span::default(),
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+        }))
+    }
+
+    /// The block after `else`, for `else if` a block holding just that `if`.
+    fn parse_else(&mut self) -> Option<BlockStatement> {
+        if !self.peek_token_is(TokenKind::If) {
+            if !self.expect_peek(TokenKind::Lbrace) {
+                return None;
+            }
+            return Some(self.parse_block_statement());
+        }
+
+        self.next_token();
+        let token = self.cur_token.clone();
+        let mut nested = self.nested(Self::parse_if_expression)?;
+        let span = self.span_from(token.span.start);
+        nested.set_span(span);
+
+        // the same block as `else { if ... }`
+        let brace = Token {
+            kind: TokenKind::Lbrace,
+            literal: String::from("{"),
+            span: token.span,
+        };
+        Some(BlockStatement {
+            token: brace,
+            span,
+            statements: vec![StatementNode::Expression(ExpressionStatement {
+                token,
+                span,
+                expression: Some(nested),
+            })],
+        })
+    }
+
+    fn parse_match_arm(&mut self) -> Option<MatchArm> {
+        self.nested(Self::parse_match_arm_unguarded)
+    }
+
+    fn parse_match_arm_unguarded(&mut self) -> Option<MatchArm> {
+        let pattern = self.parse_pattern()?;
+
+        let mut guard = None;
+        if self.peek_token_is(TokenKind::If) {
+            self.next_token();
+            self.next_token();
+            guard = Some(self.parse_expression(PredenceLevel::Lowest)?);
+        }
+
+        if !self.expect_peek(TokenKind::FatArrow) {
+            return None;
+        }
+        self.next_token();
+
+        let body = self.parse_expression(PredenceLevel::Lowest)?;
+
+        Some(MatchArm {
+            pattern,
+            guard,
+            body,
+        })
+    }
+
+    fn parse_pattern(&mut self) -> Option<Pattern> {
+        self.nested(Self::parse_pattern_unguarded)
+    }
+
+    fn parse_pattern_unguarded(&mut self) -> Option<Pattern> {
+        match self.cur_token.kind {
+            TokenKind::Ident if self.cur_token.literal == "_" => {
+                Some(Pattern::Wildcard(self.cur_token.clone()))
+            }
+            TokenKind::Ident => {
+                let ident = Identifier {
+                    token: self.cur_token.clone(),
+                    value: self.cur_token.literal.clone(),
+                };
+
+                // a bare name is resolved once the whole program is parsed,
+                // the enum may be declared after the match
+                if self.peek_token_is(TokenKind::Lparen) {
+                    self.parse_variant_pattern(ident)
+                } else {
+                    Some(Pattern::Binding(ident))
+                }
+            }
+            TokenKind::Int | TokenKind::Minus => self.parse_integer_pattern(),
+            TokenKind::True | TokenKind::False => Some(Pattern::Boolean(BooleanLiteral {
+                token: self.cur_token.clone(),
+                value: self.cur_token_is(TokenKind::True),
+            })),
+            TokenKind::String => Some(Pattern::StringNode(StringLiteral {
+                token: self.cur_token.clone(),
+                value: self.cur_token.literal.clone(),
+            })),
+            TokenKind::Lbracket => self.parse_array_pattern(),
+            TokenKind::Lbrace => self.parse_hash_pattern(),
+            _ => {
+                let msg = format!("expected a pattern, got {} instead", self.cur_token.kind);
+                self.push_error(msg);
+                None
+            }
+        }
+    }
+
+    fn parse_variant_pattern(&mut self, name: Identifier) -> Option<Pattern> {
+        self.next_token();
+        let mut payload = vec![];
+
+        while !self.peek_token_is(TokenKind::Rparen) {
+            self.next_token();
+            payload.push(self.parse_pattern()?);
+
+            if !self.peek_token_is(TokenKind::Rparen) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+        self.next_token();
+
+        Some(Pattern::Variant(VariantPattern { name, payload }))
+    }
+
+    fn parse_integer_pattern(&mut self) -> Option<Pattern> {
+        let negative = self.cur_token_is(TokenKind::Minus);
+        if negative && !self.expect_peek(TokenKind::Int) {
+            return None;
+        }
+
+        match self.parse_integer_literal()? {
+            ExpressionNode::Integer(mut literal) => {
+                if negative {
+                    literal.value = -literal.value;
+                    literal.token.literal = format!("-{}", literal.token.literal);
+                }
+                Some(Pattern::Integer(literal))
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_array_pattern(&mut self) -> Option<Pattern> {
+        let token = self.cur_token.clone();
+        let mut elements = vec![];
+        let mut rest = None;
+
+        while !self.peek_token_is(TokenKind::Rbracket) {
+            self.next_token();
+
+            if self.cur_token_is(TokenKind::Ellipsis) {
+                if !self.expect_peek(TokenKind::Ident) {
+                    return None;
+                }
+                rest = Some(Identifier {
+                    token: self.cur_token.clone(),
+                    value: self.cur_token.literal.clone(),
+                });
+
+                if !self.peek_token_is(TokenKind::Rbracket) {
+                    let msg = format!(
+                        "rest binding `...{}` must be the last element of an array pattern",
+                        self.cur_token.literal
+                    );
+                    self.push_error(msg);
+                    return None;
+                }
+                break;
+            }
+
+            elements.push(self.parse_pattern()?);
+
+            if !self.peek_token_is(TokenKind::Rbracket) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenKind::Rbracket) {
+            return None;
+        }
+
+        Some(Pattern::Array(ArrayPattern {
+            token,
+            elements,
+            rest,
+        }))
+    }
+
+    fn parse_hash_pattern(&mut self) -> Option<Pattern> {
+        let token = self.cur_token.clone();
+        let mut pairs = vec![];
+
+        while !self.peek_token_is(TokenKind::Rbrace) {
+            self.next_token();
+
+            let key = StringLiteral {
+                token: self.cur_token.clone(),
+                value: self.cur_token.literal.clone(),
+            };
+
+            let value = match self.cur_token.kind {
+                TokenKind::String => {
+                    if !self.expect_peek(TokenKind::Colon) {
+                        return None;
+                    }
+                    self.next_token();
+                    self.parse_pattern()?
+                }
+                TokenKind::Ident => {
+                    if self.peek_token_is(TokenKind::Colon) {
+                        self.next_token();
+                        self.next_token();
+                        self.parse_pattern()?
+                    } else {
+                        Pattern::Binding(Identifier {
+                            token: self.cur_token.clone(),
+                            value: self.cur_token.literal.clone(),
+                        })
+                    }
+                }
+                _ => {
+                    let msg = format!(
+                        "expected a hash pattern key, got {} instead",
+                        self.cur_token.kind
+                    );
+                    self.push_error(msg);
+                    return None;
+                }
+            };
+            pairs.push((key, value));
+
+            if !self.peek_token_is(TokenKind::Rbrace) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenKind::Rbrace) {
+            return None;
+        }
+
+        Some(Pattern::Hash(HashPattern { token, pairs }))
+    }
+
+    fn next_token(&mut self) {
+        self.cur_token = self.peek_token.clone();
+        self.peek_token = self.lexer.next_token();
+    }
+
+    pub fn parse_program(&mut self) -> Option<Program> {
+        let mut program = Program { statements: vec![] };
+
+        while self.cur_token.kind != TokenKind::Eof {
+            if let Some(statement) = self.parse_statement() {
+                program.statements.push(statement);
+            }
+            self.next_token();
+        }
+
+        if self.variants.is_empty() {
+            return Some(program);
+        }
+        Some(ResolveVariants(&self.variants).fold_program(program))
+    }
+
+    /// Enum variants declared in the input and the ones it was given.
+    pub fn variants(&self) -> &HashSet<String> {
+        &self.variants
+    }
+
+    fn parse_statement(&mut self) -> Option<StatementNode> {
+        let start = self.cur_token.span.start;
+        let mut stmt = match self.cur_token.kind {
+            TokenKind::Let | TokenKind::Const => self.parse_let_statement(),
+            TokenKind::Return => self.parse_return_statement(),
+            TokenKind::Infix => self.parse_infix_declaration(),
+            TokenKind::Import => self.parse_import_statement(),
+            TokenKind::Export => self.parse_export_statement(),
+            TokenKind::Struct => self.parse_struct_declaration(),
+            TokenKind::Enum => self.parse_enum_declaration(),
+            TokenKind::Throw => self.parse_throw_statement(),
+            TokenKind::Try => self.parse_try_statement(),
+            TokenKind::Yield => self.parse_yield_statement(),
+            TokenKind::For => self.parse_for_statement(),
+            _ => self.parse_expression_statement(),
+        }?;
+
+        stmt.set_span(self.span_from(start));
+        Some(stmt)
+    }
+
+    fn parse_expression(&mut self, precedence_level: PredenceLevel) -> Option<ExpressionNode> {
+        self.nested(|parser| parser.parse_expression_unguarded(precedence_level))
+    }
+
+    fn parse_expression_unguarded(
+        &mut self,
+        precedence_level: PredenceLevel,
+    ) -> Option<ExpressionNode> {
+        // a grouped expression keeps its own span, an operation on it
+        // covers the parentheses too
+        let start = self.cur_token.span.start;
+        let grouped = self.cur_token_is(TokenKind::Lparen);
+
+        let prefix = self.prefix_parse_fns.get(&self.cur_token.kind).copied();
+        if let Some(prefix_fn) = prefix {
+            let mut left_exp = prefix_fn(self);
+            if !grouped {
+                if let Some(left) = &mut left_exp {
+                    left.set_span(self.span_from(start));
+                }
+            }
+
+            let chain = self.chain;
+            let node = self.parse_infix_chain(precedence_level, start, left_exp);
+            self.chain = chain;
+
+            return node;
+        }
+
+        self.no_prefix_parse_fn_error(self.cur_token.kind.clone());
+        None
+    }
+
+    /// Applies infix and postfix operators to `left_exp` as long as they bind tighter
+    /// than `precedence_level`.
+    ///
+    /// Every operator nests the operand one level deeper, so a long chain like
+    /// `a + a + ...` or `f(1)(1)...` counts towards `max_chain`.
+    fn parse_infix_chain(
+        &mut self,
+        precedence_level: PredenceLevel,
+        start: Position,
+        mut left_exp: Option<ExpressionNode>,
+    ) -> Option<ExpressionNode> {
+        while !self.peek_token_is(TokenKind::Semicolon) && precedence_level < self.peek_precedence()
+        {
+            let infix = self.infix_parse_fns.get(&self.peek_token.kind).copied();
+            match (infix, left_exp) {
+                (Some(infix_fn), Some(left)) => {
+                    if !self.link() {
+                        return None;
+                    }
+                    self.next_token();
+                    left_exp = infix_fn(self, left);
+                    if let Some(left) = &mut left_exp {
+                        left.set_span(self.span_from(start));
+                    }
+                }
+                (_, left) => return left,
+            }
+        }
+
+        left_exp
+    }
+
+    /// The span from `start` to the end of the current token.
+    fn span_from(&self, start: Position) -> Span {
+        Span {
+            start,
+            end: self.cur_token.span.end,
+        }
+    }
+
+    fn peek_precedence(&self) -> PredenceLevel {
+        self.precedence_of(&self.peek_token)
+    }
+
+    fn cur_precedence(&self) -> PredenceLevel {
+        self.precedence_of(&self.cur_token)
+    }
+
+    fn precedence_of(&self, token: &Token) -> PredenceLevel {
+        match token.kind {
+            TokenKind::Operator => self
+                .operators
+                .get(&token.literal)
+                .map_or(PredenceLevel::Lowest, |(precedence, _)| *precedence),
+            _ => Self::token_precedence(&token.kind),
+        }
+    }
+
+    fn token_precedence(token_kind: &TokenKind) -> PredenceLevel {
+        match token_kind {
+            TokenKind::Eq | TokenKind::NotEq => PredenceLevel::Equals,
+            TokenKind::Lt | TokenKind::Gt => PredenceLevel::LessGreather,
+            TokenKind::Plus | TokenKind::Minus => PredenceLevel::Sum,
+            TokenKind::Slash | TokenKind::Asteriks => PredenceLevel::Product,
+            TokenKind::Assign => PredenceLevel::Assign,
+            TokenKind::DotDot | TokenKind::DotDotEq => PredenceLevel::Range,
+            TokenKind::QuestionQuestion => PredenceLevel::Coalesce,
+            TokenKind::Lparen | TokenKind::Dot | TokenKind::QuestionDot => PredenceLevel::Call,
+            TokenKind::Lbracket => PredenceLevel::Index,
+            _ => PredenceLevel::Lowest,
+        }
+    }
+
+    fn parse_let_statement(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+        self.next_token();
+
+        let name = self.parse_pattern()?;
+        if let Some(refutable) = Self::refutable_pattern(&name) {
+            let kind = match refutable {
+                Pattern::Variant(_) => "variant",
+                _ => "literal",
+            };
+            let msg = format!(
+                "let binding `{}` can not destructure the {} pattern `{}`",
+                name.print_string(),
+                kind,
+                refutable.print_string()
+            );
+            self.push_error(msg);
+            return None;
+        }
+
+        let annotation = self.parse_type_annotation()?;
+
+        if !self.expect_peek(TokenKind::Assign) {
+            return None;
+        }
+        self.next_token();
+
+        let value = self.parse_expression(PredenceLevel::Lowest);
+
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
+        }
+
+        Some(StatementNode::Let(LetStatement {
+            token,
+            span: Span::default(),
+            name,
+            annotation,
+            value,
+        }))
+    }
+
+    /// Returns the first literal or variant sub-pattern, a let binding must match any value.
+    fn refutable_pattern(pattern: &Pattern) -> Option<&Pattern> {
+        match pattern {
+            Pattern::Integer(_)
+            | Pattern::Boolean(_)
+            | Pattern::StringNode(_)
+            | Pattern::Variant(_) => Some(pattern),
+            Pattern::Binding(_) | Pattern::Wildcard(_) => None,
+            Pattern::Array(array) => array.elements.iter().find_map(Self::refutable_pattern),
+            Pattern::Hash(hash) => hash
+                .pairs
+                .iter()
+                .find_map(|(_, value)| Self::refutable_pattern(value)),
+        }
+    }
+
+    fn parse_infix_declaration(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::Operator) {
+            return None;
+        }
+        let operator = Identifier {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        };
+        if BUILTIN_OPERATORS.contains(&operator.value.as_str()) {
+            let msg = format!(
+                "can not redefine the built-in operator `{}`",
+                operator.value
+            );
+            self.push_error(msg);
+            return None;
+        }
+        if self.operators.contains_key(&operator.value) {
+            let msg = format!("operator `{}` is already declared", operator.value);
+            self.push_error(msg);
+            return None;
+        }
+
+        if !self.expect_peek(TokenKind::Int) {
+            return None;
+        }
+        let declared = self.cur_token.literal.parse::<u8>().ok();
+        let declared = declared.and_then(|precedence| {
+            PredenceLevel::declared(precedence).map(|level| (precedence, level))
+        });
+        let (precedence, level) = match declared {
+            Some(found) => found,
+            None => {
+                let msg = format!(
+                    "precedence of `{}` must be between 1 and 4, got {}",
+                    operator.value, self.cur_token.literal
+                );
+                self.push_error(msg);
+                return None;
+            }
+        };
+
+        if !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+        let associativity = match self.cur_token.literal.as_str() {
+            "left" => Associativity::Left,
+            "right" => Associativity::Right,
+            other => {
+                let msg = format!("expected associativity `left` or `right`, got `{}`", other);
+                self.push_error(msg);
+                return None;
+            }
+        };
+
+        // declared before the value is parsed so the function can use itself
+        self.lexer.register_operator(&operator.value);
+        self.operators
+            .insert(operator.value.clone(), (level, associativity));
+
+        if !self.expect_peek(TokenKind::Assign) {
+            return None;
+        }
+        self.next_token();
+
+        let value = self.parse_expression(PredenceLevel::Lowest);
+
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
+        }
+
+        Some(StatementNode::Infix(InfixDeclaration {
+            binding: LetStatement {
+                token: token.clone(),
+                span: self.span_from(token.span.start),
+                name: Pattern::Binding(operator),
+                annotation: None,
+                value,
+            },
+            token,
+            span: Span::default(),
+            precedence,
+            associativity,
+        }))
+    }
+
+    fn parse_member_expression(&mut self, object: ExpressionNode) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+        let property = Identifier {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        };
+
+        Some(ExpressionNode::Member(MemberExpression {
+            token,
+            span: Span::default(),
+            object: Box::new(object),
+            property,
+            optional: false,
+        }))
+    }
+
+    /// `a?.b` or `a?.[i]`, the access is skipped when `a` is null.
+    fn parse_optional_chain(&mut self, object: ExpressionNode) -> Option<ExpressionNode> {
+        let mut chain = if self.peek_token_is(TokenKind::Lbracket) {
+            self.next_token();
+            self.parse_index_expression(object)?
+        } else {
+            self.parse_member_expression(object)?
+        };
+
+        match &mut chain {
+            ExpressionNode::Member(member) => member.optional = true,
+            ExpressionNode::Index(index) => index.optional = true,
+            _ => {}
+        }
+
+        Some(chain)
+    }
+
+    fn parse_index_expression(&mut self, left: ExpressionNode) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        self.next_token();
+
+        let index = self.parse_expression(PredenceLevel::Lowest)?;
+
+        if !self.expect_peek(TokenKind::Rbracket) {
+            return None;
+        }
+
+        Some(ExpressionNode::Index(IndexExpression {
+            token,
+            span: Span::default(),
+            left: Box::new(left),
+            index: Box::new(index),
+            optional: false,
+        }))
+    }
+
+    /// `..5` or `..`, a range without a start.
+    fn parse_open_range(&mut self) -> Option<ExpressionNode> {
+        self.parse_range(None)
+    }
+
+    fn parse_range_expression(&mut self, start: ExpressionNode) -> Option<ExpressionNode> {
+        if let ExpressionNode::Range(_) = start {
+            self.push_error(String::from("ranges can not be chained"));
+            return None;
+        }
+
+        self.parse_range(Some(Box::new(start)))
+    }
+
+    fn parse_range(&mut self, start: Option<Box<ExpressionNode>>) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let inclusive = self.cur_token_is(TokenKind::DotDotEq);
+
+        // a range is open at the end when nothing that could start a bound follows
+        let end = match self.peek_token.kind {
+            TokenKind::Rbracket
+            | TokenKind::Rparen
+            | TokenKind::Rbrace
+            | TokenKind::Lbrace
+            | TokenKind::Semicolon
+            | TokenKind::Comma
+            | TokenKind::Eof => None,
+            _ => {
+                self.next_token();
+                Some(Box::new(self.parse_expression(PredenceLevel::Range)?))
+            }
+        };
+
+        if inclusive && end.is_none() {
+            self.push_error(String::from("an inclusive range `..=` needs an end"));
+            return None;
+        }
+
+        Some(ExpressionNode::Range(RangeExpression {
+            token,
+            span: Span::default(),
+            start,
+            end,
+            inclusive,
+        }))
+    }
+
+    /// Parses `left <+> right` into the call `<+>(left, right)`.
+    fn parse_operator_expression(&mut self, left: ExpressionNode) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let (precedence, associativity) = *self.operators.get(&token.literal)?;
+
+        self.next_token();
+
+        let precedence = match associativity {
+            Associativity::Left => precedence,
+            Associativity::Right => precedence.looser(),
+        };
+        let right = self.parse_expression(precedence)?;
+
+        let function = ExpressionNode::IdentifierNode(Identifier {
+            token: token.clone(),
+            value: token.literal.clone(),
+        });
+        let arguments = [left, right]
+            .into_iter()
+            .map(|value| Argument { name: None, value })
+            .collect();
+
+        Some(ExpressionNode::Call(CallExpression {
+            token,
+            span: Span::default(),
+            function: Box::new(function),
+            arguments,
+        }))
+    }
+
+    fn parse_import_statement(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+        if !self.at_top_level() {
+            return None;
+        }
+
+        if !self.expect_peek(TokenKind::String) {
+            return None;
+        }
+        let path = StringLiteral {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        };
+
+        if !self.expect_peek(TokenKind::As) || !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+        let alias = Identifier {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        };
+
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
+        }
+
+        Some(StatementNode::Import(ImportStatement {
+            token,
+            span: Span::default(),
+            path,
+            alias,
+        }))
+    }
 
-        parser.next_token();
-        parser.next_token();
+    fn parse_export_statement(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+        if !self.at_top_level() {
+            return None;
+        }
 
-        parser
+        if self.peek_token_is(TokenKind::Const) {
+            self.next_token();
+        } else if !self.expect_peek(TokenKind::Let) {
+            return None;
+        }
+
+        match self.parse_let_statement()? {
+            StatementNode::Let(mut binding) => {
+                binding.span = self.span_from(binding.token.span.start);
+                Some(StatementNode::Export(ExportStatement {
+                    token,
+                    span: Span::default(),
+                    binding,
+                }))
+            }
+            _ => None,
+        }
     }
 
-    fn parse_identifier(&mut self) -> Option<ExpressionNode> {
-        Some(ExpressionNode::IdentifierNode(Identifier {
+    fn parse_struct_declaration(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+        let name = Identifier {
             token: self.cur_token.clone(),
             value: self.cur_token.literal.clone(),
+        };
+
+        if !self.expect_peek(TokenKind::Lbrace) {
+            return None;
+        }
+
+        let mut fields: Vec<Identifier> = vec![];
+        while !self.peek_token_is(TokenKind::Rbrace) {
+            let field = self.parse_field_name()?;
+            if fields.iter().any(|f| f.value == field.value) {
+                let msg = format!(
+                    "duplicate field `{}` in struct `{}`",
+                    field.value, name.value
+                );
+                self.push_error(msg);
+                return None;
+            }
+            fields.push(field);
+
+            if !self.peek_token_is(TokenKind::Rbrace) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+        self.next_token();
+
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
+        }
+
+        Some(StatementNode::Struct(StructDeclaration {
+            token,
+            span: Span::default(),
+            name,
+            fields,
         }))
     }
 
-    fn parse_integer_literal(&mut self) -> Option<ExpressionNode> {
-        let mut literal = IntegerLiteral{
+    fn parse_enum_declaration(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+        let name = Identifier {
             token: self.cur_token.clone(),
-            value: Default::default()
+            value: self.cur_token.literal.clone(),
         };
 
-        return match self.cur_token.literal.parse::<i64>() {
-            Ok(value) => {
-                literal.value = value;
-                Some(ExpressionNode::Integer(literal))
+        if !self.expect_peek(TokenKind::Lbrace) {
+            return None;
+        }
+
+        let mut variants: Vec<Variant> = vec![];
+        while !self.peek_token_is(TokenKind::Rbrace) {
+            let variant = self.parse_field_name()?;
+            if variants.iter().any(|v| v.name.value == variant.value) {
+                let msg = format!(
+                    "duplicate variant `{}` in enum `{}`",
+                    variant.value, name.value
+                );
+                self.push_error(msg);
+                return None;
             }
-            Err(_) => {
-                let msg = format!("could not parse {} as integer", self.cur_token.literal);
-                self.errors.push(msg);
-                None
-            },
-        };
 
+            let mut fields = vec![];
+            if self.peek_token_is(TokenKind::Lparen) {
+                self.next_token();
+                while !self.peek_token_is(TokenKind::Rparen) {
+                    fields.push(self.parse_field_name()?);
+
+                    if !self.peek_token_is(TokenKind::Rparen) && !self.expect_peek(TokenKind::Comma)
+                    {
+                        return None;
+                    }
+                }
+                self.next_token();
+            }
+
+            self.variants.insert(variant.value.clone());
+            variants.push(Variant {
+                name: variant,
+                fields,
+            });
+
+            if !self.peek_token_is(TokenKind::Rbrace) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+        self.next_token();
+
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
+        }
+
+        Some(StatementNode::Enum(EnumDeclaration {
+            token,
+            span: Span::default(),
+            name,
+            variants,
+        }))
     }
 
-    fn next_token(&mut self) {
-        self.cur_token = self.peek_token.clone();
-        self.peek_token = self.lexer.next_token();
+    /// Imports and exports belong to the module, not to a function body.
+    fn at_top_level(&mut self) -> bool {
+        if self.depth > 0 {
+            let msg = format!(
+                "{} is only allowed at the top level of a module",
+                self.cur_token.literal
+            );
+            self.push_error(msg);
+            return false;
+        }
+        true
     }
 
-    pub fn parse_program(&mut self) -> Option<Program> {
-        let mut program = Program { statements: vec![] };
+    fn parse_return_statement(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+        self.next_token();
 
-        while self.cur_token.kind != TokenKind::Eof {
-            if let Some(statement) = self.parse_statement() {
-                program.statements.push(statement);
-            }
+        let ret_value = self.parse_expression(PredenceLevel::Lowest);
+
+        if self.peek_token_is(TokenKind::Semicolon) {
             self.next_token();
         }
 
-        Some(program)
+        Some(StatementNode::Return(ReturnStatement {
+            token,
+            span: Span::default(),
+            ret_value,
+        }))
     }
 
-    fn parse_statement(&mut self) -> Option<StatementNode> {
-        match self.cur_token.kind {
-            TokenKind::Let => self.parse_let_statement(),
-            TokenKind::Return => self.parse_return_statement(),
-            _ => self.parse_expression_statement(),
+    fn parse_throw_statement(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+        self.next_token();
+
+        let value = self.parse_expression(PredenceLevel::Lowest)?;
+
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
         }
+
+        Some(StatementNode::Throw(ThrowStatement {
+            token,
+            span: Span::default(),
+            value,
+        }))
     }
 
-    fn parse_expression(&mut self, precedence_level: PredenceLevel) -> Option<ExpressionNode> {
-        let prefix = self.prefix_parse_fns.get(&self.cur_token.kind);
-        if let Some(prefix_fn) = prefix {
-            let left_exp = prefix_fn(self);
+    fn parse_yield_statement(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+        if self.functions == 0 {
+            self.push_error(String::from("yield is only allowed inside a function"));
+            return None;
+        }
+        self.next_token();
+
+        let value = self.parse_expression(PredenceLevel::Lowest)?;
 
-            return left_exp;
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
         }
 
-        None
+        Some(StatementNode::Yield(YieldStatement {
+            token,
+            span: Span::default(),
+            value,
+        }))
     }
 
-    fn parse_let_statement(&mut self) -> Option<StatementNode> {
-        let mut stmt = LetStatement {
+    fn parse_for_statement(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+        let variable = Identifier {
             token: self.cur_token.clone(),
-            name: Default::default(),
-            value: Default::default(),
+            value: self.cur_token.literal.clone(),
         };
 
-        return if !self.expect_peek(TokenKind::Ident) {
-            None
-        } else {
-            stmt.name = Identifier {
+        if !self.expect_peek(TokenKind::In) {
+            return None;
+        }
+        self.next_token();
+
+        self.no_struct_literal = true;
+        let iterable = self.parse_expression(PredenceLevel::Lowest);
+        self.no_struct_literal = false;
+        let iterable = iterable?;
+
+        if !self.expect_peek(TokenKind::Lbrace) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
+        }
+
+        Some(StatementNode::For(ForStatement {
+            token,
+            span: Span::default(),
+            variable,
+            iterable,
+            body,
+        }))
+    }
+
+    fn parse_try_statement(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::Lbrace) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+
+        let mut catch = None;
+        if self.peek_token_is(TokenKind::Catch) {
+            self.next_token();
+            if !self.expect_peek(TokenKind::Lparen) || !self.expect_peek(TokenKind::Ident) {
+                return None;
+            }
+            let parameter = Identifier {
                 token: self.cur_token.clone(),
                 value: self.cur_token.literal.clone(),
             };
-            if !self.expect_peek(TokenKind::Assign) {
-                None
-            } else {
-                self.next_token();
-                while !self.cur_token_is(TokenKind::Semicolon) {
-                    self.next_token();
-                }
+            if !self.expect_peek(TokenKind::Rparen) || !self.expect_peek(TokenKind::Lbrace) {
+                return None;
+            }
+
+            catch = Some(CatchClause {
+                parameter,
+                body: self.parse_block_statement(),
+            });
+        }
 
-                Some(StatementNode::Let(stmt))
+        let mut finally = None;
+        if self.peek_token_is(TokenKind::Finally) {
+            self.next_token();
+            if !self.expect_peek(TokenKind::Lbrace) {
+                return None;
             }
-        };
+            finally = Some(self.parse_block_statement());
+        }
+
+        if catch.is_none() && finally.is_none() {
+            self.push_error(String::from(
+                "expected catch or finally after the try block",
+            ));
+            return None;
+        }
+
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
+        }
+
+        Some(StatementNode::Try(TryStatement {
+            token,
+            span: Span::default(),
+            body,
+            catch,
+            finally,
+        }))
     }
 
-    fn parse_return_statement(&mut self) -> Option<StatementNode> {
-        let stmt = ReturnStatement {
-            token: self.cur_token.clone(),
-            ret_value: Default::default(),
-        };
+    fn parse_block_statement(&mut self) -> BlockStatement {
+        let token = self.cur_token.clone();
+
+        self.nested(|parser| Some(parser.parse_block_statement_unguarded()))
+            .unwrap_or(BlockStatement {
+                span: self.span_from(token.span.start),
+                token,
+                statements: vec![],
+            })
+    }
+
+    fn parse_block_statement_unguarded(&mut self) -> BlockStatement {
+        let token = self.cur_token.clone();
+        let mut statements = vec![];
+
         self.next_token();
 
-        while !self.cur_token_is(TokenKind::Semicolon) {
+        while !self.cur_token_is(TokenKind::Rbrace) && !self.cur_token_is(TokenKind::Eof) {
+            if let Some(stmt) = self.parse_statement() {
+                statements.push(stmt);
+            }
             self.next_token();
         }
-        Some(StatementNode::Return(stmt))
+
+        BlockStatement {
+            span: self.span_from(token.span.start),
+            token,
+            statements,
+        }
     }
 
     fn parse_expression_statement(&mut self) -> Option<StatementNode> {
         let stmt = ExpressionStatement {
             token: self.cur_token.clone(),
+            span: Span::default(),
             expression: self.parse_expression(PredenceLevel::Lowest),
         };
 
@@ -185,17 +1922,43 @@ impl Parser {
         self.cur_token.kind == token_kind
     }
 
-    fn errors(&self) -> &Vec<String> {
+    /// The comments of the input read so far, see [`Lexer::with_comments`].
+    pub fn comments(&self) -> &[Comment] {
+        self.lexer.comments()
+    }
+
+    pub fn errors(&self) -> &Vec<ParseError> {
         &self.errors
     }
 
+    fn push_error(&mut self, message: String) {
+        if self.too_deep {
+            return;
+        }
+        self.errors.push(ParseError {
+            message,
+            span: self.cur_token.span,
+        });
+    }
+
     fn peek_error(&mut self, token_kind: TokenKind) {
+        if self.too_deep {
+            return;
+        }
         let msg = format!(
             "expected next token to be {}, got {} intead",
             token_kind, self.peek_token.kind
         );
 
-        self.errors.push(msg);
+        self.errors.push(ParseError {
+            message: msg,
+            span: self.peek_token.span,
+        });
+    }
+
+    fn no_prefix_parse_fn_error(&mut self, token_kind: TokenKind) {
+        let msg = format!("no prefix parse function for {} found", token_kind);
+        self.push_error(msg);
     }
 
     fn register_prefix(&mut self, token_kind: TokenKind, prefix_fn: PrefixParseFn) {
@@ -209,16 +1972,58 @@ impl Parser {
 
 //
 
+/// Turns the bare names of declared variants in match patterns into variant
+/// patterns, the parser takes every bare name for a binding.
+struct ResolveVariants<'v>(&'v HashSet<String>);
+
+impl ResolveVariants<'_> {
+    fn resolve(&self, pattern: &mut Pattern) {
+        match pattern {
+            Pattern::Binding(name) if self.0.contains(&name.value) => {
+                *pattern = Pattern::Variant(VariantPattern {
+                    name: name.clone(),
+                    payload: vec![],
+                });
+            }
+            Pattern::Array(array) => array.elements.iter_mut().for_each(|p| self.resolve(p)),
+            Pattern::Hash(hash) => hash.pairs.iter_mut().for_each(|(_, p)| self.resolve(p)),
+            Pattern::Variant(variant) => variant.payload.iter_mut().for_each(|p| self.resolve(p)),
+            _ => {}
+        }
+    }
+}
+
+impl Fold for ResolveVariants<'_> {
+    fn fold_expression(&mut self, mut expression: ExpressionNode) -> ExpressionNode {
+        if let ExpressionNode::Match(match_exp) = &mut expression {
+            for arm in &mut match_exp.arms {
+                self.resolve(&mut arm.pattern);
+            }
+        }
+        ast::fold_expression(self, expression)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use core::error;
+    use std::thread;
 
     use crate::{
-        ast::{ExpressionNode, Node, StatementNode},
+        ast::{
+            Associativity, ExpressionNode, ExpressionStatement, LetStatement, Node, Pattern,
+            StatementNode,
+        },
+        build::{
+            array, assign, boolean, call, const_stmt, expr_stmt, function, hash, ident, index,
+            infix, int, let_stmt, member, null, prefix, program, return_stmt, string,
+        },
+        check::check_program,
+        formatter::{format_program, Config},
         lexer::Lexer,
+        parse,
     };
 
-    use super::Parser;
+    use super::{Parser, DEFAULT_MAX_CHAIN, DEFAULT_MAX_DEPTH};
 
     #[test]
     fn test_let_statements() {
@@ -255,6 +2060,389 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_const_and_assignment() {
+        let tests = vec![
+            ("const x = 5;", "const x = 5;", true),
+            ("const [a, b] = xs;", "const [a, b] = xs;", true),
+            ("export const n = 1;", "export const n = 1;", true),
+            ("let y = 1;", "let y = 1;", false),
+            ("x = 5;", "(x = 5)", false),
+            ("a = b = 1 + 2;", "(a = (b = (1 + 2)))", false),
+            ("x = a ?? 0..n", "(x = ((a ?? 0)..n))", false),
+            ("f(x = 1)", "f((x = 1))", false),
+        ];
+
+        for (input, expected, constant) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(
+                program.print_string(),
+                expected,
+                "wrong program for {}",
+                input
+            );
+            let is_const = match &program.statements[0] {
+                StatementNode::Let(let_stmt) => let_stmt.is_const(),
+                StatementNode::Export(export) => export.binding.is_const(),
+                _ => false,
+            };
+            assert_eq!(is_const, constant, "wrong const flag for {}", input);
+        }
+    }
+
+    #[test]
+    fn test_assignment_errors() {
+        let tests = vec![
+            ("1 + 2 = 3;", "can not assign to `(1 + 2)`, only to a name"),
+            ("xs[0] = 1;", "can not assign to `(xs[0])`, only to a name"),
+            ("const = 1;", "expected a pattern, got Assign instead"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn test_destructuring_let_statements() {
+        let tests = vec![
+            ("let [a, b, ...rest] = xs;", "let [a, b, ...rest] = xs;"),
+            ("let [first, _] = [1, 2];", "let [first, _] = [1, 2];"),
+            (
+                "let {name, age} = person;",
+                r#"let {"name": name, "age": age} = person;"#,
+            ),
+            (
+                r#"let {"pos": [x, y], id: key} = {"pos": [1, 2], "id": 3};"#,
+                r#"let {"pos": [x, y], "id": key} = {"pos": [1, 2], "id": 3};"#,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(
+                program.statements.len(),
+                1,
+                "statements does not contain 1 statement, got{}",
+                program.statements.len()
+            );
+            assert_eq!(
+                program.print_string(),
+                expected,
+                "expected = {}, got = {}",
+                expected,
+                program.print_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_destructuring_let_errors() {
+        let tests = vec![
+            (
+                "let [a, ...rest, b] = xs;",
+                "rest binding `...rest` must be the last element of an array pattern",
+            ),
+            (
+                "let [a, 1] = xs;",
+                "let binding `[a, 1]` can not destructure the literal pattern `1`",
+            ),
+            (
+                "let 5 = x;",
+                "let binding `5` can not destructure the literal pattern `5`",
+            ),
+            (
+                "let [a, ...] = xs;",
+                "expected next token to be Ident, got Rbracket intead",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn test_function_literal_parsing() {
+        let tests = vec![
+            ("fn() {};", "fn() "),
+            ("fn(x, y) { x + y; }", "fn(x, y) (x + y)"),
+            (
+                "fn(x, y = 10, ...rest) { return x; }",
+                "fn(x, y = 10, ...rest) return x;",
+            ),
+            (
+                "fn(x = 1 + 2, y = fn() { 3 }) { x }",
+                "fn(x = (1 + 2), y = fn() 3) x",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(
+                program.print_string(),
+                expected,
+                "expected = {}, got = {}",
+                expected,
+                program.print_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_macro_literal_parsing() {
+        let input = "macro(x, y) { quote(unquote(x) + y); }";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+        check_parser_errors(parser);
+
+        assert_eq!(
+            program.print_string(),
+            "macro(x, y) quote((unquote(x) + y))"
+        );
+
+        let tests = vec![
+            (
+                "macro(x = 1) { x }",
+                "macro parameter `x = 1` must be a plain name",
+            ),
+            (
+                "macro(...xs) { xs }",
+                "macro parameter `...xs` must be a plain name",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn test_function_parameters() {
+        let input = "fn(x, y = 10, ...rest) {}";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+        check_parser_errors(parser);
+
+        let func = match &program.statements[0] {
+            StatementNode::Expression(exp_stmt) => match exp_stmt.expression.as_ref() {
+                Some(ExpressionNode::Function(func)) => func,
+                other => panic!("expression not a FunctionLiteral, got = {:?}", other),
+            },
+            other => panic!(
+                "program.statements[0] is not ExpressionStatement. got ={:?}",
+                other
+            ),
+        };
+
+        let params: Vec<(&str, bool, bool)> = func
+            .parameters
+            .iter()
+            .map(|p| (p.name.value.as_str(), p.default.is_some(), p.rest))
+            .collect();
+        assert_eq!(
+            params,
+            vec![
+                ("x", false, false),
+                ("y", true, false),
+                ("rest", false, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_call_expression_parsing() {
+        let tests = vec![
+            ("add(1, 2 * 3, 4 + 5);", "add(1, (2 * 3), (4 + 5))"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            (
+                "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
+                "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
+            ),
+            ("f(1, y: 2, z: x * 2)", "f(1, y: 2, z: (x * 2))"),
+            ("f()()", "f()()"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(
+                program.print_string(),
+                expected,
+                "expected = {}, got = {}",
+                expected,
+                program.print_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_function_parameter_errors() {
+        let tests = vec![
+            (
+                "fn(...rest, x) {}",
+                "rest parameter `...rest` must be the last parameter",
+            ),
+            (
+                "fn(x = 1, y) {}",
+                "parameter `y` without a default follows a parameter with a default",
+            ),
+            (
+                "fn(...rest = 1) {}",
+                "rest parameter `...rest` can not have a default",
+            ),
+            ("fn(x, x) {}", "duplicate parameter `x`"),
+            ("fn(1) {}", "expected a parameter name, got Int instead"),
+            (
+                "f(y: 1, 2)",
+                "positional argument can not follow the named argument `y`",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn test_type_annotation_parsing() {
+        let tests = vec![
+            ("let x: int = 5;", "let x: int = 5;"),
+            ("let xs: [string] = ys;", "let xs: [string] = ys;"),
+            (
+                "let {a}: {string: int} = h;",
+                r#"let {"a": a}: {string: int} = h;"#,
+            ),
+            (
+                "fn(a: int, b: string = \"x\") -> bool { a > 0 }",
+                r#"fn(a: int, b: string = "x") -> bool (a > 0)"#,
+            ),
+            (
+                "let apply: fn(fn(int) -> int, int) -> int = f;",
+                "let apply: fn(fn(int) -> int, int) -> int = f;",
+            ),
+            ("fn(...rest: [int]) {}", "fn(...rest: [int]) "),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(
+                program.print_string(),
+                expected,
+                "expected = {}, got = {}",
+                expected,
+                program.print_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_type_annotation_errors() {
+        let tests = vec![
+            ("let x: = 5;", "expected a type, got Assign instead"),
+            (
+                "let x: [int = 5;",
+                "expected next token to be Rbracket, got Assign intead",
+            ),
+            (
+                "let f: fn(int) = g;",
+                "expected next token to be ->, got Assign intead",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
     #[test]
     fn test_return_statements() {
         let input = r#"
@@ -389,6 +2577,239 @@ mod test {
         ////burada qalmidam 06.52
     }
 
+    #[test]
+    fn test_operator_precedence_parsing() {
+        let tests = vec![
+            ("-a * b", "((-a) * b)"),
+            ("!-a", "(!(-a))"),
+            ("a + b + c", "((a + b) + c)"),
+            ("a + b - c", "((a + b) - c)"),
+            ("a * b * c", "((a * b) * c)"),
+            ("a + b / c", "(a + (b / c))"),
+            ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
+            ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
+            (
+                "3 + 4 * 5 == 3 * 1 + 4 * 5",
+                "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))",
+            ),
+            ("true != false", "(true != false)"),
+            ("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4)"),
+            ("-(5 + 5)", "(-(5 + 5))"),
+            ("!(true == true)", "(!(true == true))"),
+            ("a * [1, 2][b * c] * d", "((a * ([1, 2][(b * c)])) * d)"),
+            ("add(a * b[2], b[1])", "add((a * (b[2])), (b[1]))"),
+            ("-xs[0]", "(-(xs[0]))"),
+            ("1..n + 1", "(1..(n + 1))"),
+            ("0..=n == m", "(0..=(n == m))"),
+            ("xs[1..3]", "(xs[(1..3)])"),
+            ("s[..5]", "(s[(..5)])"),
+            ("xs[1..]", "(xs[(1..)])"),
+            ("xs[..]", "(xs[(..)])"),
+            ("f(1.., ..2)", "f((1..), (..2))"),
+            ("1_000_000", "1_000_000"),
+            ("a ?? b ?? c", "((a ?? b) ?? c)"),
+            ("a ?? b == c", "(a ?? (b == c))"),
+            ("a ?? 1..n", "((a ?? 1)..n)"),
+            ("a?.b?.c ?? d", "(a?.b?.c ?? d)"),
+            ("a?.[i + 1]", "(a?.[(i + 1)])"),
+            ("-a?.b", "(-a?.b)"),
+            ("f(x)?.y.z", "f(x)?.y.z"),
+            ("null ?? 0", "(null ?? 0)"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(
+                program.print_string(),
+                expected,
+                "expected = {}, got = {}",
+                expected,
+                program.print_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_match_expression() {
+        let input = r#"match (x + 1) { 0 => "zero", -1 => "minus", n if n > 10 => n, [a, _] => a, {"k": v, name} => v, _ => false }"#;
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+        check_parser_errors(parser);
+
+        assert_eq!(
+            program.statements.len(),
+            1,
+            "program.statements does not contain 1 statement, got={}",
+            program.statements.len()
+        );
+
+        let match_exp = match &program.statements[0] {
+            StatementNode::Expression(exp_stmt) => match exp_stmt.expression.as_ref() {
+                Some(ExpressionNode::Match(match_exp)) => match_exp,
+                other => panic!("expression not a MatchExpression, got = {:?}", other),
+            },
+            other => panic!(
+                "program.statements[0] is not ExpressionStatement. got ={:?}",
+                other
+            ),
+        };
+
+        assert_eq!(match_exp.subject.print_string(), "(x + 1)");
+        assert_eq!(
+            match_exp.arms.len(),
+            6,
+            "match does not contain 6 arms, got = {}",
+            match_exp.arms.len()
+        );
+
+        match &match_exp.arms[1].pattern {
+            Pattern::Integer(int) => {
+                assert_eq!(int.value, -1, "pattern value not -1, got = {}", int.value)
+            }
+            other => panic!("pattern not an integer, got = {:?}", other),
+        }
+        match &match_exp.arms[2].pattern {
+            Pattern::Binding(ident) => assert_eq!(ident.value, "n"),
+            other => panic!("pattern not a binding, got = {:?}", other),
+        }
+        assert_eq!(
+            match_exp.arms[2]
+                .guard
+                .as_ref()
+                .map(|guard| guard.print_string()),
+            Some(String::from("(n > 10)"))
+        );
+        match &match_exp.arms[3].pattern {
+            Pattern::Array(array) => assert_eq!(array.elements.len(), 2),
+            other => panic!("pattern not an array pattern, got = {:?}", other),
+        }
+        match &match_exp.arms[4].pattern {
+            Pattern::Hash(hash) => assert_eq!(hash.pairs.len(), 2),
+            other => panic!("pattern not a hash pattern, got = {:?}", other),
+        }
+        assert!(matches!(match_exp.arms[5].pattern, Pattern::Wildcard(_)));
+
+        assert_eq!(
+            program.print_string(),
+            r#"match ((x + 1)) { 0 => "zero", -1 => "minus", n if (n > 10) => n, [a, _] => a, {"k": v, "name": name} => v, _ => false }"#
+        );
+    }
+
+    #[test]
+    fn test_match_expression_errors() {
+        let tests = vec![
+            (
+                "match x { _ => 1 }",
+                "expected next token to be Lparen, got Ident intead",
+            ),
+            (
+                "match (x) { + => 1 }",
+                "expected a pattern, got Plus instead",
+            ),
+            (
+                "match (x) { a 1 }",
+                "expected next token to be =>, got Int intead",
+            ),
+            (
+                "match (x) { a => 1 b => 2 }",
+                "expected next token to be Comma, got Ident intead",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let tests = vec![
+            ("if (x < y) { x }", "if ((x < y)) { x }"),
+            ("if (x) { 1 } else { 2 }", "if (x) { 1 } else { 2 }"),
+            (
+                "if (a) { 1 } else if (b) { 2 } else { 3 }",
+                "if (a) { 1 } else { if (b) { 2 } else { 3 } }",
+            ),
+            (
+                "let v = if (ok) { f(); 1 } + 1;",
+                "let v = (if (ok) { f()1 } + 1);",
+            ),
+            (
+                "match (x) { n if n > 0 => if (n > 9) { 2 } else { 1 } }",
+                "match (x) { n if (n > 0) => if ((n > 9)) { 2 } else { 1 } }",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(program.print_string(), expected, "for {}", input);
+        }
+
+        // `else if` is the same tree as an `else` block holding the `if`
+        assert_eq!(
+            parse("if (a) { 1 } else if (b) { 2 }").unwrap(),
+            parse("if (a) { 1 } else { if (b) { 2 } }").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_if_expression_errors() {
+        let tests = vec![
+            (
+                "if x { 1 }",
+                "expected next token to be Lparen, got Ident intead",
+            ),
+            (
+                "if (x) 1",
+                "expected next token to be Lbrace, got Int intead",
+            ),
+            (
+                "if (x) { 1 } else 2",
+                "expected next token to be Lbrace, got Int intead",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
     fn test_let_statement(stmt: &StatementNode, expected: &str) {
         // if stmt.token_literal() !=  {}
         assert_eq!(
@@ -399,29 +2820,806 @@ mod test {
         );
 
         match stmt {
-            StatementNode::Let(let_stmt) => {
-                assert_eq!(
-                    let_stmt.name.value, expected,
-                    "LetStatement name value not {}. got {}",
-                    expected, let_stmt.name.value
-                );
+            StatementNode::Let(let_stmt) => match &let_stmt.name {
+                Pattern::Binding(name) => {
+                    assert_eq!(
+                        name.value, expected,
+                        "LetStatement name value not {}. got {}",
+                        expected, name.value
+                    );
+
+                    assert_eq!(
+                        name.token_literal(),
+                        expected,
+                        "LetStatement name value not {}. got {}",
+                        expected,
+                        name.value
+                    );
+                }
+                other => panic!("LetStatement name not a binding, got {:?}", other),
+            },
+            other => panic!("not a Let Statement got {:?}", other),
+        }
+    }
 
-                assert_eq!(
-                    let_stmt.name.token_literal(),
-                    expected,
-                    "LetStatement name value not {}. got {}",
-                    expected,
-                    let_stmt.name.value
-                );
+    #[test]
+    fn test_infix_declarations() {
+        let declarations = "infix <+> 3 left = fn(a, b) { a + b };\
+                            infix ^^ 4 right = fn(a, b) { a * b };\
+                            infix |> 1 left = fn(x, f) { f(x) };";
+        let tests = vec![
+            ("1 <+> 2 <+> 3", "((1 <+> 2) <+> 3)"),
+            ("2 ^^ 3 ^^ 4", "(2 ^^ (3 ^^ 4))"),
+            ("1 + 2 <+> 3 * 4", "((1 + 2) <+> (3 * 4))"),
+            ("1 <+> 2 ^^ 3", "(1 <+> (2 ^^ 3))"),
+            ("a < b |> f", "((a < b) |> f)"),
+            ("x |> f == y", "((x |> f) == y)"),
+            ("-a <+> b(c)", "((-a) <+> b(c))"),
+        ];
+
+        for (input, expected) in tests {
+            let input = format!("{declarations}{input}");
+            let lexer = Lexer::new(input.as_str());
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(program.statements.len(), 4);
+            assert_eq!(program.statements[3].print_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_infix_declaration_parsing() {
+        let input = "infix <+> 3 left = fn(a, b) { a <+> b };";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+        check_parser_errors(parser);
+
+        assert_eq!(
+            program.print_string(),
+            "infix <+> 3 left = fn(a, b) (a <+> b);"
+        );
+
+        match &program.statements[0] {
+            StatementNode::Infix(infix) => {
+                assert_eq!(infix.precedence, 3);
+                assert_eq!(infix.associativity, Associativity::Left);
+                assert_eq!(infix.binding.name.print_string(), "<+>");
             }
-            other => panic!("not a Let Statement got {:?}", other),
+            other => panic!("expected an infix declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_infix_declaration_errors() {
+        let tests = vec![
+            (
+                "infix + 3 left = fn(a, b) { a };",
+                "can not redefine the built-in operator `+`",
+            ),
+            (
+                "infix ?? 1 left = fn(a, b) { a };",
+                "can not redefine the built-in operator `??`",
+            ),
+            (
+                "infix <+> 5 left = fn(a, b) { a };",
+                "precedence of `<+>` must be between 1 and 4, got 5",
+            ),
+            (
+                "infix <+> 3 up = fn(a, b) { a };",
+                "expected associativity `left` or `right`, got `up`",
+            ),
+            (
+                "infix <+> 3 left = fn(a, b) { a }; infix <+> 2 right = fn(a, b) { b };",
+                "operator `<+>` is already declared",
+            ),
+            (
+                "infix add 3 left = fn(a, b) { a };",
+                "expected next token to be Operator, got Ident intead",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn test_module_statements() {
+        let tests = vec![
+            ("import \"lib/vec\" as vec;", "import \"lib/vec\" as vec;"),
+            ("export let x = 5;", "export let x = 5;"),
+            ("export let [a, b] = pair;", "export let [a, b] = pair;"),
+            ("vec.add(1, 2)", "vec.add(1, 2)"),
+            ("a.b.c", "a.b.c"),
+            ("-m.x * 2", "((-m.x) * 2)"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(program.print_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_module_statement_errors() {
+        let tests = vec![
+            (
+                "import vec as v;",
+                "expected next token to be String, got Ident intead",
+            ),
+            (
+                "import \"vec\";",
+                "expected next token to be As, got Semicolon intead",
+            ),
+            (
+                "export fn() {};",
+                "expected next token to be Let, got Function intead",
+            ),
+            (
+                "let f = fn() { export let x = 1; };",
+                "export is only allowed at the top level of a module",
+            ),
+            (
+                "let f = fn() { import \"a\" as a; };",
+                "import is only allowed at the top level of a module",
+            ),
+            ("m.1", "expected next token to be Ident, got Int intead"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn test_struct_parsing() {
+        let tests = vec![
+            ("struct Point { x, y }", "struct Point { x, y }"),
+            ("struct Empty {};", "struct Empty {  }"),
+            ("struct P { x, y, }", "struct P { x, y }"),
+            ("Point { x: 1, y: 2 }", "Point { x: 1, y: 2 }"),
+            ("let p = P { x: 1 + 2, };", "let p = P { x: (1 + 2) };"),
+            ("p.x * p.y", "(p.x * p.y)"),
+            ("Line { a: P { x: 1 } }.a.x", "Line { a: P { x: 1 } }.a.x"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(program.print_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_struct_errors() {
+        let tests = vec![
+            (
+                "struct { x }",
+                "expected next token to be Ident, got Lbrace intead",
+            ),
+            ("struct P { x, x }", "duplicate field `x` in struct `P`"),
+            (
+                "struct P { 1 }",
+                "expected next token to be Ident, got Int intead",
+            ),
+            ("P { x 1 }", "expected next token to be :, got Int intead"),
+            (
+                "P { x: 1 y: 2 }",
+                "expected next token to be Comma, got Ident intead",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn test_enum_parsing() {
+        let tests = vec![
+            (
+                "enum Shape { Circle(r), Rect(w, h) }",
+                "enum Shape { Circle(r), Rect(w, h) }",
+            ),
+            (
+                "enum Option { None, Some(x), };",
+                "enum Option { None, Some(x) }",
+            ),
+            ("Rect(1, 2)", "Rect(1, 2)"),
+            (
+                "match (s) { Circle(r) => r, Rect(_, [h, ...t]) => h, other => 0 }",
+                "match (s) { Circle(r) => r, Rect(_, [h, ...t]) => h, other => 0 }",
+            ),
+            (
+                "enum Option { None, Some(x) } match (o) { Some(Some(x)) => x, None => 0 }",
+                "enum Option { None, Some(x) }match (o) { Some(Some(x)) => x, None => 0 }",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(program.print_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_variant_patterns() {
+        let input = "enum Option { None, Some(x) } match (o) { None => 0, Some(x) => x, y => y }";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+        check_parser_errors(parser);
+
+        let match_exp = match &program.statements[1] {
+            StatementNode::Expression(ExpressionStatement {
+                expression: Some(ExpressionNode::Match(match_exp)),
+                ..
+            }) => match_exp,
+            other => panic!("expected a match expression, got {:?}", other),
+        };
+
+        let patterns: Vec<&Pattern> = match_exp.arms.iter().map(|arm| &arm.pattern).collect();
+        assert!(matches!(patterns[0], Pattern::Variant(v) if v.payload.is_empty()));
+        assert!(matches!(patterns[1], Pattern::Variant(v) if v.payload.len() == 1));
+        assert!(matches!(patterns[2], Pattern::Binding(_)));
+    }
+
+    #[test]
+    fn test_variant_patterns_resolved_after_parsing() {
+        let tests = vec![
+            (
+                "match (o) { None => 0, [None, n] => n, y => y } enum Option { None, Some(x) }",
+                vec![],
+            ),
+            (
+                "match (o) { None => 0, [None, n] => n, y => y }",
+                vec!["None"],
+            ),
+        ];
+
+        for (input, variants) in tests {
+            let lexer = Lexer::new(input);
+            let variants = variants.into_iter().map(String::from).collect();
+            let mut parser = Parser::new(lexer).with_variants(variants);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(
+                program.statements[0].print_string(),
+                "match (o) { None => 0, [None, n] => n, y => y }",
+                "for {input}"
+            );
+            let match_exp = match &program.statements[0] {
+                StatementNode::Expression(ExpressionStatement {
+                    expression: Some(ExpressionNode::Match(match_exp)),
+                    ..
+                }) => match_exp,
+                other => panic!("expected a match expression, got {:?}", other),
+            };
+            let patterns: Vec<&Pattern> = match_exp.arms.iter().map(|arm| &arm.pattern).collect();
+            assert!(matches!(patterns[0], Pattern::Variant(_)), "for {input}");
+            assert!(
+                matches!(patterns[1], Pattern::Array(a) if matches!(a.elements[0], Pattern::Variant(_))),
+                "for {input}"
+            );
+            assert!(matches!(patterns[2], Pattern::Binding(_)), "for {input}");
+        }
+    }
+
+    #[test]
+    fn test_enum_errors() {
+        let tests = vec![
+            (
+                "enum { A }",
+                "expected next token to be Ident, got Lbrace intead",
+            ),
+            ("enum E { A, A(x) }", "duplicate variant `A` in enum `E`"),
+            (
+                "enum E { A(1) }",
+                "expected next token to be Ident, got Int intead",
+            ),
+            (
+                "enum E { A B }",
+                "expected next token to be Comma, got Ident intead",
+            ),
+            (
+                "let Some(x) = o;",
+                "let binding `Some(x)` can not destructure the variant pattern `Some(x)`",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn test_try_statements() {
+        let tests = vec![
+            ("throw \"oops\";", "throw \"oops\";"),
+            ("throw f(1)", "throw f(1);"),
+            (
+                "try { risky(); } catch (e) { puts(e); }",
+                "try { risky() } catch (e) { puts(e) }",
+            ),
+            (
+                "try { risky() } finally { close() }",
+                "try { risky() } finally { close() }",
+            ),
+            (
+                "try { throw 1; } catch (e) { throw e; } finally { 2 }",
+                "try { throw 1; } catch (e) { throw e; } finally { 2 }",
+            ),
+            (
+                "let f = fn() { try { return 1; } catch (e) { return 2; } };",
+                "let f = fn() try { return 1; } catch (e) { return 2; };",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(program.print_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_try_statement_errors() {
+        let tests = vec![
+            ("try { 1 }", "expected catch or finally after the try block"),
+            (
+                "try 1 catch (e) { 2 }",
+                "expected next token to be Lbrace, got Int intead",
+            ),
+            (
+                "try { 1 } catch e { 2 }",
+                "expected next token to be Lparen, got Ident intead",
+            ),
+            (
+                "try { 1 } catch (1) { 2 }",
+                "expected next token to be Ident, got Int intead",
+            ),
+            ("throw;", "no prefix parse function for Semicolon found"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn test_generator_statements() {
+        let tests = vec![
+            ("fn() { yield 1; yield 2 }", "fn() yield 1;yield 2;"),
+            ("for x in xs { puts(x); }", "for x in xs { puts(x) }"),
+            ("for p in points { p.x };", "for p in points { p.x }"),
+            (
+                "fn(rows) { for row in rows.next() { yield row * 2; } }",
+                "fn(rows) for row in rows.next() { yield (row * 2); }",
+            ),
+            (
+                "for x in (P { x: 1 }).x { x }",
+                "for x in P { x: 1 }.x { x }",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(program.print_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_generator_statement_errors() {
+        let tests = vec![
+            ("yield 1;", "yield is only allowed inside a function"),
+            (
+                "fn() { try { 1 } finally { 2 } }; yield 1;",
+                "yield is only allowed inside a function",
+            ),
+            (
+                "for 1 in xs { x }",
+                "expected next token to be Ident, got Int intead",
+            ),
+            (
+                "for x of xs { x }",
+                "expected next token to be In, got Ident intead",
+            ),
+            (
+                "for x in xs x",
+                "expected next token to be Lbrace, got Ident intead",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn test_range_errors() {
+        let tests = vec![
+            ("1..2..3", "ranges can not be chained"),
+            ("xs[1..=]", "an inclusive range `..=` needs an end"),
+            ("xs[1", "expected next token to be Rbracket, got Eof intead"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert!(
+                parser
+                    .errors()
+                    .iter()
+                    .any(|error| error.message == expected),
+                "expected error {:?} for {}, got = {:?}",
+                expected,
+                input,
+                parser.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn test_nesting_depth_limit() {
+        let depth = 100_000;
+        let nesting = format!("nesting exceeds the maximum depth of {}", DEFAULT_MAX_DEPTH);
+        let chain = format!("chain exceeds the maximum length of {}", DEFAULT_MAX_CHAIN);
+        let tests = vec![
+            (
+                format!("{}1{}", "(".repeat(depth), ")".repeat(depth)),
+                &nesting,
+            ),
+            (format!("{}1", "-".repeat(depth)), &nesting),
+            (format!("{}1", "!-".repeat(depth)), &nesting),
+            ("[".repeat(depth), &nesting),
+            (format!("let {}x = 1;", "[".repeat(depth)), &nesting),
+            (format!("let x: {}int = 1;", "[".repeat(depth)), &nesting),
+            ("fn() {".repeat(depth), &nesting),
+            ("match (x) { _ => ".repeat(depth), &nesting),
+            (format!("a{}", " + a".repeat(depth)), &chain),
+            (format!("f{}", "(1)".repeat(depth)), &chain),
+            (format!("x{}", ".a".repeat(depth)), &chain),
+            (format!("x{}", "[0]".repeat(depth)), &chain),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input.as_str());
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            let messages: Vec<&str> = parser
+                .errors()
+                .iter()
+                .map(|error| error.message.as_str())
+                .collect();
+            assert_eq!(
+                messages,
+                vec![expected.as_str()],
+                "for input starting with {:?}",
+                &input[..10]
+            );
+        }
+    }
+
+    #[test]
+    fn test_flat_chains_within_limits() {
+        let tests = vec![
+            format!("let x = 1{};", " + 1".repeat(129)),
+            format!("let s = \"a\"{};", " + \"a\"".repeat(199)),
+            format!("let y = f{};", "(1)".repeat(300)),
+            format!("let z = {}1{};", "(1 + ".repeat(100), ")".repeat(100)),
+        ];
+
+        for input in tests {
+            let lexer = Lexer::new(input.as_str());
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+            check_parser_errors(parser);
+        }
+    }
+
+    #[test]
+    fn test_long_chains_stay_shallow() {
+        let depth = 100_000;
+        let tests = vec![
+            format!("let y = a{};", " + a".repeat(depth)),
+            format!("f{};", "(1)".repeat(depth)),
+            format!("x{};", ".a".repeat(depth)),
+        ];
+
+        for input in tests {
+            let lexer = Lexer::new(input.as_str());
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            assert_eq!(parser.errors().len(), 1, "for input {:?}", &input[..10]);
+
+            // whatever was parsed before the limit must be safe to walk and
+            // drop, on a stack like the binary's
+            thread::Builder::new()
+                .stack_size(64 * 1024 * 1024)
+                .spawn(move || {
+                    check_program(&program);
+                    format_program(&program, &Config::default());
+                    drop(program);
+                })
+                .unwrap()
+                .join()
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_nesting_within_depth_limit() {
+        let input = format!("{}1{};", "(".repeat(3), ")".repeat(3));
+
+        let lexer = Lexer::new(input.as_str());
+        let mut parser = Parser::new(lexer).with_max_depth(4);
+        let program = parser.parse_program().unwrap();
+        check_parser_errors(parser);
+        assert_eq!(program.print_string(), "1");
+
+        let input = format!("{}1{};", "(".repeat(4), ")".repeat(4));
+
+        let lexer = Lexer::new(input.as_str());
+        let mut parser = Parser::new(lexer).with_max_depth(4);
+        parser.parse_program();
+        assert_eq!(
+            parser.errors()[0].to_string(),
+            "1:5-6: nesting exceeds the maximum depth of 4"
+        );
+    }
+
+    #[test]
+    fn test_whole_trees() {
+        let tests = vec![
+            ("let x = 5;", vec![let_stmt("x", int(5))]),
+            (
+                "const add = fn(a, b) { return a + b; };",
+                vec![const_stmt(
+                    "add",
+                    function(
+                        &["a", "b"],
+                        vec![return_stmt(infix(ident("a"), "+", ident("b")))],
+                    ),
+                )],
+            ),
+            (
+                "-a * (b + c)",
+                vec![expr_stmt(infix(
+                    prefix("-", ident("a")),
+                    "*",
+                    infix(ident("b"), "+", ident("c")),
+                ))],
+            ),
+            (
+                r#"xs[0].name ?? "none"; total = !done"#,
+                vec![
+                    expr_stmt(infix(
+                        member(index(ident("xs"), int(0)), "name"),
+                        "??",
+                        string("none"),
+                    )),
+                    expr_stmt(assign("total", prefix("!", ident("done")))),
+                ],
+            ),
+            (
+                r#"puts([1, true], {"k": null})"#,
+                vec![expr_stmt(call(
+                    ident("puts"),
+                    vec![
+                        array(vec![int(1), boolean(true)]),
+                        hash(vec![(string("k"), null())]),
+                    ],
+                ))],
+            ),
+        ];
+
+        for (input, statements) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let parsed = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            assert_eq!(parsed, program(statements), "wrong tree for {}", input);
+        }
+    }
+
+    #[test]
+    fn test_node_spans() {
+        // input, span of the statement, span of its expression or value
+        let tests = vec![
+            ("-a + b", "1:1-7", "1:1-7"),
+            ("(a + b) * c;", "1:1-13", "1:1-12"),
+            ("(a + b)", "1:1-8", "1:2-7"),
+            ("!(a)", "1:1-5", "1:1-5"),
+            ("let x = 5;", "1:1-11", "1:9-10"),
+            ("let [a, b] = f(x)", "1:1-18", "1:14-18"),
+            ("return f(x, y);", "1:1-16", "1:8-15"),
+            ("xs[1..3]", "1:1-9", "1:1-9"),
+            ("a?.b.c", "1:1-7", "1:1-7"),
+            ("x = y = 1", "1:1-10", "1:1-10"),
+            ("Point { x: 1 }", "1:1-15", "1:1-15"),
+            ("[1, 2]", "1:1-7", "1:1-7"),
+            (r#"{"a": 1}"#, "1:1-9", "1:1-9"),
+            ("match (x) { _ => 1 }", "1:1-21", "1:1-21"),
+            ("fn(x) {\n  x\n}", "1:1-3:2", "1:1-3:2"),
+            ("macro(x) { x }", "1:1-15", "1:1-15"),
+            ("throw e;", "1:1-9", ""),
+            ("for x in xs { f(x) }", "1:1-21", ""),
+            ("try { a } catch (e) { b }", "1:1-26", ""),
+            ("export let x = 1;", "1:1-18", ""),
+            (r#"import "a" as a"#, "1:1-16", ""),
+            ("struct P { x }", "1:1-15", ""),
+            ("enum O { None, Some(x) }", "1:1-25", ""),
+            ("infix <+> 3 left = fn(a, b) { a };", "1:1-35", ""),
+        ];
+
+        for (input, stmt_span, exp_span) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            check_parser_errors(parser);
+
+            let stmt = &program.statements[0];
+            let exp = match stmt {
+                StatementNode::Expression(exp_stmt) => exp_stmt.expression.as_ref(),
+                StatementNode::Let(let_stmt) => let_stmt.value.as_ref(),
+                StatementNode::Return(ret_stmt) => ret_stmt.ret_value.as_ref(),
+                _ => None,
+            };
+
+            assert_eq!(stmt.span().to_string(), stmt_span, "statement of {}", input);
+            assert_eq!(
+                exp.map_or(String::new(), |exp| exp.span().to_string()),
+                exp_span,
+                "expression of {}",
+                input
+            );
         }
     }
 
+    #[test]
+    fn test_nested_node_spans() {
+        let input = "let f = fn(x) {\n  for y in x { 1 + y }\n};\nexport const n = 2;";
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+        check_parser_errors(parser);
+
+        let StatementNode::Let(LetStatement {
+            value: Some(ExpressionNode::Function(func)),
+            ..
+        }) = &program.statements[0]
+        else {
+            panic!(
+                "expected a function binding, got {:?}",
+                program.statements[0]
+            );
+        };
+        assert_eq!(func.body.span.to_string(), "1:15-3:2");
+
+        let StatementNode::For(for_stmt) = &func.body.statements[0] else {
+            panic!("expected a for loop, got {:?}", func.body.statements[0]);
+        };
+        assert_eq!(for_stmt.span.to_string(), "2:3-23");
+        assert_eq!(for_stmt.body.span.to_string(), "2:14-23");
+
+        let StatementNode::Export(export) = &program.statements[1] else {
+            panic!("expected an export, got {:?}", program.statements[1]);
+        };
+        assert_eq!(export.span.to_string(), "4:1-20");
+        assert_eq!(export.binding.span.to_string(), "4:8-20");
+    }
+
     fn check_parser_errors(parser: Parser) {
         let errors = parser.errors();
 
-        if errors.len() == 0 {
+        if errors.is_empty() {
             return;
         }
 
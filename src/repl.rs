@@ -1,29 +1,108 @@
-use std::io::{Stdin, Stdout, Write};
+use std::{
+    collections::HashSet,
+    io::{self, Stdin, Stdout, Write},
+};
 
-use crate::{
-    lexer::{self, Lexer},
-    token::TokenKind,
+use interpreter_kurs::{
+    check, dump::Format, eval::Evaluator, macro_expansion, optimize, Lexer, Parser,
 };
 
-pub fn start(stdin: Stdin, mut stdout: Stdout) {
+/// Reads, checks and runs one line at a time, the bindings of each line stay
+/// in scope for the next.
+///
+/// A line is checked together with the lines run before it, and does not run
+/// if the checks find errors other than type inference ones, like `run`.
+/// A line that runs is optimized first.
+pub fn start(stdin: Stdin, mut stdout: Stdout, max_call_depth: usize, max_stack: usize) {
+    let mut evaluator = Evaluator::new(io::stdout())
+        .with_max_call_depth(max_call_depth)
+        .with_max_stack(max_stack);
+    let mut session = check::Session::default();
+    // enum variants declared on the lines run so far
+    let mut variants = HashSet::new();
+
     loop {
         write!(stdout, ">> ").expect("");
         stdout.flush().expect("should have flushed stdouut");
 
         let mut input = String::new();
 
-        if let Err(e) = stdin.read_line(&mut input) {
-            write!(stdout, "Error: {e}").expect("should have written error message");
+        match stdin.read_line(&mut input) {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(e) => write!(stdout, "Error: {e}").expect("should have written error message"),
+        }
+
+        // `:sexpr`, `:dot` or `:json` in front of the code dumps its tree instead
+        let (format, code) = match input.strip_prefix(':') {
+            Some(command) => {
+                let (name, code) = command.split_once(' ').unwrap_or((command.trim(), ""));
+                match Format::from_name(name) {
+                    Some(format) => (Some(format), code),
+                    None => {
+                        writeln!(
+                            stdout,
+                            "\tunknown command `:{name}`, try :sexpr, :dot or :json"
+                        )
+                        .expect("error should have been written");
+                        continue;
+                    }
+                }
+            }
+            None => (None, input.as_str()),
+        };
+
+        let mut parser = Parser::new(Lexer::new(code)).with_variants(variants.clone());
+        let program = match parser.parse_program() {
+            Some(program) if parser.errors().is_empty() => program,
+            _ => {
+                for error in parser.errors() {
+                    writeln!(stdout, "\t{error}").expect("error should have been written");
+                }
+                continue;
+            }
+        };
+
+        let program = match macro_expansion::expand_macros(program) {
+            Ok(program) => program,
+            Err(errors) => {
+                for error in errors {
+                    writeln!(stdout, "\terror: {error}").expect("error should have been written");
+                }
+                continue;
+            }
+        };
+
+        let report = session.check(&program);
+        let failed = !report.errors.is_empty();
+        for error in report.errors {
+            writeln!(stdout, "\terror: {error}").expect("error should have been written");
+        }
+        for warning in report.type_errors.into_iter().chain(report.warnings) {
+            writeln!(stdout, "\twarning: {warning}").expect("warning should have been written");
+        }
+        if let Some(format) = format {
+            writeln!(stdout, "{}", format.render(&program)).expect("dump should have been written");
+            continue;
+        }
+
+        if failed {
+            continue;
         }
+        session.accept();
+        variants.clone_from(parser.variants());
 
-        let mut lexer = Lexer::new(input.as_str());
+        // values borrow the code of their functions, which has to outlive them
+        let program = Box::leak(Box::new(optimize::optimize_program(program)));
 
-        loop {
-            let token = lexer.next_token();
-            if token.kind == TokenKind::Eof {
-                break;
+        match evaluator.eval_program(program) {
+            Ok(value) => writeln!(stdout, "{value}").expect("value should have been written"),
+            Err(error) => {
+                writeln!(stdout, "\terror: {error}").expect("error should have been written");
+                for entry in error.stack {
+                    writeln!(stdout, "\t\tat {entry}").expect("stack should have been written");
+                }
             }
-            writeln!(stdout, "{token:?}").expect("Token should haven been written");
         }
     }
 }
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use crate::ast::{
+    walk_block, walk_expression, walk_statement, BlockStatement, ExpressionNode, Identifier,
+    LetStatement, Pattern, Program, StatementNode, Visitor,
+};
+
+/// Resolves every assignment to the binding it targets and returns an error
+/// for each one that targets a `const`, naming both the assignment and the
+/// declaration.
+///
+/// Names that are not declared in the program, like builtins, are skipped.
+/// Top-level bindings are declared before anything is resolved, a function
+/// may be called only after a binding below it.
+pub fn check_program(program: &Program) -> Vec<String> {
+    let mut resolver = Resolver {
+        scopes: vec![HashMap::new()],
+        errors: vec![],
+    };
+
+    for stmt in &program.statements {
+        resolver.hoist(stmt);
+    }
+    resolver.visit_program(program);
+
+    resolver.errors
+}
+
+struct Resolver<'a> {
+    /// Every binding in scope, with the declaring identifier of the `const` ones.
+    scopes: Vec<HashMap<&'a str, Option<&'a Identifier>>>,
+    errors: Vec<String>,
+}
+
+impl<'a> Visitor<'a> for Resolver<'a> {
+    fn visit_statement(&mut self, stmt: &'a StatementNode) {
+        match stmt {
+            StatementNode::Let(let_stmt) => self.resolve_let(let_stmt),
+            StatementNode::Infix(infix) => self.resolve_let(&infix.binding),
+            StatementNode::Export(export) => self.resolve_let(&export.binding),
+            StatementNode::Import(import) => self.declare(&import.alias, false),
+            StatementNode::Enum(declaration) => {
+                for variant in &declaration.variants {
+                    self.declare(&variant.name, false);
+                }
+            }
+            StatementNode::For(for_stmt) => {
+                self.visit_expression(&for_stmt.iterable);
+                self.scoped(|resolver| {
+                    resolver.declare(&for_stmt.variable, false);
+                    walk_block(resolver, &for_stmt.body);
+                });
+            }
+            StatementNode::Try(try_stmt) => {
+                self.visit_block(&try_stmt.body);
+                if let Some(catch) = &try_stmt.catch {
+                    self.scoped(|resolver| {
+                        resolver.declare(&catch.parameter, false);
+                        walk_block(resolver, &catch.body);
+                    });
+                }
+                if let Some(finally) = &try_stmt.finally {
+                    self.visit_block(finally);
+                }
+            }
+            _ => walk_statement(self, stmt),
+        }
+    }
+
+    fn visit_block(&mut self, block: &'a BlockStatement) {
+        self.scoped(|resolver| walk_block(resolver, block));
+    }
+
+    fn visit_expression(&mut self, expression: &'a ExpressionNode) {
+        match expression {
+            ExpressionNode::Assign(assign) => {
+                walk_expression(self, expression);
+                if let Some(declaration) = self.lookup(&assign.name.value) {
+                    let msg = format!(
+                        "{}: can not assign to const `{}`, it is declared at {}",
+                        assign.name.token.span, assign.name.value, declaration.token.span
+                    );
+                    self.errors.push(msg);
+                }
+            }
+            ExpressionNode::Function(func) => self.scoped(|resolver| {
+                for param in &func.parameters {
+                    if let Some(default) = &param.default {
+                        resolver.visit_expression(default);
+                    }
+                    resolver.declare(&param.name, false);
+                }
+                walk_block(resolver, &func.body);
+            }),
+            ExpressionNode::Match(match_exp) => {
+                self.visit_expression(&match_exp.subject);
+                for arm in &match_exp.arms {
+                    self.scoped(|resolver| {
+                        resolver.declare_pattern(&arm.pattern, false);
+                        if let Some(guard) = &arm.guard {
+                            resolver.visit_expression(guard);
+                        }
+                        resolver.visit_expression(&arm.body);
+                    });
+                }
+            }
+            _ => walk_expression(self, expression),
+        }
+    }
+}
+
+impl<'a> Resolver<'a> {
+    /// Declares the bindings of a top-level statement without resolving it.
+    fn hoist(&mut self, stmt: &'a StatementNode) {
+        let let_stmt = match stmt {
+            StatementNode::Let(let_stmt) => let_stmt,
+            StatementNode::Infix(infix) => &infix.binding,
+            StatementNode::Export(export) => &export.binding,
+            _ => return,
+        };
+        self.declare_pattern(&let_stmt.name, let_stmt.is_const());
+    }
+
+    fn resolve_let(&mut self, let_stmt: &'a LetStatement) {
+        let constant = let_stmt.is_const();
+
+        // a function sees its own name, so it can not assign to itself either
+        if let Some(value @ ExpressionNode::Function(_)) = &let_stmt.value {
+            self.declare_pattern(&let_stmt.name, constant);
+            self.visit_expression(value);
+            return;
+        }
+
+        if let Some(value) = &let_stmt.value {
+            self.visit_expression(value);
+        }
+        self.declare_pattern(&let_stmt.name, constant);
+    }
+
+    /// Runs `resolve` in a new scope, whose bindings end with it.
+    fn scoped(&mut self, resolve: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        resolve(self);
+        self.scopes.pop();
+    }
+
+    fn declare_pattern(&mut self, pattern: &'a Pattern, constant: bool) {
+        for name in pattern.bindings() {
+            self.declare(name, constant);
+        }
+    }
+
+    fn declare(&mut self, name: &'a Identifier, constant: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(&name.value, constant.then_some(name));
+        }
+    }
+
+    /// The `const` declaration the innermost binding of `name` comes from, if any.
+    fn lookup(&self, name: &str) -> Option<&'a Identifier> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{lexer::Lexer, parser::Parser};
+
+    use super::check_program;
+
+    fn errors_for(input: &str) -> Vec<String> {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert!(
+            parser.errors().is_empty(),
+            "parser errors: {:?}",
+            parser.errors()
+        );
+
+        check_program(&program)
+    }
+
+    #[test]
+    fn test_valid_assignments() {
+        let tests = vec![
+            "let x = 1; x = 2;",
+            "const x = 1; let x = 2; x = 3;",
+            "const x = 1; let f = fn(x) { x = 2; };",
+            "const x = 1; for x in [1, 2] { x = 3; }",
+            "const x = 1; match (5) { x => x = 2 };",
+            "const e = 1; try { throw 1; } catch (e) { e = 2; }",
+            "let y = 0; let f = fn() { y = 1; };",
+            "undeclared = 5;",
+            "const x = 1; let x = 2; let f = fn() { x = 3; };",
+            "let f = fn() { x = 1; }; let x = 0;",
+        ];
+
+        for input in tests {
+            let errors = errors_for(input);
+            assert!(
+                errors.is_empty(),
+                "unexpected errors for {}: {:?}",
+                input,
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn test_const_reassignment_names_both_sites() {
+        let tests = vec![
+            (
+                "const x = 1;\nx = 2;",
+                "2:1-2: can not assign to const `x`, it is declared at 1:7-8",
+            ),
+            (
+                "const x = 1; let f = fn() { x = 2; };",
+                "1:29-30: can not assign to const `x`, it is declared at 1:7-8",
+            ),
+            (
+                "const [a, b] = [1, 2]; b = 3;",
+                "1:24-25: can not assign to const `b`, it is declared at 1:11-12",
+            ),
+            (
+                "const f = fn() { f = 1; };",
+                "1:18-19: can not assign to const `f`, it is declared at 1:7-8",
+            ),
+            (
+                "export const n = 1; try { n = 2; } finally { 1 }",
+                "1:27-28: can not assign to const `n`, it is declared at 1:14-15",
+            ),
+            (
+                "let x = 0; const y = 1; x = y = 2;",
+                "1:29-30: can not assign to const `y`, it is declared at 1:18-19",
+            ),
+            (
+                "let f = fn() { c = 2; }; const c = 1; f();",
+                "1:16-17: can not assign to const `c`, it is declared at 1:32-33",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let errors = errors_for(input);
+            assert_eq!(errors, vec![expected], "wrong errors for {}", input);
+        }
+    }
+}
@@ -0,0 +1,1155 @@
+use crate::{
+    ast::{
+        Argument, ArrayLiteral, ArrayPattern, ArrayType, AssignExpression, Associativity,
+        BlockStatement, BooleanLiteral, CallExpression, CatchClause, EnumDeclaration,
+        ExportStatement, ExpressionNode, ExpressionStatement, ForStatement, FunctionLiteral,
+        FunctionType, HashLiteral, HashPattern, HashType, Identifier, IfExpression,
+        ImportStatement, IndexExpression, InfixDeclaration, InfixExpression, IntegerLiteral,
+        LetStatement, MacroLiteral, MatchArm, MatchExpression, MemberExpression, NullLiteral,
+        Parameter, Pattern, PrefixExpression, Program, RangeExpression, ReturnStatement,
+        StatementNode, StringLiteral, StructDeclaration, StructLiteral, ThrowStatement,
+        TryStatement, TypeExpr, Variant, VariantPattern, YieldStatement,
+    },
+    json::Json,
+    token::{Position, Span, Token, TokenKind},
+};
+
+/// Encodes the program as JSON.
+///
+/// Every node is an object with a `type` tag naming its struct, its fields,
+/// the `span` it covers and the `token` itself, so that [`from_json`] can
+/// rebuild the exact same tree.
+pub fn to_json(program: &Program) -> Json {
+    object(vec![
+        ("type", Json::String(String::from("Program"))),
+        ("statements", list(&program.statements, statement)),
+    ])
+}
+
+/// Rebuilds a program from the JSON made by [`to_json`].
+pub fn from_json(json: &Json) -> Result<Program, String> {
+    expect_type(json, "Program")?;
+
+    Ok(Program {
+        statements: decode_list(json, "statements", decode_statement)?,
+    })
+}
+
+fn object(pairs: Vec<(&str, Json)>) -> Json {
+    Json::Object(
+        pairs
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+    )
+}
+
+fn list<T>(items: &[T], encode: impl Fn(&T) -> Json) -> Json {
+    Json::Array(items.iter().map(encode).collect())
+}
+
+fn optional<T>(item: Option<&T>, encode: impl Fn(&T) -> Json) -> Json {
+    item.map_or(Json::Null, encode)
+}
+
+fn string(value: &str) -> Json {
+    Json::String(value.to_string())
+}
+
+/// A node that covers no more source than its token.
+fn node(type_name: &str, token: &Token, fields: Vec<(&str, Json)>) -> Json {
+    spanned(type_name, token, &token.span, fields)
+}
+
+/// A node with its `type`, `span`, `fields` and `token`, in that order.
+fn spanned(type_name: &str, token: &Token, node_span: &Span, fields: Vec<(&str, Json)>) -> Json {
+    let mut pairs = vec![("type", string(type_name)), ("span", span(node_span))];
+    pairs.extend(fields);
+    pairs.push((
+        "token",
+        object(vec![
+            ("kind", string(&format!("{:?}", token.kind))),
+            ("literal", string(&token.literal)),
+            ("span", span(&token.span)),
+        ]),
+    ));
+
+    object(pairs)
+}
+
+fn span(span: &Span) -> Json {
+    let position = |position: &Position| {
+        object(vec![
+            ("line", Json::Number(position.line as i64)),
+            ("column", Json::Number(position.column as i64)),
+        ])
+    };
+
+    object(vec![
+        ("start", position(&span.start)),
+        ("end", position(&span.end)),
+    ])
+}
+
+fn statement(stmt: &StatementNode) -> Json {
+    match stmt {
+        StatementNode::Let(let_stmt) => let_statement(let_stmt),
+        StatementNode::Return(ret_stmt) => spanned(
+            "ReturnStatement",
+            &ret_stmt.token,
+            &ret_stmt.span,
+            vec![("value", optional(ret_stmt.ret_value.as_ref(), expression))],
+        ),
+        StatementNode::Expression(exp_stmt) => spanned(
+            "ExpressionStatement",
+            &exp_stmt.token,
+            &exp_stmt.span,
+            vec![(
+                "expression",
+                optional(exp_stmt.expression.as_ref(), expression),
+            )],
+        ),
+        StatementNode::Infix(infix) => spanned(
+            "InfixDeclaration",
+            &infix.token,
+            &infix.span,
+            vec![
+                ("precedence", Json::Number(infix.precedence as i64)),
+                (
+                    "associativity",
+                    string(match infix.associativity {
+                        Associativity::Left => "left",
+                        Associativity::Right => "right",
+                    }),
+                ),
+                ("binding", let_statement(&infix.binding)),
+            ],
+        ),
+        StatementNode::Import(import) => spanned(
+            "ImportStatement",
+            &import.token,
+            &import.span,
+            vec![
+                ("path", string_literal(&import.path)),
+                ("alias", identifier(&import.alias)),
+            ],
+        ),
+        StatementNode::Export(export) => spanned(
+            "ExportStatement",
+            &export.token,
+            &export.span,
+            vec![("binding", let_statement(&export.binding))],
+        ),
+        StatementNode::Struct(declaration) => spanned(
+            "StructDeclaration",
+            &declaration.token,
+            &declaration.span,
+            vec![
+                ("name", identifier(&declaration.name)),
+                ("fields", list(&declaration.fields, identifier)),
+            ],
+        ),
+        StatementNode::Enum(declaration) => spanned(
+            "EnumDeclaration",
+            &declaration.token,
+            &declaration.span,
+            vec![
+                ("name", identifier(&declaration.name)),
+                (
+                    "variants",
+                    list(&declaration.variants, |variant| {
+                        object(vec![
+                            ("type", string("Variant")),
+                            ("name", identifier(&variant.name)),
+                            ("fields", list(&variant.fields, identifier)),
+                        ])
+                    }),
+                ),
+            ],
+        ),
+        StatementNode::Throw(throw) => spanned(
+            "ThrowStatement",
+            &throw.token,
+            &throw.span,
+            vec![("value", expression(&throw.value))],
+        ),
+        StatementNode::Try(try_stmt) => spanned(
+            "TryStatement",
+            &try_stmt.token,
+            &try_stmt.span,
+            vec![
+                ("body", block(&try_stmt.body)),
+                (
+                    "catch",
+                    optional(try_stmt.catch.as_ref(), |catch| {
+                        object(vec![
+                            ("type", string("CatchClause")),
+                            ("parameter", identifier(&catch.parameter)),
+                            ("body", block(&catch.body)),
+                        ])
+                    }),
+                ),
+                ("finally", optional(try_stmt.finally.as_ref(), block)),
+            ],
+        ),
+        StatementNode::Yield(yield_stmt) => spanned(
+            "YieldStatement",
+            &yield_stmt.token,
+            &yield_stmt.span,
+            vec![("value", expression(&yield_stmt.value))],
+        ),
+        StatementNode::For(for_stmt) => spanned(
+            "ForStatement",
+            &for_stmt.token,
+            &for_stmt.span,
+            vec![
+                ("variable", identifier(&for_stmt.variable)),
+                ("iterable", expression(&for_stmt.iterable)),
+                ("body", block(&for_stmt.body)),
+            ],
+        ),
+    }
+}
+
+fn let_statement(let_stmt: &LetStatement) -> Json {
+    spanned(
+        "LetStatement",
+        &let_stmt.token,
+        &let_stmt.span,
+        vec![
+            ("name", pattern(&let_stmt.name)),
+            (
+                "annotation",
+                optional(let_stmt.annotation.as_ref(), type_expr),
+            ),
+            ("value", optional(let_stmt.value.as_ref(), expression)),
+        ],
+    )
+}
+
+fn block(block: &BlockStatement) -> Json {
+    spanned(
+        "BlockStatement",
+        &block.token,
+        &block.span,
+        vec![("statements", list(&block.statements, statement))],
+    )
+}
+
+fn identifier(ident: &Identifier) -> Json {
+    node(
+        "Identifier",
+        &ident.token,
+        vec![("value", string(&ident.value))],
+    )
+}
+
+fn string_literal(literal: &StringLiteral) -> Json {
+    node(
+        "StringLiteral",
+        &literal.token,
+        vec![("value", string(&literal.value))],
+    )
+}
+
+fn integer_literal(literal: &IntegerLiteral) -> Json {
+    node(
+        "IntegerLiteral",
+        &literal.token,
+        vec![("value", Json::Number(literal.value))],
+    )
+}
+
+fn boolean_literal(literal: &BooleanLiteral) -> Json {
+    node(
+        "BooleanLiteral",
+        &literal.token,
+        vec![("value", Json::Bool(literal.value))],
+    )
+}
+
+fn expression(expression: &ExpressionNode) -> Json {
+    match expression {
+        ExpressionNode::IdentifierNode(ident) => identifier(ident),
+        ExpressionNode::Integer(int) => integer_literal(int),
+        ExpressionNode::Boolean(boolean) => boolean_literal(boolean),
+        ExpressionNode::StringNode(string) => string_literal(string),
+        ExpressionNode::Null(null) => node("NullLiteral", &null.token, vec![]),
+        ExpressionNode::Array(array) => spanned(
+            "ArrayLiteral",
+            &array.token,
+            &array.span,
+            vec![("elements", list(&array.elements, self::expression))],
+        ),
+        ExpressionNode::Hash(hash) => spanned(
+            "HashLiteral",
+            &hash.token,
+            &hash.span,
+            vec![(
+                "pairs",
+                list(&hash.pairs, |(key, value)| {
+                    object(vec![
+                        ("key", self::expression(key)),
+                        ("value", self::expression(value)),
+                    ])
+                }),
+            )],
+        ),
+        ExpressionNode::Struct(literal) => spanned(
+            "StructLiteral",
+            &literal.token,
+            &literal.span,
+            vec![
+                ("name", identifier(&literal.name)),
+                (
+                    "fields",
+                    list(&literal.fields, |(name, value)| {
+                        object(vec![
+                            ("name", identifier(name)),
+                            ("value", self::expression(value)),
+                        ])
+                    }),
+                ),
+            ],
+        ),
+        ExpressionNode::Prefix(prefix) => spanned(
+            "PrefixExpression",
+            &prefix.token,
+            &prefix.span,
+            vec![
+                ("operator", string(&prefix.operator)),
+                ("right", self::expression(&prefix.right)),
+            ],
+        ),
+        ExpressionNode::Infix(infix) => spanned(
+            "InfixExpression",
+            &infix.token,
+            &infix.span,
+            vec![
+                ("left", self::expression(&infix.left)),
+                ("operator", string(&infix.operator)),
+                ("right", self::expression(&infix.right)),
+            ],
+        ),
+        ExpressionNode::Assign(assign) => spanned(
+            "AssignExpression",
+            &assign.token,
+            &assign.span,
+            vec![
+                ("name", identifier(&assign.name)),
+                ("value", self::expression(&assign.value)),
+            ],
+        ),
+        ExpressionNode::Function(func) => spanned(
+            "FunctionLiteral",
+            &func.token,
+            &func.span,
+            vec![
+                (
+                    "parameters",
+                    list(&func.parameters, |param| {
+                        object(vec![
+                            ("type", string("Parameter")),
+                            ("name", identifier(&param.name)),
+                            ("annotation", optional(param.annotation.as_ref(), type_expr)),
+                            (
+                                "default",
+                                optional(param.default.as_ref(), self::expression),
+                            ),
+                            ("rest", Json::Bool(param.rest)),
+                        ])
+                    }),
+                ),
+                (
+                    "return_type",
+                    optional(func.return_type.as_ref(), type_expr),
+                ),
+                ("body", block(&func.body)),
+            ],
+        ),
+        ExpressionNode::Macro(macro_lit) => spanned(
+            "MacroLiteral",
+            &macro_lit.token,
+            &macro_lit.span,
+            vec![
+                ("parameters", list(&macro_lit.parameters, identifier)),
+                ("body", block(&macro_lit.body)),
+            ],
+        ),
+        ExpressionNode::Call(call) => spanned(
+            "CallExpression",
+            &call.token,
+            &call.span,
+            vec![
+                ("function", self::expression(&call.function)),
+                (
+                    "arguments",
+                    list(&call.arguments, |arg| {
+                        object(vec![
+                            ("type", string("Argument")),
+                            ("name", optional(arg.name.as_ref(), identifier)),
+                            ("value", self::expression(&arg.value)),
+                        ])
+                    }),
+                ),
+            ],
+        ),
+        ExpressionNode::Member(member) => spanned(
+            "MemberExpression",
+            &member.token,
+            &member.span,
+            vec![
+                ("object", self::expression(&member.object)),
+                ("property", identifier(&member.property)),
+                ("optional", Json::Bool(member.optional)),
+            ],
+        ),
+        ExpressionNode::Index(index) => spanned(
+            "IndexExpression",
+            &index.token,
+            &index.span,
+            vec![
+                ("left", self::expression(&index.left)),
+                ("index", self::expression(&index.index)),
+                ("optional", Json::Bool(index.optional)),
+            ],
+        ),
+        ExpressionNode::Range(range) => spanned(
+            "RangeExpression",
+            &range.token,
+            &range.span,
+            vec![
+                ("start", optional(range.start.as_deref(), self::expression)),
+                ("end", optional(range.end.as_deref(), self::expression)),
+                ("inclusive", Json::Bool(range.inclusive)),
+            ],
+        ),
+        ExpressionNode::Match(match_exp) => spanned(
+            "MatchExpression",
+            &match_exp.token,
+            &match_exp.span,
+            vec![
+                ("subject", self::expression(&match_exp.subject)),
+                (
+                    "arms",
+                    list(&match_exp.arms, |arm| {
+                        object(vec![
+                            ("type", string("MatchArm")),
+                            ("pattern", pattern(&arm.pattern)),
+                            ("guard", optional(arm.guard.as_ref(), self::expression)),
+                            ("body", self::expression(&arm.body)),
+                        ])
+                    }),
+                ),
+            ],
+        ),
+        ExpressionNode::If(if_exp) => spanned(
+            "IfExpression",
+            &if_exp.token,
+            &if_exp.span,
+            vec![
+                ("condition", self::expression(&if_exp.condition)),
+                ("consequence", block(&if_exp.consequence)),
+                ("alternative", optional(if_exp.alternative.as_ref(), block)),
+            ],
+        ),
+    }
+}
+
+fn pattern(pattern: &Pattern) -> Json {
+    match pattern {
+        Pattern::Integer(int) => integer_literal(int),
+        Pattern::Boolean(boolean) => boolean_literal(boolean),
+        Pattern::StringNode(string) => string_literal(string),
+        Pattern::Binding(ident) => identifier(ident),
+        Pattern::Wildcard(token) => node("Wildcard", token, vec![]),
+        Pattern::Array(array) => node(
+            "ArrayPattern",
+            &array.token,
+            vec![
+                ("elements", list(&array.elements, self::pattern)),
+                ("rest", optional(array.rest.as_ref(), identifier)),
+            ],
+        ),
+        Pattern::Hash(hash) => node(
+            "HashPattern",
+            &hash.token,
+            vec![(
+                "pairs",
+                list(&hash.pairs, |(key, value)| {
+                    object(vec![
+                        ("key", string_literal(key)),
+                        ("pattern", self::pattern(value)),
+                    ])
+                }),
+            )],
+        ),
+        Pattern::Variant(variant) => object(vec![
+            ("type", string("VariantPattern")),
+            ("name", identifier(&variant.name)),
+            ("payload", list(&variant.payload, self::pattern)),
+        ]),
+    }
+}
+
+fn type_expr(type_expr: &TypeExpr) -> Json {
+    match type_expr {
+        TypeExpr::Named(name) => identifier(name),
+        TypeExpr::Array(array) => node(
+            "ArrayType",
+            &array.token,
+            vec![("element", self::type_expr(&array.element))],
+        ),
+        TypeExpr::Hash(hash) => node(
+            "HashType",
+            &hash.token,
+            vec![
+                ("key", self::type_expr(&hash.key)),
+                ("value", self::type_expr(&hash.value)),
+            ],
+        ),
+        TypeExpr::Function(func) => node(
+            "FunctionType",
+            &func.token,
+            vec![
+                ("parameters", list(&func.parameters, self::type_expr)),
+                ("return_type", self::type_expr(&func.return_type)),
+            ],
+        ),
+    }
+}
+
+fn type_name(json: &Json) -> Result<&str, String> {
+    match json.get("type") {
+        Some(Json::String(name)) => Ok(name),
+        _ => Err(format!("expected a node with a `type`, got {}", json)),
+    }
+}
+
+fn expect_type(json: &Json, expected: &str) -> Result<(), String> {
+    match type_name(json)? {
+        name if name == expected => Ok(()),
+        other => Err(format!("expected a {} node, got {}", expected, other)),
+    }
+}
+
+fn field<'j>(json: &'j Json, key: &str) -> Result<&'j Json, String> {
+    json.get(key).ok_or_else(|| {
+        let name = type_name(json).unwrap_or("node");
+        format!("{} is missing `{}`", name, key)
+    })
+}
+
+fn mismatch(json: &Json, key: &str, expected: &str) -> String {
+    let name = type_name(json).unwrap_or("node");
+    format!("expected `{}` of {} to be {}", key, name, expected)
+}
+
+fn decode_string(json: &Json, key: &str) -> Result<String, String> {
+    match field(json, key)? {
+        Json::String(value) => Ok(value.clone()),
+        _ => Err(mismatch(json, key, "a string")),
+    }
+}
+
+fn decode_number(json: &Json, key: &str) -> Result<i64, String> {
+    match field(json, key)? {
+        Json::Number(value) => Ok(*value),
+        _ => Err(mismatch(json, key, "a number")),
+    }
+}
+
+fn decode_bool(json: &Json, key: &str) -> Result<bool, String> {
+    match field(json, key)? {
+        Json::Bool(value) => Ok(*value),
+        _ => Err(mismatch(json, key, "a boolean")),
+    }
+}
+
+fn decode_list<T>(
+    json: &Json,
+    key: &str,
+    decode: impl Fn(&Json) -> Result<T, String>,
+) -> Result<Vec<T>, String> {
+    match field(json, key)? {
+        Json::Array(items) => items.iter().map(decode).collect(),
+        _ => Err(mismatch(json, key, "an array")),
+    }
+}
+
+fn decode_optional<T>(
+    json: &Json,
+    key: &str,
+    decode: impl Fn(&Json) -> Result<T, String>,
+) -> Result<Option<T>, String> {
+    match field(json, key)? {
+        Json::Null => Ok(None),
+        value => decode(value).map(Some),
+    }
+}
+
+fn decode_with<T>(
+    json: &Json,
+    key: &str,
+    decode: impl Fn(&Json) -> Result<T, String>,
+) -> Result<T, String> {
+    decode(field(json, key)?)
+}
+
+fn decode_span(json: &Json) -> Result<Span, String> {
+    let position = |json: &Json| -> Result<Position, String> {
+        Ok(Position {
+            line: decode_number(json, "line")? as usize,
+            column: decode_number(json, "column")? as usize,
+        })
+    };
+    let span = field(json, "span")?;
+
+    Ok(Span {
+        start: decode_with(span, "start", position)?,
+        end: decode_with(span, "end", position)?,
+    })
+}
+
+fn decode_token(json: &Json) -> Result<Token, String> {
+    let token = field(json, "token")?;
+    let kind = decode_string(token, "kind")?;
+
+    Ok(Token {
+        kind: token_kind(&kind).ok_or_else(|| format!("unknown token kind `{}`", kind))?,
+        literal: decode_string(token, "literal")?,
+        span: decode_span(token)?,
+    })
+}
+
+fn decode_statement(json: &Json) -> Result<StatementNode, String> {
+    let token = || decode_token(json);
+
+    let stmt = match type_name(json)? {
+        "LetStatement" => StatementNode::Let(decode_let(json)?),
+        "ReturnStatement" => StatementNode::Return(ReturnStatement {
+            token: token()?,
+            span: decode_span(json)?,
+            ret_value: decode_optional(json, "value", decode_expression)?,
+        }),
+        "ExpressionStatement" => StatementNode::Expression(ExpressionStatement {
+            token: token()?,
+            span: decode_span(json)?,
+            expression: decode_optional(json, "expression", decode_expression)?,
+        }),
+        "InfixDeclaration" => StatementNode::Infix(InfixDeclaration {
+            token: token()?,
+            span: decode_span(json)?,
+            precedence: decode_number(json, "precedence")? as u8,
+            associativity: match decode_string(json, "associativity")?.as_str() {
+                "left" => Associativity::Left,
+                "right" => Associativity::Right,
+                _ => return Err(mismatch(json, "associativity", "`left` or `right`")),
+            },
+            binding: decode_with(json, "binding", decode_let)?,
+        }),
+        "ImportStatement" => StatementNode::Import(ImportStatement {
+            token: token()?,
+            span: decode_span(json)?,
+            path: decode_with(json, "path", decode_string_literal)?,
+            alias: decode_with(json, "alias", decode_identifier)?,
+        }),
+        "ExportStatement" => StatementNode::Export(ExportStatement {
+            token: token()?,
+            span: decode_span(json)?,
+            binding: decode_with(json, "binding", decode_let)?,
+        }),
+        "StructDeclaration" => StatementNode::Struct(StructDeclaration {
+            token: token()?,
+            span: decode_span(json)?,
+            name: decode_with(json, "name", decode_identifier)?,
+            fields: decode_list(json, "fields", decode_identifier)?,
+        }),
+        "EnumDeclaration" => StatementNode::Enum(EnumDeclaration {
+            token: token()?,
+            span: decode_span(json)?,
+            name: decode_with(json, "name", decode_identifier)?,
+            variants: decode_list(json, "variants", |variant| {
+                expect_type(variant, "Variant")?;
+                Ok(Variant {
+                    name: decode_with(variant, "name", decode_identifier)?,
+                    fields: decode_list(variant, "fields", decode_identifier)?,
+                })
+            })?,
+        }),
+        "ThrowStatement" => StatementNode::Throw(ThrowStatement {
+            token: token()?,
+            span: decode_span(json)?,
+            value: decode_with(json, "value", decode_expression)?,
+        }),
+        "TryStatement" => StatementNode::Try(TryStatement {
+            token: token()?,
+            span: decode_span(json)?,
+            body: decode_with(json, "body", decode_block)?,
+            catch: decode_optional(json, "catch", |catch| {
+                expect_type(catch, "CatchClause")?;
+                Ok(CatchClause {
+                    parameter: decode_with(catch, "parameter", decode_identifier)?,
+                    body: decode_with(catch, "body", decode_block)?,
+                })
+            })?,
+            finally: decode_optional(json, "finally", decode_block)?,
+        }),
+        "YieldStatement" => StatementNode::Yield(YieldStatement {
+            token: token()?,
+            span: decode_span(json)?,
+            value: decode_with(json, "value", decode_expression)?,
+        }),
+        "ForStatement" => StatementNode::For(ForStatement {
+            token: token()?,
+            span: decode_span(json)?,
+            variable: decode_with(json, "variable", decode_identifier)?,
+            iterable: decode_with(json, "iterable", decode_expression)?,
+            body: decode_with(json, "body", decode_block)?,
+        }),
+        other => return Err(format!("expected a statement, got {}", other)),
+    };
+
+    Ok(stmt)
+}
+
+fn decode_let(json: &Json) -> Result<LetStatement, String> {
+    expect_type(json, "LetStatement")?;
+
+    Ok(LetStatement {
+        token: decode_token(json)?,
+        span: decode_span(json)?,
+        name: decode_with(json, "name", decode_pattern)?,
+        annotation: decode_optional(json, "annotation", decode_type_expr)?,
+        value: decode_optional(json, "value", decode_expression)?,
+    })
+}
+
+fn decode_block(json: &Json) -> Result<BlockStatement, String> {
+    expect_type(json, "BlockStatement")?;
+
+    Ok(BlockStatement {
+        token: decode_token(json)?,
+        span: decode_span(json)?,
+        statements: decode_list(json, "statements", decode_statement)?,
+    })
+}
+
+fn decode_identifier(json: &Json) -> Result<Identifier, String> {
+    expect_type(json, "Identifier")?;
+
+    Ok(Identifier {
+        token: decode_token(json)?,
+        value: decode_string(json, "value")?,
+    })
+}
+
+fn decode_string_literal(json: &Json) -> Result<StringLiteral, String> {
+    expect_type(json, "StringLiteral")?;
+
+    Ok(StringLiteral {
+        token: decode_token(json)?,
+        value: decode_string(json, "value")?,
+    })
+}
+
+fn decode_integer_literal(json: &Json) -> Result<IntegerLiteral, String> {
+    Ok(IntegerLiteral {
+        token: decode_token(json)?,
+        value: decode_number(json, "value")?,
+    })
+}
+
+fn decode_boolean_literal(json: &Json) -> Result<BooleanLiteral, String> {
+    Ok(BooleanLiteral {
+        token: decode_token(json)?,
+        value: decode_bool(json, "value")?,
+    })
+}
+
+fn decode_boxed(json: &Json) -> Result<Box<ExpressionNode>, String> {
+    decode_expression(json).map(Box::new)
+}
+
+fn decode_expression(json: &Json) -> Result<ExpressionNode, String> {
+    let token = || decode_token(json);
+
+    let expression = match type_name(json)? {
+        "Identifier" => ExpressionNode::IdentifierNode(decode_identifier(json)?),
+        "IntegerLiteral" => ExpressionNode::Integer(decode_integer_literal(json)?),
+        "BooleanLiteral" => ExpressionNode::Boolean(decode_boolean_literal(json)?),
+        "StringLiteral" => ExpressionNode::StringNode(decode_string_literal(json)?),
+        "NullLiteral" => ExpressionNode::Null(NullLiteral { token: token()? }),
+        "ArrayLiteral" => ExpressionNode::Array(ArrayLiteral {
+            token: token()?,
+            span: decode_span(json)?,
+            elements: decode_list(json, "elements", decode_expression)?,
+        }),
+        "HashLiteral" => ExpressionNode::Hash(HashLiteral {
+            token: token()?,
+            span: decode_span(json)?,
+            pairs: decode_list(json, "pairs", |pair| {
+                Ok((
+                    decode_with(pair, "key", decode_expression)?,
+                    decode_with(pair, "value", decode_expression)?,
+                ))
+            })?,
+        }),
+        "StructLiteral" => ExpressionNode::Struct(StructLiteral {
+            token: token()?,
+            span: decode_span(json)?,
+            name: decode_with(json, "name", decode_identifier)?,
+            fields: decode_list(json, "fields", |field| {
+                Ok((
+                    decode_with(field, "name", decode_identifier)?,
+                    decode_with(field, "value", decode_expression)?,
+                ))
+            })?,
+        }),
+        "PrefixExpression" => ExpressionNode::Prefix(PrefixExpression {
+            token: token()?,
+            span: decode_span(json)?,
+            operator: decode_string(json, "operator")?,
+            right: decode_with(json, "right", decode_boxed)?,
+        }),
+        "InfixExpression" => ExpressionNode::Infix(InfixExpression {
+            token: token()?,
+            span: decode_span(json)?,
+            left: decode_with(json, "left", decode_boxed)?,
+            operator: decode_string(json, "operator")?,
+            right: decode_with(json, "right", decode_boxed)?,
+        }),
+        "AssignExpression" => ExpressionNode::Assign(AssignExpression {
+            token: token()?,
+            span: decode_span(json)?,
+            name: decode_with(json, "name", decode_identifier)?,
+            value: decode_with(json, "value", decode_boxed)?,
+        }),
+        "FunctionLiteral" => ExpressionNode::Function(FunctionLiteral {
+            token: token()?,
+            span: decode_span(json)?,
+            parameters: decode_list(json, "parameters", |param| {
+                expect_type(param, "Parameter")?;
+                Ok(Parameter {
+                    name: decode_with(param, "name", decode_identifier)?,
+                    annotation: decode_optional(param, "annotation", decode_type_expr)?,
+                    default: decode_optional(param, "default", decode_expression)?,
+                    rest: decode_bool(param, "rest")?,
+                })
+            })?,
+            return_type: decode_optional(json, "return_type", decode_type_expr)?,
+            body: decode_with(json, "body", decode_block)?,
+        }),
+        "MacroLiteral" => ExpressionNode::Macro(MacroLiteral {
+            token: token()?,
+            span: decode_span(json)?,
+            parameters: decode_list(json, "parameters", decode_identifier)?,
+            body: decode_with(json, "body", decode_block)?,
+        }),
+        "CallExpression" => ExpressionNode::Call(CallExpression {
+            token: token()?,
+            span: decode_span(json)?,
+            function: decode_with(json, "function", decode_boxed)?,
+            arguments: decode_list(json, "arguments", |arg| {
+                expect_type(arg, "Argument")?;
+                Ok(Argument {
+                    name: decode_optional(arg, "name", decode_identifier)?,
+                    value: decode_with(arg, "value", decode_expression)?,
+                })
+            })?,
+        }),
+        "MemberExpression" => ExpressionNode::Member(MemberExpression {
+            token: token()?,
+            span: decode_span(json)?,
+            object: decode_with(json, "object", decode_boxed)?,
+            property: decode_with(json, "property", decode_identifier)?,
+            optional: decode_bool(json, "optional")?,
+        }),
+        "IndexExpression" => ExpressionNode::Index(IndexExpression {
+            token: token()?,
+            span: decode_span(json)?,
+            left: decode_with(json, "left", decode_boxed)?,
+            index: decode_with(json, "index", decode_boxed)?,
+            optional: decode_bool(json, "optional")?,
+        }),
+        "RangeExpression" => ExpressionNode::Range(RangeExpression {
+            token: token()?,
+            span: decode_span(json)?,
+            start: decode_optional(json, "start", decode_boxed)?,
+            end: decode_optional(json, "end", decode_boxed)?,
+            inclusive: decode_bool(json, "inclusive")?,
+        }),
+        "MatchExpression" => ExpressionNode::Match(MatchExpression {
+            token: token()?,
+            span: decode_span(json)?,
+            subject: decode_with(json, "subject", decode_boxed)?,
+            arms: decode_list(json, "arms", |arm| {
+                expect_type(arm, "MatchArm")?;
+                Ok(MatchArm {
+                    pattern: decode_with(arm, "pattern", decode_pattern)?,
+                    guard: decode_optional(arm, "guard", decode_expression)?,
+                    body: decode_with(arm, "body", decode_expression)?,
+                })
+            })?,
+        }),
+        "IfExpression" => ExpressionNode::If(IfExpression {
+            token: token()?,
+            span: decode_span(json)?,
+            condition: decode_with(json, "condition", decode_boxed)?,
+            consequence: decode_with(json, "consequence", decode_block)?,
+            alternative: decode_optional(json, "alternative", decode_block)?,
+        }),
+        other => return Err(format!("expected an expression, got {}", other)),
+    };
+
+    Ok(expression)
+}
+
+fn decode_pattern(json: &Json) -> Result<Pattern, String> {
+    let pattern = match type_name(json)? {
+        "IntegerLiteral" => Pattern::Integer(decode_integer_literal(json)?),
+        "BooleanLiteral" => Pattern::Boolean(decode_boolean_literal(json)?),
+        "StringLiteral" => Pattern::StringNode(decode_string_literal(json)?),
+        "Identifier" => Pattern::Binding(decode_identifier(json)?),
+        "Wildcard" => Pattern::Wildcard(decode_token(json)?),
+        "ArrayPattern" => Pattern::Array(ArrayPattern {
+            token: decode_token(json)?,
+            elements: decode_list(json, "elements", decode_pattern)?,
+            rest: decode_optional(json, "rest", decode_identifier)?,
+        }),
+        "HashPattern" => Pattern::Hash(HashPattern {
+            token: decode_token(json)?,
+            pairs: decode_list(json, "pairs", |pair| {
+                Ok((
+                    decode_with(pair, "key", decode_string_literal)?,
+                    decode_with(pair, "pattern", decode_pattern)?,
+                ))
+            })?,
+        }),
+        "VariantPattern" => Pattern::Variant(VariantPattern {
+            name: decode_with(json, "name", decode_identifier)?,
+            payload: decode_list(json, "payload", decode_pattern)?,
+        }),
+        other => return Err(format!("expected a pattern, got {}", other)),
+    };
+
+    Ok(pattern)
+}
+
+fn decode_type_expr(json: &Json) -> Result<TypeExpr, String> {
+    let type_expr = match type_name(json)? {
+        "Identifier" => TypeExpr::Named(decode_identifier(json)?),
+        "ArrayType" => TypeExpr::Array(ArrayType {
+            token: decode_token(json)?,
+            element: Box::new(decode_with(json, "element", decode_type_expr)?),
+        }),
+        "HashType" => TypeExpr::Hash(HashType {
+            token: decode_token(json)?,
+            key: Box::new(decode_with(json, "key", decode_type_expr)?),
+            value: Box::new(decode_with(json, "value", decode_type_expr)?),
+        }),
+        "FunctionType" => TypeExpr::Function(FunctionType {
+            token: decode_token(json)?,
+            parameters: decode_list(json, "parameters", decode_type_expr)?,
+            return_type: Box::new(decode_with(json, "return_type", decode_type_expr)?),
+        }),
+        other => return Err(format!("expected a type, got {}", other)),
+    };
+
+    Ok(type_expr)
+}
+
+/// The kind written by `{:?}` in [`to_json`].
+fn token_kind(name: &str) -> Option<TokenKind> {
+    let kind = match name {
+        "Illegal" => TokenKind::Illegal,
+        "Eof" => TokenKind::Eof,
+        "Ident" => TokenKind::Ident,
+        "Int" => TokenKind::Int,
+        "String" => TokenKind::String,
+        "Assign" => TokenKind::Assign,
+        "Plus" => TokenKind::Plus,
+        "Minus" => TokenKind::Minus,
+        "Bang" => TokenKind::Bang,
+        "Asteriks" => TokenKind::Asteriks,
+        "Slash" => TokenKind::Slash,
+        "Lt" => TokenKind::Lt,
+        "Gt" => TokenKind::Gt,
+        "Eq" => TokenKind::Eq,
+        "NotEq" => TokenKind::NotEq,
+        "Comma" => TokenKind::Comma,
+        "Semicolon" => TokenKind::Semicolon,
+        "Colon" => TokenKind::Colon,
+        "FatArrow" => TokenKind::FatArrow,
+        "Arrow" => TokenKind::Arrow,
+        "Ellipsis" => TokenKind::Ellipsis,
+        "DotDot" => TokenKind::DotDot,
+        "DotDotEq" => TokenKind::DotDotEq,
+        "Dot" => TokenKind::Dot,
+        "QuestionDot" => TokenKind::QuestionDot,
+        "QuestionQuestion" => TokenKind::QuestionQuestion,
+        "Operator" => TokenKind::Operator,
+        "Lparen" => TokenKind::Lparen,
+        "Rparen" => TokenKind::Rparen,
+        "Lbrace" => TokenKind::Lbrace,
+        "Rbrace" => TokenKind::Rbrace,
+        "Lbracket" => TokenKind::Lbracket,
+        "Rbracket" => TokenKind::Rbracket,
+        "Function" => TokenKind::Function,
+        "Let" => TokenKind::Let,
+        "Const" => TokenKind::Const,
+        "If" => TokenKind::If,
+        "Else" => TokenKind::Else,
+        "Return" => TokenKind::Return,
+        "True" => TokenKind::True,
+        "False" => TokenKind::False,
+        "Match" => TokenKind::Match,
+        "Infix" => TokenKind::Infix,
+        "Macro" => TokenKind::Macro,
+        "Import" => TokenKind::Import,
+        "Export" => TokenKind::Export,
+        "As" => TokenKind::As,
+        "Struct" => TokenKind::Struct,
+        "Enum" => TokenKind::Enum,
+        "Throw" => TokenKind::Throw,
+        "Try" => TokenKind::Try,
+        "Catch" => TokenKind::Catch,
+        "Finally" => TokenKind::Finally,
+        "Yield" => TokenKind::Yield,
+        "For" => TokenKind::For,
+        "In" => TokenKind::In,
+        "Null" => TokenKind::Null,
+        _ => return None,
+    };
+
+    Some(kind)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ast::Node, json::Json, lexer::Lexer, parser::Parser};
+
+    use super::{from_json, to_json};
+
+    #[test]
+    fn test_round_trip() {
+        let tests = vec![
+            "let x= 5;\nlet y= 10;\nlet foobar = 838383;",
+            "const x = 5; export const n = 1; x = 5; a = b = 1 + 2; f(x = 1)",
+            "let [a, b, ...rest] = xs; let [first, _] = [1, 2];",
+            r#"let {name, age} = person; let {"pos": [x, y], id: key} = {"pos": [1, 2], "id": 3};"#,
+            "fn() {}; fn(x, y) { x + y; }; fn(x, y = 10, ...rest) { return x; }",
+            "fn(x = 1 + 2, y = fn() { 3 }) { x }",
+            "macro(x, y) { quote(unquote(x) + y); }",
+            "let x: int = 5; let xs: [string] = ys; let {a}: {string: int} = h;",
+            r#"fn(a: int, b: string = "x") -> bool { a > 0 }"#,
+            "let apply: fn(fn(int) -> int, int) -> int = f; fn(...rest: [int]) {}",
+            "return 5;\nreturn 10;\nreturn x + y;",
+            "-a * b; !-a; a + b * c + d / e - f; 5 > 4 == 3 < 4; true != false",
+            "a * [1, 2][b * c] * d; add(a * b[2], b[1]); -xs[0]",
+            "1..n + 1; 0..=n == m; xs[1..3]; s[..5]; xs[1..]; xs[..]; f(1.., ..2); 1_000_000",
+            "a ?? b ?? c; a?.b?.c ?? d; a?.[i + 1]; -a?.b; f(x)?.y.z; null ?? 0",
+            r#"match (x + 1) { 0 => "zero", -1 => "minus", n if n > 10 => n, [a, _] => a, {"k": v, name} => v, _ => false }"#,
+            "infix <+> 3 left = fn(a, b) { a + b }; infix ^^ 4 right = fn(a, b) { a * b }; 1 <+> 2 ^^ 3",
+            r#"import "lib/vec" as vec; export let x = 5; vec.add(1, 2); a.b.c"#,
+            "struct Point { x, y } let p = Point { x: 1 + 2, y: 2 }; p.x * p.y",
+            "Line { a: P { x: 1 } }.a.x; {\"a\": 1, true: [\"tab\\there\"]}",
+            "enum Shape { Circle(r), Rect(w, h) } match (s) { Circle(r) => r, Rect(_, [h, ...t]) => h, other => 0 }",
+            "throw \"oops\"; try { risky(); } catch (e) { throw e; } finally { 2 }",
+            "try { risky() } finally { close() }",
+            "if (x > 1) { puts(x); x } else if (x == 1) { 1 } else { 0 }; if (ok) {}",
+            "fn(rows) { for row in rows.next() { yield row * 2; } }; for x in xs { puts(x); }",
+        ];
+
+        for input in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program().unwrap();
+            assert!(
+                parser.errors().is_empty(),
+                "parser errors for {}: {:?}",
+                input,
+                parser.errors()
+            );
+
+            let encoded = to_json(&program).to_string();
+            let decoded = from_json(&Json::parse(&encoded).unwrap())
+                .unwrap_or_else(|err| panic!("can not decode {}: {}", input, err));
+
+            assert_eq!(
+                decoded.print_string(),
+                program.print_string(),
+                "wrong program for {}",
+                input
+            );
+            assert_eq!(
+                to_json(&decoded).to_string(),
+                encoded,
+                "wrong json for {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_node_layout() {
+        let lexer = Lexer::new("x?.y");
+        let program = Parser::new(lexer).parse_program().unwrap();
+
+        assert_eq!(
+            to_json(&program).to_string(),
+            concat!(
+                r#"{"type":"Program","statements":[{"type":"ExpressionStatement","#,
+                r#""span":{"start":{"line":1,"column":1},"end":{"line":1,"column":5}},"#,
+                r#""expression":{"type":"MemberExpression","#,
+                r#""span":{"start":{"line":1,"column":1},"end":{"line":1,"column":5}},"#,
+                r#""object":{"type":"Identifier","#,
+                r#""span":{"start":{"line":1,"column":1},"end":{"line":1,"column":2}},"#,
+                r#""value":"x","token":{"kind":"Ident","literal":"x","#,
+                r#""span":{"start":{"line":1,"column":1},"end":{"line":1,"column":2}}}},"#,
+                r#""property":{"type":"Identifier","#,
+                r#""span":{"start":{"line":1,"column":4},"end":{"line":1,"column":5}},"#,
+                r#""value":"y","token":{"kind":"Ident","literal":"y","#,
+                r#""span":{"start":{"line":1,"column":4},"end":{"line":1,"column":5}}}},"#,
+                r#""optional":true,"token":{"kind":"QuestionDot","literal":"?.","#,
+                r#""span":{"start":{"line":1,"column":2},"end":{"line":1,"column":4}}}},"#,
+                r#""token":{"kind":"Ident","literal":"x","#,
+                r#""span":{"start":{"line":1,"column":1},"end":{"line":1,"column":2}}}}]}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let tests = vec![
+            ("[]", "expected a node with a `type`, got []"),
+            (r#"{"type": "Block"}"#, "expected a Program node, got Block"),
+            (r#"{"type": "Program"}"#, "Program is missing `statements`"),
+            (
+                r#"{"type": "Program", "statements": {}}"#,
+                "expected `statements` of Program to be an array",
+            ),
+            (
+                r#"{"type": "Program", "statements": [{"type": "Identifier"}]}"#,
+                "expected a statement, got Identifier",
+            ),
+            (
+                r#"{"type": "Program", "statements": [{"type": "ExpressionStatement",
+                    "span": {"start": {"line": 1, "column": 1}, "end": {"line": 1, "column": 2}},
+                    "token": {"kind": "Word", "literal": "x",
+                    "span": {"start": {"line": 1, "column": 1}, "end": {"line": 1, "column": 2}}}}]}"#,
+                "unknown token kind `Word`",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let json = Json::parse(input).unwrap();
+            assert_eq!(
+                from_json(&json).err(),
+                Some(expected.to_string()),
+                "wrong error for {}",
+                input
+            );
+        }
+    }
+}
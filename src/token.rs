@@ -1,9 +1,56 @@
 use std::fmt::Display;
 
-#[derive(PartialEq, Debug, Default, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub literal: String,
+    pub span: Span,
+}
+
+/// Tokens are equal when their kind and literal are, wherever they are in
+/// the source.
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.literal == other.literal
+    }
+}
+
+/// 1-based line and column of a character in the source.
+#[derive(PartialEq, Debug, Default, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Source range from `start` up to, but not including, `end`.
+#[derive(PartialEq, Debug, Default, Clone, Copy)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.start.line == self.end.line {
+            write!(f, "{}-{}", self.start, self.end.column)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+/// A `// ...` line comment, kept by a lexer made [`with_comments`](crate::lexer::Lexer::with_comments).
+#[derive(PartialEq, Debug, Clone)]
+pub struct Comment {
+    /// The whole comment, `//` included, without the line break.
+    pub text: String,
+    pub span: Span,
 }
 
 #[derive(PartialEq, Debug, Default, Clone, Hash, Eq)]
@@ -14,6 +61,7 @@ pub enum TokenKind {
 
     Ident,
     Int,
+    String,
 
     Assign,
     Plus,
@@ -30,19 +78,49 @@ pub enum TokenKind {
 
     Comma,
     Semicolon,
+    Colon,
+    FatArrow,
+    Arrow,
+    Ellipsis,
+    DotDot,
+    DotDotEq,
+    Dot,
+    QuestionDot,
+    QuestionQuestion,
+    /// A symbol declared with `infix`, such as `<+>`.
+    Operator,
 
     Lparen,
     Rparen,
     Lbrace,
     Rbrace,
+    Lbracket,
+    Rbracket,
 
     Function,
     Let,
+    Const,
     If,
     Else,
     Return,
     True,
     False,
+    Match,
+    Infix,
+    Macro,
+    Import,
+    Export,
+    As,
+    Struct,
+    Enum,
+    Throw,
+    Try,
+    Catch,
+    Finally,
+    Yield,
+    For,
+    In,
+    Null,
 }
 
 impl Display for TokenKind {
@@ -52,16 +130,30 @@ impl Display for TokenKind {
             TokenKind::Eof => write!(f, "Eof"),
             TokenKind::Ident => write!(f, "Ident"),
             TokenKind::Int => write!(f, "Int"),
+            TokenKind::String => write!(f, "String"),
             TokenKind::Assign => write!(f, "Assign"),
             TokenKind::Plus => write!(f, "Plus"),
             TokenKind::Comma => write!(f, "Comma"),
             TokenKind::Semicolon => write!(f, "Semicolon"),
+            TokenKind::Colon => write!(f, ":"),
+            TokenKind::FatArrow => write!(f, "=>"),
+            TokenKind::Arrow => write!(f, "->"),
+            TokenKind::Ellipsis => write!(f, "..."),
+            TokenKind::DotDot => write!(f, ".."),
+            TokenKind::DotDotEq => write!(f, "..="),
+            TokenKind::Dot => write!(f, "."),
+            TokenKind::QuestionDot => write!(f, "?."),
+            TokenKind::QuestionQuestion => write!(f, "??"),
+            TokenKind::Operator => write!(f, "Operator"),
             TokenKind::Lparen => write!(f, "Lparen"),
             TokenKind::Rparen => write!(f, "Rparen"),
             TokenKind::Lbrace => write!(f, "Lbrace"),
             TokenKind::Rbrace => write!(f, "Rbrace"),
+            TokenKind::Lbracket => write!(f, "Lbracket"),
+            TokenKind::Rbracket => write!(f, "Rbracket"),
             TokenKind::Function => write!(f, "Function"),
             TokenKind::Let => write!(f, "Let"),
+            TokenKind::Const => write!(f, "Const"),
             TokenKind::Minus => write!(f, "-"),
             TokenKind::Bang => write!(f, "!"),
             TokenKind::Asteriks => write!(f, "*"),
@@ -75,19 +167,52 @@ impl Display for TokenKind {
             TokenKind::False => write!(f, "False"),
             TokenKind::Eq => write!(f, "=="),
             TokenKind::NotEq => write!(f, "!="),
+            TokenKind::Match => write!(f, "Match"),
+            TokenKind::Infix => write!(f, "Infix"),
+            TokenKind::Macro => write!(f, "Macro"),
+            TokenKind::Import => write!(f, "Import"),
+            TokenKind::Export => write!(f, "Export"),
+            TokenKind::As => write!(f, "As"),
+            TokenKind::Struct => write!(f, "Struct"),
+            TokenKind::Enum => write!(f, "Enum"),
+            TokenKind::Throw => write!(f, "Throw"),
+            TokenKind::Try => write!(f, "Try"),
+            TokenKind::Catch => write!(f, "Catch"),
+            TokenKind::Finally => write!(f, "Finally"),
+            TokenKind::Yield => write!(f, "Yield"),
+            TokenKind::For => write!(f, "For"),
+            TokenKind::In => write!(f, "In"),
+            TokenKind::Null => write!(f, "Null"),
         }
     }
 }
 
-pub fn lookup_ident(identifier: &String) -> TokenKind {
-    match identifier.as_str() {
+pub fn lookup_ident(identifier: &str) -> TokenKind {
+    match identifier {
         "fn" => TokenKind::Function,
         "let" => TokenKind::Let,
+        "const" => TokenKind::Const,
         "if" => TokenKind::If,
         "else" => TokenKind::Else,
         "return" => TokenKind::Return,
         "true" => TokenKind::True,
         "false" => TokenKind::False,
+        "match" => TokenKind::Match,
+        "infix" => TokenKind::Infix,
+        "macro" => TokenKind::Macro,
+        "import" => TokenKind::Import,
+        "export" => TokenKind::Export,
+        "as" => TokenKind::As,
+        "struct" => TokenKind::Struct,
+        "enum" => TokenKind::Enum,
+        "throw" => TokenKind::Throw,
+        "try" => TokenKind::Try,
+        "catch" => TokenKind::Catch,
+        "finally" => TokenKind::Finally,
+        "yield" => TokenKind::Yield,
+        "for" => TokenKind::For,
+        "in" => TokenKind::In,
+        "null" => TokenKind::Null,
         _ => TokenKind::Ident,
     }
 }
@@ -0,0 +1,1102 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{
+    ast::{
+        AssignExpression, CallExpression, EnumDeclaration, ExportStatement, ExpressionNode,
+        ExpressionStatement, FunctionLiteral, Identifier, IfExpression, IndexExpression,
+        InfixDeclaration, InfixExpression, MatchExpression, Node, Pattern, PrefixExpression,
+        Program, RangeExpression, StatementNode, StructDeclaration, StructLiteral, TypeExpr,
+    },
+    token::{Span, Token},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int,
+    Bool,
+    String,
+    Array(Box<Type>),
+    Hash(Box<Type>, Box<Type>),
+    Function(Vec<Type>, Box<Type>),
+    /// `a..b`, a lazy sequence of ints.
+    Range,
+    /// A value of a `struct` or `enum` declaration, compared by name.
+    Named(String),
+    /// The type of `null`, which is a valid value of every other type.
+    Null,
+    /// Anything without an annotation, compatible with every other type.
+    Unknown,
+}
+
+impl Type {
+    fn is_compatible(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Unknown, _) | (_, Type::Unknown) => true,
+            (Type::Null, _) | (_, Type::Null) => true,
+            (Type::Array(a), Type::Array(b)) => a.is_compatible(b),
+            (Type::Hash(ak, av), Type::Hash(bk, bv)) => {
+                ak.is_compatible(bk) && av.is_compatible(bv)
+            }
+            (Type::Function(a_params, a_ret), Type::Function(b_params, b_ret)) => {
+                a_params.len() == b_params.len()
+                    && a_params
+                        .iter()
+                        .zip(b_params)
+                        .all(|(a, b)| a.is_compatible(b))
+                    && a_ret.is_compatible(b_ret)
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// The shared type of all `types`, `Unknown` when they disagree. A `null`
+    /// among them takes the type of the others.
+    fn unify_all(types: Vec<Type>) -> Type {
+        if types.iter().all(|t| *t == Type::Null) {
+            return types.into_iter().next().unwrap_or(Type::Unknown);
+        }
+        let mut types = types.into_iter().filter(|t| *t != Type::Null);
+
+        match types.next() {
+            Some(first) if types.all(|t| t == first) => first,
+            _ => Type::Unknown,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Array(element) => write!(f, "[{}]", element),
+            Type::Hash(key, value) => write!(f, "{{{}: {}}}", key, value),
+            Type::Function(params, ret) => {
+                let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+                write!(f, "fn({}) -> {}", params.join(", "), ret)
+            }
+            Type::Range => write!(f, "range"),
+            Type::Named(name) => write!(f, "{}", name),
+            Type::Null => write!(f, "null"),
+            Type::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Checks the program against its type annotations.
+///
+/// Unannotated bindings get the type of their value where it is obvious
+/// and are `Unknown` otherwise, so unannotated code never fails the check.
+/// Every error is prefixed with the span of the offending token.
+pub fn check_program(program: &Program) -> Vec<String> {
+    let mut checker = TypeChecker {
+        scopes: vec![HashMap::new()],
+        return_types: vec![],
+        structs: HashMap::new(),
+        enums: top_level_enums(program),
+        errors: vec![],
+    };
+
+    checker.check_statements(&program.statements);
+
+    checker.errors
+}
+
+struct TypeChecker {
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Declared return type of every function being checked, innermost last.
+    return_types: Vec<Option<Type>>,
+    structs: HashMap<String, StructDeclaration>,
+    enums: HashMap<String, EnumDeclaration>,
+    errors: Vec<String>,
+}
+
+struct Binding {
+    ty: Type,
+    /// The parameter names of a function literal, which named arguments refer to.
+    parameters: Option<Vec<String>>,
+    /// Whether the type was declared, only then an assignment has to keep to it.
+    annotated: bool,
+}
+
+impl TypeChecker {
+    fn error(&mut self, span: Span, msg: String) {
+        self.errors.push(format!("{}: {}", span, msg));
+    }
+
+    fn binding(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn lookup(&self, name: &str) -> Type {
+        self.binding(name)
+            .map_or(Type::Unknown, |binding| binding.ty.clone())
+    }
+
+    /// Gives an existing binding a new type in the scope that declares it.
+    fn redefine(&mut self, name: &str, ty: Type) {
+        if let Some(scope) = self.scopes.iter_mut().rev().find(|s| s.contains_key(name)) {
+            let annotated = scope.get(name).is_some_and(|binding| binding.annotated);
+            let binding = Binding {
+                ty,
+                parameters: None,
+                annotated,
+            };
+            scope.insert(name.to_string(), binding);
+        }
+    }
+
+    /// Marks a binding of the innermost scope as declared with its type.
+    fn annotate(&mut self, name: &str) {
+        if let Some(binding) = self.scopes.last_mut().and_then(|scope| scope.get_mut(name)) {
+            binding.annotated = true;
+        }
+    }
+
+    fn define(&mut self, name: &str, ty: Type) {
+        self.define_with_parameters(name, ty, None);
+    }
+
+    fn define_with_parameters(&mut self, name: &str, ty: Type, parameters: Option<Vec<String>>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let binding = Binding {
+                ty,
+                parameters,
+                annotated: false,
+            };
+            scope.insert(name.to_string(), binding);
+        }
+    }
+
+    fn resolve(&mut self, type_expr: &TypeExpr) -> Type {
+        match type_expr {
+            TypeExpr::Named(name) => match name.value.as_str() {
+                "int" => Type::Int,
+                "bool" => Type::Bool,
+                "string" => Type::String,
+                other if self.structs.contains_key(other) || self.enums.contains_key(other) => {
+                    Type::Named(other.to_string())
+                }
+                other => {
+                    self.error(name.token.span, format!("unknown type `{}`", other));
+                    Type::Unknown
+                }
+            },
+            TypeExpr::Array(array) => Type::Array(Box::new(self.resolve(&array.element))),
+            TypeExpr::Hash(hash) => Type::Hash(
+                Box::new(self.resolve(&hash.key)),
+                Box::new(self.resolve(&hash.value)),
+            ),
+            TypeExpr::Function(func) => {
+                let params = func.parameters.iter().map(|p| self.resolve(p)).collect();
+                Type::Function(params, Box::new(self.resolve(&func.return_type)))
+            }
+        }
+    }
+
+    fn check_statements(&mut self, statements: &[StatementNode]) {
+        for stmt in statements {
+            self.check_statement(stmt);
+        }
+    }
+
+    fn check_block(&mut self, statements: &[StatementNode], parameter: Option<&str>) {
+        self.scopes.push(HashMap::new());
+        if let Some(parameter) = parameter {
+            self.define(parameter, Type::Unknown);
+        }
+        self.check_statements(statements);
+        self.scopes.pop();
+    }
+
+    fn check_statement(&mut self, stmt: &StatementNode) {
+        match stmt {
+            StatementNode::Let(let_stmt)
+            | StatementNode::Infix(InfixDeclaration {
+                binding: let_stmt, ..
+            })
+            | StatementNode::Export(ExportStatement {
+                binding: let_stmt, ..
+            }) => {
+                let value_type = match &let_stmt.value {
+                    Some(value) => self.check_expression(value),
+                    None => Type::Unknown,
+                };
+
+                let ty = match &let_stmt.annotation {
+                    Some(annotation) => {
+                        let declared = self.resolve(annotation);
+                        if let Some(value) = &let_stmt.value {
+                            if !declared.is_compatible(&value_type) {
+                                let msg = format!(
+                                    "type mismatch: `{}` is declared as {}, but the value is {}",
+                                    let_stmt.name.print_string(),
+                                    declared,
+                                    value_type
+                                );
+                                self.error(value.token().span, msg);
+                            }
+                        }
+                        declared
+                    }
+                    None => value_type,
+                };
+
+                match (&let_stmt.name, &let_stmt.value) {
+                    (Pattern::Binding(name), Some(ExpressionNode::Function(func))) => {
+                        self.define_with_parameters(&name.value, ty, Some(parameter_names(func)))
+                    }
+                    (pattern, _) => self.bind_pattern(pattern, &ty),
+                }
+                if let (Pattern::Binding(name), Some(_)) = (&let_stmt.name, &let_stmt.annotation) {
+                    self.annotate(&name.value);
+                }
+            }
+            StatementNode::Struct(declaration) => {
+                self.structs
+                    .insert(declaration.name.value.clone(), declaration.clone());
+            }
+            // variants are constructors, their payload values are untyped
+            StatementNode::Enum(declaration) => {
+                let named = Type::Named(declaration.name.value.clone());
+                for variant in &declaration.variants {
+                    let ty = if variant.fields.is_empty() {
+                        named.clone()
+                    } else {
+                        let params = variant.fields.iter().map(|_| Type::Unknown).collect();
+                        Type::Function(params, Box::new(named.clone()))
+                    };
+                    self.define(&variant.name.value, ty);
+                }
+                self.enums
+                    .insert(declaration.name.value.clone(), declaration.clone());
+            }
+            StatementNode::Throw(throw) => {
+                self.check_expression(&throw.value);
+            }
+            // a generator's declared return type is the type of the values it yields
+            StatementNode::Yield(yield_stmt) => {
+                let value_type = self.check_expression(&yield_stmt.value);
+                if let Some(Some(declared)) = self.return_types.last().cloned() {
+                    if !declared.is_compatible(&value_type) {
+                        let msg = format!(
+                            "type mismatch: generator yields {}, but the value is {}",
+                            declared, value_type
+                        );
+                        self.error(yield_stmt.value.token().span, msg);
+                    }
+                }
+            }
+            StatementNode::For(for_stmt) => {
+                let element = match self.check_expression(&for_stmt.iterable) {
+                    Type::Array(element) => *element,
+                    Type::Range => Type::Int,
+                    _ => Type::Unknown,
+                };
+                self.scopes.push(HashMap::new());
+                self.define(&for_stmt.variable.value, element);
+                self.check_statements(&for_stmt.body.statements);
+                self.scopes.pop();
+            }
+            // the thrown value is unknown, so is the catch parameter
+            StatementNode::Try(try_stmt) => {
+                self.check_block(&try_stmt.body.statements, None);
+                if let Some(catch) = &try_stmt.catch {
+                    self.check_block(&catch.body.statements, Some(&catch.parameter.value));
+                }
+                if let Some(finally) = &try_stmt.finally {
+                    self.check_block(&finally.statements, None);
+                }
+            }
+            // modules are checked on their own, their members are unknown here
+            StatementNode::Import(import) => self.define(&import.alias.value, Type::Unknown),
+            StatementNode::Return(ret_stmt) => {
+                let value_type = match &ret_stmt.ret_value {
+                    Some(value) => self.check_expression(value),
+                    None => Type::Unknown,
+                };
+
+                if let (Some(Some(declared)), Some(value)) =
+                    (self.return_types.last().cloned(), &ret_stmt.ret_value)
+                {
+                    self.check_return(&declared, &value_type, value);
+                }
+            }
+            StatementNode::Expression(exp_stmt) => {
+                if let Some(expression) = &exp_stmt.expression {
+                    self.check_expression(expression);
+                }
+            }
+        }
+    }
+
+    fn check_struct_literal(&mut self, literal: &StructLiteral) -> Type {
+        for (_, value) in &literal.fields {
+            self.check_expression(value);
+        }
+
+        let errors = match self.structs.get(&literal.name.value) {
+            Some(declaration) => declaration.check_literal(literal),
+            None => {
+                let msg = format!("unknown struct `{}`", literal.name.value);
+                self.error(literal.name.token.span, msg);
+                return Type::Unknown;
+            }
+        };
+        for (span, msg) in errors {
+            self.error(span, msg);
+        }
+
+        Type::Named(literal.name.value.clone())
+    }
+
+    fn check_field(&mut self, name: &str, field: &Identifier) {
+        if let Some(declaration) = self.structs.get(name) {
+            if !declaration.has_field(&field.value) {
+                let msg = format!("struct `{}` has no field `{}`", name, field.value);
+                self.error(field.token.span, msg);
+            }
+        }
+    }
+
+    fn check_return(&mut self, declared: &Type, value_type: &Type, value: &ExpressionNode) {
+        if !declared.is_compatible(value_type) {
+            let msg = format!(
+                "type mismatch: function returns {}, but the value is {}",
+                declared, value_type
+            );
+            self.error(value.token().span, msg);
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern, ty: &Type) {
+        match pattern {
+            Pattern::Binding(name) => self.define(&name.value, ty.clone()),
+            Pattern::Wildcard(_) => {}
+            Pattern::Integer(int) => self.check_literal_pattern(pattern, &int.token, Type::Int, ty),
+            Pattern::Boolean(boolean) => {
+                self.check_literal_pattern(pattern, &boolean.token, Type::Bool, ty)
+            }
+            Pattern::StringNode(string) => {
+                self.check_literal_pattern(pattern, &string.token, Type::String, ty)
+            }
+            Pattern::Array(array) => {
+                let element = match ty {
+                    Type::Array(element) => element.as_ref().clone(),
+                    Type::Unknown => Type::Unknown,
+                    other => {
+                        let msg = format!(
+                            "type mismatch: can not destructure {} with `{}`",
+                            other,
+                            pattern.print_string()
+                        );
+                        self.error(array.token.span, msg);
+                        Type::Unknown
+                    }
+                };
+
+                for el in &array.elements {
+                    self.bind_pattern(el, &element);
+                }
+                if let Some(rest) = &array.rest {
+                    self.define(&rest.value, Type::Array(Box::new(element)));
+                }
+            }
+            Pattern::Hash(hash) => {
+                let value = match ty {
+                    Type::Hash(_, value) => value.as_ref().clone(),
+                    Type::Unknown => Type::Unknown,
+                    other => {
+                        let msg = format!(
+                            "type mismatch: can not destructure {} with `{}`",
+                            other,
+                            pattern.print_string()
+                        );
+                        self.error(hash.token.span, msg);
+                        Type::Unknown
+                    }
+                };
+
+                for (_, el) in &hash.pairs {
+                    self.bind_pattern(el, &value);
+                }
+            }
+            Pattern::Variant(variant) => {
+                let span = variant.name.token.span;
+                let declaration = self
+                    .enums
+                    .values()
+                    .find(|declaration| declaration.variant(&variant.name.value).is_some());
+
+                match declaration {
+                    Some(declaration) => {
+                        let named = Type::Named(declaration.name.value.clone());
+                        let arity_error = declaration
+                            .variant(&variant.name.value)
+                            .and_then(|v| v.check_pattern(variant));
+                        if let Some(msg) = arity_error {
+                            self.error(span, msg);
+                        }
+                        self.check_literal_pattern(pattern, &variant.name.token, named, ty);
+                    }
+                    None => {
+                        self.error(span, format!("unknown variant `{}`", variant.name.value));
+                    }
+                }
+
+                for el in &variant.payload {
+                    self.bind_pattern(el, &Type::Unknown);
+                }
+            }
+        }
+    }
+
+    fn check_literal_pattern(
+        &mut self,
+        pattern: &Pattern,
+        token: &Token,
+        literal: Type,
+        ty: &Type,
+    ) {
+        if !literal.is_compatible(ty) {
+            let msg = format!(
+                "type mismatch: pattern `{}` of type {} can not match {}",
+                pattern.print_string(),
+                literal,
+                ty
+            );
+            self.error(token.span, msg);
+        }
+    }
+
+    fn check_expression(&mut self, expression: &ExpressionNode) -> Type {
+        match expression {
+            ExpressionNode::IdentifierNode(ident) => self.lookup(&ident.value),
+            ExpressionNode::Integer(_) => Type::Int,
+            ExpressionNode::Boolean(_) => Type::Bool,
+            ExpressionNode::StringNode(_) => Type::String,
+            ExpressionNode::Null(_) => Type::Null,
+            // macros are expanded before type checking, a leftover one is opaque
+            ExpressionNode::Macro(_) => Type::Unknown,
+            ExpressionNode::Array(array) => {
+                let elements = array
+                    .elements
+                    .iter()
+                    .map(|el| self.check_expression(el))
+                    .collect();
+                Type::Array(Box::new(Type::unify_all(elements)))
+            }
+            ExpressionNode::Hash(hash) => {
+                let mut keys = vec![];
+                let mut values = vec![];
+                for (key, value) in &hash.pairs {
+                    keys.push(self.check_expression(key));
+                    values.push(self.check_expression(value));
+                }
+                Type::Hash(
+                    Box::new(Type::unify_all(keys)),
+                    Box::new(Type::unify_all(values)),
+                )
+            }
+            ExpressionNode::Prefix(prefix) => self.check_prefix(prefix),
+            ExpressionNode::Member(member) => match self.check_expression(&member.object) {
+                Type::Named(name) => {
+                    self.check_field(&name, &member.property);
+                    Type::Unknown
+                }
+                Type::Null if member.optional => Type::Null,
+                Type::Null => {
+                    let msg = format!(
+                        "can not access `{}` on null in `{}`, use `?.` if it may be null",
+                        member.property.value,
+                        member.print_string()
+                    );
+                    self.error(member.property.token.span, msg);
+                    Type::Unknown
+                }
+                _ => Type::Unknown,
+            },
+            ExpressionNode::Assign(assign) => self.check_assign(assign),
+            ExpressionNode::Struct(literal) => self.check_struct_literal(literal),
+            ExpressionNode::Index(index) => self.check_index(index),
+            ExpressionNode::Range(range) => self.check_range(range),
+            ExpressionNode::Infix(infix) => self.check_infix(infix),
+            ExpressionNode::Match(match_exp) => self.check_match(match_exp),
+            ExpressionNode::If(if_exp) => self.check_if(if_exp),
+            ExpressionNode::Function(func) => self.check_function(func),
+            ExpressionNode::Call(call) => {
+                let function = self.check_expression(&call.function);
+                let arguments: Vec<Type> = call
+                    .arguments
+                    .iter()
+                    .map(|arg| self.check_expression(&arg.value))
+                    .collect();
+
+                match function {
+                    Type::Function(params, ret) => {
+                        self.check_arguments(call, &arguments, &params);
+                        *ret
+                    }
+                    _ => Type::Unknown,
+                }
+            }
+        }
+    }
+
+    /// Checks positional arguments against the parameters in order, and named
+    /// ones against the parameter of that name when the callee is known.
+    fn check_arguments(&mut self, call: &CallExpression, arguments: &[Type], params: &[Type]) {
+        let names = match call.function.as_ref() {
+            ExpressionNode::Function(func) => Some(parameter_names(func)),
+            ExpressionNode::IdentifierNode(ident) => self
+                .binding(&ident.value)
+                .and_then(|binding| binding.parameters.clone()),
+            _ => None,
+        };
+
+        let mut position = 0;
+        for (arg, arg_type) in call.arguments.iter().zip(arguments) {
+            let (idx, described) = match &arg.name {
+                Some(name) => {
+                    let idx = names
+                        .as_ref()
+                        .and_then(|names| names.iter().position(|n| *n == name.value));
+                    (idx, format!("argument `{}`", name.value))
+                }
+                None => {
+                    position += 1;
+                    (Some(position - 1), format!("argument {}", position))
+                }
+            };
+
+            let Some(param) = idx.and_then(|idx| params.get(idx)) else {
+                continue;
+            };
+            if !param.is_compatible(arg_type) {
+                let msg = format!(
+                    "type mismatch: {} of `{}` expects {}, got {}",
+                    described,
+                    call.print_string(),
+                    param,
+                    arg_type
+                );
+                self.error(arg.value.token().span, msg);
+            }
+        }
+    }
+
+    /// Only a binding declared with a type has to keep it, any other may be
+    /// assigned a value of another type.
+    fn check_assign(&mut self, assign: &AssignExpression) -> Type {
+        let target = self.lookup(&assign.name.value);
+        let annotated = self
+            .binding(&assign.name.value)
+            .is_some_and(|binding| binding.annotated);
+        let value = self.check_expression(&assign.value);
+
+        if target.is_compatible(&value) {
+            // a binding that started as null may hold anything it is assigned
+            if target == Type::Null {
+                self.redefine(&assign.name.value, Type::Unknown);
+            }
+        } else if annotated {
+            let msg = format!(
+                "type mismatch: `{}` is {}, but the assigned value is {}",
+                assign.name.value, target, value
+            );
+            self.error(assign.value.token().span, msg);
+        } else {
+            self.redefine(&assign.name.value, Type::Unknown);
+        }
+
+        value
+    }
+
+    fn check_index(&mut self, index: &IndexExpression) -> Type {
+        let left = self.check_expression(&index.left);
+        let index_type = self.check_expression(&index.index);
+
+        if left == Type::Null {
+            if index.optional {
+                return Type::Null;
+            }
+            let msg = format!(
+                "can not index null in `{}`, use `?.[` if it may be null",
+                index.print_string()
+            );
+            self.error(index.token.span, msg);
+            return Type::Unknown;
+        }
+
+        if index.is_slice() {
+            return match left {
+                Type::Array(_) | Type::String | Type::Unknown => left,
+                other => {
+                    let msg = format!("type mismatch: can not slice {}", other);
+                    self.error(index.token.span, msg);
+                    Type::Unknown
+                }
+            };
+        }
+
+        let (key, value) = match left {
+            Type::Array(element) => (Type::Int, *element),
+            Type::String => (Type::Int, Type::String),
+            Type::Hash(key, value) => (*key, *value),
+            Type::Unknown => return Type::Unknown,
+            other => {
+                let msg = format!("type mismatch: can not index {}", other);
+                self.error(index.token.span, msg);
+                return Type::Unknown;
+            }
+        };
+
+        if !key.is_compatible(&index_type) {
+            let msg = format!(
+                "type mismatch: index of `{}` must be {}, got {}",
+                index.print_string(),
+                key,
+                index_type
+            );
+            self.error(index.index.token().span, msg);
+        }
+
+        value
+    }
+
+    fn check_range(&mut self, range: &RangeExpression) -> Type {
+        for bound in [&range.start, &range.end].into_iter().flatten() {
+            let ty = self.check_expression(bound);
+            if !Type::Int.is_compatible(&ty) {
+                let msg = format!("type mismatch: range bounds must be int, got {}", ty);
+                self.error(bound.token().span, msg);
+            }
+        }
+
+        Type::Range
+    }
+
+    fn check_prefix(&mut self, prefix: &PrefixExpression) -> Type {
+        let right = self.check_expression(&prefix.right);
+
+        match prefix.operator.as_str() {
+            "!" => Type::Bool,
+            "-" => {
+                if !Type::Int.is_compatible(&right) {
+                    let msg = format!("type mismatch: -{}", right);
+                    self.error(prefix.token.span, msg);
+                }
+                Type::Int
+            }
+            _ => Type::Unknown,
+        }
+    }
+
+    fn check_infix(&mut self, infix: &InfixExpression) -> Type {
+        let left = self.check_expression(&infix.left);
+        let right = self.check_expression(&infix.right);
+
+        let result = match infix.operator.as_str() {
+            "+" => match (&left, &right) {
+                (Type::Int, Type::Int)
+                | (Type::Int, Type::Unknown)
+                | (Type::Unknown, Type::Int) => Some(Type::Int),
+                (Type::String, Type::String)
+                | (Type::String, Type::Unknown)
+                | (Type::Unknown, Type::String) => Some(Type::String),
+                (Type::Unknown, Type::Unknown) => Some(Type::Unknown),
+                _ => None,
+            },
+            "-" | "*" | "/" => (Type::Int.is_compatible(&left) && Type::Int.is_compatible(&right))
+                .then_some(Type::Int),
+            "<" | ">" => (Type::Int.is_compatible(&left) && Type::Int.is_compatible(&right))
+                .then_some(Type::Bool),
+            "==" | "!=" => Some(Type::Bool),
+            "??" => Some(Type::unify_all(vec![left.clone(), right.clone()])),
+            _ => Some(Type::Unknown),
+        };
+
+        result.unwrap_or_else(|| {
+            let msg = format!("type mismatch: {} {} {}", left, infix.operator, right);
+            self.error(infix.token.span, msg);
+            Type::Unknown
+        })
+    }
+
+    fn check_match(&mut self, match_exp: &MatchExpression) -> Type {
+        let subject = self.check_expression(&match_exp.subject);
+        let mut arms = vec![];
+
+        for arm in &match_exp.arms {
+            self.scopes.push(HashMap::new());
+            self.bind_pattern(&arm.pattern, &subject);
+
+            if let Some(guard) = &arm.guard {
+                let guard_type = self.check_expression(guard);
+                if !Type::Bool.is_compatible(&guard_type) {
+                    let msg = format!(
+                        "type mismatch: match guard must be bool, got {}",
+                        guard_type
+                    );
+                    self.error(guard.token().span, msg);
+                }
+            }
+
+            arms.push(self.check_expression(&arm.body));
+            self.scopes.pop();
+        }
+
+        Type::unify_all(arms)
+    }
+
+    /// The shared type of the blocks' last expression statements, a block
+    /// without one and a missing `else` are null.
+    fn check_if(&mut self, if_exp: &IfExpression) -> Type {
+        let condition = self.check_expression(&if_exp.condition);
+        if !Type::Bool.is_compatible(&condition) {
+            let msg = format!(
+                "type mismatch: if condition must be bool, got {}",
+                condition
+            );
+            self.error(if_exp.condition.token().span, msg);
+        }
+
+        let mut values = vec![];
+        for block in if_exp.blocks() {
+            self.scopes.push(HashMap::new());
+            let value = match block.statements.split_last() {
+                Some((
+                    StatementNode::Expression(ExpressionStatement {
+                        expression: Some(value),
+                        ..
+                    }),
+                    init,
+                )) => {
+                    self.check_statements(init);
+                    self.check_expression(value)
+                }
+                _ => {
+                    self.check_statements(&block.statements);
+                    Type::Null
+                }
+            };
+            self.scopes.pop();
+            values.push(value);
+        }
+        if if_exp.alternative.is_none() {
+            values.push(Type::Null);
+        }
+
+        Type::unify_all(values)
+    }
+
+    fn check_function(&mut self, func: &FunctionLiteral) -> Type {
+        self.scopes.push(HashMap::new());
+        let mut params = vec![];
+
+        for param in &func.parameters {
+            let declared = param.annotation.as_ref().map(|a| self.resolve(a));
+
+            let default = param
+                .default
+                .as_ref()
+                .map(|d| (d, self.check_expression(d)));
+            if let (Some(declared), Some((value, value_type))) = (&declared, &default) {
+                if !declared.is_compatible(value_type) {
+                    let msg = format!(
+                        "type mismatch: parameter `{}` is declared as {}, but its default is {}",
+                        param.name.value, declared, value_type
+                    );
+                    self.error(value.token().span, msg);
+                }
+            }
+
+            let ty = match (declared, default) {
+                (Some(declared), _) => declared,
+                (None, Some((_, value_type))) => value_type,
+                (None, None) if param.rest => Type::Array(Box::new(Type::Unknown)),
+                (None, None) => Type::Unknown,
+            };
+
+            if !param.rest {
+                params.push(ty.clone());
+            }
+            self.define(&param.name.value, ty);
+            if param.annotation.is_some() {
+                self.annotate(&param.name.value);
+            }
+        }
+
+        let declared = func.return_type.as_ref().map(|r| self.resolve(r));
+        self.return_types.push(declared.clone());
+
+        let is_generator = func.is_generator();
+        match (&declared, func.body.statements.split_last()) {
+            // the last expression statement is the implicit return value
+            (Some(declared), Some((StatementNode::Expression(exp_stmt), init)))
+                if !is_generator =>
+            {
+                self.check_statements(init);
+                if let Some(value) = &exp_stmt.expression {
+                    let value_type = self.check_expression(value);
+                    self.check_return(declared, &value_type, value);
+                }
+            }
+            _ => self.check_statements(&func.body.statements),
+        }
+
+        self.return_types.pop();
+        self.scopes.pop();
+
+        match declared {
+            Some(declared) if !is_generator => Type::Function(params, Box::new(declared)),
+            _ => Type::Function(params, Box::new(Type::Unknown)),
+        }
+    }
+}
+
+/// The names of the parameters that take an argument each, without the rest one.
+fn parameter_names(func: &FunctionLiteral) -> Vec<String> {
+    func.parameters
+        .iter()
+        .filter(|param| !param.rest)
+        .map(|param| param.name.value.clone())
+        .collect()
+}
+
+/// The enums declared at the top level, a match may use their variants
+/// before the declaration.
+fn top_level_enums(program: &Program) -> HashMap<String, EnumDeclaration> {
+    program
+        .statements
+        .iter()
+        .filter_map(|stmt| match stmt {
+            StatementNode::Enum(declaration) => {
+                Some((declaration.name.value.clone(), declaration.clone()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{lexer::Lexer, parser::Parser};
+
+    use super::check_program;
+
+    fn errors_for(input: &str) -> Vec<String> {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+
+        assert!(
+            parser.errors().is_empty(),
+            "parser errors: {:?}",
+            parser.errors()
+        );
+
+        check_program(&program)
+    }
+
+    #[test]
+    fn test_well_typed_programs() {
+        let tests = vec![
+            "let x = 5; let y = x + 10; let s = \"a\" + \"b\";",
+            "let add = fn(a, b) { a + b }; add(1, \"two\");",
+            "let x: int = 5; let y: int = x * 2;",
+            "let name: string = \"Ali\"; let ok: bool = 1 < 2;",
+            "let xs: [int] = [1, 2, 3]; let [a, b, ...rest] = xs; let c: int = a + b;",
+            "let h: {string: int} = {\"a\": 1}; let {a} = h; let d: int = a;",
+            "let f = fn(a: int, b: string) -> bool { a > 0 }; let r: bool = f(1, \"x\");",
+            "let f = fn(a: int) -> int { return a; }; let g: fn(int) -> int = f;",
+            "let f = fn(x: int = 10) { x }; let m = match (1) { 1 => \"one\", n if n > 1 => \"many\" };",
+            "let unknown = fn(x) { x }; let y: string = unknown(5);",
+            "struct Point { x, y } let p: Point = Point { x: 1, y: 2 }; let x = p.x;",
+            "enum Shape { Circle(r), Empty } let s: Shape = Circle(1); let e: Shape = Empty;",
+            "enum Shape { Circle(r) } let f = fn(s: Shape) -> int { match (s) { Circle(r) => r } };",
+            "let xs: [int] = [1]; for x in xs { let y: int = x; }",
+            "for i in 0..1_000_000_000 { let j: int = i; }",
+            "let xs: [int] = [1, 2]; let x: int = xs[0]; let ys: [int] = xs[1..]; let s: string = \"abc\"[..2];",
+            "let h: {string: bool} = {\"a\": true}; let b: bool = h[\"a\"];",
+            "let g = fn() -> int { yield 1; yield 2; }; let h = fn() -> string { for x in g() { yield \"a\"; } };",
+            "let x: int = 1; try { let x = \"a\"; } catch (x) { let y: string = x; } let z: int = x;",
+            "let n = null; let a: int = n ?? 1; let b = n?.x; let c = n?.[0]; let d: string = null;",
+            "let xs: [int] = [1, null]; let x: int = xs[0] ?? 0;",
+            "let n = null; n = 5; let y = n.x; const c: int = 1; let d: int = c;",
+            "let f = fn(a: int, b: string = \"\") { a }; f(b: \"x\", a: 1); f(1, b: \"y\");",
+            "let f = fn(a: int) { a }; let g = fn(f) { f(a: \"s\") };",
+            "let x = 1; x = \"s\"; let y: string = x;",
+            "let a: int = if (true) { 1 }; let b: string = if (false) { \"x\" } else { \"y\" };",
+            "let c: int = if (true) { 1 } else { \"s\" }; if (true) { let a: string = \"s\"; }",
+        ];
+
+        for input in tests {
+            let errors = errors_for(input);
+            assert!(
+                errors.is_empty(),
+                "unexpected errors for {}: {:?}",
+                input,
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn test_type_mismatches_with_spans() {
+        let tests = vec![
+            (
+                "let x: int = \"five\";",
+                "1:14-20: type mismatch: `x` is declared as int, but the value is string",
+            ),
+            ("let y = 5 + \"a\";", "1:11-12: type mismatch: int + string"),
+            ("let z = -true;", "1:9-10: type mismatch: -bool"),
+            (
+                "let x: string = \"a\";\nlet y: int = x;",
+                "2:14-15: type mismatch: `y` is declared as int, but the value is string",
+            ),
+            (
+                "let f = fn(a: int, b: string) -> bool { a }",
+                "1:41-42: type mismatch: function returns bool, but the value is int",
+            ),
+            (
+                "let f = fn(a: int) -> int {\n  return \"no\";\n};",
+                "2:10-14: type mismatch: function returns int, but the value is string",
+            ),
+            (
+                "let f = fn(a: int, b: string) { a }; f(\"x\", \"y\");",
+                "1:40-43: type mismatch: argument 1 of `f(\"x\", \"y\")` expects int, got string",
+            ),
+            (
+                "let f = fn(a: int) { a }; f(a: \"s\");",
+                "1:32-35: type mismatch: argument `a` of `f(a: \"s\")` expects int, got string",
+            ),
+            (
+                "let f = fn(a, b: string = \"\") { a }; f(1, b: 2);",
+                "1:46-47: type mismatch: argument `b` of `f(1, b: 2)` expects string, got int",
+            ),
+            (
+                "fn(a: int, b: bool) { a }(b: 1, a: 2);",
+                "1:30-31: type mismatch: argument `b` of `fn(a: int, b: bool) a(b: 1, a: 2)` expects bool, got int",
+            ),
+            (
+                "let f = fn(a: int = \"x\") { a };",
+                "1:21-24: type mismatch: parameter `a` is declared as int, but its default is string",
+            ),
+            ("let x: float = 1;", "1:8-13: unknown type `float`"),
+            (
+                "let xs: [int] = [1, 2]; let {a} = xs;",
+                "1:29-30: type mismatch: can not destructure [int] with `{\"a\": a}`",
+            ),
+            (
+                "let n: int = 1; match (n) { \"one\" => 1, _ => 0 };",
+                "1:29-34: type mismatch: pattern `\"one\"` of type string can not match int",
+            ),
+            (
+                "match (1) { n if n + 1 => n };",
+                "1:20-21: type mismatch: match guard must be bool, got int",
+            ),
+            (
+                "if (1 + 1) { 2 };",
+                "1:7-8: type mismatch: if condition must be bool, got int",
+            ),
+            (
+                "let s: string = if (true) { 1 } else { 2 };",
+                "1:17-19: type mismatch: `s` is declared as string, but the value is int",
+            ),
+            (
+                "struct P { x } let p = P { x: 1 }; p.z;",
+                "1:38-39: struct `P` has no field `z`",
+            ),
+            (
+                "struct P { x, y }\nP { x: 1 };",
+                "2:1-2: missing field `y` in `P { x: 1 }`",
+            ),
+            (
+                "struct P { x } P { x: 1, x: 2 };",
+                "1:26-27: field `x` is given more than once",
+            ),
+            ("let q = Q { a: 1 };", "1:9-10: unknown struct `Q`"),
+            (
+                "enum E { A(x) } let e: int = A(1);",
+                "1:30-31: type mismatch: `e` is declared as int, but the value is E",
+            ),
+            (
+                "enum E { A(x) } match (1) { A(x) => x };",
+                "1:29-30: type mismatch: pattern `A(x)` of type E can not match int",
+            ),
+            (
+                "enum E { A(x) } match (A(1)) { A => 1 };",
+                "1:32-33: variant `A` has 1 values, but the pattern `A` has 0",
+            ),
+            (
+                "try { let x: int = \"a\"; } finally { 1 }",
+                "1:20-23: type mismatch: `x` is declared as int, but the value is string",
+            ),
+            (
+                "let f = fn() -> int { try { return true; } catch (e) { return 0; } };",
+                "1:36-40: type mismatch: function returns int, but the value is bool",
+            ),
+            (
+                "let g = fn() -> int { yield \"a\"; };",
+                "1:29-32: type mismatch: generator yields int, but the value is string",
+            ),
+            (
+                "let xs: [string] = []; for x in xs { let y: int = x; }",
+                "1:51-52: type mismatch: `y` is declared as int, but the value is string",
+            ),
+            (
+                "let r = 1..\"a\";",
+                "1:12-15: type mismatch: range bounds must be int, got string",
+            ),
+            (
+                "let xs: [int] = []; let y: string = xs[0];",
+                "1:39-40: type mismatch: `y` is declared as string, but the value is int",
+            ),
+            (
+                "let xs: [int] = []; xs[true];",
+                "1:24-28: type mismatch: index of `(xs[true])` must be int, got bool",
+            ),
+            ("true[0];", "1:5-6: type mismatch: can not index bool"),
+            ("5[1..2];", "1:2-3: type mismatch: can not slice int"),
+            (
+                "match (x) { B(y) => y };",
+                "1:13-14: unknown variant `B`",
+            ),
+            (
+                "let n = null; n.x;",
+                "1:17-18: can not access `x` on null in `n.x`, use `?.` if it may be null",
+            ),
+            (
+                "let n = null; n?.a.b;",
+                "1:20-21: can not access `b` on null in `n?.a.b`, use `?.` if it may be null",
+            ),
+            (
+                "let n = null; n[0];",
+                "1:16-17: can not index null in `(n[0])`, use `?.[` if it may be null",
+            ),
+            (
+                "let s: string = null ?? 1;",
+                "1:22-24: type mismatch: `s` is declared as string, but the value is int",
+            ),
+            (
+                "let x: int = 1; x = \"a\";",
+                "1:21-24: type mismatch: `x` is int, but the assigned value is string",
+            ),
+            (
+                "let f = fn(n: int) { n = \"a\"; };",
+                "1:26-29: type mismatch: `n` is int, but the assigned value is string",
+            ),
+            (
+                "struct P { x } let p: P = 1;",
+                "1:27-28: type mismatch: `p` is declared as P, but the value is int",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let errors = errors_for(input);
+            assert_eq!(errors, vec![expected], "wrong errors for {}", input);
+        }
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    rc::Rc,
+};
+
+use crate::{
+    ast::{FunctionLiteral, Node, StructDeclaration, Variant},
+    eval::Generator,
+};
+
+/// A value the evaluator computes, borrowing the code of its functions from
+/// the program that created it.
+#[derive(Debug, Clone)]
+pub enum Value<'a> {
+    Null,
+    Int(i64),
+    Bool(bool),
+    String(Rc<str>),
+    Array(Rc<Vec<Value<'a>>>),
+    /// Keeps the keys in insertion order.
+    Hash(Rc<Vec<(HashKey, Value<'a>)>>),
+    Function(Rc<Closure<'a>>),
+    Builtin(Builtin),
+    /// What the name of a `struct` declaration evaluates to.
+    StructType(&'a StructDeclaration),
+    Struct(Rc<StructValue<'a>>),
+    /// What the name of an enum variant with a payload evaluates to, calling
+    /// it builds the variant.
+    Constructor(&'a Variant),
+    /// A value built by an enum variant.
+    Variant(Rc<VariantValue<'a>>),
+    Range(Range),
+    Generator(Rc<Generator<'a>>),
+    /// The `next` of a generator, which runs it to its next `yield`.
+    GeneratorNext(Rc<Generator<'a>>),
+    /// An error caught by `catch`.
+    Error(Rc<ErrorValue<'a>>),
+    /// The exports of an imported module, by name.
+    Module(Rc<HashMap<String, Value<'a>>>),
+}
+
+impl<'a> Value<'a> {
+    /// The name of the value's type, used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Int(_) => "int",
+            Value::Bool(_) => "bool",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Hash(_) => "hash",
+            Value::Function(_)
+            | Value::Builtin(_)
+            | Value::Constructor(_)
+            | Value::GeneratorNext(_) => "function",
+            Value::Range(_) => "range",
+            Value::Generator(_) => "generator",
+            Value::StructType(_) => "struct type",
+            Value::Struct(_) => "struct",
+            Value::Variant(_) => "enum value",
+            Value::Error(_) => "error",
+            Value::Module(_) => "module",
+        }
+    }
+
+    /// Only `null` and `false` are falsy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Null | Value::Bool(false))
+    }
+
+    /// Compares by value, functions, generators, errors and modules are only
+    /// equal to themselves.
+    pub fn equals(&self, other: &Value<'a>) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Int(left), Value::Int(right)) => left == right,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Array(left), Value::Array(right)) => {
+                left.len() == right.len() && left.iter().zip(right.iter()).all(|(l, r)| l.equals(r))
+            }
+            (Value::Hash(left), Value::Hash(right)) => {
+                left.len() == right.len()
+                    && left.iter().all(|(key, value)| {
+                        right
+                            .iter()
+                            .any(|(other_key, other)| key == other_key && value.equals(other))
+                    })
+            }
+            (Value::Struct(left), Value::Struct(right)) => {
+                left.name == right.name
+                    && left
+                        .fields
+                        .iter()
+                        .zip(&right.fields)
+                        .all(|((_, l), (_, r))| l.equals(r))
+            }
+            (Value::Variant(left), Value::Variant(right)) => {
+                std::ptr::eq(left.variant, right.variant)
+                    && left
+                        .payload
+                        .iter()
+                        .zip(&right.payload)
+                        .all(|(l, r)| l.equals(r))
+            }
+            (Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+            (Value::Error(left), Value::Error(right)) => Rc::ptr_eq(left, right),
+            (Value::Range(left), Value::Range(right)) => left == right,
+            (Value::Generator(left), Value::Generator(right)) => Rc::ptr_eq(left, right),
+            (Value::GeneratorNext(left), Value::GeneratorNext(right)) => Rc::ptr_eq(left, right),
+            (Value::Builtin(left), Value::Builtin(right)) => left == right,
+            (Value::StructType(left), Value::StructType(right)) => std::ptr::eq(*left, *right),
+            (Value::Constructor(left), Value::Constructor(right)) => std::ptr::eq(*left, *right),
+            (Value::Module(left), Value::Module(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+
+    /// Writes the value as it would appear inside an array, strings quoted.
+    fn write_nested(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(value) => write!(f, "\"{}\"", value),
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
+impl Display for Value<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Array(elements) => {
+                write!(f, "[")?;
+                for (idx, element) in elements.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.write_nested(f)?;
+                }
+                write!(f, "]")
+            }
+            Value::Hash(pairs) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in pairs.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", key)?;
+                    value.write_nested(f)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Function(closure) => {
+                let params: Vec<String> = closure
+                    .literal
+                    .parameters
+                    .iter()
+                    .map(|param| param.print_string())
+                    .collect();
+                write!(f, "fn({})", params.join(", "))
+            }
+            Value::Builtin(builtin) => write!(f, "builtin {}", builtin.name()),
+            Value::StructType(declaration) => write!(f, "struct {}", declaration.name.value),
+            Value::Struct(value) => {
+                write!(f, "{} {{", value.name)?;
+                for (idx, (name, field)) in value.fields.iter().enumerate() {
+                    write!(f, "{}{}: ", if idx > 0 { ", " } else { " " }, name)?;
+                    field.write_nested(f)?;
+                }
+                write!(f, " }}")
+            }
+            Value::Constructor(variant) => write!(f, "variant {}", variant.print_string()),
+            Value::Variant(value) => {
+                write!(f, "{}", value.variant.name.value)?;
+                if value.payload.is_empty() {
+                    return Ok(());
+                }
+                write!(f, "(")?;
+                for (idx, element) in value.payload.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.write_nested(f)?;
+                }
+                write!(f, ")")
+            }
+            Value::Range(range) => write!(f, "{}", range),
+            Value::Generator(_) => write!(f, "generator"),
+            Value::GeneratorNext(_) => write!(f, "builtin next"),
+            Value::Error(err) => write!(f, "error: {}", err.message),
+            Value::Module(exports) => {
+                let mut names: Vec<&String> = exports.keys().collect();
+                names.sort();
+                let names: Vec<&str> = names.into_iter().map(String::as_str).collect();
+                write!(f, "module {{ {} }}", names.join(", "))
+            }
+        }
+    }
+}
+
+/// A value that can key a hash.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    Int(i64),
+    Bool(bool),
+    String(Rc<str>),
+}
+
+impl HashKey {
+    /// The key for `value`, if it can be one.
+    pub fn from_value(value: &Value) -> Option<HashKey> {
+        match value {
+            Value::Int(value) => Some(HashKey::Int(*value)),
+            Value::Bool(value) => Some(HashKey::Bool(*value)),
+            Value::String(value) => Some(HashKey::String(Rc::clone(value))),
+            _ => None,
+        }
+    }
+
+    pub fn to_value<'a>(&self) -> Value<'a> {
+        match self {
+            HashKey::Int(value) => Value::Int(*value),
+            HashKey::Bool(value) => Value::Bool(*value),
+            HashKey::String(value) => Value::String(Rc::clone(value)),
+        }
+    }
+}
+
+impl Display for HashKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashKey::Int(value) => write!(f, "{}", value),
+            HashKey::Bool(value) => write!(f, "{}", value),
+            HashKey::String(value) => write!(f, "\"{}\"", value),
+        }
+    }
+}
+
+/// `a..b` or `a..=b` with its bounds evaluated, counted up only as a `for`
+/// loop asks for the next number.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub inclusive: bool,
+}
+
+impl Range {
+    /// The start and end of the part of a sequence of `len` elements the
+    /// range covers, bounds past either end are moved to it.
+    pub fn slice(&self, len: usize) -> (usize, usize) {
+        let clamp = |bound: i64| usize::try_from(bound).map_or(0, |bound| bound.min(len));
+        let start = self.start.map_or(0, clamp);
+        let end = match self.end {
+            Some(end) if self.inclusive => clamp(end.saturating_add(1)),
+            Some(end) => clamp(end),
+            None => len,
+        };
+
+        (start, end.max(start))
+    }
+}
+
+impl Display for Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(start) = self.start {
+            write!(f, "{}", start)?;
+        }
+        write!(f, "{}", if self.inclusive { "..=" } else { ".." })?;
+        if let Some(end) = self.end {
+            write!(f, "{}", end)?;
+        }
+        Ok(())
+    }
+}
+
+/// A function literal together with the scope it was created in.
+pub struct Closure<'a> {
+    pub literal: &'a FunctionLiteral,
+    pub env: Env<'a>,
+    /// The name it was bound to by `let`, used in error messages.
+    pub name: Option<String>,
+}
+
+// the scope may hold the closure itself, printing it would never end
+impl std::fmt::Debug for Closure<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Closure")
+            .field("literal", &self.literal.print_string())
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+/// Functions that are always in scope, unless a binding shadows them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Builtin {
+    /// Prints each argument on its own line.
+    Puts,
+    /// The number of elements of an array or hash, or of characters of a string.
+    Len,
+}
+
+impl Builtin {
+    pub fn lookup(name: &str) -> Option<Builtin> {
+        match name {
+            "puts" => Some(Builtin::Puts),
+            "len" => Some(Builtin::Len),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::Puts => "puts",
+            Builtin::Len => "len",
+        }
+    }
+}
+
+/// A value of a `struct` declaration, its fields in declaration order.
+#[derive(Debug)]
+pub struct StructValue<'a> {
+    pub name: String,
+    pub fields: Vec<(String, Value<'a>)>,
+}
+
+/// A value of an enum variant, its payload in the order of the variant's fields.
+#[derive(Debug)]
+pub struct VariantValue<'a> {
+    pub variant: &'a Variant,
+    pub payload: Vec<Value<'a>>,
+}
+
+/// An error caught by `catch`, its fields are read like those of a struct.
+#[derive(Debug)]
+pub struct ErrorValue<'a> {
+    pub message: String,
+    /// Where the error was raised, innermost call first.
+    pub stack: Vec<String>,
+    /// What `throw` threw, null for an error raised by running the code.
+    pub value: Value<'a>,
+}
+
+impl<'a> ErrorValue<'a> {
+    /// The field `message`, `stack` or `value`.
+    pub fn field(&self, name: &str) -> Option<Value<'a>> {
+        match name {
+            "message" => Some(Value::String(self.message.as_str().into())),
+            "stack" => {
+                let stack = self
+                    .stack
+                    .iter()
+                    .map(|entry| Value::String(entry.as_str().into()));
+                Some(Value::Array(Rc::new(stack.collect())))
+            }
+            "value" => Some(self.value.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// The bindings of one scope and the scope around it.
+#[derive(Debug, Clone, Default)]
+pub struct Env<'a>(Rc<RefCell<Scope<'a>>>);
+
+#[derive(Debug, Default)]
+struct Scope<'a> {
+    values: HashMap<String, Value<'a>>,
+    /// The names of `values` declared with `const`.
+    constants: HashSet<String>,
+    outer: Option<Env<'a>>,
+}
+
+impl<'a> Env<'a> {
+    pub fn new() -> Env<'a> {
+        Env::default()
+    }
+
+    /// A new scope inside this one.
+    pub fn enclosed(&self) -> Env<'a> {
+        Env(Rc::new(RefCell::new(Scope {
+            values: HashMap::new(),
+            constants: HashSet::new(),
+            outer: Some(self.clone()),
+        })))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value<'a>> {
+        let scope = self.0.borrow();
+        match scope.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => scope.outer.as_ref().and_then(|outer| outer.get(name)),
+        }
+    }
+
+    /// Binds `name` in this scope, shadowing any outer binding.
+    pub fn define(&self, name: &str, value: Value<'a>) {
+        let mut scope = self.0.borrow_mut();
+        scope.constants.remove(name);
+        scope.values.insert(name.to_string(), value);
+    }
+
+    /// Binds `name` like [`Env::define`], for good.
+    pub fn define_const(&self, name: &str, value: Value<'a>) {
+        let mut scope = self.0.borrow_mut();
+        scope.constants.insert(name.to_string());
+        scope.values.insert(name.to_string(), value);
+    }
+
+    /// Whether the innermost binding of `name` is declared with `const`.
+    pub fn is_const(&self, name: &str) -> bool {
+        let scope = self.0.borrow();
+        if scope.values.contains_key(name) {
+            return scope.constants.contains(name);
+        }
+        scope
+            .outer
+            .as_ref()
+            .is_some_and(|outer| outer.is_const(name))
+    }
+
+    /// Changes the innermost binding of `name`, `false` if there is none.
+    pub fn assign(&self, name: &str, value: Value<'a>) -> bool {
+        let mut scope = self.0.borrow_mut();
+        if let Some(slot) = scope.values.get_mut(name) {
+            *slot = value;
+            return true;
+        }
+        match &scope.outer {
+            Some(outer) => outer.assign(name, value),
+            None => false,
+        }
+    }
+}